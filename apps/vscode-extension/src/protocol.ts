@@ -1,4 +1,41 @@
-export type BridgeCmd = "connect" | "query" | "execute" | "tables" | "columns";
+export type BridgeCmd =
+  | "connect"
+  | "close"
+  | "disconnect"
+  | "connection_info"
+  | "attach"
+  | "detach"
+  | "close_all"
+  | "query"
+  | "execute"
+  | "execute_batch"
+  | "browse_table"
+  | "explain_query"
+  | "search"
+  | "insert_row"
+  | "update_row"
+  | "delete_row"
+  | "read_blob"
+  | "backup"
+  | "optimize"
+  | "dump_schema"
+  | "dump_all"
+  | "begin"
+  | "commit"
+  | "rollback"
+  | "tables"
+  | "objects"
+  | "ddl"
+  | "diff_schema"
+  | "columns"
+  | "indexes"
+  | "foreign_keys"
+  | "foreign_key_check"
+  | "database_size_report"
+  | "triggers"
+  | "cancel"
+  | "ping"
+  | "capabilities";
 
 export interface BridgeRequest<TPayload> {
   v: 1;
@@ -11,16 +48,74 @@ export interface BridgeResponse<TData> {
   v: 1;
   id: string;
   status: "ok" | "error";
+  /** Present on streamed query responses (`payload.stream: true`) and on dumps. */
+  frame?: "columns" | "row" | "chunk" | "end";
   data?: TData;
   error?: string;
   code?: string;
+  /** SQL errors carry `sqlite_code`/`extended_code`, plus `error_offset` (byte offset into the
+   * request's SQL) when SQLite can point at the offending token. */
   details?: Record<string, unknown>;
 }
 
+/** `ping` data; answered even while another request runs, and for any `v`. */
+export interface PingResult {
+  pong: true;
+  /** sqlite-helper package version. */
+  version: string;
+  /** The only `v` other commands accept. */
+  protocol_version: number;
+  uptime_ms: number;
+}
+
+/** `capabilities` data; like `ping`, answered for any `v`. */
+export interface Capabilities {
+  version: string;
+  /** `v` values accepted by every other command. */
+  protocol_versions: number[];
+  sqlite_version: string;
+  commands: BridgeCmd[];
+  features: {
+    streaming: boolean;
+    transactions: boolean;
+    parameters: boolean;
+    named_parameters: boolean;
+    cursor_paging: boolean;
+    csv: boolean;
+    ndjson: boolean;
+    markdown: boolean;
+    blobs: boolean;
+    backup: boolean;
+    attach: boolean;
+    cancel: boolean;
+    /** Started with `--readonly`. */
+    readonly: boolean;
+    /**
+     * New connections enforce foreign keys (`--foreign-keys`, default true, or a later
+     * `--pragma foreign_keys=...`). `connect` `pragmas` can change it for one database; its actual
+     * state is `connection_info` `pragmas.foreign_keys`.
+     */
+    foreign_keys: boolean;
+  };
+}
+
 export interface ColumnMeta {
   name: string;
   decl_type?: string | null;
-  sqlite_type?: string | null;
+  /** Runtime storage class of the first non-NULL value; "null" if all were NULL. */
+  sqlite_type?: "integer" | "real" | "text" | "blob" | "null" | null;
+  /** Schema columns (`columns` command) only: 1-based primary key position, 0 if not a key column. */
+  pk?: number;
+  notnull?: boolean;
+  /** SQL text of the DEFAULT expression, e.g. `'x'` or `CURRENT_TIMESTAMP`; absent if none. */
+  default_value?: string;
+  autoincrement?: boolean;
+  /** Hidden virtual-table column (e.g. FTS5's own-name and `rank` columns); not in `SELECT *`. */
+  hidden?: boolean;
+  /** Absent unless the column is generated; INSERTs must leave these columns out. */
+  generated?: "virtual" | "stored";
+  /** Generation expression as written in the table's DDL. */
+  generation_expr?: string;
 }
 
 export interface QueryResult {
@@ -28,10 +123,445 @@ export interface QueryResult {
   rows: Array<Record<string, unknown>>;
   truncated?: boolean;
   next_offset?: number | null;
+  /** Present in cursor mode (`payload.cursor_key`); pass back as `payload.cursor` for the next page. */
+  next_cursor?: string;
+  /** Time spent inside SQLite (prepare + step). */
+  duration_ms: number;
+  /** Rows visited by full table scans. */
+  rows_scanned: number;
+  /**
+   * Only with `payload.timing: true` (not streamed): server wall time including the wait for the
+   * worker and row value encoding, excluding serializing the response.
+   */
+  elapsed_ms?: number;
+}
+
+/** One result set of a `query` with `payload.multi: true`; `data` is `{ results: StatementResult[] }`. */
+export interface StatementResult extends QueryResult {
+  /** 0-based position of the statement in the script. */
+  index: number;
+  sql: string;
+}
+
+/**
+ * `browse_table` payload: rows `offset..offset + limit` of `table`, optionally sorted by one of its
+ * columns. Result: `BrowseResult`.
+ */
+export interface BrowseTablePayload {
+  table: string;
+  limit?: number;
+  offset?: number;
+  order_by?: string;
+  /** Only with `order_by`; default "ASC". */
+  order_dir?: "ASC" | "DESC";
+  path?: string;
+}
+
+/** The page of a `browse_table` plus the table's `count(*)`, read from the same snapshot. */
+export interface BrowseResult extends QueryResult {
+  total: number;
+}
+
+/**
+ * `search` payload: rows of the FTS5 table `table` matching `query` (FTS5 syntax). Result:
+ * `QueryResult` whose rows carry `rowid`, the table's columns and `score` (bm25, lower is better),
+ * best first.
+ */
+export interface SearchPayload {
+  table: string;
+  query: string;
+  limit?: number;
+  offset?: number;
+  path?: string;
+}
+
+/** `explain_query` payload; the statement must be read-only and is only planned, never run. */
+export interface ExplainQueryPayload {
+  sql: string;
+  /** Parameters left unbound are planned as NULL. */
+  params?: unknown[] | Record<string, unknown>;
+  named_params?: Record<string, unknown>;
+  path?: string;
+}
+
+export interface PlanNode {
+  id: number;
+  parent: number;
+  detail: string;
+  children: PlanNode[];
+}
+
+/** `explain_query` result. */
+export interface ExplainResult {
+  /** Raw `EXPLAIN QUERY PLAN` rows. */
+  rows: Array<{ id: number; parent: number; notused: number; detail: string }>;
+  plan: PlanNode[];
+  /** Tables the plan visits, as it names them; `uses_index: false` means a full scan. */
+  tables: Array<{ table: string; uses_index: boolean }>;
 }
 
 export interface ExecResult {
   changes: number;
-  last_insert_rowid?: number | null;
+  /** Only present when the statement inserted a row. */
+  last_insert_rowid?: number;
+  /** Cumulative rows changed on this connection. */
+  total_changes?: number;
+  duration_ms: number;
+  rows_scanned: number;
+  /** Present when the statement has a RETURNING clause. */
+  rows?: Array<Record<string, unknown>>;
+  /** True when the statement was rolled back after running (`payload.dry_run`). */
+  dry_run?: boolean;
+}
+
+export interface BatchStatementResult {
+  sql: string;
+  changes: number;
+  error?: string | null;
+}
+
+/** `execute_batch` payload; an array of scripts runs in one transaction, so they may not contain BEGIN/COMMIT/ROLLBACK/SAVEPOINT/RELEASE. */
+export interface ExecuteBatchPayload {
+  sql: string | string[];
+  path?: string;
+}
+
+/**
+ * `insert_row` payload: column -> value (`params` value forms); empty or missing `values`
+ * inserts `DEFAULT VALUES`. Result: `ExecResult` with the new row's `last_insert_rowid`.
+ */
+export interface InsertRowPayload {
+  table: string;
+  values?: Record<string, unknown>;
+  path?: string;
+}
+
+/**
+ * `delete_row` payload: the row of `table` whose primary key is `key` (every primary key column,
+ * nothing else). Result: `ExecResult`; fails when no row matches unless `allow_no_match`.
+ */
+export interface DeleteRowPayload {
+  table: string;
+  key: Record<string, unknown>;
+  allow_no_match?: boolean;
+  path?: string;
+}
+
+/** `update_row` payload: `set` maps columns to new values, in the `params` value forms. */
+export interface UpdateRowPayload extends DeleteRowPayload {
+  set: Record<string, unknown>;
+}
+
+/** Totals (`changes` is 0 when rolled back) plus the per-statement breakdown. */
+export interface BatchResult extends Omit<ExecResult, "rows" | "dry_run"> {
+  statements: BatchStatementResult[];
+  committed: boolean;
+  failed_index?: number | null;
+}
+
+export interface ConnectPayload {
+  path: string;
+  /** Open read-only; writes on this connection fail with code "READ_ONLY". */
+  readonly?: boolean;
+  /** Create the file if it does not exist; otherwise a missing file fails with "DB_OPEN_FAILED". */
+  create?: boolean;
+  /** Per-database pragmas on top of `--pragma`, e.g. `{ foreign_keys: true }`. */
+  pragmas?: Record<string, boolean | number | string>;
+}
+
+/** `connection_info` data; `pragmas` holds the current value of each settable pragma and `journal_mode`. */
+export interface ConnectionInfo {
+  path: string;
+  readonly: boolean;
+  in_transaction: boolean;
+  pragmas: Record<string, number | string | null>;
+  attached: AttachedDatabase[];
+}
+
+/** `attach` payload; the attached tables are then queried as `alias.table`. */
+export interface AttachPayload {
+  alias: string;
+  /** Database file to attach; must be inside `--allowed-dir`. */
+  file: string;
+  path?: string;
+}
+
+export interface DetachPayload {
+  alias: string;
+  path?: string;
+}
+
+/** `attach`/`detach` answer with every database then attached. */
+export interface AttachedDatabase {
+  alias: string;
+  path: string;
+}
+
+/** `tables` payload; with `include_attached`, attached tables follow as `alias.table`. */
+export interface TablesPayload {
+  path?: string;
+  include_attached?: boolean;
+  /**
+   * Only this database of the connection ("main", "temp" or an attached alias) instead of main
+   * plus `include_attached` ones; tables outside main are named `alias.table`.
+   */
+  schema?: string;
+  /** Also list views, after each database's tables; `objects` tells them apart. */
+  include_views?: boolean;
+  /** Return `TableRowCount`s instead of names. */
+  include_row_counts?: boolean;
+  /** Row counts from the last ANALYZE (`sqlite_stat1`) instead of `count(*)`. */
+  approximate?: boolean;
+}
+
+/** `tables` entry with `include_row_counts`; `null` if the count failed, timed out or (approximate) the table was never analyzed. */
+export interface TableRowCount {
+  name: string;
+  row_count: number | null;
+}
+
+/** `close` and `disconnect`; without `path`, the database of the last `connect`. */
+export interface ClosePayload {
+  path?: string;
+}
+
+/** `close`/`disconnect`: whether a connection was open; `close_all`: how many were closed. */
+export interface CloseResult {
+  closed: boolean | number;
+}
+
+export interface DumpPayload {
+  only?: string[];
+  path?: string;
+}
+
+/** Data of the terminal `end` frame of `dump_schema` / `dump_all`; `chunk` frames carry SQL text. */
+export interface DumpEnd {
+  tables: number;
+  rows: number;
+}
+
+export type MaintenanceOp = "optimize" | "analyze" | "vacuum";
+
+export interface OptimizePayload {
+  operations?: MaintenanceOp[];
+  path?: string;
+}
+
+export interface MaintenanceStep {
+  operation: MaintenanceOp;
+  duration_ms: number;
+}
+
+export interface OptimizeResult {
+  steps: MaintenanceStep[];
+  size_before?: number | null;
+  size_after?: number | null;
+  freed_bytes?: number | null;
+  duration_ms: number;
+}
+
+/** `backup`: fails with `DB_BUSY` when the source stays locked for 5 s without a step getting through. */
+export interface BackupPayload {
+  dest: string;
+  overwrite?: boolean;
+  path?: string;
+}
+
+export interface BackupResult {
+  dest: string;
+  pages_total: number;
+  pages_remaining: number;
+  steps: number;
+  size_bytes?: number | null;
+  duration_ms: number;
+}
+
+
+export interface IndexMeta {
+  name: string;
+  unique: boolean;
+  origin: string;
+  partial: boolean;
+  columns: Array<string | null>;
+}
+
+export interface ForeignKeyMeta {
+  id: number;
+  table: string;
+  from: string[];
+  to: Array<string | null>;
+  on_update: string;
+  on_delete: string;
+}
+
+/** `foreign_key_check` payload: one table (`alias.table` when attached) or the whole main database. */
+export interface ForeignKeyCheckPayload {
+  table?: string;
+  /** Violations listed (capped by `--max-rows`); all are counted in `total`. */
+  limit?: number;
+  path?: string;
+}
+
+/** `foreign_key_check` result; `violations` is `[]` for a clean database. */
+export interface ForeignKeyCheck {
+  violations: Array<{
+    table: string;
+    /** null for WITHOUT ROWID tables. */
+    rowid: number | null;
+    /** Parent table the row should reference. */
+    references: string;
+    /** `id` of the broken constraint in `ForeignKeyMeta`. */
+    constraint_index: number;
+    from: string[];
+    to: Array<string | null>;
+  }>;
+  total: number;
+  by_table: Record<string, number>;
+  truncated: boolean;
+}
+
+/** `database_size_report` payload; the report reads every page of the file. */
+export interface DatabaseSizeReportPayload {
+  path?: string;
+}
+
+/** `database_size_report` result, from the `dbstat` virtual table; error code `UNSUPPORTED` without it. */
+export interface SizeReport {
+  /** Every table and index b-tree of the main database, largest first. */
+  objects: Array<{
+    name: string;
+    kind: "table" | "index";
+    /** Table an index belongs to; the name again for a table. */
+    table: string;
+    pages: number;
+    bytes: number;
+    unused_bytes: number;
+    payload_bytes: number;
+  }>;
+  /** Sums over `objects`; free pages are not counted. */
+  pages: number;
+  bytes: number;
+  unused_bytes: number;
+}
+
+/** `begin` payload `mode`; defaults to "deferred". */
+export type TransactionMode = "deferred" | "immediate" | "exclusive";
+
+/**
+ * `query` payload `format`; other than "json", `data` is a `TextQueryResult` instead of a
+ * `QueryResult`. Its `text` is, with "csv", the CSV (header line unless `csv_header: false`; NULL
+ * as an empty field, "" as `""`, complete BLOBs as `base64:<data>`, cut-short ones as
+ * `<blob N bytes>`), with "ndjson" JSON lines: `{"columns": ColumnMeta[]}`, then one row object per
+ * line, with "markdown" a GitHub-flavored table (long cells shortened, NULL and BLOB placeholders).
+ */
+export type ResultFormat = "json" | "csv" | "ndjson" | "markdown";
+
+/** `query` result with `format` csv, ndjson or markdown; paging fields as in `QueryResult`. */
+export interface TextQueryResult {
+  text: string;
+  truncated: boolean;
+  next_offset: number | null;
+  next_cursor?: string;
+}
+
+export interface SchemaObject {
+  name: string;
+  /** `index` only appears in `DdlResult`. */
+  type: "table" | "view" | "trigger" | "index";
+  tbl_name: string;
+  sql?: string | null;
+  /** Virtual table module (`fts5`, `rtree`, ...); previews of these may behave differently. */
+  module?: string;
+  /** Internal table backing a virtual table, e.g. `docs_data` of an fts5 table `docs`. */
+  shadow?: boolean;
+}
+
+/** `triggers` payload; without `table` all triggers of the main database are returned. */
+export interface TriggersPayload {
+  path?: string;
+  table?: string;
+}
+
+export interface TriggerMeta {
+  name: string;
+  /** Table or view, spelled as in the trigger's DDL. */
+  table: string;
+  /** "BEFORE" is also SQLite's default when the DDL names none. */
+  timing: "BEFORE" | "AFTER" | "INSTEAD OF";
+  event: "INSERT" | "UPDATE" | "DELETE";
+  /** Columns of an `UPDATE OF a, b` trigger. */
+  columns?: string[];
+  sql: string | null;
+}
+
+/** `objects` payload; names are returned without the schema. */
+export interface ObjectsPayload {
+  path?: string;
+  /** "main" (default), "temp" or an attached alias. */
+  schema?: string;
+}
+
+/** `ddl` payload; without `table` the whole schema is returned. */
+export interface DdlPayload {
+  path?: string;
+  /** Table or view (`alias.table` for attached databases); its indexes and triggers are included. */
+  table?: string;
+}
+
+/** `ddl` result, in creation order as the sqlite3 shell's `.schema` prints it. */
+export interface DdlResult {
+  /** Internal autoindexes are listed with `sql: null`. */
+  objects: SchemaObject[];
+  /** The non-null statements, each terminated by `;\n`. */
+  sql: string;
+}
+
+/** `diff_schema` payload: compares `path` (database A, the active one by default) with `other`. */
+export interface DiffSchemaPayload {
+  other: string;
+  path?: string;
+}
+
+export interface ColumnDiff {
+  name: string;
+  /** Differing `ColumnMeta` fields, e.g. `["notnull", "default_value"]`. */
+  fields: string[];
+  a: ColumnMeta;
+  b: ColumnMeta;
+}
+
+export interface TableDiff {
+  name: string;
+  columns_only_in_a: string[];
+  columns_only_in_b: string[];
+  changed_columns: ColumnDiff[];
+  /** Both set when the `CREATE TABLE` statements differ beyond whitespace and keyword case. */
+  sql_a?: string | null;
+  sql_b?: string | null;
 }
 
+export interface ObjectRef {
+  type: "index" | "view" | "trigger";
+  name: string;
+}
+
+/** `diff_schema` result; only tables and objects that differ are listed. */
+export interface SchemaDiff {
+  identical: boolean;
+  tables_only_in_a: string[];
+  tables_only_in_b: string[];
+  changed_tables: TableDiff[];
+  objects_only_in_a: ObjectRef[];
+  objects_only_in_b: ObjectRef[];
+  changed_objects: ObjectRef[];
+}
+
+/**
+ * `cancel` payload: the `id` of an earlier request, which then fails with code `CANCELLED`.
+ * Running requests are stopped by interrupting their connection, so only SQL still executing
+ * is cut short.
+ */
+export interface CancelPayload {
+  id: string;
+}
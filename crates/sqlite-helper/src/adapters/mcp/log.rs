@@ -0,0 +1,192 @@
+//! Server-initiated log messages (`notifications/message`), filtered by the level the client
+//! picks with `logging/setLevel`.
+
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// RFC 5424 severities as MCP names them, least severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl LogLevel {
+    const ALL: [LogLevel; 8] = [
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Notice,
+        LogLevel::Warning,
+        LogLevel::Error,
+        LogLevel::Critical,
+        LogLevel::Alert,
+        LogLevel::Emergency,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Notice => "notice",
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error",
+            LogLevel::Critical => "critical",
+            LogLevel::Alert => "alert",
+            LogLevel::Emergency => "emergency",
+        }
+    }
+}
+
+/// Minimum level to send, shared by the read loop (which handles `logging/setLevel`) and the
+/// tasks answering tool calls. Until the client sets one, warnings and above are sent.
+#[derive(Debug, Clone)]
+pub struct McpLogger {
+    min: Arc<AtomicU8>,
+    slow_query_ms: Option<f64>,
+}
+
+impl McpLogger {
+    pub fn new(slow_query_ms: u64) -> Self {
+        Self {
+            min: Arc::new(AtomicU8::new(LogLevel::Warning as u8)),
+            slow_query_ms: (slow_query_ms > 0).then_some(slow_query_ms as f64),
+        }
+    }
+
+    pub fn set_level(&self, level: LogLevel) {
+        self.min.store(level as u8, Ordering::Relaxed);
+    }
+
+    /// The `notifications/message` for `data`, or `None` if `level` is below the client's.
+    pub fn message(&self, level: LogLevel, data: Value) -> Option<Value> {
+        let min = LogLevel::ALL[usize::from(self.min.load(Ordering::Relaxed))];
+        (level >= min).then(|| {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/message",
+                "params": { "level": level.as_str(), "logger": "sqlite-helper", "data": data }
+            })
+        })
+    }
+
+    /// Notable events in a successful tool result: slow statements, truncated rows and failed
+    /// integrity checks.
+    pub fn tool_events(&self, tool: &str, arguments: &Value, structured: &Value) -> Vec<Value> {
+        let db_path = arguments.get("db_path").cloned().unwrap_or(Value::Null);
+        let mut out = Vec::new();
+
+        let duration_ms = structured.get("duration_ms").and_then(Value::as_f64);
+        if let (Some(duration_ms), Some(slow_ms)) = (duration_ms, self.slow_query_ms) {
+            if duration_ms >= slow_ms {
+                out.extend(self.message(
+                    LogLevel::Warning,
+                    serde_json::json!({
+                        "event": "slow_query",
+                        "tool": tool,
+                        "db_path": db_path,
+                        "sql": arguments.get("sql"),
+                        "duration_ms": duration_ms,
+                    }),
+                ));
+            }
+        }
+
+        if structured.get("truncated").and_then(Value::as_bool) == Some(true) {
+            out.extend(self.message(
+                LogLevel::Info,
+                serde_json::json!({
+                    "event": "truncated",
+                    "tool": tool,
+                    "db_path": db_path,
+                    "rows": structured.get("rows").and_then(Value::as_array).map(Vec::len),
+                    "next_offset": structured.get("next_offset"),
+                    "next_cursor": structured.get("next_cursor"),
+                }),
+            ));
+        }
+
+        // `integrity_check` is an IntegrityReport.
+        if structured.pointer("/integrity_check/ok").and_then(Value::as_bool) == Some(false) {
+            out.extend(self.message(
+                LogLevel::Error,
+                serde_json::json!({
+                    "event": "integrity_check_failed",
+                    "db_path": db_path,
+                    "problems": structured.pointer("/integrity_check/errors"),
+                }),
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(log: &McpLogger, tool: &str, structured: Value) -> Vec<Value> {
+        let arguments = serde_json::json!({ "db_path": "/tmp/x.db", "sql": "SELECT 1" });
+        log.tool_events(tool, &arguments, &structured)
+            .into_iter()
+            .map(|msg| msg["params"]["data"].clone())
+            .collect()
+    }
+
+    #[test]
+    fn tool_events_report_slow_truncated_and_failed_checks() {
+        let log = McpLogger::new(100);
+        log.set_level(LogLevel::Debug);
+
+        let slow = events(&log, "read_query", serde_json::json!({ "duration_ms": 250.0, "rows": [] }));
+        assert_eq!(slow.len(), 1);
+        assert_eq!(slow[0]["event"], "slow_query");
+        assert_eq!(slow[0]["sql"], "SELECT 1");
+        assert!(events(&log, "read_query", serde_json::json!({ "duration_ms": 5.0 })).is_empty());
+
+        let truncated = serde_json::json!({ "rows": [{}, {}], "truncated": true, "next_offset": 2 });
+        let truncated = events(&log, "read_query", truncated);
+        assert_eq!(truncated[0]["event"], "truncated");
+        assert_eq!((truncated[0]["rows"].as_u64(), truncated[0]["next_offset"].as_u64()), (Some(2), Some(2)));
+
+        let failed = serde_json::json!({
+            "integrity_check": {
+                "check": "integrity_check",
+                "ok": false,
+                "errors": ["row 3 missing from index i"],
+            }
+        });
+        let failed = events(&log, "analyze_db_health", failed);
+        assert_eq!(failed[0]["event"], "integrity_check_failed");
+        assert_eq!(failed[0]["problems"], serde_json::json!(["row 3 missing from index i"]));
+        let healthy = serde_json::json!({
+            "integrity_check": { "check": "quick_check", "ok": true, "errors": [] }
+        });
+        assert!(events(&log, "analyze_db_health", healthy).is_empty());
+    }
+
+    #[test]
+    fn tool_events_respect_the_client_level() {
+        // Default level is warning: a truncation (info) is dropped, a slow query is not.
+        let log = McpLogger::new(100);
+        let structured = serde_json::json!({ "duration_ms": 250.0, "truncated": true });
+        let sent = events(&log, "read_query", structured);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0]["event"], "slow_query");
+
+        // With --slow-query-ms 0, nothing is slow.
+        let never_slow = McpLogger::new(0);
+        assert!(events(&never_slow, "read_query", serde_json::json!({ "duration_ms": 1e9 })).is_empty());
+    }
+}
@@ -1,50 +1,74 @@
-use std::path::{Component, Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
 use serde_json::Value;
-use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt};
+use tokio::{
+    io::{self, AsyncBufReadExt, AsyncWriteExt},
+    sync::{mpsc, Mutex},
+    task::JoinSet,
+};
 
 use crate::{
     cli::Args,
-    core::{connection::ConnectionManager, limits::effective_limit, schema},
+    core::{
+        blob::BlobRow,
+        cancel::{self, CancelRegistry},
+        csv, diff,
+        connection::{ConnectionManager, OpenMode, WorkerHandle},
+        limits::effective_limit,
+        markdown, ndjson,
+        paging::Page,
+        params::{ParamsInput, QueryParams},
+        paths::{self, validate_db_path},
+        rows::RowEdit,
+        schema,
+        types::{AttachedDatabase, BatchSql, DumpFrame, EncodeOptions, MaintenanceOp, ResultFormat, TransactionMode},
+    },
     error::{AppError, AppResult},
 };
 
+use super::log::{LogLevel, McpLogger};
+use crate::adapters::vscode_bridge::protocol::PROTOCOL_VERSION as BRIDGE_PROTOCOL_VERSION;
+
 /// MCP server (JSON-RPC 2.0 over stdio).
 ///
 /// Implements the minimal set required by RFC-001/RFC-002:
 /// - initialize
 /// - tools/list
-/// - tools/call: read_query, write_query, get_schema
-/// - resources/read (sqlite://.../tables/...)
+/// - tools/call: read_query, explain_query, write_query, execute_batch, begin/commit/rollback_transaction,
+///   read_blob, get_schema
+/// - resources/list, resources/templates/list, resources/read (sqlite://.../tables/...), with
+///   `notifications/resources/list_changed` when a database is opened for the first time
+///   (without `--allowed-dir`)
 /// - prompts/list, prompts/get (analyze-db-health)
+/// - logging/setLevel, with `notifications/message` for slow queries, truncated results and
+///   failed integrity checks
 pub async fn run(args: Args) -> AppResult<()> {
-    let cm = ConnectionManager::new();
+    let cm = ConnectionManager::new(args.connection_config());
+    let cancels = CancelRegistry::default();
+    let log = McpLogger::new(args.slow_query_ms);
+    // `cm.opened_count()` as of the last `notifications/resources/list_changed`.
+    let listed = Arc::new(AtomicUsize::new(0));
 
-    let mut stdin = io::BufReader::new(io::stdin());
-    let mut stdout = io::BufWriter::new(io::stdout());
-    let mut line = String::new();
+    let mut lines = spawn_line_reader();
+    let stdout: SharedWriter = Arc::new(Mutex::new(io::BufWriter::new(io::stdout())));
+    let mut in_flight = JoinSet::new();
 
-    loop {
-        line.clear();
-        let n = stdin.read_line(&mut line).await?;
-        if n == 0 {
-            break;
-        }
-        let raw = line.trim();
-        if raw.is_empty() {
+    while let Some(line) = lines.recv().await {
+        let Some(msg) = parse_message(&stdout, &line?).await? else {
             continue;
-        }
-
-        let msg: Value = match serde_json::from_str(raw) {
-            Ok(v) => v,
-            Err(e) => {
-                let resp = jsonrpc_error(Value::Null, -32700, format!("parse error: {e}"), None);
-                write_line(&mut stdout, &resp).await?;
-                continue;
-            }
         };
+        if handle_cancel_notification(&msg, &cancels) {
+            continue;
+        }
 
-        // Notifications (no id) are ignored.
+        // Other notifications (no id) are ignored.
         let id = msg.get("id").cloned().unwrap_or(Value::Null);
         if id.is_null() {
             continue;
@@ -52,47 +76,196 @@ pub async fn run(args: Args) -> AppResult<()> {
 
         let Some(method) = msg.get("method").and_then(|m| m.as_str()) else {
             let resp = jsonrpc_error(id, -32600, "invalid request: missing method".into(), None);
-            write_line(&mut stdout, &resp).await?;
+            write_line(&stdout, &resp).await?;
             continue;
         };
 
         let params = msg.get("params").cloned().unwrap_or(Value::Null);
 
+        // Calls that touch a database run on their own task so one slow query does not hold up
+        // the rest; each database still executes its requests in order on its worker thread.
+        // Everything else is answered inline, so `initialize` completes before any later call.
+        if matches!(method, "tools/call" | "resources/read" | "resources/list" | "completion/complete") {
+            let key = id.to_string();
+            let token = cancels.start(&key);
+            let (method, args, cm, cancels, log, stdout, listed) = (
+                method.to_string(),
+                args.clone(),
+                cm.clone(),
+                cancels.clone(),
+                log.clone(),
+                stdout.clone(),
+                listed.clone(),
+            );
+            in_flight.spawn(async move {
+                let handled = async {
+                    match method.as_str() {
+                        "tools/call" => handle_tools_call(id, params, &args, &cm, &log).await,
+                        "resources/list" => (handle_resources_list(id, params, &args, &cm).await, Vec::new()),
+                        "completion/complete" => {
+                            (handle_completion_complete(id, params, &args, &cm).await, Vec::new())
+                        }
+                        _ => (handle_resources_read(id, params, &args, &cm).await, Vec::new()),
+                    }
+                };
+                let (resp, notes) = cancel::scoped(token, handled).await;
+                // Per the MCP spec, a cancelled request gets no response at all; a database it
+                // opened is announced along with the next response.
+                if cancels.finish(&key) {
+                    return;
+                }
+                let list_changed = resources_list_changed(&args, &cm, &listed);
+                // Log notifications go first, so the client sees them alongside the result.
+                for msg in notes.iter().chain([&resp]).chain(list_changed.as_ref()) {
+                    if let Err(e) = write_line(&stdout, msg).await {
+                        tracing::warn!("failed to write response: {e}");
+                        return;
+                    }
+                }
+            });
+            continue;
+        }
+
         let resp = match method {
-            "initialize" => handle_initialize(id),
+            "initialize" => handle_initialize(id, params, &args),
             "tools/list" => handle_tools_list(id),
-            "tools/call" => handle_tools_call(id, params, &args, &cm).await,
-            "resources/list" => handle_resources_list(id),
-            "resources/read" => handle_resources_read(id, params, &args, &cm).await,
+            "resources/templates/list" => handle_resource_templates_list(id),
             "prompts/list" => handle_prompts_list(id),
             "prompts/get" => handle_prompts_get(id, params),
+            "logging/setLevel" => handle_set_level(id, params, &log),
             _ => jsonrpc_error(id, -32601, format!("method not found: {method}"), None),
         };
-
-        write_line(&mut stdout, &resp).await?;
+        write_line(&stdout, &resp).await?;
     }
 
+    // stdin closed: let in-flight calls answer before the workers go away.
+    while in_flight.join_next().await.is_some() {}
+    cm.shutdown().await;
     Ok(())
 }
 
-fn handle_initialize(id: Value) -> Value {
+/// Stdout shared by the read loop and in-flight calls; one message is written per lock.
+type SharedWriter = Arc<Mutex<io::BufWriter<io::Stdout>>>;
+
+/// Read stdin lines on a separate task so messages keep arriving while a request runs.
+fn spawn_line_reader() -> mpsc::UnboundedReceiver<AppResult<String>> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut stdin = io::BufReader::new(io::stdin());
+        loop {
+            let mut line = String::new();
+            match stdin.read_line(&mut line).await {
+                Ok(0) => break,
+                Ok(_) => {
+                    if tx.send(Ok(line)).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e.into()));
+                    break;
+                }
+            }
+        }
+    });
+    rx
+}
+
+/// Parse one JSON-RPC line; blank or malformed lines yield `None` (the latter after replying
+/// with a parse error).
+async fn parse_message(stdout: &SharedWriter, line: &str) -> AppResult<Option<Value>> {
+    let raw = line.trim();
+    if raw.is_empty() {
+        return Ok(None);
+    }
+    match serde_json::from_str(raw) {
+        Ok(v) => Ok(Some(v)),
+        Err(e) => {
+            let resp = jsonrpc_error(Value::Null, -32700, format!("parse error: {e}"), None);
+            write_line(stdout, &resp).await?;
+            Ok(None)
+        }
+    }
+}
+
+/// `notifications/resources/list_changed` if a database was opened for the first time since the
+/// last one. Only without `--allowed-dir`: with it, the list comes from scanning the directories,
+/// which opening a database does not change.
+fn resources_list_changed(args: &Args, cm: &ConnectionManager, listed: &AtomicUsize) -> Option<Value> {
+    if !args.allowed_dir.is_empty() {
+        return None;
+    }
+    let opened = cm.opened_count();
+    (listed.fetch_max(opened, Ordering::SeqCst) < opened)
+        .then(|| serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/resources/list_changed" }))
+}
+
+/// Handle `notifications/cancelled` by abandoning the request's worker tasks: queued ones are
+/// skipped, and a running statement is interrupted only if it is the request's own. Returns
+/// `false` for any other message.
+fn handle_cancel_notification(msg: &Value, cancels: &CancelRegistry) -> bool {
+    if msg.get("method").and_then(|m| m.as_str()) != Some("notifications/cancelled") {
+        return false;
+    }
+    if let Some(request_id) = msg.get("params").and_then(|p| p.get("requestId")) {
+        cancels.cancel(&request_id.to_string());
+    }
+    true
+}
+
+/// MCP protocol revisions this server implements, newest first.
+const MCP_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// The client's requested revision if we implement it, otherwise our newest (the client then
+/// decides whether it can work with that).
+fn negotiate_protocol_version(requested: Option<&str>) -> &'static str {
+    MCP_PROTOCOL_VERSIONS
+        .iter()
+        .find(|v| Some(**v) == requested)
+        .unwrap_or(&MCP_PROTOCOL_VERSIONS[0])
+}
+
+/// `initialize`: negotiates the MCP revision, and advertises the sqlite-helper protocol
+/// version (`--protocol-version`, shared with the bridge) as an experimental capability.
+fn handle_initialize(id: Value, params: Value, args: &Args) -> Value {
+    let requested = params.get("protocolVersion").and_then(Value::as_str);
     serde_json::json!({
         "jsonrpc": "2.0",
         "id": id,
         "result": {
+            "protocolVersion": negotiate_protocol_version(requested),
             "serverInfo": {
                 "name": "sqlite-helper",
                 "version": env!("CARGO_PKG_VERSION")
             },
             "capabilities": {
                 "tools": { "listChanged": false },
-                "resources": { },
-                "prompts": { }
+                "resources": { "listChanged": true },
+                "prompts": { },
+                "logging": { },
+                "completions": { },
+                "experimental": {
+                    "sqliteHelper": {
+                        "protocolVersion": args.protocol_version.unwrap_or(BRIDGE_PROTOCOL_VERSION)
+                    }
+                }
             }
         }
     })
 }
 
+/// `logging/setLevel`: the minimum severity of `notifications/message` sent from now on.
+fn handle_set_level(id: Value, params: Value, log: &McpLogger) -> Value {
+    let level = params.get("level").cloned().map(serde_json::from_value::<LogLevel>);
+    match level {
+        Some(Ok(level)) => {
+            log.set_level(level);
+            serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": {} })
+        }
+        _ => jsonrpc_error(id, -32602, "invalid params: unknown or missing level".into(), None),
+    }
+}
+
 fn handle_tools_list(id: Value) -> Value {
     // MCP tools/list: https://modelcontextprotocol.io/specification/.../server/tools
     serde_json::json!({
@@ -100,6 +273,25 @@ fn handle_tools_list(id: Value) -> Value {
         "id": id,
         "result": {
             "tools": [
+                {
+                    "name": "list_databases",
+                    "description": "Find SQLite database files under the server's allowed directories (--allowed-dir), returning path, size_bytes and modified_ms for each. Only files that start with the SQLite header are listed. Fails if the server has no allowed directories.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "pattern": {
+                                "type": "string",
+                                "description": "Comma-separated file name globs (* and ?, case-insensitive); default \"*.db,*.sqlite,*.sqlite3,*.db3\"."
+                            },
+                            "max_depth": {
+                                "type": "integer",
+                                "minimum": 0,
+                                "maximum": 16,
+                                "description": "Directory levels to descend below each allowed directory (default 4)."
+                            }
+                        }
+                    }
+                },
                 {
                     "name": "read_query",
                     "description": "Execute a read-only SQL query (SELECT/PRAGMA/EXPLAIN) to analyze data.",
@@ -108,27 +300,349 @@ fn handle_tools_list(id: Value) -> Value {
                         "properties": {
                             "db_path": { "type": "string" },
                             "sql": { "type": "string" },
+                            "params": {
+                                "type": ["array", "object"],
+                                "description": "Bound parameters: array for positional `?`, object for named `:name`. Blobs as {\"$type\":\"blob\",\"base64\":\"...\"}."
+                            },
+                            "named_params": {
+                                "type": "object",
+                                "description": "Named parameters (`:name`, `@name`, `$name`); may be combined with positional params."
+                            },
                             "limit": { "type": "integer", "minimum": 1 },
-                            "offset": { "type": "integer", "minimum": 0 }
+                            "offset": { "type": "integer", "minimum": 0 },
+                            "cursor_key": {
+                                "type": "string",
+                                "description": "Page by this result column (unique integers, e.g. the INTEGER PRIMARY KEY) instead of offset; rows are ordered by it and truncated results return next_cursor. SELECT statements only."
+                            },
+                            "cursor": {
+                                "type": "string",
+                                "description": "next_cursor from the previous page of the same sql."
+                            },
+                            "float_mode": {
+                                "type": "string",
+                                "enum": ["sentinel", "null"],
+                                "description": "Encoding for NaN/Infinity REAL values (default: sentinel object)."
+                            },
+                            "int_mode": {
+                                "type": "string",
+                                "enum": ["number", "string", "lossless"],
+                                "description": "Encoding for integers beyond ±(2^53 - 1): plain number, decimal string, or {\"$type\":\"int64\"} object."
+                            },
+                            "blob_mode": {
+                                "type": "string",
+                                "enum": ["truncate", "metadata", "full"],
+                                "description": "Handling of BLOBs larger than --max-blob-bytes (default: truncate)."
+                            },
+                            "timeout_ms": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "description": "Per-request deadline in ms; capped at the server's --timeout-ms."
+                            },
+                            "format": {
+                                "type": "string",
+                                "enum": ["json", "csv", "ndjson", "markdown"],
+                                "description": "csv renders the text as RFC 4180 CSV (NULL as an empty field, the empty string as \"\", complete BLOBs as base64:<data>, others as <blob N bytes>); markdown renders it as a table (long cells shortened); both keep the full JSON result in structuredContent. ndjson returns JSON lines, a {\"columns\": [...]} line then one object per row."
+                            },
+                            "text_format": {
+                                "type": "string",
+                                "enum": ["json", "markdown", "csv"],
+                                "description": "Rendering of the text content only; when both are given it wins over format (csv_header then only applies if it is csv). structuredContent stays the full JSON result (default: as format). Not combinable with format ndjson."
+                            },
+                            "max_cell_chars": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "description": "Markdown cells longer than this are cut off with … (default 80)."
+                            },
+                            "csv_header": {
+                                "type": "boolean",
+                                "description": "With format csv, start with a line of column names (default true)."
+                            },
+                            "readonly": {
+                                "type": "boolean",
+                                "description": "Run on a connection opened read-only (SQLITE_OPEN_READ_ONLY), so nothing can write."
+                            },
+                            "timing": {
+                                "type": "boolean",
+                                "description": "Add elapsed_ms: wall time on the server including the wait for the worker and row value encoding (not writing the response), to compare with duration_ms (time inside SQLite)."
+                            },
+                            "multi": {
+                                "type": "boolean",
+                                "description": "Run every statement of a script of SELECTs and return {results: [{index, sql, columns, rows, ...}]}, limit applying to each. Fails before running anything if a statement writes. Not combinable with params, offset, cursor or a csv, ndjson or markdown format."
+                            }
+                        },
+                        "required": ["db_path", "sql"]
+                    }
+                },
+                {
+                    "name": "explain_query",
+                    "description": "Show the EXPLAIN QUERY PLAN for a read-only SQL query without running it: the raw rows, a nested tree, and per table whether it is reached through an index (uses_index false means a full scan; useful for spotting missing indexes).",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "sql": { "type": "string" },
+                            "params": {
+                                "type": ["array", "object"],
+                                "description": "Optional bound parameters; unbound parameters are planned as NULL."
+                            },
+                            "named_params": { "type": "object" }
                         },
                         "required": ["db_path", "sql"]
                     }
                 },
+                {
+                    "name": "search",
+                    "description": "Full-text search of an FTS5 table (CREATE VIRTUAL TABLE ... USING fts5; get_schema marks them module: fts5): rows matching an FTS5 query, best first, each with its rowid and score (bm25, lower is better).",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "table": {
+                                "type": "string",
+                                "description": "FTS5 table name, alias.table for an attached database."
+                            },
+                            "query": {
+                                "type": "string",
+                                "description": "FTS5 query, e.g. sqlite AND \"full text\" or title:rust*; bound as a parameter."
+                            },
+                            "limit": { "type": "integer", "minimum": 1 },
+                            "offset": { "type": "integer", "minimum": 0 }
+                        },
+                        "required": ["db_path", "table", "query"]
+                    }
+                },
                 {
                     "name": "write_query",
-                    "description": "Execute a write SQL query (INSERT/UPDATE/DELETE/DDL). Requires user confirmation in the client.",
+                    "description": "Execute a write SQL query (INSERT/UPDATE/DELETE/DDL). Rows from a RETURNING clause are included as `rows`. Requires user confirmation in the client.",
                     "inputSchema": {
                         "type": "object",
                         "properties": {
                             "db_path": { "type": "string" },
-                            "sql": { "type": "string" }
+                            "sql": { "type": "string" },
+                            "params": {
+                                "type": ["array", "object"],
+                                "description": "Bound parameters: array for positional `?`, object for named `:name`. Blobs as {\"$type\":\"blob\",\"base64\":\"...\"}."
+                            },
+                            "named_params": {
+                                "type": "object",
+                                "description": "Named parameters (`:name`, `@name`, `$name`); may be combined with positional params."
+                            },
+                            "timeout_ms": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "description": "Per-request deadline in ms; capped at the server's --timeout-ms."
+                            },
+                            "dry_run": {
+                                "type": "boolean",
+                                "description": "Run the statement, report the rows it would change, then roll it back (default false)."
+                            },
+                            "create": {
+                                "type": "boolean",
+                                "description": "Create db_path if it does not exist (default false: a missing file is an error)."
+                            }
                         },
                         "required": ["db_path", "sql"]
                     }
                 },
                 {
-                    "name": "get_schema",
-                    "description": "Get database structure (tables and columns).",
+                    "name": "insert_row",
+                    "description": "Insert one row without writing SQL: INSERT INTO table (columns) VALUES (...) with bound values, or DEFAULT VALUES when values is empty. Returns changes and the new row's last_insert_rowid. Requires user confirmation in the client.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "table": { "type": "string" },
+                            "values": {
+                                "type": "object",
+                                "description": "Column -> value (default {}: every column takes its default). Blobs as {\"$type\":\"blob\",\"base64\":\"...\"}."
+                            }
+                        },
+                        "required": ["db_path", "table"]
+                    }
+                },
+                {
+                    "name": "update_row",
+                    "description": "Update one row, addressed by its full primary key, without writing SQL: UPDATE table SET ... WHERE pk = ... with bound values. Fails for tables without a primary key, for keys that leave out a primary key column, and (unless allow_no_match) when no row has the key. Requires user confirmation in the client.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "table": { "type": "string" },
+                            "key": {
+                                "type": "object",
+                                "description": "Every primary key column (and only those) -> the row's value."
+                            },
+                            "set": {
+                                "type": "object",
+                                "description": "Column -> new value. Blobs as {\"$type\":\"blob\",\"base64\":\"...\"}."
+                            },
+                            "allow_no_match": {
+                                "type": "boolean",
+                                "description": "Report changes: 0 instead of failing when no row has the key (default false)."
+                            }
+                        },
+                        "required": ["db_path", "table", "key", "set"]
+                    }
+                },
+                {
+                    "name": "delete_row",
+                    "description": "Delete one row, addressed by its full primary key (see update_row). Requires user confirmation in the client.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "table": { "type": "string" },
+                            "key": {
+                                "type": "object",
+                                "description": "Every primary key column (and only those) -> the row's value."
+                            },
+                            "allow_no_match": {
+                                "type": "boolean",
+                                "description": "Report changes: 0 instead of failing when no row has the key (default false)."
+                            }
+                        },
+                        "required": ["db_path", "table", "key"]
+                    }
+                },
+                {
+                    "name": "execute_batch",
+                    "description": "Execute a multi-statement SQL script (e.g. a migration) atomically. Returns total and per-statement changes; the first failing statement rolls back the whole batch. Requires user confirmation in the client.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "sql": {
+                                "anyOf": [
+                                    { "type": "string" },
+                                    { "type": "array", "items": { "type": "string" } }
+                                ],
+                                "description": "Statements separated by `;`, or an array of such scripts run in one transaction."
+                            }
+                        },
+                        "required": ["db_path", "sql"]
+                    }
+                },
+                {
+                    "name": "begin_transaction",
+                    "description": "Begin an explicit transaction on db_path; subsequent write_query/execute_batch calls stay uncommitted until commit_transaction or rollback_transaction. Requires user confirmation in the client.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "mode": {
+                                "type": "string",
+                                "enum": ["deferred", "immediate", "exclusive"],
+                                "description": "Locking mode (default deferred)."
+                            }
+                        },
+                        "required": ["db_path"]
+                    }
+                },
+                {
+                    "name": "commit_transaction",
+                    "description": "Commit the transaction opened by begin_transaction. Requires user confirmation in the client.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": { "db_path": { "type": "string" } },
+                        "required": ["db_path"]
+                    }
+                },
+                {
+                    "name": "rollback_transaction",
+                    "description": "Discard all changes made since begin_transaction.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": { "db_path": { "type": "string" } },
+                        "required": ["db_path"]
+                    }
+                },
+                {
+                    "name": "read_blob",
+                    "description": "Read the binary content of one BLOB cell (base64), optionally in chunks via offset/length.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "table": { "type": "string" },
+                            "column": { "type": "string" },
+                            "rowid": { "type": "integer" },
+                            "pk": {
+                                "type": "object",
+                                "description": "Primary-key column values identifying the row (alternative to rowid)."
+                            },
+                            "offset": { "type": "integer", "minimum": 0 },
+                            "length": { "type": "integer", "minimum": 0, "description": "Bytes to read (default: --max-blob-bytes)." }
+                        },
+                        "required": ["db_path", "table", "column"]
+                    }
+                },
+                {
+                    "name": "dump_schema",
+                    "description": "Export the database schema (CREATE statements) as a SQL script.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "only": {
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "description": "Limit to these tables (plus their indexes and triggers)."
+                            }
+                        },
+                        "required": ["db_path"]
+                    }
+                },
+                {
+                    "name": "dump_all",
+                    "description": "Export schema and data as a SQL script of CREATE and INSERT statements that recreates the database.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "only": {
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "description": "Limit to these tables (plus their indexes and triggers)."
+                            }
+                        },
+                        "required": ["db_path"]
+                    }
+                },
+                {
+                    "name": "optimize_db",
+                    "description": "Database maintenance: run PRAGMA optimize, ANALYZE and/or VACUUM in the given order and report timing and bytes freed. VACUUM rewrites the whole file, needs no open transaction and can take long on large databases.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "operations": {
+                                "type": "array",
+                                "items": { "type": "string", "enum": ["optimize", "analyze", "vacuum"] },
+                                "description": "Steps to run, in order (default [\"optimize\"])."
+                            }
+                        },
+                        "required": ["db_path"]
+                    }
+                },
+                {
+                    "name": "backup_db",
+                    "description": "Snapshot db_path to dest with SQLite's online backup API; safe while the database is in use, but fails with DB_BUSY if it stays locked for 5 s. dest must be inside the allowed directories.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "dest": { "type": "string", "description": "Destination file path." },
+                            "overwrite": {
+                                "type": "boolean",
+                                "description": "Replace dest if it already exists (default false)."
+                            }
+                        },
+                        "required": ["db_path", "dest"]
+                    }
+                },
+                {
+                    "name": "close_database",
+                    "description": "Close db_path's connection so the file can be moved, deleted or replaced; rolls back any open transaction. Later calls reopen it.",
                     "inputSchema": {
                         "type": "object",
                         "properties": {
@@ -137,9 +651,150 @@ fn handle_tools_list(id: Value) -> Value {
                         "required": ["db_path"]
                     }
                 },
+                {
+                    "name": "attach_database",
+                    "description": "ATTACH another database file to db_path's connection so queries can join across both; its tables are addressed as alias.table. Stays attached until detach_database, including after the connection is reopened.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "alias": {
+                                "type": "string",
+                                "description": "Schema name for the attached database: letters, digits and _, not main or temp."
+                            },
+                            "file": { "type": "string", "description": "Database file to attach (within --allowed-dir)." }
+                        },
+                        "required": ["db_path", "alias", "file"]
+                    }
+                },
+                {
+                    "name": "detach_database",
+                    "description": "DETACH a database attached with attach_database.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "alias": { "type": "string" }
+                        },
+                        "required": ["db_path", "alias"]
+                    }
+                },
+                {
+                    "name": "get_schema",
+                    "description": "Get database structure: tables with their columns, indexes, foreign keys and triggers (with timing and event; virtual tables carry their `module`, e.g. fts5, and their internal tables `shadow: true`), plus views (with their INSTEAD OF triggers) and all triggers with their SQL definitions.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "include_row_counts": {
+                                "type": "boolean",
+                                "description": "Add a `row_count` per table (exact, i.e. a full scan, unless `approximate`; null if it failed, took over 5s, or was reached after 15s of counting; default false)."
+                            },
+                            "approximate": {
+                                "type": "boolean",
+                                "description": "Take row counts from the last ANALYZE (sqlite_stat1) instead of counting; null for tables never analyzed (default false)."
+                            },
+                            "include_attached": {
+                                "type": "boolean",
+                                "description": "Also describe the tables of attached databases, named alias.table (default false)."
+                            },
+                            "schema": {
+                                "type": "string",
+                                "description": "Describe this database of the connection instead of main: temp or an attached alias; its tables are named alias.table (default main)."
+                            },
+                            "text_format": {
+                                "type": "string",
+                                "enum": ["json", "markdown", "csv"],
+                                "description": "markdown or csv renders the text content as one row per column of every table and view (table, column, type, pk, not null, default); structuredContent stays the full JSON schema (default json)."
+                            },
+                            "max_cell_chars": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "description": "Markdown cells longer than this are cut off with … (default 80)."
+                            }
+                        },
+                        "required": ["db_path"]
+                    }
+                },
+                {
+                    "name": "get_ddl",
+                    "description": "Get the stored CREATE statements, like the sqlite3 shell's .schema: of one table (or view) with its indexes and triggers, or of the whole database, in creation order. The text is the SQL script; structured output also lists each object (internal autoindexes with sql null).",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "table": {
+                                "type": "string",
+                                "description": "Table or view name, alias.table for an attached database (default: whole main schema)."
+                            }
+                        },
+                        "required": ["db_path"]
+                    }
+                },
+                {
+                    "name": "diff_schema",
+                    "description": "Compare the schemas of two databases: tables only in one of them, per-table column differences (type, notnull, default, pk, ...) and indexes, views and triggers added, removed or changed. Names are matched ignoring case, and statements are compared with whitespace and keyword case normalized.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string", "description": "Database A." },
+                            "other_db_path": { "type": "string", "description": "Database B, compared against A." }
+                        },
+                        "required": ["db_path", "other_db_path"]
+                    }
+                },
                 {
                     "name": "analyze_db_health",
-                    "description": "Run PRAGMA integrity_check and return a health report.",
+                    "description": "Run PRAGMA integrity_check (or quick_check) and return a health report: every problem found (up to --max-rows, with truncated set beyond), storage figures from cheap pragmas (page_size, page_count, freelist_count, free_bytes, fragmentation_ratio, auto_vacuum, journal_mode, user_version, application_id, encoding) with a VACUUM recommendation when free pages pass a threshold, and index_advice: findings (severity info or warn) on foreign keys without an index, redundant indexes and tables never ANALYZEd, each with a suggested_sql statement (CREATE INDEX, DROP INDEX or ANALYZE) to review and run with write_query.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "include_row_counts": {
+                                "type": "boolean",
+                                "description": "Add an exact `row_count` per table (full scan; default false)."
+                            },
+                            "quick": {
+                                "type": "boolean",
+                                "description": "Run PRAGMA quick_check instead of the full integrity_check: much faster on large files, but does not verify that indexes match their tables (default false)."
+                            },
+                            "vacuum_threshold_pct": {
+                                "type": "number",
+                                "minimum": 0,
+                                "maximum": 100,
+                                "description": "Recommend VACUUM (or incremental_vacuum) when more than this percentage of pages are free (default 25)."
+                            },
+                            "include_size_breakdown": {
+                                "type": "boolean",
+                                "description": "Add `size_breakdown`, the database_size_report result (reads every page; default false)."
+                            }
+                        },
+                        "required": ["db_path"]
+                    }
+                },
+                {
+                    "name": "foreign_key_check",
+                    "description": "Find rows breaking a foreign key (orphans left while enforcement was off) with PRAGMA foreign_key_check: {violations: [{table, rowid, references, constraint_index, from, to}], total, by_table, truncated}; violations is [] for a clean database.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "db_path": { "type": "string" },
+                            "table": {
+                                "type": "string",
+                                "description": "Check only this table, alias.table for an attached database (default: every table of the main database)."
+                            },
+                            "limit": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "description": "List at most this many violations (capped by --max-rows); all are counted."
+                            }
+                        },
+                        "required": ["db_path"]
+                    }
+                },
+                {
+                    "name": "database_size_report",
+                    "description": "Explain where a database's space goes: pages, bytes and unused bytes per table and index (from the dbstat virtual table), largest first, with totals: {objects: [{name, kind, table, pages, bytes, unused_bytes, payload_bytes}], pages, bytes, unused_bytes}. Free pages are not included (see analyze_db_health storage.free_bytes). Reads every page, so it can be slow on very large files; fails with UNSUPPORTED if SQLite was built without dbstat.",
                     "inputSchema": {
                         "type": "object",
                         "properties": {
@@ -153,20 +808,58 @@ fn handle_tools_list(id: Value) -> Value {
     })
 }
 
-async fn handle_tools_call(id: Value, params: Value, args: &Args, cm: &ConnectionManager) -> Value {
+async fn handle_tools_call(
+    id: Value,
+    params: Value,
+    args: &Args,
+    cm: &ConnectionManager,
+    log: &McpLogger,
+) -> (Value, Vec<Value>) {
     let Some(name) = params.get("name").and_then(|v| v.as_str()) else {
-        return jsonrpc_error(id, -32602, "invalid params: missing name".into(), None);
+        return (jsonrpc_error(id, -32602, "invalid params: missing name".into(), None), Vec::new());
     };
     let arguments = params.get("arguments").cloned().unwrap_or(Value::Null);
+    let res = call_tool(name, arguments.clone(), args, cm).await;
+    let notes = match &res {
+        Ok((_, structured)) => log.tool_events(name, &arguments, structured),
+        Err(_) => Vec::new(),
+    };
+    (tool_result(id, res), notes)
+}
 
-    let res = match name {
+async fn call_tool(name: &str, arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    match name {
         "read_query" => tool_read_query(arguments, args, cm).await,
+        "explain_query" => tool_explain_query(arguments, args, cm).await,
+        "search" => tool_search(arguments, args, cm).await,
         "write_query" => tool_write_query(arguments, args, cm).await,
+        "insert_row" => tool_insert_row(arguments, args, cm).await,
+        "update_row" => tool_edit_row(arguments, args, cm, false).await,
+        "delete_row" => tool_edit_row(arguments, args, cm, true).await,
+        "execute_batch" => tool_execute_batch(arguments, args, cm).await,
+        "begin_transaction" | "commit_transaction" | "rollback_transaction" => {
+            tool_transaction(name, arguments, args, cm).await
+        }
+        "read_blob" => tool_read_blob(arguments, args, cm).await,
+        "dump_schema" => tool_dump(arguments, args, cm, false).await,
+        "dump_all" => tool_dump(arguments, args, cm, true).await,
+        "optimize_db" => tool_optimize_db(arguments, args, cm).await,
+        "backup_db" => tool_backup_db(arguments, args, cm).await,
+        "close_database" => tool_close_database(arguments, args, cm).await,
+        "attach_database" => tool_attach_database(arguments, args, cm).await,
+        "detach_database" => tool_detach_database(arguments, args, cm).await,
         "get_schema" => tool_get_schema(arguments, args, cm).await,
+        "get_ddl" => tool_get_ddl(arguments, args, cm).await,
+        "diff_schema" => tool_diff_schema(arguments, args, cm).await,
+        "list_databases" => tool_list_databases(arguments, args).await,
         "analyze_db_health" => tool_analyze_db_health(arguments, args, cm).await,
+        "foreign_key_check" => tool_foreign_key_check(arguments, args, cm).await,
+        "database_size_report" => tool_database_size_report(arguments, args, cm).await,
         other => Err(AppError::InvalidRequest(format!("unknown tool: {other}"))),
-    };
+    }
+}
 
+fn tool_result(id: Value, res: AppResult<(String, Value)>) -> Value {
     match res {
         Ok((text, structured)) => serde_json::json!({
             "jsonrpc": "2.0",
@@ -177,49 +870,205 @@ async fn handle_tools_call(id: Value, params: Value, args: &Args, cm: &Connectio
                 "isError": false
             }
         }),
-        Err(e) => serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": id,
-            "result": {
+        Err(e) => {
+            let mut result = serde_json::json!({
                 "content": [{ "type": "text", "text": format!("{}: {}", e.code(), e) }],
                 "isError": true
+            });
+            if let Some(details) = e.details() {
+                result["structuredContent"] = serde_json::json!({ "code": e.code(), "details": details });
             }
-        }),
+            serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result })
+        }
     }
 }
 
-fn handle_resources_list(id: Value) -> Value {
-    // Resources are dynamic (depend on db path), so we don't enumerate here.
+const TABLE_URI_TEMPLATE: &str = "sqlite://{db_path}/tables/{table}";
+const TABLE_SCHEMA_URI_TEMPLATE: &str = "sqlite://{db_path}/tables/{table}/schema";
+
+/// Most values one `completion/complete` answer may carry, per the MCP spec.
+const MAX_COMPLETIONS: usize = 100;
+
+/// The URI shapes `resources/read` understands, so clients can build table URIs without a
+/// `resources/list` round trip.
+fn handle_resource_templates_list(id: Value) -> Value {
     serde_json::json!({
         "jsonrpc": "2.0",
         "id": id,
-        "result": { "resources": [] }
+        "result": {
+            "resourceTemplates": [
+                {
+                    "uriTemplate": TABLE_URI_TEMPLATE,
+                    "name": "sqlite-table",
+                    "title": "SQLite table preview",
+                    "description": "First rows of a table; append ?limit=N&offset=M to page through it, ?format=csv (header=false to leave out column names) for CSV text. db_path is the absolute path of the database file (within --allowed-dir), table a table or schema.table name.",
+                    "mimeType": "application/json"
+                },
+                {
+                    "uriTemplate": "sqlite://{db_path}/schema",
+                    "name": "sqlite-schema",
+                    "title": "SQLite database schema",
+                    "description": "Tables (columns, indexes, foreign keys, triggers), views and triggers of a database, as the get_schema tool returns them.",
+                    "mimeType": "application/json"
+                },
+                {
+                    "uriTemplate": TABLE_SCHEMA_URI_TEMPLATE,
+                    "name": "sqlite-table-schema",
+                    "title": "SQLite table schema",
+                    "description": "Columns, indexes, foreign keys and triggers of one table or view, with its CREATE statements in sql.",
+                    "mimeType": "application/json"
+                }
+            ]
+        }
     })
 }
 
+/// `completion/complete` for the `table` argument of the table URI templates: tables and views of
+/// the `db_path` already filled in (`context.arguments`) whose name starts with the typed value,
+/// ignoring case. Other templates and arguments, a missing or disallowed `db_path` and databases
+/// that cannot be opened all complete to an empty list. Databases that are not open stay closed.
+async fn handle_completion_complete(id: Value, params: Value, args: &Args, cm: &ConnectionManager) -> Value {
+    if params.get("ref").is_none() || params.pointer("/argument/name").is_none() {
+        return jsonrpc_error(id, -32602, "invalid params: missing ref or argument".into(), None);
+    }
+    let names = complete_table(&params, args, cm).await.unwrap_or_default();
+    let total = names.len();
+    let values: Vec<String> = names.into_iter().take(MAX_COMPLETIONS).collect();
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "result": {
+            "completion": { "values": values, "total": total, "hasMore": total > MAX_COMPLETIONS }
+        }
+    })
+}
+
+async fn complete_table(params: &Value, args: &Args, cm: &ConnectionManager) -> Option<Vec<String>> {
+    let is_template = params.pointer("/ref/type")?.as_str()? == "ref/resource"
+        && [TABLE_URI_TEMPLATE, TABLE_SCHEMA_URI_TEMPLATE].contains(&params.pointer("/ref/uri")?.as_str()?);
+    if !is_template || params.pointer("/argument/name")?.as_str()? != "table" {
+        return None;
+    }
+    let prefix = params.pointer("/argument/value").and_then(Value::as_str).unwrap_or_default();
+    let prefix = prefix.to_lowercase();
+    let db_path = params.pointer("/context/arguments/db_path")?.as_str()?;
+    let db_path = validate_db_path(Path::new(db_path), &args.allowed_dir, args.allow_uri).ok()?;
+    // Called per keystroke: use an open worker, never leave a new one behind.
+    let objects = cm.peek_objects(&db_path).await.ok()?;
+    Some(
+        objects
+            .into_iter()
+            .filter(|o| matches!(o.kind.as_str(), "table" | "view") && !o.shadow)
+            .filter(|o| o.name.to_lowercase().starts_with(&prefix))
+            .map(|o| o.name)
+            .collect(),
+    )
+}
+
+/// Databases per `resources/list` page; each contributes one resource per table.
+const RESOURCE_PAGE_DBS: usize = 20;
+
+/// Most database files `resources/list` and `list_databases` consider.
+const RESOURCE_SCAN_CAP: usize = 1000;
+
+/// Deepest directory scan `list_databases` accepts.
+const LIST_DATABASES_MAX_DEPTH: usize = 16;
+
+/// A `sqlite://{path}/schema` resource per database and a `sqlite://{path}/tables/{table}` one
+/// per table or view (shadow tables of virtual tables are internal and left out). With `--allowed-dir`, of every
+/// database file `list_databases` finds by default; without one (everything is allowed, so there is
+/// nothing sensible to scan), of every database opened this session, which is announced with
+/// `notifications/resources/list_changed`. Paged by database via `cursor`; table names are read
+/// without keeping a worker open per listed database.
+async fn handle_resources_list(id: Value, params: Value, args: &Args, cm: &ConnectionManager) -> Value {
+    let dbs = if args.allowed_dir.is_empty() {
+        cm.opened_paths()
+    } else {
+        let dirs = args.allowed_dir.clone();
+        let scan = tokio::task::spawn_blocking(move || {
+            paths::find_databases(&dirs, paths::DEFAULT_DB_PATTERNS, paths::MAX_SCAN_DEPTH, RESOURCE_SCAN_CAP)
+        });
+        match scan.await {
+            Ok(found) => found.into_iter().map(|db| db.path).collect(),
+            Err(e) => return jsonrpc_error(id, -32603, format!("scanning for databases failed: {e}"), None),
+        }
+    };
+    let start = match params.get("cursor") {
+        None | Some(Value::Null) => 0,
+        Some(cursor) => match cursor.as_str().and_then(|c| c.parse::<usize>().ok()) {
+            Some(n) => n,
+            None => return jsonrpc_error(id, -32602, "invalid params: invalid cursor".into(), None),
+        },
+    };
+    let end = start.saturating_add(RESOURCE_PAGE_DBS).min(dbs.len());
+
+    let mut resources = Vec::new();
+    for db in dbs.get(start..end).unwrap_or_default() {
+        // Files that merely look like databases (or are locked) are left out of the list.
+        match table_resources(db, cm).await {
+            Ok(r) => resources.extend(r),
+            Err(e) => tracing::debug!("resources/list: skipping {}: {e}", db.display()),
+        }
+    }
+    let mut result = serde_json::json!({ "resources": resources });
+    if end < dbs.len() {
+        result["nextCursor"] = Value::String(end.to_string());
+    }
+    serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+async fn table_resources(db: &Path, cm: &ConnectionManager) -> AppResult<Vec<Value>> {
+    // Listing must not leave a worker behind for every database it looked at.
+    let objects = cm.peek_objects(db).await?;
+    let file_name = db.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let mut resources = vec![serde_json::json!({
+        "uri": format!("sqlite://{}/schema", db.display()),
+        "name": format!("{file_name}: schema"),
+        "description": format!("Tables, views and triggers of {}", db.display()),
+        "mimeType": "application/json"
+    })];
+    let tables = objects.into_iter().filter(|o| matches!(o.kind.as_str(), "table" | "view") && !o.shadow);
+    resources.extend(tables.map(|o| {
+        let t = &o.name;
+        let kind = match &o.module {
+            Some(module) => format!("{module} virtual table"),
+            None => o.kind.clone(),
+        };
+        serde_json::json!({
+            "uri": format!("sqlite://{}/tables/{}", db.display(), percent_encode(t)),
+            "name": format!("{file_name}: {t}"),
+            "description": format!("First rows of {kind} {t} in {}", db.display()),
+            "mimeType": "application/json"
+        })
+    }));
+    Ok(resources)
+}
+
 async fn handle_resources_read(id: Value, params: Value, args: &Args, cm: &ConnectionManager) -> Value {
     let Some(uri) = params.get("uri").and_then(|v| v.as_str()) else {
         return jsonrpc_error(id, -32602, "invalid params: missing uri".into(), None);
     };
 
-    match read_sqlite_table_resource(uri, args, cm).await {
-        Ok((text, structured)) => serde_json::json!({
+    // Not part of MCP's `resources/read`; clients that want CSV without `?format=csv` pass it.
+    let mime_hint = params.get("mimeType").and_then(|v| v.as_str());
+
+    match read_sqlite_resource(uri, mime_hint, args, cm).await {
+        Ok((text, structured, mime_type)) => serde_json::json!({
             "jsonrpc": "2.0",
             "id": id,
             "result": {
                 "contents": [{
                     "uri": uri,
-                    "mimeType": "application/json",
+                    "mimeType": mime_type,
                     "text": text
                 }],
                 "structuredContent": structured
             }
         }),
-        Err(e) => serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": id,
-            "error": { "code": -32000, "message": format!("{}: {}", e.code(), e) }
-        }),
+        Err(e) => {
+            let data = e.details().map(|details| serde_json::json!({ "code": e.code(), "details": details }));
+            jsonrpc_error(id, -32000, format!("{}: {}", e.code(), e), data)
+        }
     }
 }
 
@@ -262,16 +1111,124 @@ fn handle_prompts_get(id: Value, params: Value) -> Value {
     })
 }
 
+async fn tool_list_databases(arguments: Value, args: &Args) -> AppResult<(String, Value)> {
+    if args.allowed_dir.is_empty() {
+        return Err(AppError::InvalidRequest(
+            "list_databases needs --allowed-dir: without a directory whitelist the server does not scan the \
+             filesystem; pass db_path to the other tools directly"
+                .into(),
+        ));
+    }
+    let pattern = arguments.get("pattern").and_then(|v| v.as_str()).unwrap_or(paths::DEFAULT_DB_PATTERNS);
+    let max_depth = arguments
+        .get("max_depth")
+        .and_then(|v| v.as_u64())
+        .map_or(paths::MAX_SCAN_DEPTH, |d| (d as usize).min(LIST_DATABASES_MAX_DEPTH));
+
+    // Walking the directories blocks on the filesystem.
+    let (dirs, pattern) = (args.allowed_dir.clone(), pattern.to_string());
+    let databases =
+        tokio::task::spawn_blocking(move || paths::find_databases(&dirs, &pattern, max_depth, RESOURCE_SCAN_CAP))
+            .await
+            .map_err(|e| AppError::Internal(format!("scanning for databases failed: {e}")))?;
+    let structured = serde_json::json!({
+        "databases": databases,
+        "truncated": databases.len() >= RESOURCE_SCAN_CAP
+    });
+    let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+    Ok((text, structured))
+}
+
 async fn tool_read_query(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
     let db_path = get_string(&arguments, "db_path")?;
     let sql = get_string(&arguments, "sql")?;
+    let params = get_params(&arguments)?;
     let limit = arguments.get("limit").and_then(|v| v.as_u64()).map(|n| n as usize);
     let offset = arguments.get("offset").and_then(|v| v.as_u64()).map(|n| n as usize);
+    let cursor_key = arguments.get("cursor_key").and_then(|v| v.as_str()).map(str::to_string);
+    let cursor = arguments.get("cursor").and_then(|v| v.as_str());
+    let page = Page::from_request(&sql, offset, cursor_key, cursor)?;
+    let encode = get_encode_options(&arguments, args)?;
+    let timeout_ms = arguments.get("timeout_ms").and_then(|v| v.as_u64());
+    let format: ResultFormat = get_opt_enum(&arguments, "format")?.unwrap_or_default();
+    let text_format = get_text_format(&arguments)?;
+    if format == ResultFormat::Ndjson && text_format.is_some() {
+        return Err(AppError::InvalidRequest("text_format cannot be combined with format ndjson".into()));
+    }
+    let text_format = text_format.unwrap_or(format);
 
-    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir)?;
-    let worker = cm.ensure_worker(&db_path)?;
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let mode = OpenMode {
+        readonly: get_bool(&arguments, "readonly"),
+        create: false,
+    };
+    let worker = cm.ensure_worker_with(&db_path, mode).await?.with_timeout_ms(timeout_ms);
     let limits = effective_limit(limit, args.max_rows);
-    let qr = worker.read_query(sql, limits.max_rows, offset).await?;
+    if get_bool(&arguments, "multi") {
+        if params.is_some() || !matches!(page, Page::Start) || text_format != ResultFormat::Json {
+            return Err(AppError::InvalidRequest(
+                "multi cannot be combined with params, offset, cursor or a csv, ndjson or markdown format".into(),
+            ));
+        }
+        let results = worker.read_query_multi(sql, limits.max_rows, encode).await?;
+        let structured = serde_json::json!({ "results": results });
+        let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+        return Ok((text, structured));
+    }
+    if format == ResultFormat::Ndjson {
+        let stream = worker.read_query_stream(sql, params, limits.max_rows, page, encode)?;
+        let out = ndjson::collect(stream).await?;
+        let structured = serde_json::json!({
+            "ndjson": out.text,
+            "truncated": out.truncated,
+            "next_offset": out.next_offset,
+            "next_cursor": out.next_cursor,
+        });
+        return Ok((out.text, structured));
+    }
+    let started = get_bool(&arguments, "timing").then(Instant::now);
+    let mut qr = worker.read_query(sql, params, limits.max_rows, page, encode).await?;
+    qr.elapsed_ms = started.map(|s| s.elapsed().as_micros() as f64 / 1000.0);
+    let structured = serde_json::to_value(&qr)?;
+    // CSV and markdown only change the text block; full values stay in `structuredContent`.
+    let text = match text_format {
+        ResultFormat::Csv => {
+            let header = arguments.get("csv_header").and_then(Value::as_bool).unwrap_or(true);
+            csv::to_csv(&qr, header)
+        }
+        ResultFormat::Markdown => markdown::to_markdown(&qr, get_max_cell_chars(&arguments)),
+        _ => serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into()),
+    };
+    Ok((text, structured))
+}
+
+async fn tool_explain_query(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    let db_path = get_string(&arguments, "db_path")?;
+    let sql = get_string(&arguments, "sql")?;
+    let params = get_params(&arguments)?;
+
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+    let plan = worker.explain(sql, params).await?;
+
+    let structured = serde_json::to_value(&plan)?;
+    let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+    Ok((text, structured))
+}
+
+async fn tool_search(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    let db_path = get_string(&arguments, "db_path")?;
+    let table = get_string(&arguments, "table")?;
+    let query = get_string(&arguments, "query")?;
+    let limit = arguments.get("limit").and_then(|v| v.as_u64()).map(|n| n as usize);
+    let offset = arguments.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+    let limits = effective_limit(limit, args.max_rows);
+    let qr = worker
+        .search(table, query, limits.max_rows, offset, args.encode_options())
+        .await?;
 
     let structured = serde_json::to_value(&qr)?;
     let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
@@ -279,102 +1236,660 @@ async fn tool_read_query(arguments: Value, args: &Args, cm: &ConnectionManager)
 }
 
 async fn tool_write_query(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    if cm.is_readonly() {
+        return Err(AppError::ReadonlyMode);
+    }
     let db_path = get_string(&arguments, "db_path")?;
     let sql = get_string(&arguments, "sql")?;
+    let params = get_params(&arguments)?;
+    let timeout_ms = arguments.get("timeout_ms").and_then(|v| v.as_u64());
+    let dry_run = get_bool(&arguments, "dry_run");
+
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let mode = OpenMode {
+        readonly: get_bool(&arguments, "readonly"),
+        create: get_bool(&arguments, "create"),
+    };
+    let worker = cm.ensure_worker_with(&db_path, mode).await?.with_timeout_ms(timeout_ms);
+    let er = worker.execute(sql, params, args.encode_options(), dry_run).await?;
+
+    let structured = serde_json::to_value(&er)?;
+    let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+    Ok((text, structured))
+}
+
+async fn tool_insert_row(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    if cm.is_readonly() {
+        return Err(AppError::ReadonlyMode);
+    }
+    let db_path = get_string(&arguments, "db_path")?;
+    let table = get_string(&arguments, "table")?;
+    let values = match arguments.get("values") {
+        None | Some(Value::Null) => serde_json::Map::new(),
+        Some(Value::Object(map)) => map.clone(),
+        Some(_) => return Err(AppError::InvalidRequest("values must be an object".into())),
+    };
+
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+    let er = worker.insert_row(table, values, args.encode_options()).await?;
+
+    let structured = serde_json::to_value(&er)?;
+    let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+    Ok((text, structured))
+}
 
-    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir)?;
-    let worker = cm.ensure_worker(&db_path)?;
-    let er = worker.execute(sql).await?;
+/// `update_row`, or `delete_row` with `delete`.
+async fn tool_edit_row(arguments: Value, args: &Args, cm: &ConnectionManager, delete: bool) -> AppResult<(String, Value)> {
+    if cm.is_readonly() {
+        return Err(AppError::ReadonlyMode);
+    }
+    let db_path = get_string(&arguments, "db_path")?;
+    let table = get_string(&arguments, "table")?;
+    let object = |name: &str| match arguments.get(name) {
+        Some(Value::Object(map)) => Ok(map.clone()),
+        _ => Err(AppError::InvalidRequest(format!("{name} must be an object"))),
+    };
+    let key = object("key")?;
+    let edit = if delete { RowEdit::Delete } else { RowEdit::Update(object("set")?) };
+
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+    let allow_no_match = get_bool(&arguments, "allow_no_match");
+    let er = worker.edit_row(table, key, edit, allow_no_match, args.encode_options()).await?;
 
     let structured = serde_json::to_value(&er)?;
     let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
     Ok((text, structured))
 }
 
+async fn tool_execute_batch(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    if cm.is_readonly() {
+        return Err(AppError::ReadonlyMode);
+    }
+    let db_path = get_string(&arguments, "db_path")?;
+    let sql: BatchSql = arguments
+        .get("sql")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .ok_or_else(|| AppError::InvalidRequest("sql must be a string or an array of strings".into()))?;
+
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+    let br = worker.execute_batch(sql.into_scripts()).await?;
+
+    let structured = serde_json::to_value(&br)?;
+    let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+    Ok((text, structured))
+}
+
+async fn tool_close_database(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    let db_path = get_string(&arguments, "db_path")?;
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let closed = cm.close(&db_path).await?;
+
+    let structured = serde_json::json!({ "closed": closed });
+    let text = if closed { "closed" } else { "not open" };
+    Ok((text.into(), structured))
+}
+
+async fn tool_attach_database(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    let db_path = get_string(&arguments, "db_path")?;
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let file = get_string(&arguments, "file")?;
+    let file = validate_db_path(Path::new(&file), &args.allowed_dir, args.allow_uri)?;
+    let db = AttachedDatabase::new(&get_string(&arguments, "alias")?, file)?;
+    // Open the worker first, so the attachment applies to it right away.
+    cm.ensure_worker(&db_path).await?;
+    let attached = cm.attach(&db_path, db).await?;
+
+    let structured = serde_json::json!({ "attached": attached });
+    let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+    Ok((text, structured))
+}
+
+async fn tool_detach_database(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    let db_path = get_string(&arguments, "db_path")?;
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let attached = cm.detach(&db_path, &get_string(&arguments, "alias")?).await?;
+
+    let structured = serde_json::json!({ "attached": attached });
+    let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+    Ok((text, structured))
+}
+
+async fn tool_dump(arguments: Value, args: &Args, cm: &ConnectionManager, data: bool) -> AppResult<(String, Value)> {
+    let db_path = get_string(&arguments, "db_path")?;
+    let only: Option<Vec<String>> = get_opt_enum(&arguments, "only")?;
+
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+    // MCP has no streaming results, so the script is collected into one response.
+    let mut stream = worker.dump(data, only)?;
+    let mut sql = String::new();
+    while let Some(frame) = stream.next().await {
+        match frame? {
+            DumpFrame::Chunk(chunk) => sql.push_str(&chunk),
+            DumpFrame::End { tables, rows } => {
+                let structured = serde_json::json!({ "sql": sql, "tables": tables, "rows": rows });
+                return Ok((sql, structured));
+            }
+        }
+    }
+    Err(AppError::Internal("dump stream ended without an end frame".into()))
+}
+
+async fn tool_optimize_db(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    if cm.is_readonly() {
+        return Err(AppError::ReadonlyMode);
+    }
+    let db_path = get_string(&arguments, "db_path")?;
+    let ops: Vec<MaintenanceOp> =
+        get_opt_enum(&arguments, "operations")?.unwrap_or_else(|| vec![MaintenanceOp::Optimize]);
+
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+    let or = worker.optimize(ops).await?;
+
+    let structured = serde_json::to_value(&or)?;
+    let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+    Ok((text, structured))
+}
+
+async fn tool_backup_db(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    if cm.is_readonly() {
+        return Err(AppError::ReadonlyMode);
+    }
+    let db_path = get_string(&arguments, "db_path")?;
+    let dest = get_string(&arguments, "dest")?;
+    let overwrite = get_bool(&arguments, "overwrite");
+
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let dest = validate_db_path(Path::new(&dest), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+    let br = worker.backup(dest, overwrite).await?;
+
+    let structured = serde_json::to_value(&br)?;
+    let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+    Ok((text, structured))
+}
+
+async fn tool_transaction(
+    name: &str,
+    arguments: Value,
+    args: &Args,
+    cm: &ConnectionManager,
+) -> AppResult<(String, Value)> {
+    if cm.is_readonly() {
+        return Err(AppError::ReadonlyMode);
+    }
+    let db_path = get_string(&arguments, "db_path")?;
+
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+    let text = match name {
+        "begin_transaction" => {
+            let mode: TransactionMode = get_opt_enum(&arguments, "mode")?.unwrap_or_default();
+            worker.begin(mode).await?;
+            "transaction started"
+        }
+        "commit_transaction" => {
+            worker.commit().await?;
+            "transaction committed"
+        }
+        _ => {
+            worker.rollback().await?;
+            "transaction rolled back"
+        }
+    };
+
+    Ok((text.to_string(), serde_json::json!({ "ok": true })))
+}
+
+async fn tool_read_blob(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    let db_path = get_string(&arguments, "db_path")?;
+    let table = get_string(&arguments, "table")?;
+    let column = get_string(&arguments, "column")?;
+    let rowid = arguments.get("rowid").and_then(|v| v.as_i64());
+    let pk = arguments.get("pk").and_then(|v| v.as_object()).cloned();
+    let row = BlobRow::from_parts(rowid, pk)?;
+    let offset = arguments.get("offset").and_then(|v| v.as_u64()).map(|n| n as usize);
+    let length = arguments.get("length").and_then(|v| v.as_u64()).map(|n| n as usize);
+
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+    let chunk = worker
+        .read_blob(table, column, row, offset.unwrap_or(0), length.unwrap_or(args.max_blob_bytes))
+        .await?;
+
+    let structured = serde_json::to_value(&chunk)?;
+    let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+    Ok((text, structured))
+}
+
 async fn tool_get_schema(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
     let db_path = get_string(&arguments, "db_path")?;
-    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir)?;
-    let worker = cm.ensure_worker(&db_path)?;
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+    let row_counts = get_bool(&arguments, "include_row_counts").then(|| get_bool(&arguments, "approximate"));
+    let schema = arguments.get("schema").and_then(|v| v.as_str()).unwrap_or("main");
+
+    let include_attached = get_bool(&arguments, "include_attached");
+    let structured = schema_json(&worker, schema, include_attached, row_counts).await?;
+    let text = match get_text_format(&arguments)?.unwrap_or_default() {
+        ResultFormat::Json => serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into()),
+        format => schema_text(&structured, format, get_max_cell_chars(&arguments)),
+    };
+    Ok((text, structured))
+}
+
+/// `schema_json` as one markdown table or CSV of columns, a row per column of every table and
+/// view, for reading at a glance; the rest (indexes, foreign keys, triggers) stays in the JSON.
+fn schema_text(schema: &Value, format: ResultFormat, max_cell_chars: usize) -> String {
+    const HEADER: [&str; 6] = ["table", "column", "type", "pk", "not null", "default"];
+    let objects = |key: &str| schema[key].as_array().cloned().unwrap_or_default();
+    let (tables, views) = (objects("tables"), objects("views"));
+    let mut rows: Vec<Vec<Option<String>>> = Vec::new();
+    for object in tables.iter().chain(&views) {
+        let name = object["name"].as_str().unwrap_or_default();
+        for col in object["columns"].as_array().into_iter().flatten() {
+            let text = |key: &str| col.get(key).and_then(Value::as_str).map(str::to_string);
+            let pk = col.get("pk").and_then(Value::as_u64).filter(|&n| n > 0);
+            let notnull = col.get("notnull").and_then(Value::as_bool).unwrap_or(false);
+            rows.push(vec![
+                Some(name.to_string()),
+                text("name"),
+                text("decl_type"),
+                pk.map(|n| n.to_string()),
+                notnull.then(|| "yes".to_string()),
+                text("default_value"),
+            ]);
+        }
+    }
+    if format == ResultFormat::Csv {
+        return csv::records(Some(&HEADER), &rows);
+    }
+    let cells: Vec<Vec<String>> =
+        rows.into_iter().map(|row| row.into_iter().map(Option::unwrap_or_default).collect()).collect();
+    let (mut out, cut) = markdown::table(&HEADER, &cells, max_cell_chars);
+    let mut footer = format!(
+        "{} tables, {} views; indexes, foreign keys and triggers are in structuredContent.",
+        tables.len(),
+        views.len()
+    );
+    if cut {
+        footer = format!("{} {footer}", markdown::truncation_note(max_cell_chars));
+    }
+    out.push_str(&format!("\n_{footer}_\n"));
+    out
+}
 
-    let tables = worker.tables().await?;
+/// `{tables, views, triggers}` of `schema` as `get_schema` and the `sqlite://{path}/schema`
+/// resource return it; outside `main`, tables and views are named `schema.name` (and
+/// `include_attached` does not apply). With `row_counts` (`Some(approximate)`), each table also
+/// gets a `row_count`.
+async fn schema_json(
+    worker: &WorkerHandle,
+    schema: &str,
+    include_attached: bool,
+    row_counts: Option<bool>,
+) -> AppResult<Value> {
+    let objects = worker.objects_in(schema.to_string()).await?;
+    let main = schema.eq_ignore_ascii_case("main");
+    let tables = match main {
+        true => worker.tables(include_attached, false).await?,
+        false => worker.tables_in(schema.to_string(), false).await?,
+    };
+    let qualified = |name: &str| match main {
+        true => name.to_string(),
+        false => format!("{schema}.{name}"),
+    };
+    let row_counts = match row_counts {
+        Some(approximate) => Some(worker.row_counts(&tables, approximate).await),
+        None => None,
+    };
     let mut out_tables = Vec::with_capacity(tables.len());
-    for t in tables {
-        let cols = worker.columns(t.clone()).await?;
-        out_tables.push(serde_json::json!({ "name": t, "columns": cols }));
+    for (i, t) in tables.into_iter().enumerate() {
+        let mut table = table_json(worker, &t).await?;
+        // Tag virtual tables (previews may behave differently) and their shadow tables.
+        if let Some(obj) = objects.iter().find(|o| o.kind == "table" && qualified(&o.name) == t) {
+            if let Some(module) = &obj.module {
+                table["module"] = Value::from(module.as_str());
+            }
+            if obj.shadow {
+                table["shadow"] = Value::Bool(true);
+            }
+        }
+        if let Some(counts) = &row_counts {
+            table["row_count"] = Value::from(counts[i]);
+        }
+        out_tables.push(table);
     }
 
-    let structured = serde_json::json!({ "tables": out_tables });
+    let mut views = Vec::new();
+    let mut triggers = Vec::new();
+    for obj in objects {
+        match obj.kind.as_str() {
+            "view" => {
+                let name = qualified(&obj.name);
+                let cols = worker.columns(name.clone()).await?;
+                let triggers = worker.triggers(Some(name.clone())).await?;
+                views.push(serde_json::json!({
+                    "name": name,
+                    "columns": cols,
+                    "triggers": triggers,
+                    "sql": obj.sql
+                }));
+            }
+            "trigger" => {
+                triggers.push(serde_json::json!({ "name": obj.name, "table": obj.tbl_name, "sql": obj.sql }));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(serde_json::json!({ "tables": out_tables, "views": views, "triggers": triggers }))
+}
+
+/// One entry of `schema_json`'s `tables`.
+async fn table_json(worker: &WorkerHandle, table: &str) -> AppResult<Value> {
+    let cols = worker.columns(table.to_string()).await?;
+    let indexes = worker.indexes(table.to_string()).await?;
+    let foreign_keys = worker.foreign_keys(table.to_string()).await?;
+    let triggers = worker.triggers(Some(table.to_string())).await?;
+    Ok(serde_json::json!({
+        "name": table,
+        "columns": cols,
+        "indexes": indexes,
+        "foreign_keys": foreign_keys,
+        "triggers": triggers
+    }))
+}
+
+async fn tool_get_ddl(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    let db_path = get_string(&arguments, "db_path")?;
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+    let table = arguments.get("table").and_then(|v| v.as_str()).map(str::to_string);
+
+    let ddl = worker.ddl(table).await?;
+    let structured = serde_json::to_value(&ddl)?;
+    Ok((ddl.sql, structured))
+}
+
+async fn tool_diff_schema(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    let path_a = get_string(&arguments, "db_path")?;
+    let path_a = validate_db_path(Path::new(&path_a), &args.allowed_dir, args.allow_uri)?;
+    let path_b = get_string(&arguments, "other_db_path")?;
+    let path_b = validate_db_path(Path::new(&path_b), &args.allowed_dir, args.allow_uri)?;
+
+    // Read-only: reuse whatever worker is open, else open read-only ones.
+    let a = cm.reader(&path_a).await?.schema_snapshot().await?;
+    let b = cm.reader(&path_b).await?.schema_snapshot().await?;
+    let structured = serde_json::to_value(diff::diff(&a, &b))?;
     let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
     Ok((text, structured))
 }
 
+/// `analyze_db_health` recommends reclaiming free pages past this share of the file.
+const DEFAULT_VACUUM_THRESHOLD_PCT: f64 = 25.0;
+
 async fn tool_analyze_db_health(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
     let db_path = get_string(&arguments, "db_path")?;
-    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir)?;
-    let worker = cm.ensure_worker(&db_path)?;
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
 
-    // PRAGMA integrity_check returns rows like [{ "integrity_check": "ok" }] or multiple rows with errors.
-    let integrity = worker
-        .read_query("PRAGMA integrity_check".to_string(), 50, None)
-        .await?;
+    let quick = get_bool(&arguments, "quick");
+    let vacuum_threshold_pct = arguments
+        .get("vacuum_threshold_pct")
+        .and_then(Value::as_f64)
+        .unwrap_or(DEFAULT_VACUUM_THRESHOLD_PCT);
+    if !(0.0..=100.0).contains(&vacuum_threshold_pct) {
+        return Err(AppError::InvalidRequest("vacuum_threshold_pct must be between 0 and 100".into()));
+    }
+    let integrity = worker.integrity(quick, args.max_rows).await?;
+    let storage = worker.storage_metrics(vacuum_threshold_pct / 100.0).await?;
 
     let file_size = std::fs::metadata(&db_path).map(|m| m.len()).ok();
-    let tables = worker.tables().await?;
+    let tables = worker.tables(false, false).await?;
+    let row_counts = if get_bool(&arguments, "include_row_counts") {
+        Some(worker.row_counts(&tables, false).await)
+    } else {
+        None
+    };
     let mut table_summaries = Vec::with_capacity(tables.len());
-    for t in tables {
+    for (i, t) in tables.into_iter().enumerate() {
         let cols = worker.columns(t.clone()).await?;
-        table_summaries.push(serde_json::json!({
+        let mut summary = serde_json::json!({
             "name": t,
             "column_count": cols.len(),
             "columns": cols
-        }));
+        });
+        if let Some(counts) = &row_counts {
+            summary["row_count"] = Value::from(counts[i]);
+        }
+        table_summaries.push(summary);
     }
+    let index_advice = worker.index_advice().await?;
+    let size_breakdown = if get_bool(&arguments, "include_size_breakdown") {
+        Some(worker.size_report().await?)
+    } else {
+        None
+    };
+    // Whether this connection enforces foreign keys (`--foreign-keys`, or a `--pragma` override).
+    let foreign_keys = worker.connection_info().await?.pragmas.get("foreign_keys").map(|v| v == 1);
 
-    let structured = serde_json::json!({
+    let mut structured = serde_json::json!({
         "db_path": db_path,
         "file_size_bytes": file_size,
         "integrity_check": integrity,
-        "schema": { "tables": table_summaries }
+        "storage": storage,
+        "foreign_keys": foreign_keys,
+        "schema": { "tables": table_summaries },
+        "index_advice": index_advice
     });
+    if let Some(sizes) = size_breakdown {
+        structured["size_breakdown"] = serde_json::to_value(sizes)?;
+    }
     let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
     Ok((text, structured))
 }
 
-async fn read_sqlite_table_resource(uri: &str, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
-    // RFC-001 URI: sqlite://{abs_path_to_db}/tables/{table_name}
-    let (db_path, table) = parse_sqlite_table_uri(uri)?;
-    let db_path = validate_db_path(&db_path, &args.allowed_dir)?;
-    let worker = cm.ensure_worker(&db_path)?;
+async fn tool_foreign_key_check(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    let db_path = get_string(&arguments, "db_path")?;
+    let table = arguments.get("table").and_then(|v| v.as_str()).map(str::to_string);
+    let limit = arguments.get("limit").and_then(|v| v.as_u64()).map(|n| n as usize);
 
-    // Preview first 50 rows.
-    if !schema::is_safe_table_ref(&table) {
-        return Err(AppError::InvalidRequest(format!(
-            "invalid table name in resource uri: {table}"
-        )));
-    }
-    let sql = format!("SELECT * FROM {table} LIMIT 50");
-    let qr = worker.read_query(sql, 50, None).await?;
-    let structured = serde_json::to_value(&qr)?;
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+    let check = worker
+        .foreign_key_check(table, effective_limit(limit, args.max_rows).max_rows)
+        .await?;
+
+    let structured = serde_json::to_value(&check)?;
     let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
     Ok((text, structured))
 }
 
-fn parse_sqlite_table_uri(uri: &str) -> AppResult<(PathBuf, String)> {
+async fn tool_database_size_report(arguments: Value, args: &Args, cm: &ConnectionManager) -> AppResult<(String, Value)> {
+    let db_path = get_string(&arguments, "db_path")?;
+    let db_path = validate_db_path(Path::new(&db_path), &args.allowed_dir, args.allow_uri)?;
+    let report = cm.ensure_worker(&db_path).await?.size_report().await?;
+
+    let structured = serde_json::to_value(&report)?;
+    let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+    Ok((text, structured))
+}
+
+/// The resource's text, its JSON (`structuredContent`) and the text's MIME type: table rows are
+/// CSV with `?format=csv`, or a `text/csv` hint and no `format`; everything else is JSON.
+async fn read_sqlite_resource(
+    uri: &str,
+    mime_hint: Option<&str>,
+    args: &Args,
+    cm: &ConnectionManager,
+) -> AppResult<(String, Value, &'static str)> {
+    let uri = match parse_sqlite_uri(uri)? {
+        SqliteUri::Rows(uri) => uri,
+        SqliteUri::Schema { db_path, table } => {
+            let db_path = validate_db_path(&db_path, &args.allowed_dir, args.allow_uri)?;
+            let worker = cm.ensure_worker(&db_path).await?;
+            let structured = match table {
+                None => schema_json(&worker, "main", false, None).await?,
+                Some(table) => {
+                    // Also rejects names that are neither a table nor a view.
+                    let ddl = worker.ddl(Some(table.clone())).await?;
+                    let mut structured = table_json(&worker, &table).await?;
+                    structured["sql"] = Value::String(ddl.sql);
+                    structured
+                }
+            };
+            let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+            return Ok((text, structured, "application/json"));
+        }
+    };
+    let db_path = validate_db_path(&uri.db_path, &args.allowed_dir, args.allow_uri)?;
+    let worker = cm.ensure_worker(&db_path).await?;
+
+    // `alias.table` only refers to an attached database if that alias is attached.
+    let aliases: Vec<String> = match uri.table.contains('.') {
+        true => worker.connection_info().await?.attached.into_iter().map(|db| db.alias).collect(),
+        false => Vec::new(),
+    };
+    let table = schema::TableRef::parse(&uri.table, &aliases)?;
+    // No LIMIT in the SQL: the row cap below reports `truncated`/`next_offset` for the next page.
+    let limits = effective_limit(Some(uri.limit.unwrap_or(args.preview_rows)), args.max_rows);
+    let page = uri.offset.map_or(Page::Start, Page::Offset);
+    let sql = format!("SELECT * FROM {}", table.quoted());
+    let qr = worker
+        .read_query(sql, None, limits.max_rows, page, args.encode_options())
+        .await?;
+    let structured = serde_json::to_value(&qr)?;
+    let format = uri.format.unwrap_or(match mime_hint {
+        Some("text/csv") => ResultFormat::Csv,
+        _ => ResultFormat::Json,
+    });
+    if format == ResultFormat::Csv {
+        return Ok((csv::to_csv(&qr, uri.csv_header.unwrap_or(true)), structured, "text/csv"));
+    }
+    let text = serde_json::to_string_pretty(&structured).unwrap_or_else(|_| "<result>".into());
+    Ok((text, structured, "application/json"))
+}
+
+/// What a `sqlite://` resource URI points at (RFC-001 plus the schema resources).
+#[derive(Debug, PartialEq)]
+enum SqliteUri {
+    /// `{abs_path}/tables/{table}[?limit=N&offset=M&format=csv&header=false]`: a page of the
+    /// table's rows.
+    Rows(TableUri),
+    /// `{abs_path}/schema`, or `{abs_path}/tables/{table}/schema` for one table with its DDL.
+    Schema { db_path: PathBuf, table: Option<String> },
+}
+
+#[derive(Debug, PartialEq)]
+struct TableUri {
+    db_path: PathBuf,
+    table: String,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    /// `?format=csv` (or `json`); otherwise the `mimeType` hint of the read decides.
+    format: Option<ResultFormat>,
+    /// `?header=false` leaves the column names out of CSV.
+    csv_header: Option<bool>,
+}
+
+fn parse_sqlite_uri(uri: &str) -> AppResult<SqliteUri> {
     let uri = uri.strip_prefix("sqlite://").ok_or_else(|| {
         AppError::InvalidRequest("resource uri must start with sqlite://".into())
     })?;
-    let parts: Vec<&str> = uri.split("/tables/").collect();
-    if parts.len() != 2 {
-        return Err(AppError::InvalidRequest(
-            "resource uri must be sqlite://{abs_path}/tables/{table}".into(),
-        ));
+    let (uri, query) = uri.split_once('?').unwrap_or((uri, ""));
+    let (mut limit, mut offset, mut format, mut csv_header) = (None, None, None, None);
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let invalid = || AppError::InvalidRequest(format!("invalid resource uri parameter {key}: {value}"));
+        match key {
+            "limit" => limit = Some(value.parse::<usize>().map_err(|_| invalid())?),
+            "offset" => offset = Some(value.parse::<usize>().map_err(|_| invalid())?),
+            "format" => {
+                format = Some(match value {
+                    "json" => ResultFormat::Json,
+                    "csv" => ResultFormat::Csv,
+                    _ => return Err(invalid()),
+                })
+            }
+            "header" => csv_header = Some(value.parse::<bool>().map_err(|_| invalid())?),
+            _ => {
+                return Err(AppError::InvalidRequest(format!(
+                    "unsupported resource uri parameter: {key} (allowed: limit, offset, format, header)"
+                )))
+            }
+        }
     }
-    let db_path = parts[0];
-    let table = parts[1];
-    if table.is_empty() {
+    let malformed = || {
+        AppError::InvalidRequest(
+            "resource uri must be sqlite://{abs_path}/tables/{table}, \
+             sqlite://{abs_path}/tables/{table}/schema or sqlite://{abs_path}/schema"
+                .into(),
+        )
+    };
+    let parts: Vec<&str> = uri.split("/tables/").collect();
+    let (db_path, table, schema) = match parts[..] {
+        [db_path] => (db_path.strip_suffix("/schema").ok_or_else(malformed)?, None, true),
+        [db_path, table] => match table.strip_suffix("/schema") {
+            Some(table) => (db_path, Some(table), true),
+            None => (db_path, Some(table), false),
+        },
+        _ => return Err(malformed()),
+    };
+    let table = table.map(percent_decode).transpose()?;
+    if table.as_deref() == Some("") {
         return Err(AppError::InvalidRequest("missing table name".into()));
     }
-    Ok((PathBuf::from(db_path), table.to_string()))
+    let db_path = PathBuf::from(db_path);
+    if schema {
+        if !query.is_empty() {
+            return Err(AppError::InvalidRequest("schema resource uris take no parameters".into()));
+        }
+        return Ok(SqliteUri::Schema { db_path, table });
+    }
+    Ok(SqliteUri::Rows(TableUri {
+        db_path,
+        table: table.unwrap_or_default(),
+        limit,
+        offset,
+        format,
+        csv_header,
+    }))
+}
+
+/// `%XX` escapes in a URI segment to the bytes they stand for (UTF-8 table names included).
+fn percent_decode(s: &str) -> AppResult<String> {
+    let invalid = || AppError::InvalidRequest(format!("invalid percent-encoding in resource uri: {s}"));
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = tail.get(..2).and_then(|h| std::str::from_utf8(h).ok()).ok_or_else(invalid)?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+/// Escape everything but unreserved URI characters, so any table name survives the round trip.
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+    out
 }
 
 fn get_string(obj: &Value, key: &str) -> AppResult<String> {
@@ -384,54 +1899,69 @@ fn get_string(obj: &Value, key: &str) -> AppResult<String> {
         .ok_or_else(|| AppError::InvalidRequest(format!("missing or invalid field: {key}")))
 }
 
-fn validate_db_path(db_path: &Path, allowed_dirs: &[PathBuf]) -> AppResult<PathBuf> {
-    let abs = if db_path.is_absolute() {
-        db_path.to_path_buf()
-    } else {
-        std::env::current_dir()?.join(db_path)
+fn get_params(obj: &Value) -> AppResult<Option<QueryParams>> {
+    let params: Option<ParamsInput> = match obj.get("params") {
+        None | Some(Value::Null) => None,
+        Some(v) => Some(
+            serde_json::from_value(v.clone())
+                .map_err(|_| AppError::InvalidRequest("params must be an array or an object".into()))?,
+        ),
+    };
+    let named_params = match obj.get("named_params") {
+        None | Some(Value::Null) => None,
+        Some(Value::Object(m)) => Some(m.clone()),
+        Some(_) => return Err(AppError::InvalidRequest("named_params must be an object".into())),
     };
+    Ok(QueryParams::from_input(params, named_params))
+}
 
-    if allowed_dirs.is_empty() {
-        return Ok(normalize_lexical(&abs));
+fn get_encode_options(obj: &Value, args: &Args) -> AppResult<EncodeOptions> {
+    let mut encode = args.encode_options();
+    if let Some(m) = get_opt_enum(obj, "float_mode")? {
+        encode.float_mode = m;
     }
-
-    let abs_norm = normalize_lexical(&abs);
-    for d in allowed_dirs {
-        let d = normalize_lexical(d);
-        if abs_norm.starts_with(&d) {
-            return Ok(abs_norm);
-        }
+    if let Some(m) = get_opt_enum(obj, "int_mode")? {
+        encode.int_mode = m;
     }
-    Err(AppError::PathNotAllowed(abs_norm))
+    if let Some(m) = get_opt_enum(obj, "blob_mode")? {
+        encode.blob_mode = m;
+    }
+    Ok(encode)
 }
 
-fn normalize_lexical(p: &Path) -> PathBuf {
-    // Normalize lexically (remove `.` and resolve `..`) without touching filesystem,
-    // so it works even if DB file doesn't exist yet.
-    let mut out = PathBuf::new();
+fn get_bool(obj: &Value, key: &str) -> bool {
+    obj.get(key).and_then(|v| v.as_bool()).unwrap_or(false)
+}
 
-    for comp in p.components() {
-        match comp {
-            Component::CurDir => {}
-            Component::ParentDir => {
-                // Pop only if we have something to pop and the last isn't a prefix/root.
-                let popped = out.pop();
-                if !popped {
-                    // Can't go above root/prefix; keep as-is (still prevents escaping during starts_with checks).
-                }
-            }
-            Component::Prefix(prefix) => out.push(prefix.as_os_str()),
-            Component::RootDir => out.push(comp.as_os_str()),
-            Component::Normal(c) => out.push(c),
+/// `text_format`: how to render a tool's text content; ndjson is not one of the choices.
+fn get_text_format(obj: &Value) -> AppResult<Option<ResultFormat>> {
+    match get_opt_enum(obj, "text_format")? {
+        Some(ResultFormat::Ndjson) => {
+            Err(AppError::InvalidRequest("invalid text_format: expected json, markdown or csv".into()))
         }
+        format => Ok(format),
     }
+}
 
-    out
+fn get_max_cell_chars(obj: &Value) -> usize {
+    let max = obj.get("max_cell_chars").and_then(Value::as_u64).unwrap_or(markdown::MAX_CELL_CHARS as u64);
+    max.max(1) as usize
 }
 
-async fn write_line(w: &mut io::BufWriter<io::Stdout>, v: &Value) -> AppResult<()> {
-    serde_json::to_writer(w, v)?;
-    w.write_all(b"\n").await?;
+fn get_opt_enum<T: serde::de::DeserializeOwned>(obj: &Value, key: &str) -> AppResult<Option<T>> {
+    match obj.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => serde_json::from_value(v.clone())
+            .map(Some)
+            .map_err(|e| AppError::InvalidRequest(format!("invalid {key}: {e}"))),
+    }
+}
+
+async fn write_line(w: &SharedWriter, v: &Value) -> AppResult<()> {
+    let mut buf = serde_json::to_vec(v)?;
+    buf.push(b'\n');
+    let mut w = w.lock().await;
+    w.write_all(&buf).await?;
     w.flush().await?;
     Ok(())
 }
@@ -450,3 +1980,351 @@ fn jsonrpc_error(id: Value, code: i64, message: String, data: Option<Value>) ->
     err
 }
 
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn rows(uri: &str) -> TableUri {
+        match parse_sqlite_uri(uri).unwrap() {
+            SqliteUri::Rows(uri) => uri,
+            other => panic!("{uri}: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn table_uri_query_component_is_parsed() {
+        let uri = rows("sqlite:///data/app.db/tables/main.users?limit=10&offset=20");
+        assert_eq!(
+            uri,
+            TableUri {
+                db_path: PathBuf::from("/data/app.db"),
+                table: "main.users".into(),
+                limit: Some(10),
+                offset: Some(20),
+                format: None,
+                csv_header: None,
+            }
+        );
+        let uri = rows("sqlite:///data/app.db/tables/users?format=csv&header=false");
+        assert_eq!((uri.format, uri.csv_header), (Some(ResultFormat::Csv), Some(false)));
+        let uri = rows("sqlite:///data/app.db/tables/users");
+        assert_eq!((uri.limit, uri.offset), (None, None));
+        let uri = rows("sqlite:///data/app.db/tables/order%20details");
+        assert_eq!(uri.table, "order details");
+        let name = "gr\u{f6}\u{df}e/\"x\"?%";
+        let uri = format!("sqlite:///data/app.db/tables/{}?limit=1", percent_encode(name));
+        assert_eq!(rows(&uri).table, name);
+        // A table called `schema` is still a row preview.
+        assert_eq!(rows("sqlite:///data/app.db/tables/schema").table, "schema");
+        for bad in ["tables/users?limit=-1", "tables/users?limit=ten", "tables/users?order=id", "tables/a%2", "tables/users?format=xml"] {
+            assert!(parse_sqlite_uri(&format!("sqlite:///data/app.db/{bad}")).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn schema_uris_are_parsed() {
+        let schema = |db: &str, table: Option<&str>| SqliteUri::Schema {
+            db_path: PathBuf::from(db),
+            table: table.map(str::to_string),
+        };
+        assert_eq!(parse_sqlite_uri("sqlite:///data/app.db/schema").unwrap(), schema("/data/app.db", None));
+        assert_eq!(
+            parse_sqlite_uri("sqlite:///data/app.db/tables/order%20details/schema").unwrap(),
+            schema("/data/app.db", Some("order details"))
+        );
+        for bad in ["sqlite:///data/app.db", "sqlite:///data/app.db/schema?limit=1", "sqlite:///data/app.db/tables//schema"] {
+            assert!(parse_sqlite_uri(bad).is_err(), "{bad}");
+        }
+        let err = parse_sqlite_uri("sqlite:///data/app.db/views/v").unwrap_err();
+        assert!(matches!(&err, AppError::InvalidRequest(m) if m.contains("sqlite://{abs_path}/schema")), "{err}");
+    }
+
+    #[test]
+    fn initialize_negotiates_a_supported_protocol_version() {
+        assert_eq!(negotiate_protocol_version(Some("2024-11-05")), "2024-11-05");
+        assert_eq!(negotiate_protocol_version(Some("1999-01-01")), MCP_PROTOCOL_VERSIONS[0]);
+        assert_eq!(negotiate_protocol_version(None), MCP_PROTOCOL_VERSIONS[0]);
+    }
+
+    fn parse_args(extra: &[&str]) -> Args {
+        Args::try_parse_from(["sqlite-helper", "--mcp"].iter().chain(extra)).unwrap()
+    }
+
+    /// A fresh database file in the temp dir, set up with `sql`.
+    fn temp_db(name: &str, sql: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-mcp-{name}-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        rusqlite::Connection::open(&path).unwrap().execute_batch(sql).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn list_changed_is_sent_once_per_new_database_and_never_with_allowed_dir() {
+        let path = temp_db("list-changed", "CREATE TABLE t (x)");
+        let args = parse_args(&[]);
+        let cm = ConnectionManager::new(args.connection_config());
+        let listed = AtomicUsize::new(0);
+        assert!(resources_list_changed(&args, &cm, &listed).is_none());
+
+        cm.ensure_worker(&path).await.unwrap();
+        let note = resources_list_changed(&args, &cm, &listed).unwrap();
+        assert_eq!(note["method"], "notifications/resources/list_changed");
+        assert!(resources_list_changed(&args, &cm, &listed).is_none());
+        // Reopening a known database adds nothing.
+        cm.close(&path).await.unwrap();
+        cm.ensure_worker(&path).await.unwrap();
+        assert!(resources_list_changed(&args, &cm, &listed).is_none());
+
+        let dir = path.parent().unwrap().to_string_lossy().into_owned();
+        let scanned = parse_args(&["--allowed-dir", &dir]);
+        let cm = ConnectionManager::new(scanned.connection_config());
+        cm.ensure_worker(&path).await.unwrap();
+        assert!(resources_list_changed(&scanned, &cm, &AtomicUsize::new(0)).is_none());
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    fn complete(db: &Path, uri: &str, name: &str, value: &str) -> Value {
+        serde_json::json!({
+            "ref": { "type": "ref/resource", "uri": uri },
+            "argument": { "name": name, "value": value },
+            "context": { "arguments": { "db_path": db.to_string_lossy() } }
+        })
+    }
+
+    async fn completions(params: Value, args: &Args, cm: &ConnectionManager) -> Value {
+        handle_completion_complete(Value::from(1), params, args, cm).await["result"]["completion"].clone()
+    }
+
+    #[tokio::test]
+    async fn table_names_complete_by_prefix_without_opening_a_worker() {
+        let sql = "CREATE TABLE Users (x); CREATE TABLE orders (x); CREATE VIEW user_view AS SELECT 1";
+        let path = temp_db("complete", sql);
+        let args = parse_args(&[]);
+        let cm = ConnectionManager::new(args.connection_config());
+
+        let done = completions(complete(&path, TABLE_URI_TEMPLATE, "table", ""), &args, &cm).await;
+        let mut values: Vec<String> = serde_json::from_value(done["values"].clone()).unwrap();
+        values.sort();
+        assert_eq!(values, ["Users", "orders", "user_view"]);
+        assert_eq!((done["total"].as_u64(), done["hasMore"].as_bool()), (Some(3), Some(false)));
+        let done = completions(complete(&path, TABLE_SCHEMA_URI_TEMPLATE, "table", "us"), &args, &cm).await;
+        let mut values: Vec<String> = serde_json::from_value(done["values"].clone()).unwrap();
+        values.sort();
+        assert_eq!(values, ["Users", "user_view"]);
+        assert_eq!(cm.opened_count(), 0);
+
+        // Other arguments and templates complete to nothing.
+        let done = completions(complete(&path, TABLE_URI_TEMPLATE, "db_path", ""), &args, &cm).await;
+        assert_eq!(done["values"], serde_json::json!([]));
+        let done = completions(complete(&path, "sqlite://{db_path}/schema", "table", ""), &args, &cm).await;
+        assert_eq!(done["values"], serde_json::json!([]));
+
+        // A database outside --allowed-dir is refused the same way.
+        let elsewhere = std::env::temp_dir().join(format!("sqlite-helper-mcp-other-{}", std::process::id()));
+        std::fs::create_dir_all(&elsewhere).unwrap();
+        let confined = parse_args(&["--allowed-dir", &elsewhere.to_string_lossy()]);
+        let done = completions(complete(&path, TABLE_URI_TEMPLATE, "table", ""), &confined, &cm).await;
+        assert_eq!(done["values"], serde_json::json!([]));
+
+        let missing = handle_completion_complete(Value::from(2), serde_json::json!({}), &args, &cm).await;
+        assert_eq!(missing["error"]["code"], -32602);
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_dir(&elsewhere);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn text_format_and_max_cell_chars_are_read_from_arguments() {
+        let read = |v: Value| get_text_format(&v);
+        assert_eq!(read(serde_json::json!({})).unwrap(), None);
+        assert_eq!(read(serde_json::json!({ "text_format": null })).unwrap(), None);
+        assert_eq!(read(serde_json::json!({ "text_format": "csv" })).unwrap(), Some(ResultFormat::Csv));
+        for bad in ["ndjson", "xml"] {
+            let err = read(serde_json::json!({ "text_format": bad })).unwrap_err();
+            assert!(matches!(err, AppError::InvalidRequest(m) if m.starts_with("invalid text_format")));
+        }
+        assert_eq!(get_max_cell_chars(&serde_json::json!({})), markdown::MAX_CELL_CHARS);
+        assert_eq!(get_max_cell_chars(&serde_json::json!({ "max_cell_chars": 5 })), 5);
+        assert_eq!(get_max_cell_chars(&serde_json::json!({ "max_cell_chars": 0 })), 1);
+    }
+
+    #[test]
+    fn schema_text_lists_a_row_per_column() {
+        let schema = serde_json::json!({
+            "tables": [{
+                "name": "users",
+                "columns": [
+                    { "name": "id", "decl_type": "INTEGER", "pk": 1, "notnull": false },
+                    {
+                        "name": "name", "decl_type": "TEXT", "pk": 0, "notnull": true,
+                        "default_value": "'anonymous'"
+                    }
+                ]
+            }],
+            "views": [{ "name": "v", "columns": [{ "name": "n", "pk": 0, "notnull": false }] }]
+        });
+        let csv = schema_text(&schema, ResultFormat::Csv, 80);
+        assert_eq!(
+            csv,
+            "table,column,type,pk,not null,default\r\nusers,id,INTEGER,1,,\r\n\
+             users,name,TEXT,,yes,'anonymous'\r\nv,n,,,,\r\n"
+        );
+        let md = schema_text(&schema, ResultFormat::Markdown, 9);
+        assert!(md.starts_with("| table | column | type | pk | not null | default |\n"), "{md}");
+        assert!(md.contains("| users | name | TEXT |  | yes | 'anonymo"), "{md}");
+        assert!(md.contains("Cells longer than 9 characters"), "{md}");
+        assert!(md.trim_end().ends_with(
+            "1 tables, 1 views; indexes, foreign keys and triggers are in structuredContent._"
+        ));
+    }
+
+    #[tokio::test]
+    async fn text_format_only_changes_the_text() {
+        let path = temp_db(
+            "text-format",
+            "CREATE TABLE t (a INTEGER PRIMARY KEY, b TEXT); INSERT INTO t VALUES (1, 'x')",
+        );
+        let args = parse_args(&[]);
+        let cm = ConnectionManager::new(args.connection_config());
+        let call = |extra: Value| {
+            let mut arguments = serde_json::json!({ "db_path": path, "sql": "SELECT a, b FROM t" });
+            arguments.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            arguments
+        };
+
+        let rows = serde_json::json!([{ "a": 1, "b": "x" }]);
+        let csv = serde_json::json!({ "format": "json", "text_format": "csv" });
+        let (text, structured) = tool_read_query(call(csv), &args, &cm).await.unwrap();
+        assert_eq!(text, "a,b\r\n1,x\r\n");
+        assert_eq!(structured["rows"], rows);
+        // text_format wins over format.
+        let both = serde_json::json!({ "format": "csv", "text_format": "json", "csv_header": false });
+        let (text, structured) = tool_read_query(call(both), &args, &cm).await.unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&text).unwrap()["rows"], rows);
+        assert_eq!(structured["rows"], rows);
+        let markdown = serde_json::json!({ "format": "csv", "text_format": "markdown" });
+        let (text, _) = tool_read_query(call(markdown), &args, &cm).await.unwrap();
+        assert!(text.starts_with("| a | b |"), "{text}");
+        let ndjson = serde_json::json!({ "format": "ndjson", "text_format": "json" });
+        let err = tool_read_query(call(ndjson), &args, &cm).await.unwrap_err();
+        assert!(matches!(&err, AppError::InvalidRequest(m) if m.contains("format ndjson")), "{err}");
+
+        let schema = |text_format: &str| serde_json::json!({ "db_path": path, "text_format": text_format });
+        let (text, structured) = tool_get_schema(schema("csv"), &args, &cm).await.unwrap();
+        assert!(text.starts_with("table,column,type,pk,not null,default\r\nt,a,INTEGER,1,,\r\n"), "{text}");
+        assert_eq!(structured["tables"][0]["name"], "t");
+        let (text, _) = tool_get_schema(schema("markdown"), &args, &cm).await.unwrap();
+        assert!(text.contains("| t | b | TEXT |"), "{text}");
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn timing_adds_elapsed_ms_covering_the_time_in_sqlite() {
+        let path = temp_db("timing", "CREATE TABLE t (x); INSERT INTO t VALUES (1), (2)");
+        let args = parse_args(&[]);
+        let cm = ConnectionManager::new(args.connection_config());
+        let query = |timing: bool| {
+            serde_json::json!({ "db_path": path, "sql": "SELECT x FROM t", "timing": timing })
+        };
+
+        let (_, structured) = tool_read_query(query(true), &args, &cm).await.unwrap();
+        let elapsed = structured["elapsed_ms"].as_f64().unwrap();
+        assert!(elapsed >= structured["duration_ms"].as_f64().unwrap(), "{structured}");
+        let (_, structured) = tool_read_query(query(false), &args, &cm).await.unwrap();
+        assert!(structured.get("elapsed_ms").is_none(), "{structured}");
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn readonly_mode_refuses_writes_and_never_creates_files() {
+        let path = temp_db("readonly", "CREATE TABLE t (x); INSERT INTO t VALUES (1)");
+        let args = parse_args(&["--readonly"]);
+        let cm = ConnectionManager::new(args.connection_config());
+        let call = |db: &Path, sql: &str| serde_json::json!({ "db_path": db, "sql": sql, "create": true });
+
+        let err = tool_write_query(call(&path, "DELETE FROM t"), &args, &cm).await.unwrap_err();
+        assert_eq!(err.code(), "READONLY_MODE");
+        let (_, structured) = tool_read_query(call(&path, "SELECT x FROM t"), &args, &cm).await.unwrap();
+        assert_eq!(structured["rows"], serde_json::json!([{ "x": 1 }]));
+        // The connection itself is opened read-only, whatever reaches it.
+        let worker = cm.ensure_worker(&path).await.unwrap();
+        let delete = worker.execute("DELETE FROM t".into(), None, EncodeOptions::default(), false);
+        let err = delete.await.unwrap_err();
+        assert_eq!(err.code(), "READ_ONLY");
+
+        let missing = path.with_extension("missing.db");
+        let err = tool_read_query(call(&missing, "SELECT 1"), &args, &cm).await.unwrap_err();
+        assert_eq!(err.code(), "DB_OPEN_FAILED");
+        assert!(!missing.exists());
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn only_a_write_with_create_makes_a_missing_database() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-mcp-create-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let args = parse_args(&[]);
+        let cm = ConnectionManager::new(args.connection_config());
+        let call = |sql: &str, create: bool| {
+            serde_json::json!({ "db_path": path, "sql": sql, "create": create })
+        };
+
+        // Reads never create, whatever they ask for.
+        let err = tool_read_query(call("SELECT 1", true), &args, &cm).await.unwrap_err();
+        assert_eq!(err.code(), "DB_OPEN_FAILED");
+        let err = tool_write_query(call("CREATE TABLE t (x)", false), &args, &cm).await.unwrap_err();
+        assert_eq!(err.code(), "DB_OPEN_FAILED");
+        assert!(!path.exists());
+
+        tool_write_query(call("CREATE TABLE t (x)", true), &args, &cm).await.unwrap();
+        assert!(path.exists());
+        let count = call("SELECT count(*) AS n FROM t", false);
+        let (_, structured) = tool_read_query(count, &args, &cm).await.unwrap();
+        assert_eq!(structured["rows"], serde_json::json!([{ "n": 0 }]));
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn tool_calls_send_log_notifications_at_the_level_the_client_set() {
+        let path = temp_db("log", "CREATE TABLE t (x); INSERT INTO t VALUES (1), (2), (3)");
+        let args = parse_args(&[]);
+        let cm = ConnectionManager::new(args.connection_config());
+        let log = McpLogger::new(args.slow_query_ms);
+        let call = || {
+            let arguments = serde_json::json!({ "db_path": path, "sql": "SELECT x FROM t", "limit": 2 });
+            serde_json::json!({ "name": "read_query", "arguments": arguments })
+        };
+
+        // A truncation is info; the default level (warning) drops it.
+        let (_, notes) = handle_tools_call(serde_json::json!(1), call(), &args, &cm, &log).await;
+        assert!(notes.is_empty());
+
+        let res = handle_set_level(serde_json::json!(2), serde_json::json!({ "level": "info" }), &log);
+        assert_eq!(res["result"], serde_json::json!({}));
+        let (_, notes) = handle_tools_call(serde_json::json!(3), call(), &args, &cm, &log).await;
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0]["method"], "notifications/message");
+        assert!(notes[0].get("id").is_none());
+        assert_eq!(notes[0]["params"]["level"], "info");
+        assert_eq!(notes[0]["params"]["data"]["event"], "truncated");
+
+        let res = handle_set_level(serde_json::json!(4), serde_json::json!({ "level": "loud" }), &log);
+        assert_eq!(res["error"]["code"], -32602);
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+}
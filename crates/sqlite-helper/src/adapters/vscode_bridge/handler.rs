@@ -1,30 +1,74 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 use crate::{
     cli::Args,
-    core::{connection::ConnectionManager, limits::effective_limit},
+    core::{
+        blob::BlobRow,
+        cancel::{self, CancelRegistry},
+        csv, diff,
+        connection::{ConnectionManager, DumpStream, OpenMode, QueryStream, WorkerHandle},
+        limits::effective_limit,
+        markdown, ndjson,
+        paging::Page,
+        params::QueryParams,
+        paths::validate_db_path,
+        pragmas::PragmaSetting,
+        rows::RowEdit,
+        types::{AttachedDatabase, DumpFrame, EncodeOptions, MaintenanceOp, QueryFrame, ResultFormat},
+    },
     error::{AppError, AppResult},
 };
 
-use super::protocol::*;
+use super::{io::NdjsonIo, protocol::*};
 
 pub struct BridgeHandler {
     args: Args,
     cm: ConnectionManager,
     active_db: Option<PathBuf>,
+    /// `readonly` from the last `connect`; applies to requests against `active_db`.
+    active_readonly: bool,
+    cancels: CancelRegistry,
 }
 
 impl BridgeHandler {
     pub fn new(args: Args) -> Self {
         Self {
+            cm: ConnectionManager::new(args.connection_config()),
             args,
-            cm: ConnectionManager::new(),
             active_db: None,
+            active_readonly: false,
+            cancels: CancelRegistry::default(),
         }
     }
 
-    pub async fn handle(&mut self, req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
-        if req.v != 1 {
+    /// The `v` requests must carry: `--protocol-version`, or `PROTOCOL_VERSION`.
+    pub fn protocol_version(&self) -> u32 {
+        self.args.protocol_version.unwrap_or(PROTOCOL_VERSION)
+    }
+
+    /// Shared view of in-flight requests, used to serve `cancel` while `handle` is running.
+    pub fn cancels(&self) -> CancelRegistry {
+        self.cancels.clone()
+    }
+
+    /// Handle one request and return its (final) response.
+    ///
+    /// Streamed queries write their intermediate frames to `io` before returning the `end` frame.
+    pub async fn handle(&mut self, req: BridgeRequest, io: &NdjsonIo) -> BridgeResponse<serde_json::Value> {
+        let id = req.id.clone();
+        let token = self.cancels.start(&id);
+        let resp = cancel::scoped(token, self.dispatch(req, io)).await;
+        self.cancels.finish(&id);
+        resp
+    }
+
+    async fn dispatch(&mut self, req: BridgeRequest, io: &NdjsonIo) -> BridgeResponse<serde_json::Value> {
+        // Answered for any `v`, so an older or newer client can find out what this server speaks.
+        if req.cmd == "capabilities" {
+            return self.handle_capabilities(req);
+        }
+        if req.v != self.protocol_version() {
             return BridgeResponse::err(
                 req.v,
                 req.id,
@@ -35,10 +79,42 @@ impl BridgeHandler {
 
         match req.cmd.as_str() {
             "connect" => self.handle_connect(req).await,
-            "query" => self.handle_query(req).await,
+            "close" => self.handle_close(req).await,
+            "disconnect" => self.handle_disconnect(req).await,
+            "connection_info" => self.handle_connection_info(req).await,
+            "attach" => self.handle_attach(req).await,
+            "detach" => self.handle_detach(req).await,
+            "close_all" => {
+                let closed = self.cm.close_all().await;
+                ok(req, serde_json::json!({ "closed": closed }))
+            }
+            "query" => self.handle_query(req, io).await,
             "execute" => self.handle_execute(req).await,
+            "execute_batch" => self.handle_execute_batch(req).await,
+            "browse_table" => self.handle_browse_table(req).await,
+            "explain_query" => self.handle_explain_query(req).await,
+            "search" => self.handle_search(req).await,
+            "insert_row" => self.handle_insert_row(req).await,
+            "update_row" => self.handle_update_row(req).await,
+            "delete_row" => self.handle_delete_row(req).await,
+            "read_blob" => self.handle_read_blob(req).await,
+            "backup" => self.handle_backup(req).await,
+            "optimize" => self.handle_optimize(req).await,
+            "dump_schema" => self.handle_dump(req, io, false).await,
+            "dump_all" => self.handle_dump(req, io, true).await,
+            "begin" => self.handle_transaction(req, TxCmd::Begin).await,
+            "commit" => self.handle_transaction(req, TxCmd::Commit).await,
+            "rollback" => self.handle_transaction(req, TxCmd::Rollback).await,
             "tables" => self.handle_tables(req).await,
+            "objects" => self.handle_objects(req).await,
+            "ddl" => self.handle_ddl(req).await,
+            "diff_schema" => self.handle_diff_schema(req).await,
             "columns" => self.handle_columns(req).await,
+            "indexes" => self.handle_indexes(req).await,
+            "foreign_keys" => self.handle_foreign_keys(req).await,
+            "foreign_key_check" => self.handle_foreign_key_check(req).await,
+            "database_size_report" => self.handle_database_size_report(req).await,
+            "triggers" => self.handle_triggers(req).await,
             other => BridgeResponse::err(
                 req.v,
                 req.id,
@@ -48,34 +124,311 @@ impl BridgeHandler {
         }
     }
 
-    async fn handle_connect(&mut self, req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
-        let p: ConnectPayload = match serde_json::from_value(req.payload) {
+    fn handle_capabilities(&self, req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let data = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "protocol_versions": [self.protocol_version()],
+            "sqlite_version": rusqlite::version(),
+            "commands": COMMANDS,
+            "features": {
+                "streaming": true,
+                "transactions": true,
+                "parameters": true,
+                "named_parameters": true,
+                "cursor_paging": true,
+                "csv": true,
+                "ndjson": true,
+                "markdown": true,
+                "blobs": true,
+                "backup": true,
+                "attach": true,
+                "cancel": true,
+                "readonly": self.cm.is_readonly(),
+                "foreign_keys": self.args.default_foreign_keys(),
+            },
+        });
+        ok(req, data)
+    }
+
+    async fn handle_connect(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: ConnectPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let path = match validate_db_path(Path::new(&p.path), &self.args.allowed_dir, self.args.allow_uri) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let pragmas: AppResult<Vec<PragmaSetting>> =
+            p.pragmas.iter().map(|(name, value)| PragmaSetting::from_json(name, value)).collect();
+        let pragmas = match pragmas {
+            Ok(v) => v,
+            Err(e) => return err(req, e),
+        };
+        let mode = OpenMode {
+            readonly: p.readonly,
+            create: p.create,
+        };
+        // Pragmas are kept for later reopens only once the database opened and took them.
+        if let Err(e) = self.cm.ensure_worker_with(&path, mode).await {
+            return err(req, e);
+        }
+        if let Err(e) = self.cm.set_pragmas(&path, pragmas).await {
+            return err(req, e);
+        }
+        self.active_db = Some(path);
+        self.active_readonly = p.readonly;
+        ok(req, serde_json::Value::Bool(true))
+    }
+
+    /// Release the database file; `active_db` is kept, so later requests transparently reopen it.
+    async fn handle_close(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: ClosePayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        match self.cm.close(&db_path).await {
+            Ok(closed) => ok(req, serde_json::json!({ "closed": closed })),
+            Err(e) => err(req, e),
+        }
+    }
+
+    /// Release the database file like `close`, and forget it as `active_db` if it was, so that
+    /// requests without a `path` no longer reopen it.
+    async fn handle_disconnect(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: ClosePayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        // The active database reached through another spelling (a symlink) is still the active one.
+        let db_path = match &self.active_db {
+            Some(active) if same_file(active, &db_path) => active.clone(),
+            _ => db_path,
+        };
+        let closed = match self.cm.close(&db_path).await {
+            Ok(closed) => closed,
+            Err(e) => return err(req, e),
+        };
+        if self.active_db.as_deref() == Some(db_path.as_path()) {
+            self.active_db = None;
+            self.active_readonly = false;
+        }
+        ok(req, serde_json::json!({ "closed": closed }))
+    }
+
+    async fn handle_query(&mut self, mut req: BridgeRequest, io: &NdjsonIo) -> BridgeResponse<serde_json::Value> {
+        let p: QueryPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let mode = OpenMode {
+            readonly: p.readonly.unwrap_or_else(|| self.connected_readonly(&db_path)),
+            create: false,
+        };
+        let worker = match self.worker_with(&db_path, mode).await {
+            Ok(w) => w.with_timeout_ms(p.timeout_ms),
+            Err(e) => return err(req, e),
+        };
+        let limits = effective_limit(p.limit, self.args.max_rows);
+        let params = QueryParams::from_input(p.params, p.named_params);
+        let page = match Page::from_request(&p.sql, p.offset, p.cursor_key, p.cursor.as_deref()) {
+            Ok(page) => page,
+            Err(e) => return err(req, e),
+        };
+        let defaults = self.args.encode_options();
+        let encode = EncodeOptions {
+            float_mode: p.float_mode,
+            int_mode: p.int_mode.unwrap_or(defaults.int_mode),
+            blob_mode: p.blob_mode,
+            ..defaults
+        };
+        if p.multi {
+            let plain = params.is_none() && matches!(page, Page::Start) && !p.stream;
+            if !plain || p.format != ResultFormat::Json {
+                let msg = "multi cannot be combined with params, paging, stream or csv/ndjson";
+                return err(req, AppError::InvalidRequest(msg.into()));
+            }
+            return match worker.read_query_multi(p.sql, limits.max_rows, encode).await {
+                Ok(results) => ok(req, serde_json::json!({ "results": results })),
+                Err(e) => err(req, e),
+            };
+        }
+        // Streamed `row` frames are already one JSON line per row, so `ndjson` streams as `json`.
+        if p.stream {
+            if matches!(p.format, ResultFormat::Csv | ResultFormat::Markdown) {
+                return err(req, AppError::InvalidRequest("formats csv and markdown cannot be streamed".into()));
+            }
+            return match worker.query_stream(p.sql, params, limits.max_rows, page, encode) {
+                Ok(stream) => stream_frames(req, stream, io).await,
+                Err(e) => err(req, e),
+            };
+        }
+        if p.format == ResultFormat::Ndjson {
+            let out = match worker.query_stream(p.sql, params, limits.max_rows, page, encode) {
+                Ok(stream) => ndjson::collect(stream).await,
+                Err(e) => Err(e),
+            };
+            return match out {
+                Ok(out) => ok(req, text_result(out.text, out.truncated, out.next_offset, out.next_cursor)),
+                Err(e) => err(req, e),
+            };
+        }
+        let started = p.timing.then(Instant::now);
+        match worker.query(p.sql, params, limits.max_rows, page, encode).await {
+            Ok(qr) if p.format == ResultFormat::Csv => {
+                let text = csv::to_csv(&qr, p.csv_header.unwrap_or(true));
+                ok(req, text_result(text, qr.truncated, qr.next_offset, qr.next_cursor))
+            }
+            Ok(qr) if p.format == ResultFormat::Markdown => {
+                let text = markdown::to_markdown(&qr, markdown::MAX_CELL_CHARS);
+                ok(req, text_result(text, qr.truncated, qr.next_offset, qr.next_cursor))
+            }
+            Ok(mut qr) => {
+                qr.elapsed_ms = started.map(|s| s.elapsed().as_micros() as f64 / 1000.0);
+                ok(
+                    req,
+                    serde_json::to_value(qr).unwrap_or_else(|_| serde_json::Value::Null),
+                )
+            }
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_execute(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        if self.cm.is_readonly() {
+            return err(req, AppError::ReadonlyMode);
+        }
+        let p: ExecutePayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let mode = OpenMode {
+            readonly: self.connected_readonly(&db_path),
+            create: p.create,
+        };
+        let worker = match self.worker_with(&db_path, mode).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        let params = QueryParams::from_input(p.params, p.named_params);
+        match worker.execute(p.sql, params, self.args.encode_options(), p.dry_run).await {
+            Ok(er) => ok(
+                req,
+                serde_json::to_value(er).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_execute_batch(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        if self.cm.is_readonly() {
+            return err(req, AppError::ReadonlyMode);
+        }
+        let p: ExecuteBatchPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
             Ok(v) => v,
             Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
         };
-        let path = PathBuf::from(p.path);
-        self.active_db = Some(path.clone());
-        match self.cm.ensure_worker(&path) {
-            Ok(_) => ok(req, serde_json::Value::Bool(true)),
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        match worker.execute_batch(p.sql.into_scripts()).await {
+            Ok(br) => ok(
+                req,
+                serde_json::to_value(br).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
             Err(e) => err(req, e),
         }
     }
 
-    async fn handle_query(&mut self, req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
-        let p: QueryPayload = match serde_json::from_value(req.payload) {
+    async fn handle_browse_table(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: BrowseTablePayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
             Ok(v) => v,
             Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
         };
+        let order_by = match (p.order_by, p.order_dir) {
+            (Some(column), dir) => Some((column, dir.unwrap_or_default())),
+            (None, None) => None,
+            (None, Some(_)) => return err(req, AppError::InvalidRequest("order_dir requires order_by".into())),
+        };
         let db_path = match self.resolve_db_path(p.path) {
             Ok(p) => p,
             Err(e) => return err(req, e),
         };
-        let worker = match self.cm.ensure_worker(&db_path) {
+        let worker = match self.worker(&db_path).await {
             Ok(w) => w,
             Err(e) => return err(req, e),
         };
         let limits = effective_limit(p.limit, self.args.max_rows);
-        match worker.query(p.sql, limits.max_rows, p.offset).await {
+        let encode = self.args.encode_options();
+        match worker.browse_table(p.table, order_by, limits.max_rows, p.offset, encode).await {
+            Ok(br) => ok(
+                req,
+                serde_json::to_value(br).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_explain_query(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: ExplainQueryPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        let params = QueryParams::from_input(p.params, p.named_params);
+        match worker.explain(p.sql, params).await {
+            Ok(plan) => ok(
+                req,
+                serde_json::to_value(plan).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_search(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: SearchPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        let limits = effective_limit(p.limit, self.args.max_rows);
+        let encode = self.args.encode_options();
+        match worker.search(p.table, p.query, limits.max_rows, p.offset, encode).await {
             Ok(qr) => ok(
                 req,
                 serde_json::to_value(qr).unwrap_or_else(|_| serde_json::Value::Null),
@@ -84,8 +437,11 @@ impl BridgeHandler {
         }
     }
 
-    async fn handle_execute(&mut self, req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
-        let p: ExecutePayload = match serde_json::from_value(req.payload) {
+    async fn handle_insert_row(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        if self.cm.is_readonly() {
+            return err(req, AppError::ReadonlyMode);
+        }
+        let p: InsertRowPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
             Ok(v) => v,
             Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
         };
@@ -93,11 +449,57 @@ impl BridgeHandler {
             Ok(p) => p,
             Err(e) => return err(req, e),
         };
-        let worker = match self.cm.ensure_worker(&db_path) {
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        match worker.insert_row(p.table, p.values, self.args.encode_options()).await {
+            Ok(er) => ok(
+                req,
+                serde_json::to_value(er).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_update_row(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: UpdateRowPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let edit = RowEdit::Update(p.set);
+        self.edit_row(req, p.path, p.table, p.key, edit, p.allow_no_match).await
+    }
+
+    async fn handle_delete_row(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: DeleteRowPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        self.edit_row(req, p.path, p.table, p.key, RowEdit::Delete, p.allow_no_match).await
+    }
+
+    async fn edit_row(
+        &mut self,
+        req: BridgeRequest,
+        path: Option<String>,
+        table: String,
+        key: serde_json::Map<String, serde_json::Value>,
+        edit: RowEdit,
+        allow_no_match: bool,
+    ) -> BridgeResponse<serde_json::Value> {
+        if self.cm.is_readonly() {
+            return err(req, AppError::ReadonlyMode);
+        }
+        let db_path = match self.resolve_db_path(path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
             Ok(w) => w,
             Err(e) => return err(req, e),
         };
-        match worker.execute(p.sql).await {
+        match worker.edit_row(table, key, edit, allow_no_match, self.args.encode_options()).await {
             Ok(er) => ok(
                 req,
                 serde_json::to_value(er).unwrap_or_else(|_| serde_json::Value::Null),
@@ -106,8 +508,268 @@ impl BridgeHandler {
         }
     }
 
-    async fn handle_tables(&mut self, req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
-        let p: TablesPayload = match serde_json::from_value(req.payload) {
+    async fn handle_backup(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        if self.cm.is_readonly() {
+            return err(req, AppError::ReadonlyMode);
+        }
+        let p: BackupPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let dest = match validate_db_path(Path::new(&p.dest), &self.args.allowed_dir, self.args.allow_uri) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        match worker.backup(dest, p.overwrite).await {
+            Ok(br) => ok(
+                req,
+                serde_json::to_value(br).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_optimize(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        if self.cm.is_readonly() {
+            return err(req, AppError::ReadonlyMode);
+        }
+        let p: OptimizePayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        let ops = p.operations.unwrap_or_else(|| vec![MaintenanceOp::Optimize]);
+        match worker.optimize(ops).await {
+            Ok(or) => ok(
+                req,
+                serde_json::to_value(or).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    /// Always streamed: `chunk` frames of SQL text, then `end` with table and row counts.
+    async fn handle_dump(
+        &mut self,
+        mut req: BridgeRequest,
+        io: &NdjsonIo,
+        data: bool,
+    ) -> BridgeResponse<serde_json::Value> {
+        let p: DumpPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        match worker.dump(data, p.only) {
+            Ok(stream) => stream_dump(req, stream, io).await,
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_read_blob(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: ReadBlobPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let row = match BlobRow::from_parts(p.rowid, p.pk) {
+            Ok(r) => r,
+            Err(e) => return err(req, e),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        let offset = p.offset.unwrap_or(0);
+        let length = p.length.unwrap_or(self.args.max_blob_bytes);
+        match worker.read_blob(p.table, p.column, row, offset, length).await {
+            Ok(chunk) => ok(
+                req,
+                serde_json::to_value(chunk).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_transaction(
+        &mut self,
+        mut req: BridgeRequest,
+        cmd: TxCmd,
+    ) -> BridgeResponse<serde_json::Value> {
+        let p: TransactionPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        let res = match cmd {
+            TxCmd::Begin => worker.begin(p.mode.unwrap_or_default()).await,
+            TxCmd::Commit => worker.commit().await,
+            TxCmd::Rollback => worker.rollback().await,
+        };
+        match res {
+            Ok(()) => ok(req, serde_json::Value::Bool(true)),
+            Err(e) => err(req, e),
+        }
+    }
+
+    /// Open mode and effective pragmas of the connection requests for `path` would use.
+    async fn handle_connection_info(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: ConnectionInfoPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        match worker.connection_info().await {
+            Ok(info) => ok(
+                req,
+                serde_json::to_value(info).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_attach(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: AttachPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let db = match validate_db_path(Path::new(&p.file), &self.args.allowed_dir, self.args.allow_uri)
+            .and_then(|file| AttachedDatabase::new(&p.alias, file))
+        {
+            Ok(db) => db,
+            Err(e) => return err(req, e),
+        };
+        // Open the worker first, so the attachment applies to it right away.
+        if let Err(e) = self.worker(&db_path).await {
+            return err(req, e);
+        }
+        match self.cm.attach(&db_path, db).await {
+            Ok(v) => ok(
+                req,
+                serde_json::to_value(v).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_detach(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: DetachPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        match self.cm.detach(&db_path, &p.alias).await {
+            Ok(v) => ok(
+                req,
+                serde_json::to_value(v).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_tables(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: TablesPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        let tables = match p.schema {
+            Some(schema) => worker.tables_in(schema, p.include_views).await,
+            None => worker.tables(p.include_attached, p.include_views).await,
+        };
+        let tables = match tables {
+            Ok(v) => v,
+            Err(e) => return err(req, e),
+        };
+        if !p.include_row_counts {
+            return ok(req, serde_json::to_value(tables).unwrap_or_else(|_| serde_json::Value::Null));
+        }
+        let counts = worker.row_counts(&tables, p.approximate).await;
+        let tables: Vec<serde_json::Value> = tables
+            .into_iter()
+            .zip(counts)
+            .map(|(name, row_count)| serde_json::json!({ "name": name, "row_count": row_count }))
+            .collect();
+        ok(req, serde_json::Value::Array(tables))
+    }
+
+    async fn handle_objects(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: ObjectsPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        match worker.objects_in(p.schema.unwrap_or_else(|| "main".to_string())).await {
+            Ok(v) => ok(
+                req,
+                serde_json::to_value(v).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_ddl(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: DdlPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
             Ok(v) => v,
             Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
         };
@@ -115,11 +777,11 @@ impl BridgeHandler {
             Ok(p) => p,
             Err(e) => return err(req, e),
         };
-        let worker = match self.cm.ensure_worker(&db_path) {
+        let worker = match self.worker(&db_path).await {
             Ok(w) => w,
             Err(e) => return err(req, e),
         };
-        match worker.tables().await {
+        match worker.ddl(p.table).await {
             Ok(v) => ok(
                 req,
                 serde_json::to_value(v).unwrap_or_else(|_| serde_json::Value::Null),
@@ -128,8 +790,43 @@ impl BridgeHandler {
         }
     }
 
-    async fn handle_columns(&mut self, req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
-        let p: ColumnsPayload = match serde_json::from_value(req.payload) {
+    async fn handle_diff_schema(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: DiffSchemaPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let other = match validate_db_path(Path::new(&p.other), &self.args.allowed_dir, self.args.allow_uri) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        let a = match worker.schema_snapshot().await {
+            Ok(s) => s,
+            Err(e) => return err(req, e),
+        };
+        // The other database is only read; it keeps the mode it was opened with, if any.
+        let b = match self.cm.reader(&other).await {
+            Ok(w) => w.schema_snapshot().await,
+            Err(e) => Err(e),
+        };
+        match b {
+            Ok(b) => ok(
+                req,
+                serde_json::to_value(diff::diff(&a, &b)).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_columns(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: ColumnsPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
             Ok(v) => v,
             Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
         };
@@ -137,7 +834,7 @@ impl BridgeHandler {
             Ok(p) => p,
             Err(e) => return err(req, e),
         };
-        let worker = match self.cm.ensure_worker(&db_path) {
+        let worker = match self.worker(&db_path).await {
             Ok(w) => w,
             Err(e) => return err(req, e),
         };
@@ -150,9 +847,143 @@ impl BridgeHandler {
         }
     }
 
+    async fn handle_indexes(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: IndexesPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        match worker.indexes(p.table).await {
+            Ok(v) => ok(
+                req,
+                serde_json::to_value(v).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_foreign_keys(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: ForeignKeysPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        match worker.foreign_keys(p.table).await {
+            Ok(v) => ok(
+                req,
+                serde_json::to_value(v).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_foreign_key_check(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: ForeignKeyCheckPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        let limits = effective_limit(p.limit, self.args.max_rows);
+        match worker.foreign_key_check(p.table, limits.max_rows).await {
+            Ok(v) => ok(
+                req,
+                serde_json::to_value(v).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_database_size_report(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: DatabaseSizeReportPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        match worker.size_report().await {
+            Ok(v) => ok(
+                req,
+                serde_json::to_value(v).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    async fn handle_triggers(&mut self, mut req: BridgeRequest) -> BridgeResponse<serde_json::Value> {
+        let p: TriggersPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+            Ok(v) => v,
+            Err(e) => return err(req, AppError::InvalidRequest(e.to_string())),
+        };
+        let db_path = match self.resolve_db_path(p.path) {
+            Ok(p) => p,
+            Err(e) => return err(req, e),
+        };
+        let worker = match self.worker(&db_path).await {
+            Ok(w) => w,
+            Err(e) => return err(req, e),
+        };
+        match worker.triggers(p.table).await {
+            Ok(v) => ok(
+                req,
+                serde_json::to_value(v).unwrap_or_else(|_| serde_json::Value::Null),
+            ),
+            Err(e) => err(req, e),
+        }
+    }
+
+    /// Stop all workers; open transactions are rolled back rather than left dangling.
+    pub async fn shutdown(&self) {
+        self.cm.shutdown().await;
+    }
+
+    /// Worker for `db_path`, opened read-only if it was `connect`ed that way.
+    async fn worker(&self, db_path: &Path) -> AppResult<WorkerHandle> {
+        let mode = OpenMode {
+            readonly: self.connected_readonly(db_path),
+            create: false,
+        };
+        self.worker_with(db_path, mode).await
+    }
+
+    async fn worker_with(&self, db_path: &Path, mode: OpenMode) -> AppResult<WorkerHandle> {
+        self.cm.ensure_worker_with(db_path, mode).await
+    }
+
+    /// Whether `db_path` is the database last `connect`ed with `readonly: true`.
+    fn connected_readonly(&self, db_path: &Path) -> bool {
+        self.active_readonly && self.active_db.as_deref() == Some(db_path)
+    }
+
     fn resolve_db_path(&self, payload_path: Option<String>) -> AppResult<PathBuf> {
         if let Some(p) = payload_path {
-            return Ok(PathBuf::from(p));
+            return validate_db_path(Path::new(&p), &self.args.allowed_dir, self.args.allow_uri);
         }
         self.active_db
             .clone()
@@ -160,11 +991,153 @@ impl BridgeHandler {
     }
 }
 
+/// `data` of a `query` rendered as text (csv, ndjson, markdown): the text and the paging state
+/// a `QueryResult` would have carried (`next_cursor` only in cursor mode).
+fn text_result(
+    text: String,
+    truncated: bool,
+    next_offset: Option<usize>,
+    next_cursor: Option<String>,
+) -> serde_json::Value {
+    let mut data = serde_json::json!({ "text": text, "truncated": truncated, "next_offset": next_offset });
+    if let Some(cursor) = next_cursor {
+        data["next_cursor"] = serde_json::Value::String(cursor);
+    }
+    data
+}
+
+async fn stream_frames(
+    req: BridgeRequest,
+    mut stream: QueryStream,
+    io: &NdjsonIo,
+) -> BridgeResponse<serde_json::Value> {
+    while let Some(frame) = stream.next().await {
+        let (name, data) = match frame {
+            Ok(QueryFrame::Columns(columns)) => ("columns", serde_json::to_value(columns)),
+            Ok(QueryFrame::Row(row)) => ("row", Ok(serde_json::Value::Object(row))),
+            Ok(QueryFrame::End {
+                truncated,
+                next_offset,
+                next_cursor,
+                duration_ms,
+                rows_scanned,
+                column_types,
+            }) => {
+                let data = serde_json::json!({
+                    "truncated": truncated,
+                    "next_offset": next_offset,
+                    "next_cursor": next_cursor,
+                    "duration_ms": duration_ms,
+                    "rows_scanned": rows_scanned,
+                    "column_types": column_types
+                });
+                return BridgeResponse::frame(req.v, req.id, "end", data);
+            }
+            Err(e) => return err(req, e),
+        };
+        let data = data.unwrap_or(serde_json::Value::Null);
+        if let Err(e) = io.write_json_line(&BridgeResponse::frame(req.v, req.id.clone(), name, data)) {
+            return err(req, e);
+        }
+    }
+    err(req, AppError::Internal("query stream ended without an end frame".into()))
+}
+
+async fn stream_dump(
+    req: BridgeRequest,
+    mut stream: DumpStream,
+    io: &NdjsonIo,
+) -> BridgeResponse<serde_json::Value> {
+    while let Some(frame) = stream.next().await {
+        match frame {
+            Ok(DumpFrame::Chunk(sql)) => {
+                let data = serde_json::Value::String(sql);
+                if let Err(e) = io.write_json_line(&BridgeResponse::frame(req.v, req.id.clone(), "chunk", data)) {
+                    return err(req, e);
+                }
+            }
+            Ok(DumpFrame::End { tables, rows }) => {
+                let data = serde_json::json!({ "tables": tables, "rows": rows });
+                return BridgeResponse::frame(req.v, req.id, "end", data);
+            }
+            Err(e) => return err(req, e),
+        }
+    }
+    err(req, AppError::Internal("dump stream ended without an end frame".into()))
+}
+
+/// Whether `a` and `b` name the same file, resolving symlinks when both exist.
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b || matches!((std::fs::canonicalize(a), std::fs::canonicalize(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+enum TxCmd {
+    Begin,
+    Commit,
+    Rollback,
+}
+
 fn ok(req: BridgeRequest, data: serde_json::Value) -> BridgeResponse<serde_json::Value> {
     BridgeResponse::ok(req.v, req.id, data)
 }
 
 fn err(req: BridgeRequest, e: AppError) -> BridgeResponse<serde_json::Value> {
-    BridgeResponse::err(req.v, req.id, e.code(), e.to_string())
+    BridgeResponse {
+        details: e.details(),
+        ..BridgeResponse::err(req.v, req.id, e.code(), e.to_string())
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn request(cmd: &str, payload: serde_json::Value) -> BridgeRequest {
+        BridgeRequest {
+            v: PROTOCOL_VERSION,
+            id: cmd.into(),
+            cmd: cmd.into(),
+            payload,
+        }
+    }
+
+    #[tokio::test]
+    async fn disconnect_forgets_the_active_database() {
+        let dir = std::env::temp_dir().join(format!("sqlite-helper-disconnect-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.db");
+        let _ = std::fs::remove_file(&path);
+        rusqlite::Connection::open(&path).unwrap().execute_batch("CREATE TABLE t (x)").unwrap();
+        let mut handler = BridgeHandler::new(Args::try_parse_from(["sqlite-helper"]).unwrap());
+        let connect = serde_json::json!({ "path": path });
+
+        handler.handle_connect(request("connect", connect.clone())).await;
+        let resp = handler.handle_tables(request("tables", serde_json::json!({}))).await;
+        assert_eq!(resp.status, "ok", "{:?}", resp.error);
+        let resp = handler.handle_disconnect(request("disconnect", serde_json::json!({}))).await;
+        assert_eq!(resp.data, Some(serde_json::json!({ "closed": true })));
+        // Without a path there is nothing to reopen any more.
+        let resp = handler.handle_tables(request("tables", serde_json::json!({}))).await;
+        assert_eq!(resp.code, Some("INVALID_REQUEST"));
+        let again = request("disconnect", serde_json::json!({ "path": path }));
+        let resp = handler.handle_disconnect(again).await;
+        assert_eq!(resp.data, Some(serde_json::json!({ "closed": false })));
+
+        // By explicit path, also when it reaches the active database through a symlink.
+        #[cfg(unix)]
+        {
+            handler.handle_connect(request("connect", connect)).await;
+            let link = dir.join("link.db");
+            std::os::unix::fs::symlink(&path, &link).unwrap();
+            let disconnect = request("disconnect", serde_json::json!({ "path": link }));
+            let resp = handler.handle_disconnect(disconnect).await;
+            assert_eq!(resp.data, Some(serde_json::json!({ "closed": true })));
+            assert_eq!(handler.active_db, None);
+        }
+
+        handler.cm.shutdown().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,41 +1,59 @@
 use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::sync::Mutex;
+
+use tokio::sync::mpsc;
 
 use crate::error::{AppError, AppResult};
 
+/// NDJSON writer for bridge responses. Writes take `&self` so a `cancel` acknowledgement can
+/// be written while another request is still producing its response.
 pub struct NdjsonIo {
-    stdin: BufReader<std::io::Stdin>,
-    stdout: BufWriter<std::io::Stdout>,
+    stdout: Mutex<BufWriter<std::io::Stdout>>,
+}
+
+/// Read stdin lines on a dedicated thread, so requests (notably `cancel`) can be received
+/// while another request is running. The channel closes at EOF.
+pub fn spawn_line_reader() -> mpsc::UnboundedReceiver<AppResult<String>> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        let mut stdin = BufReader::new(std::io::stdin());
+        loop {
+            let mut line = String::new();
+            match stdin.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => {
+                    let line = line.trim_end_matches(&['\r', '\n'][..]);
+                    let line = if line.trim().is_empty() { String::new() } else { line.to_string() };
+                    if tx.send(Ok(line)).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e.into()));
+                    break;
+                }
+            }
+        }
+    });
+    rx
 }
 
 impl NdjsonIo {
     pub fn new() -> Self {
         Self {
-            stdin: BufReader::new(std::io::stdin()),
-            stdout: BufWriter::new(std::io::stdout()),
+            stdout: Mutex::new(BufWriter::new(std::io::stdout())),
         }
     }
 
-    pub fn read_line(&mut self) -> AppResult<Option<String>> {
-        let mut line = String::new();
-        let n = self.stdin.read_line(&mut line)?;
-        if n == 0 {
-            return Ok(None);
-        }
-        let line = line.trim_end_matches(&['\r', '\n'][..]).to_string();
-        if line.trim().is_empty() {
-            return Ok(Some(String::new()));
-        }
-        Ok(Some(line))
-    }
-
-    pub fn write_json_line<T: serde::Serialize>(&mut self, v: &T) -> AppResult<()> {
-        serde_json::to_writer(&mut self.stdout, v)?;
-        self.stdout.write_all(b"\n")?;
-        self.stdout.flush()?;
+    pub fn write_json_line<T: serde::Serialize>(&self, v: &T) -> AppResult<()> {
+        let mut stdout = self.stdout.lock().map_err(|_| AppError::Internal("poisoned lock".into()))?;
+        serde_json::to_writer(&mut *stdout, v)?;
+        stdout.write_all(b"\n")?;
+        stdout.flush()?;
         Ok(())
     }
 
-    pub fn protocol_error(&mut self, id: String, v: u32, msg: String) -> AppResult<()> {
+    pub fn protocol_error(&self, id: String, v: u32, msg: String) -> AppResult<()> {
         #[derive(serde::Serialize)]
         struct ErrResp<'a> {
             v: u32,
@@ -54,4 +72,3 @@ impl NdjsonIo {
         self.write_json_line(&r)
     }
 }
-
@@ -1,12 +1,18 @@
 mod handler;
 mod io;
-mod protocol;
+pub(crate) mod protocol;
 
-use crate::{cli::Args, error::AppResult};
+use std::{collections::VecDeque, time::Instant};
+
+use crate::{
+    cli::Args,
+    core::cancel::CancelRegistry,
+    error::{AppError, AppResult},
+};
 
 use handler::BridgeHandler;
 use io::NdjsonIo;
-use protocol::BridgeRequest;
+use protocol::{BridgeRequest, BridgeResponse, CancelPayload};
 
 pub fn run(args: Args) -> AppResult<()> {
     let rt = tokio::runtime::Builder::new_current_thread()
@@ -14,30 +20,126 @@ pub fn run(args: Args) -> AppResult<()> {
         .build()
         .map_err(|e| crate::error::AppError::Internal(e.to_string()))?;
 
+    let started = Instant::now();
     rt.block_on(async move {
-        let mut io = NdjsonIo::new();
+        let io = NdjsonIo::new();
+        let mut lines = io::spawn_line_reader();
         let mut handler = BridgeHandler::new(args);
+        let cancels = handler.cancels();
+        let protocol_version = handler.protocol_version();
+        // Requests read while another one was running; they still run one at a time, in order.
+        let mut queued: VecDeque<BridgeRequest> = VecDeque::new();
+        let mut eof = false;
 
         loop {
-            let Some(line) = io.read_line()? else { break };
-            if line.is_empty() {
+            let req = match queued.pop_front() {
+                Some(req) => req,
+                None if eof => break,
+                None => match lines.recv().await {
+                    Some(line) => match parse_request(&io, line?)? {
+                        Some(req) => req,
+                        None => continue,
+                    },
+                    None => break,
+                },
+            };
+            if req.cmd == "cancel" {
+                io.write_json_line(&handle_cancel(req, &cancels, &mut queued, &io)?)?;
+                continue;
+            }
+            if req.cmd == "ping" {
+                io.write_json_line(&handle_ping(req, started, protocol_version))?;
                 continue;
             }
 
-            let req: BridgeRequest = match serde_json::from_str(&line) {
-                Ok(r) => r,
-                Err(e) => {
-                    // best-effort: unknown id; still return something
-                    let _ = io.protocol_error("".to_string(), 1, e.to_string());
-                    continue;
+            // Keep reading while the request runs so a `cancel` (or `ping`) can be answered.
+            let fut = handler.handle(req, &io);
+            tokio::pin!(fut);
+            let resp = loop {
+                tokio::select! {
+                    resp = &mut fut => break resp,
+                    line = lines.recv(), if !eof => {
+                        let Some(line) = line else {
+                            eof = true;
+                            continue;
+                        };
+                        let Some(next) = parse_request(&io, line?)? else { continue };
+                        if next.cmd == "cancel" {
+                            io.write_json_line(&handle_cancel(next, &cancels, &mut queued, &io)?)?;
+                        } else if next.cmd == "ping" {
+                            io.write_json_line(&handle_ping(next, started, protocol_version))?;
+                        } else {
+                            queued.push_back(next);
+                        }
+                    }
                 }
             };
-
-            let resp = handler.handle(req).await;
             io.write_json_line(&resp)?;
         }
 
+        handler.shutdown().await;
         Ok(())
     })
 }
 
+/// Parse one NDJSON line; blank or malformed lines yield `None` (the latter after reporting).
+fn parse_request(io: &NdjsonIo, line: String) -> AppResult<Option<BridgeRequest>> {
+    if line.is_empty() {
+        return Ok(None);
+    }
+    match serde_json::from_str(&line) {
+        Ok(r) => Ok(Some(r)),
+        Err(e) => {
+            // best-effort: unknown id; still return something
+            io.protocol_error("".to_string(), 1, e.to_string())?;
+            Ok(None)
+        }
+    }
+}
+
+/// Liveness check answered straight from the read loop, even while a request is running; it
+/// touches no database. Accepted with any `v`, so a client can read `protocol_version` before
+/// settling on one.
+fn handle_ping(req: BridgeRequest, started: Instant, protocol_version: u32) -> BridgeResponse<serde_json::Value> {
+    let data = serde_json::json!({
+        "pong": true,
+        "version": env!("CARGO_PKG_VERSION"),
+        "protocol_version": protocol_version,
+        "uptime_ms": started.elapsed().as_millis() as u64,
+    });
+    BridgeResponse::ok(req.v, req.id, data)
+}
+
+/// Cancel a running request (abandoning its worker tasks) or drop a queued one.
+///
+/// The target answers with a `CANCELLED` error; the `cancel` request itself gets
+/// `{"cancelled": bool}`, false when the id is unknown or already answered.
+///
+/// A running request's tasks still queued on a worker are skipped, and its statement is stopped
+/// with `sqlite3_interrupt`; only work inside SQLite statements can be cut short, so anything
+/// else the worker is doing for it (e.g. an online `backup`) runs to completion and is answered
+/// as usual.
+fn handle_cancel(
+    mut req: BridgeRequest,
+    cancels: &CancelRegistry,
+    queued: &mut VecDeque<BridgeRequest>,
+    io: &NdjsonIo,
+) -> AppResult<BridgeResponse<serde_json::Value>> {
+    let p: CancelPayload = match serde_json::from_value(std::mem::take(&mut req.payload)) {
+        Ok(v) => v,
+        Err(e) => {
+            let e = AppError::InvalidRequest(e.to_string());
+            return Ok(BridgeResponse::err(req.v, req.id, e.code(), e.to_string()));
+        }
+    };
+    let cancelled = if let Some(pos) = queued.iter().position(|q| q.id == p.id) {
+        let target = queued.remove(pos).expect("position is in bounds");
+        let e = AppError::Cancelled;
+        let resp: BridgeResponse<serde_json::Value> = BridgeResponse::err(target.v, target.id, e.code(), e.to_string());
+        io.write_json_line(&resp)?;
+        true
+    } else {
+        cancels.cancel(&p.id)
+    };
+    Ok(BridgeResponse::ok(req.v, req.id, serde_json::json!({ "cancelled": cancelled })))
+}
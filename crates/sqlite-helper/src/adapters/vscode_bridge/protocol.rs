@@ -1,6 +1,56 @@
 use serde::{Deserialize, Serialize};
 
-use crate::core::types::{ColumnMeta, ExecResult, QueryResult};
+use crate::core::{
+    params::ParamsInput,
+    types::{
+        BatchSql, BlobMode, FloatMode, IntMode, MaintenanceOp, OrderDir, ResultFormat, TransactionMode,
+    },
+};
+
+/// The `v` this server speaks unless `--protocol-version` says otherwise; requests with
+/// another `v` are rejected (except `ping` and `capabilities`).
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Every `cmd` the bridge understands, as reported by `capabilities`.
+pub const COMMANDS: &[&str] = &[
+    "ping",
+    "capabilities",
+    "cancel",
+    "connect",
+    "close",
+    "disconnect",
+    "close_all",
+    "connection_info",
+    "attach",
+    "detach",
+    "query",
+    "execute",
+    "execute_batch",
+    "browse_table",
+    "explain_query",
+    "search",
+    "insert_row",
+    "update_row",
+    "delete_row",
+    "read_blob",
+    "backup",
+    "optimize",
+    "dump_schema",
+    "dump_all",
+    "begin",
+    "commit",
+    "rollback",
+    "tables",
+    "objects",
+    "ddl",
+    "diff_schema",
+    "columns",
+    "indexes",
+    "foreign_keys",
+    "foreign_key_check",
+    "database_size_report",
+    "triggers",
+];
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -17,6 +67,10 @@ pub struct BridgeResponse<T> {
     pub v: u32,
     pub id: String,
     pub status: &'static str,
+    /// Set on streamed responses: `columns`, `row`, then a terminal `end` for queries;
+    /// `chunk`s then `end` for dumps.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame: Option<&'static str>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<T>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -33,6 +87,7 @@ impl<T> BridgeResponse<T> {
             v,
             id,
             status: "ok",
+            frame: None,
             data: Some(data),
             error: None,
             code: None,
@@ -40,11 +95,19 @@ impl<T> BridgeResponse<T> {
         }
     }
 
+    pub fn frame(v: u32, id: String, frame: &'static str, data: T) -> Self {
+        Self {
+            frame: Some(frame),
+            ..Self::ok(v, id, data)
+        }
+    }
+
     pub fn err(v: u32, id: String, code: &'static str, error: String) -> Self {
         Self {
             v,
             id,
             status: "error",
+            frame: None,
             data: None,
             error: Some(error),
             code: Some(code),
@@ -58,23 +121,265 @@ impl<T> BridgeResponse<T> {
 #[derive(Debug, Deserialize)]
 pub struct ConnectPayload {
     pub path: String,
+    /// Open with SQLITE_OPEN_READ_ONLY; later requests without a `path` use that connection.
+    #[serde(default)]
+    pub readonly: bool,
+    /// Create the database file if it does not exist; otherwise a missing file is an error.
+    #[serde(default)]
+    pub create: bool,
+    /// Pragmas for this database on top of `--pragma`, e.g. `{ "foreign_keys": true }`; they
+    /// also apply when the connection is reopened later.
+    #[serde(default)]
+    pub pragmas: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct QueryPayload {
     pub sql: String,
     #[serde(default)]
+    pub params: Option<ParamsInput>,
+    #[serde(default)]
+    pub named_params: Option<serde_json::Map<String, serde_json::Value>>,
+    #[serde(default)]
     pub path: Option<String>,
     #[serde(default)]
     pub limit: Option<usize>,
     #[serde(default)]
     pub offset: Option<usize>,
+    /// Page by this integer key column instead of `offset`; see `core::paging`.
+    #[serde(default)]
+    pub cursor_key: Option<String>,
+    /// `next_cursor` of the previous page, for the same `sql`.
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub float_mode: FloatMode,
+    /// Falls back to the `--int-mode` CLI default when omitted.
+    #[serde(default)]
+    pub int_mode: Option<IntMode>,
+    #[serde(default)]
+    pub blob_mode: BlobMode,
+    /// Emit `columns`/`row`/`end` frames instead of one buffered response.
+    #[serde(default)]
+    pub stream: bool,
+    /// Per-request deadline; capped at the server's `--timeout-ms`.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// `csv` returns the result as a CSV string in `data` (not available with `stream`);
+    /// `ndjson` as a JSON-lines string (a `columns` line, then one line per row), and with
+    /// `stream` the usual frames, which are already one line per row; `markdown` as a
+    /// markdown table string (not available with `stream`).
+    #[serde(default)]
+    pub format: ResultFormat,
+    /// With `csv`, whether the first line names the columns (default true).
+    #[serde(default)]
+    pub csv_header: Option<bool>,
+    /// Run on a read-only connection; defaults to the mode chosen at `connect`.
+    #[serde(default)]
+    pub readonly: Option<bool>,
+    /// Add `elapsed_ms` to the result; ignored with `stream` and a format other than `json`.
+    #[serde(default)]
+    pub timing: bool,
+    /// Run every statement of a read-only script; `data` is `{results: [StatementResult]}`.
+    /// Not combinable with params, paging, `stream` or csv.
+    #[serde(default)]
+    pub multi: bool,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ExecutePayload {
     pub sql: String,
     #[serde(default)]
+    pub params: Option<ParamsInput>,
+    #[serde(default)]
+    pub named_params: Option<serde_json::Map<String, serde_json::Value>>,
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Run, report the would-be changes, then roll back.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Create the database file if it does not exist.
+    #[serde(default)]
+    pub create: bool,
+}
+
+/// `insert_row`: add one row; empty `values` inserts `DEFAULT VALUES`.
+#[derive(Debug, Deserialize)]
+pub struct InsertRowPayload {
+    pub table: String,
+    /// Column -> value, in the `params` value forms.
+    #[serde(default)]
+    pub values: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// `update_row`: set columns of the one row of `table` whose full primary key is `key`.
+#[derive(Debug, Deserialize)]
+pub struct UpdateRowPayload {
+    pub table: String,
+    /// Every primary key column (and only those) with the row's value.
+    pub key: serde_json::Map<String, serde_json::Value>,
+    /// Column -> new value, in the `params` value forms.
+    pub set: serde_json::Map<String, serde_json::Value>,
+    /// Report `changes: 0` instead of failing when no row has the key.
+    #[serde(default)]
+    pub allow_no_match: bool,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// `delete_row`: delete the one row of `table` whose full primary key is `key`.
+#[derive(Debug, Deserialize)]
+pub struct DeleteRowPayload {
+    pub table: String,
+    pub key: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    pub allow_no_match: bool,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// `browse_table`: a page of a table plus its total row count, in one round trip.
+#[derive(Debug, Deserialize)]
+pub struct BrowseTablePayload {
+    pub table: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+    /// Sort by this column of the table; unsorted (storage order) when omitted.
+    #[serde(default)]
+    pub order_by: Option<String>,
+    /// `ASC` (default) or `DESC`; only with `order_by`.
+    #[serde(default)]
+    pub order_dir: Option<OrderDir>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// `explain_query`: the plan of one read-only statement; it is never run.
+#[derive(Debug, Deserialize)]
+pub struct ExplainQueryPayload {
+    pub sql: String,
+    /// Optional; parameters left unbound are planned as NULL.
+    #[serde(default)]
+    pub params: Option<ParamsInput>,
+    #[serde(default)]
+    pub named_params: Option<serde_json::Map<String, serde_json::Value>>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// `search`: rows of an FTS5 table matching `query` (FTS5 syntax), best `bm25()` score first.
+#[derive(Debug, Deserialize)]
+pub struct SearchPayload {
+    pub table: String,
+    pub query: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExecuteBatchPayload {
+    /// A script, or an array of scripts run in order in the same transaction.
+    pub sql: BatchSql,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DumpPayload {
+    /// Limit the dump to these tables (and their indexes and triggers).
+    #[serde(default)]
+    pub only: Option<Vec<String>>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OptimizePayload {
+    /// Run in order; defaults to `["optimize"]`.
+    #[serde(default)]
+    pub operations: Option<Vec<MaintenanceOp>>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BackupPayload {
+    /// Destination file; must be inside `--allowed-dir`.
+    pub dest: String,
+    /// Replace `dest` if it already exists.
+    #[serde(default)]
+    pub overwrite: bool,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReadBlobPayload {
+    pub table: String,
+    pub column: String,
+    #[serde(default)]
+    pub rowid: Option<i64>,
+    #[serde(default)]
+    pub pk: Option<serde_json::Map<String, serde_json::Value>>,
+    #[serde(default)]
+    pub offset: Option<usize>,
+    /// Defaults to `--max-blob-bytes`.
+    #[serde(default)]
+    pub length: Option<usize>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelPayload {
+    /// `id` of the request to cancel.
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransactionPayload {
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Only used by `begin`; defaults to `deferred`.
+    #[serde(default)]
+    pub mode: Option<TransactionMode>,
+}
+
+/// `close` and `disconnect`; without a `path`, the active database.
+#[derive(Debug, Deserialize)]
+pub struct ClosePayload {
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConnectionInfoPayload {
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AttachPayload {
+    /// Name the attached tables are queried under, as `alias.table`.
+    pub alias: String,
+    /// Database file to attach; must be inside `--allowed-dir`.
+    pub file: String,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DetachPayload {
+    pub alias: String,
+    #[serde(default)]
     pub path: Option<String>,
 }
 
@@ -82,6 +387,61 @@ pub struct ExecutePayload {
 pub struct TablesPayload {
     #[serde(default)]
     pub path: Option<String>,
+    /// Also list attached databases' tables, as `alias.table`.
+    #[serde(default)]
+    pub include_attached: bool,
+    /// List only this database of the connection (`main`, `temp` or an attached alias; tables
+    /// outside `main` are named `alias.table`) instead of `main` plus `include_attached` ones.
+    #[serde(default)]
+    pub schema: Option<String>,
+    /// Also list views, after the tables of each database (`objects` tells them apart).
+    #[serde(default)]
+    pub include_views: bool,
+    /// Return `{name, row_count}` objects instead of names; a count is `null` if it failed or
+    /// took over 5s (capped by the request timeout).
+    #[serde(default)]
+    pub include_row_counts: bool,
+    /// Take row counts from the last `ANALYZE` (`sqlite_stat1`) instead of counting rows;
+    /// `null` for tables never analyzed.
+    #[serde(default)]
+    pub approximate: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ObjectsPayload {
+    #[serde(default)]
+    pub path: Option<String>,
+    /// `main` (default), `temp` or an attached alias; names are returned without it.
+    #[serde(default)]
+    pub schema: Option<String>,
+}
+
+/// `ddl`: stored `CREATE` statements of `table` (with its indexes and triggers) or, without
+/// one, of the whole schema.
+#[derive(Debug, Deserialize)]
+pub struct DdlPayload {
+    #[serde(default)]
+    pub table: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// `diff_schema`: how the schema of `other` differs from that of `path` (the active database
+/// by default).
+#[derive(Debug, Deserialize)]
+pub struct DiffSchemaPayload {
+    pub other: String,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// `triggers`: all triggers of the main database, or only those on `table`.
+#[derive(Debug, Deserialize)]
+pub struct TriggersPayload {
+    #[serde(default)]
+    pub table: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -91,10 +451,36 @@ pub struct ColumnsPayload {
     pub path: Option<String>,
 }
 
-// Response data wrappers (keeps protocol explicit)
-pub type ConnectResult = bool;
-pub type TablesResult = Vec<String>;
-pub type ColumnsResult = Vec<ColumnMeta>;
-pub type QueryResultData = QueryResult;
-pub type ExecuteResultData = ExecResult;
+#[derive(Debug, Deserialize)]
+pub struct IndexesPayload {
+    pub table: String,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForeignKeysPayload {
+    pub table: String,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// `foreign_key_check`: rows breaking a foreign key, of `table` or the whole main database.
+#[derive(Debug, Deserialize)]
+pub struct ForeignKeyCheckPayload {
+    #[serde(default)]
+    pub table: Option<String>,
+    /// At most this many violations are listed (capped by `--max-rows`); all are counted.
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// `database_size_report`: space per table and index of the main database (reads every page).
+#[derive(Debug, Deserialize)]
+pub struct DatabaseSizeReportPayload {
+    #[serde(default)]
+    pub path: Option<String>,
+}
 
@@ -1,7 +1,13 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use clap::Parser;
 
+use crate::core::{
+    connection::ConnectionConfig,
+    pragmas::PragmaSetting,
+    types::{EncodeOptions, IntMode, JournalMode, Synchronous, DEFAULT_MAX_BLOB_BYTES},
+};
+
 #[derive(Parser, Debug, Clone)]
 #[command(name = "sqlite-helper")]
 pub struct Args {
@@ -13,20 +19,156 @@ pub struct Args {
     #[arg(long, default_value = "info")]
     pub log_level: String,
 
+    /// Open every database read-only; execute/write_query are rejected and missing files are not created.
+    #[arg(long)]
+    pub readonly: bool,
+
+    /// Journal mode set on every read-write connection when it is opened (default: leave the
+    /// database's mode). Read-only connections cannot change it and keep the database's mode.
+    #[arg(long, value_enum)]
+    pub journal_mode: Option<JournalMode>,
+
+    /// `PRAGMA synchronous` level set on every connection (default: SQLite's default).
+    #[arg(long, value_enum)]
+    pub synchronous: Option<Synchronous>,
+
+    /// Enforce foreign key constraints (`PRAGMA foreign_keys`, off in SQLite unless set) on every
+    /// connection; `--foreign-keys=false` leaves them unenforced.
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub foreign_keys: bool,
+
+    /// PRAGMA applied to every connection when it is opened, as NAME=VALUE (repeatable), e.g.
+    /// `--pragma foreign_keys=ON`. Only per-connection pragmas are accepted; see `connection_info`.
+    #[arg(long = "pragma", value_name = "NAME=VALUE")]
+    pub pragmas: Vec<PragmaSetting>,
+
     /// Maximum rows returned per query (unless a smaller limit is provided).
     #[arg(long, default_value_t = 1000)]
     pub max_rows: usize,
 
-    /// Soft timeout for a single request.
+    /// Rows shown by table resource previews (capped by `--max-rows`); resource URIs may ask for
+    /// a different `?limit=`.
+    #[arg(long, default_value_t = 50)]
+    pub preview_rows: usize,
+
+    /// How long SQLite waits for another connection's lock before reporting SQLITE_BUSY; the
+    /// worker then retries with backoff until the request timeout before failing with `DB_BUSY`.
+    #[arg(long, default_value_t = 2_000)]
+    pub busy_timeout_ms: u64,
+
+    /// Prepared statements each worker keeps for reuse, keyed by SQL text; repeated queries
+    /// (e.g. a polling dashboard) skip parsing and planning (0 disables).
+    #[arg(long, default_value_t = 32)]
+    pub statement_cache_size: usize,
+
+    /// Soft timeout for a single request; the running statement is interrupted when it elapses (0 disables).
     #[arg(long, default_value_t = 30_000)]
     pub timeout_ms: u64,
 
-    /// Allowed directory whitelist (repeatable). Mainly for MCP mode.
+    /// Shut down a database worker after this long without requests (0 keeps workers forever).
+    /// Workers with an open transaction are never evicted.
+    #[arg(long, default_value_t = 600_000)]
+    pub idle_ttl_ms: u64,
+
+    /// `--idle-ttl-ms` in seconds; takes its place when given.
+    #[arg(long, conflicts_with = "idle_ttl_ms")]
+    pub idle_timeout_secs: Option<u64>,
+
+    /// Keep at most this many databases open, closing the least recently used idle worker to
+    /// make room (0 = unlimited). Busy workers are never closed, so the cap is soft.
+    #[arg(long, default_value_t = 0)]
+    pub max_connections: usize,
+
+    /// Default encoding for integers beyond ±(2^53 - 1); requests may override with `int_mode`.
+    #[arg(long, value_enum, default_value_t = IntMode::Number)]
+    pub int_mode: IntMode,
+
+    /// MCP: tool calls whose statements take at least this long are reported as `warning` log
+    /// notifications (0 disables).
+    #[arg(long, default_value_t = 1_000)]
+    pub slow_query_ms: u64,
+
+    /// BLOBs larger than this are truncated in query results (see the per-request `blob_mode`).
+    #[arg(long, default_value_t = DEFAULT_MAX_BLOB_BYTES)]
+    pub max_blob_bytes: usize,
+
+    /// Allowed directory whitelist (repeatable), enforced in both MCP and bridge modes.
     #[arg(long)]
     pub allowed_dir: Vec<PathBuf>,
 
-    /// Force protocol version (reserved for future).
+    /// Accept SQLite `file:` URI filenames (e.g. `file:/media/app.db?immutable=1`) wherever a
+    /// database path is; only the `mode`, `immutable`, `cache` and `nolock` parameters are allowed.
+    #[arg(long)]
+    pub allow_uri: bool,
+
+    /// Helper protocol version (default: 1). The bridge rejects requests with another `v` and
+    /// reports it in `ping`/`capabilities`; the MCP server advertises it in `initialize`.
     #[arg(long)]
     pub protocol_version: Option<u32>,
 }
 
+impl Args {
+    /// Value encoding defaults; requests override individual fields.
+    pub fn encode_options(&self) -> EncodeOptions {
+        EncodeOptions {
+            int_mode: self.int_mode,
+            max_blob_bytes: self.max_blob_bytes,
+            ..EncodeOptions::default()
+        }
+    }
+
+    /// `--idle-timeout-secs` if given, else `--idle-ttl-ms`; zero disables eviction.
+    fn idle_ttl(&self) -> Duration {
+        self.idle_timeout_secs
+            .map_or(Duration::from_millis(self.idle_ttl_ms), Duration::from_secs)
+    }
+
+    /// `PRAGMA foreign_keys` newly opened connections get: the last `--pragma foreign_keys=...`,
+    /// which is applied after `--foreign-keys`, or else `--foreign-keys`.
+    pub fn default_foreign_keys(&self) -> bool {
+        let pragma = self.pragmas.iter().rev().find(|p| p.name == "foreign_keys");
+        pragma.map_or(self.foreign_keys, |p| ["on", "true", "yes", "1"].contains(&p.value.as_str()))
+    }
+
+    pub fn connection_config(&self) -> ConnectionConfig {
+        ConnectionConfig {
+            busy_timeout_ms: self.busy_timeout_ms,
+            statement_cache_size: self.statement_cache_size,
+            request_timeout: (self.timeout_ms > 0).then(|| Duration::from_millis(self.timeout_ms)),
+            readonly: self.readonly,
+            journal_mode: self.journal_mode,
+            synchronous: self.synchronous,
+            foreign_keys: self.foreign_keys,
+            idle_ttl: Some(self.idle_ttl()).filter(|ttl| !ttl.is_zero()),
+            max_workers: (self.max_connections > 0).then_some(self.max_connections),
+            pragmas: self.pragmas.clone(),
+            ..ConnectionConfig::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_timeout_secs_replaces_idle_ttl_ms() {
+        let ttl = |argv: &[&str]| Args::try_parse_from(argv).map(|a| a.connection_config().idle_ttl);
+        assert_eq!(ttl(&["sqlite-helper"]).unwrap(), Some(Duration::from_secs(600)));
+        let secs = ttl(&["sqlite-helper", "--idle-timeout-secs", "30"]).unwrap();
+        assert_eq!(secs, Some(Duration::from_secs(30)));
+        assert_eq!(ttl(&["sqlite-helper", "--idle-timeout-secs", "0"]).unwrap(), None);
+        assert_eq!(ttl(&["sqlite-helper", "--idle-ttl-ms", "0"]).unwrap(), None);
+        assert!(ttl(&["sqlite-helper", "--idle-ttl-ms", "5", "--idle-timeout-secs", "5"]).is_err());
+    }
+
+    #[test]
+    fn pragma_foreign_keys_overrides_the_flag() {
+        let fks = |argv: &[&str]| Args::try_parse_from(argv).unwrap().default_foreign_keys();
+        assert!(fks(&["sqlite-helper"]));
+        assert!(!fks(&["sqlite-helper", "--foreign-keys", "false"]));
+        assert!(!fks(&["sqlite-helper", "--pragma", "foreign_keys=OFF"]));
+        assert!(fks(&["sqlite-helper", "--foreign-keys", "false", "--pragma", "foreign_keys=1"]));
+        assert!(fks(&["sqlite-helper", "--pragma", "foreign_keys=off", "--pragma", "foreign_keys=on"]));
+    }
+}
@@ -0,0 +1,475 @@
+//! Checks behind `analyze_db_health`: file integrity, storage figures, space per table and
+//! index, and index advice (foreign keys without an index, indexes made redundant by another
+//! one, and tables the planner has no statistics for).
+
+use std::collections::HashMap;
+
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{
+    core::{
+        schema::{self, quote_ident, TableRef},
+        types::{
+            FindingKind, ForeignKeyCheck, ForeignKeyMeta, ForeignKeyViolation, IndexFinding, IndexMeta,
+            IntegrityReport, ObjectSize, Severity, SizeReport, StorageMetrics,
+        },
+    },
+    error::{AppError, AppResult},
+};
+
+/// `PRAGMA integrity_check`, or the faster `quick_check` (which skips verifying that indexes
+/// match their tables), keeping at most `max_errors` of the problems found.
+pub fn integrity(conn: &Connection, quick: bool, max_errors: usize) -> AppResult<IntegrityReport> {
+    let check = if quick { "quick_check" } else { "integrity_check" };
+    let max_errors = max_errors.max(1);
+    // The argument caps the problems SQLite collects; one more tells whether any were left out.
+    let mut stmt = conn.prepare(&format!("PRAGMA main.{check}({})", max_errors + 1))?;
+    let mut rows: Vec<String> = stmt.query_map([], |r| r.get(0))?.collect::<Result<_, _>>()?;
+    let ok = rows.len() == 1 && rows[0] == "ok";
+    if ok {
+        rows.clear();
+    }
+    let truncated = rows.len() > max_errors;
+    rows.truncate(max_errors);
+    Ok(IntegrityReport {
+        check: check.to_string(),
+        ok,
+        errors: rows,
+        truncated,
+    })
+}
+
+/// `PRAGMA foreign_key_check` of `table` (`alias.table` for an attached database) or of every
+/// table of the main database, each violation named by its constraint from
+/// `PRAGMA foreign_key_list`; at most `max_rows` are listed, all are counted. Finds the orphans
+/// that pile up while foreign keys are not enforced.
+pub fn foreign_key_check(conn: &Connection, table: Option<&str>, max_rows: usize) -> AppResult<ForeignKeyCheck> {
+    let target = table.map(|t| TableRef::resolve(conn, t)).transpose()?;
+    let sql = match &target {
+        Some(t) => format!("PRAGMA {}foreign_key_check({})", t.schema_prefix(), quote_ident(t.name)),
+        None => "PRAGMA main.foreign_key_check".to_string(),
+    };
+    // Reported names are bare; constraints of an attached table are looked up as `alias.table`.
+    let qualify = |name: &str| match target.as_ref().and_then(|t| t.schema) {
+        Some(schema) => format!("{schema}.{name}"),
+        None => name.to_string(),
+    };
+
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([])?;
+    let mut constraints: HashMap<String, Vec<ForeignKeyMeta>> = HashMap::new();
+    let mut check = ForeignKeyCheck {
+        violations: Vec::new(),
+        total: 0,
+        by_table: Default::default(),
+        truncated: false,
+    };
+    while let Some(row) = rows.next()? {
+        let table: String = row.get("table")?;
+        check.total += 1;
+        *check.by_table.entry(table.clone()).or_default() += 1;
+        if check.violations.len() >= max_rows {
+            check.truncated = true;
+            continue;
+        }
+        let constraint_index: i64 = row.get("fkid")?;
+        if !constraints.contains_key(&table) {
+            let fks = schema::list_foreign_keys(conn, &qualify(&table))?;
+            constraints.insert(table.clone(), fks);
+        }
+        let fk = constraints[&table].iter().find(|fk| fk.id == constraint_index);
+        check.violations.push(ForeignKeyViolation {
+            rowid: row.get("rowid")?,
+            references: row.get("parent")?,
+            constraint_index,
+            from: fk.map(|fk| fk.from.clone()).unwrap_or_default(),
+            to: fk.map(|fk| fk.to.clone()).unwrap_or_default(),
+            table,
+        });
+    }
+    Ok(check)
+}
+
+/// Page and header figures of the main database, none of which read table data. When more
+/// than `vacuum_threshold` (a fraction) of the pages are free, `recommendation` names the
+/// statement that gives them back to the file system.
+pub fn storage_metrics(conn: &Connection, vacuum_threshold: f64) -> AppResult<StorageMetrics> {
+    let int = |pragma: &str| conn.query_row(&format!("PRAGMA main.{pragma}"), [], |r| r.get::<_, i64>(0));
+    let text = |pragma: &str| conn.query_row(&format!("PRAGMA main.{pragma}"), [], |r| r.get::<_, String>(0));
+    let page_size = int("page_size")? as u64;
+    let page_count = int("page_count")? as u64;
+    let freelist_count = int("freelist_count")? as u64;
+    let auto_vacuum = match int("auto_vacuum")? {
+        1 => "full",
+        2 => "incremental",
+        _ => "none",
+    };
+    let fragmentation_ratio = match page_count {
+        0 => 0.0,
+        n => freelist_count as f64 / n as f64,
+    };
+    let free_bytes = page_size * freelist_count;
+    let recommendation = (freelist_count > 0 && fragmentation_ratio > vacuum_threshold).then(|| {
+        let statement = match auto_vacuum {
+            "incremental" => "PRAGMA incremental_vacuum;",
+            _ => "VACUUM;",
+        };
+        format!(
+            "{statement} {:.0}% of the file ({free_bytes} bytes) is free pages",
+            fragmentation_ratio * 100.0
+        )
+    });
+    Ok(StorageMetrics {
+        page_size,
+        page_count,
+        freelist_count,
+        auto_vacuum: auto_vacuum.to_string(),
+        journal_mode: text("journal_mode")?,
+        user_version: int("user_version")?,
+        application_id: int("application_id")?,
+        encoding: text("encoding")?,
+        total_bytes: page_size * page_count,
+        free_bytes,
+        fragmentation_ratio,
+        recommendation,
+    })
+}
+
+/// Pages, bytes and unused bytes of every table and index b-tree of the main database, largest
+/// first, from the `dbstat` virtual table in its aggregate mode. This reads every page of the
+/// file, so it is slow on large databases. Builds without `SQLITE_ENABLE_DBSTAT_VTAB` get
+/// `Unsupported`.
+pub fn size_report(conn: &Connection) -> AppResult<SizeReport> {
+    let sql = "SELECT s.name, coalesce(m.type, 'table'), coalesce(m.tbl_name, s.name), s.pageno, s.pgsize, \
+               s.unused, s.payload \
+               FROM dbstat('main', 1) AS s LEFT JOIN main.sqlite_schema AS m ON m.name = s.name \
+               ORDER BY s.pgsize DESC, s.name";
+    let mut stmt = conn.prepare(sql).map_err(|e| {
+        if e.to_string().contains("no such table: dbstat") {
+            AppError::Unsupported("the dbstat virtual table (SQLITE_ENABLE_DBSTAT_VTAB) is missing".into())
+        } else {
+            e.into()
+        }
+    })?;
+    let objects = stmt
+        .query_map([], |r| {
+            Ok(ObjectSize {
+                name: r.get(0)?,
+                kind: r.get(1)?,
+                table: r.get(2)?,
+                pages: r.get::<_, i64>(3)? as u64,
+                bytes: r.get::<_, i64>(4)? as u64,
+                unused_bytes: r.get::<_, i64>(5)? as u64,
+                payload_bytes: r.get::<_, i64>(6)? as u64,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(SizeReport {
+        pages: objects.iter().map(|o| o.pages).sum(),
+        bytes: objects.iter().map(|o| o.bytes).sum(),
+        unused_bytes: objects.iter().map(|o| o.unused_bytes).sum(),
+        objects,
+    })
+}
+
+/// Index advice for the ordinary tables of the main database (virtual tables and their shadow
+/// tables are left out), table by table; only tables holding rows are checked for statistics.
+/// Nothing is changed: each finding carries the statement the user could run.
+pub fn index_advice(conn: &Connection) -> AppResult<Vec<IndexFinding>> {
+    let tables: Vec<String> = schema::list_objects(conn, "main")?
+        .into_iter()
+        .filter(|o| o.kind == "table" && o.module.is_none() && !o.shadow)
+        .map(|o| o.name)
+        .collect();
+    let analyzed = analyzed_tables(conn)?;
+
+    let mut findings = Vec::new();
+    for table in &tables {
+        let indexes = schema::list_indexes(conn, table)?;
+        unindexed_foreign_keys(conn, table, &indexes, &mut findings)?;
+        redundant_indexes(table, &indexes, &mut findings);
+        if !analyzed.iter().any(|t| t.eq_ignore_ascii_case(table)) && has_rows(conn, table)? {
+            findings.push(IndexFinding {
+                severity: Severity::Info,
+                kind: FindingKind::NotAnalyzed,
+                table: table.clone(),
+                index: None,
+                columns: Vec::new(),
+                message: format!("{table} has never been analyzed; the planner guesses its row counts"),
+                suggested_sql: format!("ANALYZE {};", quote_ident(table)),
+            });
+        }
+    }
+    Ok(findings)
+}
+
+/// `ANALYZE` records nothing for an empty table, and there is nothing to plan for in one.
+fn has_rows(conn: &Connection, table: &str) -> AppResult<bool> {
+    let sql = format!("SELECT 1 FROM main.{} LIMIT 1", quote_ident(table));
+    Ok(conn.query_row(&sql, [], |_| Ok(())).optional()?.is_some())
+}
+
+/// Tables with `sqlite_stat1` rows (none if `ANALYZE` never ran).
+fn analyzed_tables(conn: &Connection) -> AppResult<Vec<String>> {
+    let stat1: Option<i64> = conn
+        .query_row(
+            "SELECT 1 FROM main.sqlite_master WHERE type = 'table' AND name = 'sqlite_stat1'",
+            [],
+            |r| r.get(0),
+        )
+        .optional()?;
+    if stat1.is_none() {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("SELECT DISTINCT tbl FROM main.sqlite_stat1")?;
+    let tables = stmt.query_map([], |r| r.get(0))?.collect::<Result<Vec<String>, _>>()?;
+    Ok(tables)
+}
+
+/// A foreign key is covered when its columns, in any order, are the leading columns of a
+/// full (not partial) index, or it is the table's `INTEGER PRIMARY KEY` (the rowid).
+fn unindexed_foreign_keys(
+    conn: &Connection,
+    table: &str,
+    indexes: &[IndexMeta],
+    findings: &mut Vec<IndexFinding>,
+) -> AppResult<()> {
+    let columns = schema::list_columns(conn, table)?;
+    let pk: Vec<_> = columns.iter().filter(|c| c.pk.unwrap_or(0) > 0).collect();
+    let rowid = match pk.as_slice() {
+        [c] if c.decl_type.as_deref().is_some_and(|t| t.eq_ignore_ascii_case("INTEGER")) => Some(&c.name),
+        _ => None,
+    };
+
+    for fk in schema::list_foreign_keys(conn, table)? {
+        let is_rowid = matches!((rowid, fk.from.as_slice()), (Some(r), [c]) if r.eq_ignore_ascii_case(c));
+        let covered = is_rowid
+            || indexes.iter().filter(|i| !i.partial).any(|i| {
+                i.columns.len() >= fk.from.len()
+                    && fk.from.iter().all(|c| {
+                        i.columns[..fk.from.len()].iter().flatten().any(|ic| ic.eq_ignore_ascii_case(c))
+                    })
+            });
+        if covered {
+            continue;
+        }
+        let quoted: Vec<String> = fk.from.iter().map(|c| quote_ident(c)).collect();
+        findings.push(IndexFinding {
+            severity: Severity::Warn,
+            kind: FindingKind::UnindexedForeignKey,
+            table: table.to_string(),
+            index: None,
+            columns: fk.from.clone(),
+            message: format!(
+                "foreign key {table}({}) -> {} has no index; changes to {} scan all of {table}",
+                fk.from.join(", "),
+                fk.table,
+                fk.table
+            ),
+            suggested_sql: format!(
+                "CREATE INDEX {} ON {} ({});",
+                quote_ident(&format!("{table}_{}_idx", fk.from.join("_"))),
+                quote_ident(table),
+                quoted.join(", ")
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// An index created with `CREATE INDEX` (not backing a constraint), neither unique nor partial,
+/// whose columns lead a full index of the table is redundant; of two identical ones, the one
+/// sorting last by name is reported.
+fn redundant_indexes(table: &str, indexes: &[IndexMeta], findings: &mut Vec<IndexFinding>) {
+    // Expression columns are `None`; such indexes are skipped rather than compared blindly.
+    let plain = |i: &IndexMeta| i.columns.iter().all(Option::is_some) && !i.partial;
+    for a in indexes.iter().filter(|i| plain(i) && i.origin == "c" && !i.unique) {
+        let covering = indexes.iter().filter(|b| plain(b) && b.name != a.name).find(|b| {
+            let leads = b.columns.len() >= a.columns.len()
+                && a.columns.iter().zip(&b.columns).all(|(x, y)| x.as_deref().zip(y.as_deref())
+                    .is_some_and(|(x, y)| x.eq_ignore_ascii_case(y)));
+            // A duplicate that is itself droppable is only reported once.
+            let duplicate = b.columns.len() == a.columns.len() && b.origin == "c" && !b.unique;
+            leads && !(duplicate && b.name > a.name)
+        });
+        let Some(b) = covering else { continue };
+        let duplicate = b.columns.len() == a.columns.len();
+        let columns: Vec<String> = a.columns.iter().flatten().cloned().collect();
+        findings.push(IndexFinding {
+            severity: if duplicate { Severity::Warn } else { Severity::Info },
+            kind: FindingKind::RedundantIndex,
+            table: table.to_string(),
+            index: Some(a.name.clone()),
+            message: match duplicate {
+                true => format!("index {} duplicates {} on {table}({})", a.name, b.name, columns.join(", ")),
+                false => format!(
+                    "index {} on {table}({}) is a prefix of {}, which serves the same lookups",
+                    a.name,
+                    columns.join(", "),
+                    b.name
+                ),
+            },
+            columns,
+            suggested_sql: format!("DROP INDEX {};", quote_ident(&a.name)),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"
+        CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT UNIQUE, name TEXT);
+        CREATE TABLE teams (a INT, b INT, PRIMARY KEY (a, b));
+        CREATE TABLE posts (
+            id INTEGER PRIMARY KEY,
+            author_id INTEGER REFERENCES users (id),
+            editor_id INTEGER REFERENCES users (id),
+            team_a INT, team_b INT,
+            title TEXT,
+            FOREIGN KEY (team_a, team_b) REFERENCES teams (a, b)
+        );
+        CREATE INDEX posts_editor ON posts (editor_id, title);
+        CREATE INDEX posts_team ON posts (team_b, team_a);
+        CREATE INDEX posts_title ON posts (title);
+        CREATE INDEX posts_title_again ON posts (title);
+        CREATE INDEX posts_editor_only ON posts (editor_id);
+        CREATE INDEX users_email ON users (email);
+        CREATE INDEX users_name_partial ON users (name) WHERE name IS NOT NULL;
+        CREATE VIRTUAL TABLE docs USING fts5(body);
+    "#;
+
+    #[test]
+    fn reports_free_pages_and_integrity() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "PRAGMA user_version = 7;
+             CREATE TABLE t (b BLOB);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 50)
+             INSERT INTO t SELECT zeroblob(8000) FROM n;
+             DELETE FROM t;",
+        )
+        .unwrap();
+        let m = storage_metrics(&conn, 0.2).unwrap();
+        assert_eq!((m.user_version, m.auto_vacuum.as_str(), m.encoding.as_str()), (7, "none", "UTF-8"));
+        assert!(m.freelist_count > 0 && m.free_bytes == m.freelist_count * m.page_size);
+        assert!(m.fragmentation_ratio > 0.2, "{m:?}");
+        assert!(m.recommendation.as_deref().unwrap().starts_with("VACUUM;"));
+        assert_eq!(storage_metrics(&conn, 0.99).unwrap().recommendation, None);
+
+        conn.execute_batch("VACUUM").unwrap();
+        assert_eq!(storage_metrics(&conn, 0.2).unwrap().freelist_count, 0);
+        for quick in [false, true] {
+            let report = integrity(&conn, quick, 10).unwrap();
+            assert!(report.ok && report.errors.is_empty() && !report.truncated);
+        }
+    }
+
+    #[test]
+    fn sizes_every_table_and_index_largest_first() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE small (v);
+             CREATE TABLE big (id INTEGER PRIMARY KEY, code TEXT UNIQUE, body TEXT);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 200)
+             INSERT INTO big (code, body) SELECT printf('%020d', i), printf('%0500d', i) FROM n;",
+        )
+        .unwrap();
+
+        let report = size_report(&conn).unwrap();
+        let names: Vec<&str> = report.objects.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names[..2], ["big", "sqlite_autoindex_big_1"]);
+        assert!(names.contains(&"small") && names.contains(&"sqlite_schema"), "{names:?}");
+        let index = &report.objects[1];
+        assert_eq!((index.kind.as_str(), index.table.as_str()), ("index", "big"));
+        assert!(report.objects.windows(2).all(|w| w[0].bytes >= w[1].bytes));
+        let page_size: u64 = conn.query_row("PRAGMA page_size", [], |r| r.get(0)).unwrap();
+        let page_count: u64 = conn.query_row("PRAGMA page_count", [], |r| r.get(0)).unwrap();
+        assert_eq!((report.pages, report.bytes), (page_count, page_count * page_size));
+        assert!(report.objects[0].payload_bytes >= 200 * 500);
+    }
+
+    #[test]
+    fn finds_rows_breaking_foreign_keys() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+             CREATE TABLE users (id INTEGER PRIMARY KEY);
+             CREATE TABLE teams (a, b, PRIMARY KEY (a, b));
+             CREATE TABLE posts (
+                 author INTEGER REFERENCES users,
+                 team_a, team_b, FOREIGN KEY (team_a, team_b) REFERENCES teams (a, b)
+             );
+             INSERT INTO users VALUES (1);
+             INSERT INTO posts VALUES (1, NULL, NULL), (2, NULL, NULL), (1, 'x', 'y'), (3, 'x', 'y');",
+        )
+        .unwrap();
+
+        let check = foreign_key_check(&conn, None, 100).unwrap();
+        assert_eq!((check.total, check.truncated), (4, false));
+        assert_eq!(check.by_table.get("posts"), Some(&4));
+        let broken: Vec<(Option<i64>, &str, &[String])> =
+            check.violations.iter().map(|v| (v.rowid, v.references.as_str(), &v.from[..])).collect();
+        let (author, team) = (["author".to_string()], ["team_a".to_string(), "team_b".to_string()]);
+        assert_eq!(
+            broken,
+            [
+                (Some(2), "users", &author[..]),
+                (Some(3), "teams", &team[..]),
+                (Some(4), "teams", &team[..]),
+                (Some(4), "users", &author[..]),
+            ]
+        );
+        assert_eq!(check.violations[0].to, [None]);
+
+        let capped = foreign_key_check(&conn, Some("posts"), 1).unwrap();
+        assert_eq!((capped.violations.len(), capped.total, capped.truncated), (1, 4, true));
+        let clean = foreign_key_check(&conn, Some("users"), 100).unwrap();
+        assert!(clean.violations.is_empty() && clean.total == 0 && clean.by_table.is_empty());
+    }
+
+    fn summary(findings: &[IndexFinding]) -> Vec<(FindingKind, Severity, &str, &str)> {
+        findings
+            .iter()
+            .map(|f| (f.kind, f.severity, f.table.as_str(), f.index.as_deref().unwrap_or("")))
+            .collect()
+    }
+
+    #[test]
+    fn advises_on_the_fixture_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(FIXTURE).unwrap();
+        conn.execute_batch(
+            "INSERT INTO users (email) VALUES ('a'); ANALYZE users;
+             INSERT INTO posts (author_id, title) VALUES (1, 'hello');",
+        )
+        .unwrap();
+        let findings = index_advice(&conn).unwrap();
+
+        use FindingKind::*;
+        use Severity::*;
+        assert_eq!(
+            summary(&findings),
+            [
+                (UnindexedForeignKey, Warn, "posts", ""),
+                (RedundantIndex, Info, "posts", "posts_editor_only"),
+                (RedundantIndex, Warn, "posts", "posts_title_again"),
+                (NotAnalyzed, Info, "posts", ""),
+                // teams is empty, so it has nothing to analyze.
+                // users_email duplicates the UNIQUE constraint's autoindex.
+                (RedundantIndex, Warn, "users", "users_email"),
+            ]
+        );
+        assert_eq!(findings[0].columns, ["author_id"]);
+        assert_eq!(findings[0].suggested_sql, r#"CREATE INDEX "posts_author_id_idx" ON "posts" ("author_id");"#);
+        assert_eq!(findings[1].suggested_sql, r#"DROP INDEX "posts_editor_only";"#);
+        assert_eq!(findings[3].suggested_sql, r#"ANALYZE "posts";"#);
+
+        // Acting on the advice clears it.
+        for f in &findings {
+            conn.execute_batch(&f.suggested_sql).unwrap();
+        }
+        assert_eq!(summary(&index_advice(&conn).unwrap()), []);
+    }
+}
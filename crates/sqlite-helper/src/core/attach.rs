@@ -0,0 +1,131 @@
+//! `ATTACH DATABASE` on a worker connection, for queries that join across database files.
+//!
+//! Attached tables are addressed as `alias.table`. The connection's own `PRAGMA
+//! database_list` is the source of truth for what is attached; the `ConnectionManager`
+//! additionally remembers attachments made through it so a respawned worker gets them back.
+//!
+//! Worker connections refuse `ATTACH`/`DETACH` in client SQL (see `guard`): an attached file
+//! would otherwise be readable without passing `--allowed-dir`.
+
+use std::{cell::Cell, path::PathBuf};
+
+use rusqlite::{
+    hooks::{AuthAction, AuthContext, Authorization},
+    Connection,
+};
+
+use crate::core::{paths, schema::is_safe_identifier, types::AttachedDatabase};
+use crate::error::{AppError, AppResult};
+
+impl AttachedDatabase {
+    /// Validate `alias` (a plain identifier, not `main`/`temp`) and `path` (a file path, not a
+    /// `file:` URI, which the connection may not have been opened to accept). `path` must
+    /// already have passed `validate_db_path`.
+    pub fn new(alias: &str, path: PathBuf) -> AppResult<Self> {
+        if !is_safe_identifier(alias) {
+            return Err(AppError::InvalidRequest(format!("invalid database alias: {alias}")));
+        }
+        if alias.eq_ignore_ascii_case("main") || alias.eq_ignore_ascii_case("temp") {
+            return Err(AppError::InvalidRequest(format!("database alias {alias} is reserved")));
+        }
+        if paths::is_uri(&path) {
+            return Err(AppError::InvalidRequest(
+                "attach takes a file path, not a file: URI".into(),
+            ));
+        }
+        Ok(Self {
+            alias: alias.to_string(),
+            path,
+        })
+    }
+}
+
+thread_local! {
+    /// Set while `attach`/`detach` run; each worker's connection lives on its own thread.
+    static ATTACH_ALLOWED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Deny `ATTACH` and `DETACH` on `conn` except from `attach` and `detach` below, whose paths
+/// have passed `validate_db_path`. Checked when a statement is prepared, so it covers every
+/// way SQL reaches the connection.
+pub fn guard(conn: &Connection) {
+    conn.authorizer(Some(|ctx: AuthContext<'_>| match ctx.action {
+        AuthAction::Attach { .. } | AuthAction::Detach { .. } if !ATTACH_ALLOWED.with(Cell::get) => {
+            Authorization::Deny
+        }
+        _ => Authorization::Allow,
+    }));
+}
+
+fn allowed<T>(f: impl FnOnce() -> T) -> T {
+    ATTACH_ALLOWED.with(|a| a.set(true));
+    let res = f();
+    ATTACH_ALLOWED.with(|a| a.set(false));
+    res
+}
+
+/// Databases attached to `conn`, in attach order (`main` and `temp` excluded).
+pub fn list(conn: &Connection) -> AppResult<Vec<AttachedDatabase>> {
+    let mut stmt = conn.prepare("SELECT name, file FROM pragma_database_list WHERE seq > 1 ORDER BY seq")?;
+    let rows = stmt
+        .query_map([], |r| {
+            Ok(AttachedDatabase {
+                alias: r.get(0)?,
+                path: PathBuf::from(r.get::<_, String>(1)?),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+pub fn attach(conn: &Connection, db: &AttachedDatabase) -> AppResult<()> {
+    if list(conn)?.iter().any(|a| a.alias.eq_ignore_ascii_case(&db.alias)) {
+        return Err(AppError::InvalidRequest(format!(
+            "a database is already attached as {}",
+            db.alias
+        )));
+    }
+    // Both the file name and the alias are expressions in ATTACH, so they can be bound.
+    allowed(|| conn.execute("ATTACH DATABASE ?1 AS ?2", (db.path.to_string_lossy(), db.alias.as_str())))?;
+    Ok(())
+}
+
+/// Detach `alias`; a no-op if this connection does not have it attached.
+pub fn detach(conn: &Connection, alias: &str) -> AppResult<()> {
+    if list(conn)?.iter().any(|a| a.alias.eq_ignore_ascii_case(alias)) {
+        allowed(|| conn.execute("DETACH DATABASE ?1", [alias]))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::schema;
+
+    #[test]
+    fn attach_lists_tables_and_rejects_duplicates() {
+        let dir = std::env::temp_dir().join(format!("sqlite-helper-attach-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let other = dir.join("other.db");
+        let _ = std::fs::remove_file(&other);
+        Connection::open(&other).unwrap().execute_batch("CREATE TABLE items (id)").unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE local (id)").unwrap();
+        let db = AttachedDatabase::new("other", other.clone()).unwrap();
+        attach(&conn, &db).unwrap();
+        assert!(attach(&conn, &db).is_err());
+        assert!(AttachedDatabase::new("main", other.clone()).is_err());
+        assert!(AttachedDatabase::new("x; DROP", other.clone()).is_err());
+
+        assert_eq!(list(&conn).unwrap()[0].alias, "other");
+        assert_eq!(schema::list_tables(&conn, false, false).unwrap(), ["local"]);
+        assert_eq!(schema::list_tables(&conn, true, false).unwrap(), ["local", "other.items"]);
+        assert_eq!(schema::list_columns(&conn, "other.items").unwrap()[0].name, "id");
+
+        detach(&conn, "other").unwrap();
+        assert!(list(&conn).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rusqlite::{
+    backup::{Backup, StepResult},
+    Connection, OpenFlags,
+};
+
+use crate::core::types::BackupResult;
+use crate::error::{AppError, AppResult, SqlErrorInfo};
+
+/// Pages copied per step; the source is only locked while a step runs, so other
+/// connections can keep reading and writing in between.
+const PAGES_PER_STEP: i32 = 256;
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(10);
+/// How long the source may stay locked without a step getting through before the backup
+/// gives up with `DB_BUSY`.
+const MAX_BUSY_WAIT: Duration = Duration::from_secs(5);
+
+/// Copy the worker's database to `dest` with SQLite's online backup API.
+///
+/// `dest` must already be validated against the whitelist. An existing file is only
+/// replaced when `overwrite` is set. Steps refused by a lock are retried until the source
+/// has been locked for `MAX_BUSY_WAIT`, or until `stop` is set (deadline or `cancel`).
+pub fn backup_to(
+    conn: &Connection,
+    dest: &Path,
+    overwrite: bool,
+    stop: &AtomicBool,
+) -> AppResult<BackupResult> {
+    backup_with_patience(conn, dest, overwrite, stop, MAX_BUSY_WAIT)
+}
+
+fn backup_with_patience(
+    conn: &Connection,
+    dest: &Path,
+    overwrite: bool,
+    stop: &AtomicBool,
+    max_busy_wait: Duration,
+) -> AppResult<BackupResult> {
+    if conn.path().is_some_and(|src| Path::new(src) == dest) {
+        return Err(AppError::InvalidRequest(
+            "backup destination is the source database".into(),
+        ));
+    }
+    if dest.exists() && !overwrite {
+        return Err(AppError::InvalidRequest(format!(
+            "backup destination already exists: {} (set overwrite to replace it)",
+            dest.display()
+        )));
+    }
+
+    let started = Instant::now();
+    let flags = OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE;
+    let mut dst = Connection::open_with_flags(dest, flags)
+        .map_err(|source| AppError::DbOpenFailed {
+            path: dest.to_path_buf(),
+            source,
+        })?;
+    let backup = Backup::new(conn, &mut dst)?;
+    let mut steps = 0u64;
+    let mut busy_since = None;
+    loop {
+        steps += 1;
+        match backup.step(PAGES_PER_STEP)? {
+            StepResult::Done => break,
+            StepResult::More => busy_since = None,
+            // `StepResult` is non-exhaustive; treat unknown results like a busy source.
+            _ => {
+                let since = *busy_since.get_or_insert_with(Instant::now);
+                if since.elapsed() >= max_busy_wait || stop.load(Ordering::SeqCst) {
+                    return Err(AppError::DbBusy(SqlErrorInfo {
+                        message: "backup source stayed locked; try again later".into(),
+                        sqlite_code: Some(5),
+                        extended_code: Some(5),
+                        error_offset: None,
+                    }));
+                }
+                thread::sleep(BUSY_RETRY_DELAY);
+            }
+        }
+    }
+    let progress = backup.progress();
+    drop(backup);
+
+    Ok(BackupResult {
+        dest: dest.to_path_buf(),
+        pages_total: progress.pagecount.max(0) as u64,
+        pages_remaining: progress.remaining.max(0) as u64,
+        steps,
+        size_bytes: std::fs::metadata(dest).map(|m| m.len()).ok(),
+        duration_ms: started.elapsed().as_micros() as f64 / 1000.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp(name: &str) -> std::path::PathBuf {
+        let file = format!("sqlite-helper-backup-{name}-{}.db", std::process::id());
+        let path = std::env::temp_dir().join(file);
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn copies_the_database_and_refuses_to_overwrite() {
+        let (src, dest) = (temp("src"), temp("dest"));
+        let conn = Connection::open(&src).unwrap();
+        conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1), (2);").unwrap();
+        let stop = AtomicBool::new(false);
+
+        let res = backup_to(&conn, &dest, false, &stop).unwrap();
+        assert_eq!((res.pages_remaining, res.size_bytes.is_some()), (0, true));
+        let copy = Connection::open(&dest).unwrap();
+        let n: i64 = copy.query_row("SELECT count(*) FROM t", [], |r| r.get(0)).unwrap();
+        assert_eq!(n, 2);
+
+        let again = backup_to(&conn, &dest, false, &stop);
+        let refused = matches!(&again, Err(AppError::InvalidRequest(m)) if m.contains("already exists"));
+        assert!(refused, "{again:?}");
+        conn.execute("INSERT INTO t VALUES (3)", []).unwrap();
+        backup_to(&conn, &dest, true, &stop).unwrap();
+        let n: i64 = copy.query_row("SELECT count(*) FROM t", [], |r| r.get(0)).unwrap();
+        assert_eq!(n, 3);
+
+        assert!(matches!(backup_to(&conn, &src, true, &stop), Err(AppError::InvalidRequest(_))));
+        let _ = std::fs::remove_file(&src);
+        let _ = std::fs::remove_file(&dest);
+    }
+
+    #[test]
+    fn gives_up_when_the_source_stays_locked() {
+        let (src, dest) = (temp("locked-src"), temp("locked-dest"));
+        let conn = Connection::open(&src).unwrap();
+        conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1);").unwrap();
+        // Each step would otherwise wait out SQLite's own busy handler first.
+        conn.busy_timeout(Duration::ZERO).unwrap();
+        let holder = Connection::open(&src).unwrap();
+        holder.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        let stop = AtomicBool::new(false);
+
+        let started = Instant::now();
+        let res = backup_with_patience(&conn, &dest, false, &stop, Duration::from_millis(50));
+        assert!(matches!(res, Err(AppError::DbBusy(_))), "{res:?}");
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // A deadline or cancel stops the wait at once.
+        stop.store(true, Ordering::SeqCst);
+        let res = backup_with_patience(&conn, &dest, true, &stop, Duration::from_secs(60));
+        assert!(matches!(res, Err(AppError::DbBusy(_))), "{res:?}");
+        let _ = std::fs::remove_file(&src);
+        let _ = std::fs::remove_file(&dest);
+    }
+}
@@ -0,0 +1,145 @@
+//! Retrying tasks that fail with SQLITE_BUSY/SQLITE_LOCKED.
+//!
+//! SQLite's busy handler already waits `--busy-timeout-ms` for the lock before giving up. When
+//! the host application holds it longer than that, the worker re-runs the whole task a few
+//! times with jittered exponential backoff, as long as the request deadline allows, and only
+//! then reports `DB_BUSY`. Tasks are only retried when they are safe to re-run: a failed
+//! statement leaves no changes behind, and a batch whose commit is refused rolls its savepoint
+//! back first. Nothing is retried inside a client's transaction, where the lock may be wanted
+//! by a writer waiting on this very transaction; the client decides whether to roll back.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::{AppError, AppResult};
+
+/// Retries after the first attempt.
+const MAX_RETRIES: u32 = 4;
+
+/// Backoff before the first retry; doubled for each further one.
+const BASE_DELAY: Duration = Duration::from_millis(25);
+
+/// Busy-retry policy for the tasks of one worker.
+#[derive(Debug, Clone, Copy)]
+pub struct BusyRetry<'a> {
+    /// Time a task may spend, retries included; `None` leaves only `MAX_RETRIES` as the bound.
+    budget: Option<Duration>,
+    /// Set when the request is interrupted (deadline or `cancel`); no retry starts after that.
+    stop: &'a AtomicBool,
+    max_retries: u32,
+}
+
+impl<'a> BusyRetry<'a> {
+    pub fn new(budget: Option<Duration>, stop: &'a AtomicBool) -> Self {
+        Self {
+            budget,
+            stop,
+            max_retries: MAX_RETRIES,
+        }
+    }
+
+    /// The policy for a task starting inside a transaction (`!conn.is_autocommit()`): no retries.
+    pub fn in_transaction(self, in_transaction: bool) -> Self {
+        Self {
+            max_retries: if in_transaction { 0 } else { self.max_retries },
+            ..self
+        }
+    }
+
+    /// Set once the request is interrupted, for tasks that wait on locks themselves.
+    pub fn stop_flag(&self) -> &'a AtomicBool {
+        self.stop
+    }
+
+    /// Run `op`, re-running it while it fails with `DbBusy` and retries remain.
+    pub fn run<T>(&self, mut op: impl FnMut() -> AppResult<T>) -> AppResult<T> {
+        let started = Instant::now();
+        let mut delay = BASE_DELAY;
+        for _ in 0..self.max_retries {
+            match op() {
+                Err(AppError::DbBusy(info)) => {
+                    let delay_now = jitter(delay);
+                    let in_budget = self.budget.is_none_or(|b| started.elapsed() + delay_now < b);
+                    if !in_budget || self.stop.load(Ordering::SeqCst) {
+                        return Err(AppError::DbBusy(info));
+                    }
+                    tracing::debug!(delay_ms = delay_now.as_millis() as u64, "database busy; retrying");
+                    thread::sleep(delay_now);
+                    if self.stop.load(Ordering::SeqCst) {
+                        return Err(AppError::DbBusy(info));
+                    }
+                    delay *= 2;
+                }
+                res => return res,
+            }
+        }
+        op()
+    }
+}
+
+/// Somewhere in `[delay / 2, delay]`, so workers contending for the same lock spread out.
+fn jitter(delay: Duration) -> Duration {
+    let half = delay / 2;
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+    half + Duration::from_nanos(u64::from(nanos) % (half.as_nanos() as u64 + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::SqlErrorInfo;
+
+    fn busy() -> AppError {
+        AppError::DbBusy(SqlErrorInfo {
+            message: "database is locked".into(),
+            sqlite_code: Some(5),
+            extended_code: Some(5),
+            error_offset: None,
+        })
+    }
+
+    #[test]
+    fn retries_busy_failures_within_budget() {
+        let stop = AtomicBool::new(false);
+        let retry = BusyRetry::new(Some(Duration::from_secs(5)), &stop);
+        let mut attempts = 0;
+        let res = retry.run(|| {
+            attempts += 1;
+            if attempts < 3 { Err(busy()) } else { Ok(attempts) }
+        });
+        assert_eq!(res.unwrap(), 3);
+
+        // Out of budget (or interrupted): the busy error surfaces after the first attempt.
+        let retry = BusyRetry::new(Some(Duration::ZERO), &stop);
+        let mut attempts = 0;
+        let res: AppResult<()> = retry.run(|| {
+            attempts += 1;
+            Err(busy())
+        });
+        assert!(matches!(res, Err(AppError::DbBusy(_))));
+        assert_eq!(attempts, 1);
+
+        // Other errors are never retried.
+        let retry = BusyRetry::new(None, &stop);
+        let mut attempts = 0;
+        let res: AppResult<()> = retry.run(|| {
+            attempts += 1;
+            Err(AppError::NotReadonly)
+        });
+        assert!(matches!(res, Err(AppError::NotReadonly)));
+        assert_eq!(attempts, 1);
+
+        // Inside a transaction the first busy error is final.
+        let retry = BusyRetry::new(None, &stop).in_transaction(true);
+        let mut attempts = 0;
+        let res: AppResult<()> = retry.run(|| {
+            attempts += 1;
+            Err(busy())
+        });
+        assert!(matches!(res, Err(AppError::DbBusy(_))));
+        assert_eq!(attempts, 1);
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use crate::core::connection::CancelToken;
+
+tokio::task_local! {
+    /// Token of the request being served; every worker task sent while serving it carries it.
+    static REQUEST: CancelToken;
+}
+
+/// Serve one request: worker tasks queued by `fut` can be abandoned with `token`.
+pub async fn scoped<F: Future>(token: CancelToken, fut: F) -> F::Output {
+    REQUEST.scope(token, fut).await
+}
+
+/// Token of the request being served, if any.
+pub fn current_request() -> Option<CancelToken> {
+    REQUEST.try_with(CancelToken::clone).ok()
+}
+
+/// In-flight requests by client request id, so a later `cancel` can abandon their worker tasks:
+/// those still queued are skipped, and a statement only is interrupted if it is theirs.
+///
+/// Lifecycle per request: `start` when it is picked up (serve it within `scoped` with the
+/// returned token), `finish` when its response is ready. Cloning shares the same registry.
+#[derive(Debug, Clone, Default)]
+pub struct CancelRegistry {
+    inner: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+#[derive(Debug, Default)]
+struct Entry {
+    token: CancelToken,
+    cancelled: bool,
+}
+
+impl CancelRegistry {
+    pub fn start(&self, id: &str) -> CancelToken {
+        let entry = Entry::default();
+        let token = entry.token.clone();
+        if let Ok(mut guard) = self.inner.lock() {
+            guard.insert(id.to_string(), entry);
+        }
+        token
+    }
+
+    /// Mark `id` cancelled and abandon its worker tasks. Returns `false` for unknown ids
+    /// (never issued, or already answered).
+    pub fn cancel(&self, id: &str) -> bool {
+        let Ok(mut guard) = self.inner.lock() else { return false };
+        let Some(entry) = guard.get_mut(id) else { return false };
+        entry.cancelled = true;
+        entry.token.cancel();
+        true
+    }
+
+    /// Forget `id`; returns whether it was cancelled (MCP then sends no response).
+    pub fn finish(&self, id: &str) -> bool {
+        self.inner
+            .lock()
+            .ok()
+            .and_then(|mut guard| guard.remove(id))
+            .is_some_and(|entry| entry.cancelled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_cancelled_requests_finish_as_cancelled() {
+        let cancels = CancelRegistry::default();
+        cancels.start("slow");
+        cancels.start("deadline");
+        assert!(cancels.cancel("slow"));
+        assert!(!cancels.cancel("never-issued"));
+
+        // An interrupt of "slow" is reported as CANCELLED; "deadline" keeps TIMEOUT.
+        assert!(cancels.finish("slow"));
+        assert!(!cancels.finish("deadline"));
+        // Once answered, the id is forgotten.
+        assert!(!cancels.cancel("slow"));
+    }
+}
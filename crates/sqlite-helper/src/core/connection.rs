@@ -1,236 +1,1919 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
+    ops::Deref,
+    panic::{self, AssertUnwindSafe},
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
     thread,
+    time::{Duration, Instant},
 };
 
-use rusqlite::{Connection, OpenFlags};
-use tokio::sync::oneshot;
+use rusqlite::{Connection, InterruptHandle, OpenFlags};
+use tokio::sync::{mpsc, oneshot};
 
 use crate::{
-    core::{query, readonly, schema, types::ExecResult, types::QueryResult},
+    core::{
+        analysis, attach, backup,
+        cancel,
+        blob::{self, BlobRow},
+        busy::BusyRetry,
+        dump, explain, fts, maintenance,
+        paging::Page,
+        params::QueryParams,
+        paths,
+        pragmas::{self, PragmaSetting},
+        diff::{self, SchemaSnapshot},
+        query, readonly,
+        rows::{self, RowEdit},
+        schema, transaction,
+        types::{
+            AttachedDatabase, BackupResult, BrowseResult, ExplainResult, ForeignKeyCheck, IndexFinding, IntegrityReport, BatchResult, BlobChunk, ConnectionInfo, DdlResult, DumpFrame, EncodeOptions, ExecResult, ForeignKeyMeta,
+            IndexMeta, JournalMode, MaintenanceOp, OptimizeResult, OrderDir, QueryFrame, QueryResult,
+            SchemaObject, SizeReport, StatementResult, StorageMetrics, Synchronous, TransactionMode, TriggerMeta,
+        },
+    },
     error::{AppError, AppResult},
 };
 
+/// Settings applied to every worker the manager spawns.
+#[derive(Debug, Clone)]
+pub struct ConnectionConfig {
+    pub busy_timeout_ms: u64,
+    /// Capacity of each connection's prepared statement cache (`prepare_cached`).
+    pub statement_cache_size: usize,
+    /// Soft per-request deadline; the running statement is interrupted once it elapses.
+    pub request_timeout: Option<Duration>,
+    /// Open workers with SQLITE_OPEN_READ_ONLY (and without SQLITE_OPEN_CREATE).
+    pub readonly: bool,
+    /// `PRAGMA journal_mode` to set on open; `None` keeps the database's current mode.
+    pub journal_mode: Option<JournalMode>,
+    /// `PRAGMA synchronous` to set on open; `None` keeps SQLite's default.
+    pub synchronous: Option<Synchronous>,
+    /// `PRAGMA foreign_keys` to set on open (SQLite's own default is off).
+    pub foreign_keys: bool,
+    /// Workers unused for this long (and not inside a transaction) are shut down; `None` keeps them forever.
+    pub idle_ttl: Option<Duration>,
+    /// Soft cap on open workers; the least recently used idle one is closed to make room.
+    pub max_workers: Option<usize>,
+    /// Create a missing database file (SQLITE_OPEN_CREATE). Chosen per open from `OpenMode`.
+    pub create: bool,
+    /// `--pragma` settings, applied on open after `journal_mode`, `synchronous` and
+    /// `foreign_keys` (so an explicit `foreign_keys` pragma wins).
+    pub pragmas: Vec<PragmaSetting>,
+    /// Databases to attach on open, from `ConnectionManager::attach`.
+    pub attached: Vec<AttachedDatabase>,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            busy_timeout_ms: 2_000,
+            statement_cache_size: 32,
+            request_timeout: None,
+            readonly: false,
+            journal_mode: None,
+            synchronous: None,
+            foreign_keys: true,
+            idle_ttl: None,
+            max_workers: None,
+            create: false,
+            pragmas: Vec::new(),
+            attached: Vec::new(),
+        }
+    }
+}
+
+/// How a request wants its database opened; only consulted when a worker has to be spawned.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenMode {
+    /// Use a separate worker opened with SQLITE_OPEN_READ_ONLY.
+    pub readonly: bool,
+    /// Create the file if it does not exist. Without it a missing file fails with
+    /// `DbOpenFailed` instead of silently becoming an empty database.
+    pub create: bool,
+}
+
+/// Workers are keyed by path and open mode, so read-only and read-write requests for the
+/// same file get separate connections.
+type WorkerKey = (PathBuf, bool);
+
 #[derive(Debug, Clone)]
 pub struct ConnectionManager {
-    inner: Arc<Mutex<HashMap<PathBuf, WorkerHandle>>>,
-    busy_timeout_ms: u64,
+    inner: Arc<Workers>,
+    /// Per-database pragmas from `set_pragmas`, kept so a respawned worker gets them again.
+    pragmas: Arc<Mutex<HashMap<PathBuf, Vec<PragmaSetting>>>>,
+    /// Per-database attachments from `attach`, likewise restored on respawn.
+    attached: Arc<Mutex<HashMap<PathBuf, Vec<AttachedDatabase>>>>,
+    /// Databases a worker was opened for during this session, even if since closed.
+    opened: Arc<Mutex<Opened>>,
+    config: ConnectionConfig,
 }
 
 impl ConnectionManager {
-    pub fn new() -> Self {
+    pub fn new(config: ConnectionConfig) -> Self {
         Self {
-            inner: Arc::new(Mutex::new(HashMap::new())),
-            busy_timeout_ms: 2_000,
+            inner: Arc::new(Workers(Mutex::new(HashMap::new()))),
+            pragmas: Arc::new(Mutex::new(HashMap::new())),
+            attached: Arc::new(Mutex::new(HashMap::new())),
+            opened: Arc::new(Mutex::new(Opened::default())),
+            config,
+        }
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.config.readonly
+    }
+
+    /// Worker for `db_path`, spawning it if needed (including after it was evicted as idle
+    /// or its thread died).
+    ///
+    /// Idle eviction runs lazily here, so it costs nothing while the server is quiet.
+    pub async fn ensure_worker(&self, db_path: &Path) -> AppResult<WorkerHandle> {
+        self.ensure_worker_with(db_path, OpenMode::default()).await
+    }
+
+    /// Like `ensure_worker`, with `mode.readonly` selecting a separate worker opened with
+    /// SQLITE_OPEN_READ_ONLY (so nothing sent to it can write) and `mode.create` allowing a
+    /// missing file to be created. Under `--readonly` every worker is read-only regardless.
+    pub async fn ensure_worker_with(&self, db_path: &Path, mode: OpenMode) -> AppResult<WorkerHandle> {
+        let readonly = mode.readonly || self.config.readonly;
+        let key = (canonicalize_lossy(db_path)?, readonly);
+        let (live, mut evicted) = {
+            let mut guard = self.inner.lock().map_err(|_| AppError::Internal("poisoned lock".into()))?;
+            let evicted = self.take_idle(&mut guard, &key);
+            let live = match guard.get(&key) {
+                Some(h) if h.is_alive() => {
+                    // Counts as use, so a handle just given out is the last LRU candidate.
+                    h.activity.touch();
+                    Some(h.clone())
+                }
+                stale => {
+                    if stale.is_some() {
+                        tracing::warn!(path = %key.0.display(), "db worker exited; respawning");
+                    }
+                    None
+                }
+            };
+            (live, evicted)
+        };
+        let handle = match live {
+            Some(h) => Ok(h),
+            // Opened without holding the lock, on the worker's thread: opening can wait out (and
+            // retry) a lock held by another process, and every other request would be stuck
+            // behind it.
+            None => self.spawn_worker(&key, mode).await.map(|h| {
+                let mut guard = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+                match guard.get(&key) {
+                    // Another caller opened one meanwhile; keep that and drop ours.
+                    Some(other) if other.is_alive() => {
+                        evicted.push(h);
+                        other.clone()
+                    }
+                    _ => {
+                        evicted.extend(self.take_lru(&mut guard, &key));
+                        guard.insert(key.clone(), h.clone());
+                        h
+                    }
+                }
+            }),
+        };
+        // Retired outside the lock; they exit once the tasks already queued have run.
+        for w in evicted {
+            tracing::debug!(path = %w.db_path.display(), "evicting db worker");
+            w.retire();
+        }
+        handle
+    }
+
+    /// Open a worker for `key` with the manager's config plus what was set for that database.
+    async fn spawn_worker(&self, key: &WorkerKey, mode: OpenMode) -> AppResult<WorkerHandle> {
+        let (path, readonly) = (&key.0, key.1);
+        let mut config = ConnectionConfig {
+            readonly,
+            create: mode.create && !readonly,
+            ..self.config.clone()
+        };
+        // Setting the journal mode writes to the database, which a read-only connection cannot.
+        if readonly {
+            config.journal_mode = None;
+        }
+        if let Some(extra) = self.pragmas.lock().ok().and_then(|p| p.get(path).cloned()) {
+            config.pragmas.extend(extra);
+        }
+        if let Some(attached) = self.attached.lock().ok().and_then(|a| a.get(path).cloned()) {
+            config.attached = attached;
+        }
+        let h = WorkerHandle::spawn(path.clone(), config).await?;
+        if let Ok(mut opened) = self.opened.lock() {
+            opened.record(path);
+        }
+        Ok(h)
+    }
+
+    fn take_idle(&self, workers: &mut HashMap<WorkerKey, WorkerHandle>, keep: &WorkerKey) -> Vec<WorkerHandle> {
+        let Some(ttl) = self.config.idle_ttl else { return Vec::new() };
+        let idle: Vec<WorkerKey> = workers
+            .iter()
+            .filter(|(key, h)| *key != keep && h.activity.is_idle(ttl))
+            .map(|(key, _)| key.clone())
+            .collect();
+        idle.iter().filter_map(|key| workers.remove(key)).collect()
+    }
+
+    /// Make room for one more worker under `max_workers` by removing the least recently used
+    /// idle ones. Busy workers are skipped, even if that leaves the map over the cap.
+    fn take_lru(&self, workers: &mut HashMap<WorkerKey, WorkerHandle>, keep: &WorkerKey) -> Vec<WorkerHandle> {
+        let Some(max) = self.config.max_workers else { return Vec::new() };
+        let open = workers.keys().filter(|key| *key != keep).count();
+        let excess = (open + 1).saturating_sub(max);
+        if excess == 0 {
+            return Vec::new();
+        }
+        let mut candidates: Vec<(Instant, WorkerKey)> = workers
+            .iter()
+            .filter(|(key, h)| *key != keep && h.activity.is_idle(Duration::ZERO))
+            .filter_map(|(key, h)| Some((h.activity.last_used()?, key.clone())))
+            .collect();
+        candidates.sort();
+        candidates
+            .into_iter()
+            .take(excess)
+            .filter_map(|(_, key)| workers.remove(&key))
+            .collect()
+    }
+
+    /// Close the workers for `db_path` (both open modes), releasing its file handle (and
+    /// WAL/SHM files). Tasks already queued run first; an open transaction is rolled back. The
+    /// next request for the path reopens it. Returns `false` if no worker was open.
+    pub async fn close(&self, db_path: &Path) -> AppResult<bool> {
+        let db_path = canonicalize_lossy(db_path)?;
+        let workers: Vec<WorkerHandle> = {
+            let mut guard = self.inner.lock().map_err(|_| AppError::Internal("poisoned lock".into()))?;
+            [false, true]
+                .into_iter()
+                .filter_map(|readonly| guard.remove(&(db_path.clone(), readonly)))
+                .collect()
+        };
+        for w in &workers {
+            w.shutdown().await;
+        }
+        Ok(!workers.is_empty())
+    }
+
+    /// Set pragmas for one database on top of `--pragma`: applied to its open workers now and,
+    /// once that succeeded, to any worker opened for it later. A setting replaces an earlier one
+    /// of the same name.
+    pub async fn set_pragmas(&self, db_path: &Path, settings: Vec<PragmaSetting>) -> AppResult<()> {
+        if settings.is_empty() {
+            return Ok(());
         }
+        let db_path = canonicalize_lossy(db_path)?;
+        for w in self.live_workers(&db_path)? {
+            w.apply_pragmas(settings.clone()).await?;
+        }
+        let mut guard = self.pragmas.lock().map_err(|_| AppError::Internal("poisoned lock".into()))?;
+        let stored = guard.entry(db_path).or_default();
+        stored.retain(|p| !settings.iter().any(|s| s.name == p.name));
+        stored.extend(settings);
+        Ok(())
     }
 
-    pub fn ensure_worker(&self, db_path: &Path) -> AppResult<WorkerHandle> {
+    /// Attach `db` to the workers of `db_path`, now and whenever one is reopened. Returns the
+    /// databases now attached to the last worker (empty if none is open).
+    pub async fn attach(&self, db_path: &Path, db: AttachedDatabase) -> AppResult<Vec<AttachedDatabase>> {
         let db_path = canonicalize_lossy(db_path)?;
-        let mut guard = self.inner.lock().map_err(|_| AppError::Internal("poisoned lock".into()))?;
-        if let Some(h) = guard.get(&db_path) {
-            return Ok(h.clone());
+        let already = self
+            .attached
+            .lock()
+            .map_err(|_| AppError::Internal("poisoned lock".into()))?
+            .get(&db_path)
+            .is_some_and(|list| list.iter().any(|a| a.alias.eq_ignore_ascii_case(&db.alias)));
+        if already {
+            return Err(AppError::InvalidRequest(format!(
+                "a database is already attached as {}",
+                db.alias
+            )));
+        }
+        let mut attached = Vec::new();
+        for w in self.live_workers(&db_path)? {
+            attached = w.attach(db.clone()).await?;
+        }
+        self.attached
+            .lock()
+            .map_err(|_| AppError::Internal("poisoned lock".into()))?
+            .entry(db_path)
+            .or_default()
+            .push(db);
+        Ok(attached)
+    }
+
+    /// Undo `attach`; fails for aliases that were not attached through it.
+    pub async fn detach(&self, db_path: &Path, alias: &str) -> AppResult<Vec<AttachedDatabase>> {
+        let db_path = canonicalize_lossy(db_path)?;
+        let removed = {
+            let mut guard = self.attached.lock().map_err(|_| AppError::Internal("poisoned lock".into()))?;
+            let list = guard.entry(db_path.clone()).or_default();
+            let before = list.len();
+            list.retain(|a| !a.alias.eq_ignore_ascii_case(alias));
+            list.len() < before
+        };
+        if !removed {
+            return Err(AppError::InvalidRequest(format!("no database attached as {alias}")));
+        }
+        let mut attached = Vec::new();
+        for w in self.live_workers(&db_path)? {
+            attached = w.detach(alias.to_string()).await?;
+        }
+        Ok(attached)
+    }
+
+    /// Open workers for a canonical `db_path`, read-write first.
+    fn live_workers(&self, db_path: &Path) -> AppResult<Vec<WorkerHandle>> {
+        let guard = self.inner.lock().map_err(|_| AppError::Internal("poisoned lock".into()))?;
+        Ok([false, true]
+            .into_iter()
+            .filter_map(|readonly| guard.get(&(db_path.to_path_buf(), readonly)).cloned())
+            .collect())
+    }
+
+    /// Databases opened this session (including closed and evicted ones), sorted; the
+    /// `MAX_REMEMBERED_OPENED` most recently added.
+    pub fn opened_paths(&self) -> Vec<PathBuf> {
+        let opened = self.opened.lock().map(|o| o.paths.iter().cloned().collect());
+        let mut paths: Vec<PathBuf> = opened.unwrap_or_default();
+        paths.sort();
+        paths
+    }
+
+    /// How many times a database not in `opened_paths` was opened; grows with every addition,
+    /// even once the oldest entries are forgotten.
+    pub fn opened_count(&self) -> usize {
+        self.opened.lock().map(|o| o.added).unwrap_or_default()
+    }
+
+    /// A worker that can read `db_path`: an open one in either mode, so listing a database
+    /// does not open a second connection to it, or else a new read-only one.
+    pub async fn reader(&self, db_path: &Path) -> AppResult<WorkerHandle> {
+        let path = canonicalize_lossy(db_path)?;
+        if let Some(w) = self.live_workers(&path)?.into_iter().find(WorkerHandle::is_alive) {
+            w.activity.touch();
+            return Ok(w);
+        }
+        let mode = OpenMode {
+            readonly: true,
+            create: false,
+        };
+        self.ensure_worker_with(&path, mode).await
+    }
+
+    /// Tables, views and triggers of `db_path`'s main schema without keeping it open: read from
+    /// a live worker when there is one, else through a read-only connection on a blocking
+    /// thread that is closed again (for listings that touch many databases).
+    pub async fn peek_objects(&self, db_path: &Path) -> AppResult<Vec<SchemaObject>> {
+        let path = canonicalize_lossy(db_path)?;
+        if let Some(w) = self.live_workers(&path)?.into_iter().find(WorkerHandle::is_alive) {
+            return w.objects().await;
+        }
+        let config = ConnectionConfig {
+            readonly: true,
+            create: false,
+            journal_mode: None,
+            pragmas: Vec::new(),
+            attached: Vec::new(),
+            ..self.config.clone()
+        };
+        tokio::task::spawn_blocking(move || {
+            let conn = open_conn(&path, &config)?;
+            schema::list_objects(&conn, "main")
+        })
+        .await
+        .map_err(|e| AppError::Internal(format!("schema read failed: {e}")))?
+    }
+
+    /// Close every worker as `close` does; returns how many were open.
+    pub async fn close_all(&self) -> usize {
+        let workers: Vec<WorkerHandle> = match self.inner.lock() {
+            Ok(mut guard) => guard.drain().map(|(_, h)| h).collect(),
+            Err(_) => return 0,
+        };
+        for w in &workers {
+            w.shutdown().await;
+        }
+        workers.len()
+    }
+
+    /// Stop every worker, rolling back any transaction still open, and wait for them to finish.
+    pub async fn shutdown(&self) {
+        self.close_all().await;
+    }
+}
+
+/// Most databases `opened_paths` remembers; the oldest addition is forgotten beyond that.
+const MAX_REMEMBERED_OPENED: usize = 1000;
+
+#[derive(Debug, Default)]
+struct Opened {
+    /// Oldest addition first.
+    paths: VecDeque<PathBuf>,
+    added: usize,
+}
+
+impl Opened {
+    fn record(&mut self, path: &Path) {
+        if self.paths.iter().any(|p| p == path) {
+            return;
+        }
+        if self.paths.len() >= MAX_REMEMBERED_OPENED {
+            self.paths.pop_front();
+        }
+        self.paths.push_back(path.to_path_buf());
+        self.added += 1;
+    }
+}
+
+/// The open workers, shared by every clone of a `ConnectionManager`. When the last clone is
+/// dropped without `shutdown`, the workers still running are stopped and joined here, so their
+/// connections are closed (and the WAL checkpointed) before the process exits.
+#[derive(Debug)]
+struct Workers(Mutex<HashMap<WorkerKey, WorkerHandle>>);
+
+impl Deref for Workers {
+    type Target = Mutex<HashMap<WorkerKey, WorkerHandle>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        let workers = self.0.get_mut().unwrap_or_else(PoisonError::into_inner);
+        for (_, w) in workers.drain() {
+            w.stop();
+        }
+    }
+}
+
+/// Usage shared between a worker's handles and its thread, for idle eviction.
+#[derive(Debug)]
+struct WorkerActivity {
+    last_used: Mutex<Instant>,
+    in_flight: AtomicUsize,
+    /// Updated by the worker after every task; an open transaction pins the worker.
+    in_transaction: AtomicBool,
+    /// Set by `WorkerHandle::interrupt` and cleared before each task, so a request that timed
+    /// out or was cancelled is not retried on SQLITE_BUSY.
+    interrupted: AtomicBool,
+    /// Tokens of the task the worker is running, if any.
+    running: Mutex<Vec<CancelToken>>,
+}
+
+impl WorkerActivity {
+    fn new() -> Self {
+        Self {
+            last_used: Mutex::new(Instant::now()),
+            in_flight: AtomicUsize::new(0),
+            in_transaction: AtomicBool::new(false),
+            interrupted: AtomicBool::new(false),
+            running: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn touch(&self) {
+        if let Ok(mut t) = self.last_used.lock() {
+            *t = Instant::now();
+        }
+    }
+
+    fn last_used(&self) -> Option<Instant> {
+        self.last_used.lock().ok().map(|t| *t)
+    }
+
+    fn is_idle(&self, ttl: Duration) -> bool {
+        self.in_flight.load(Ordering::SeqCst) == 0
+            && !self.in_transaction.load(Ordering::SeqCst)
+            && self.last_used.lock().map(|t| t.elapsed() >= ttl).unwrap_or(false)
+    }
+}
+
+/// Abandons the worker tasks carrying it: a task still queued is skipped (its caller gets no
+/// response), and a running one has its statement interrupted. Statements of other tasks on the
+/// same worker are never touched. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<TokenState>);
+
+#[derive(Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    /// Cancelled because the request's deadline passed, rather than by the client.
+    timed_out: AtomicBool,
+    /// Workers sent a task carrying this token: where `cancel` looks for one running it.
+    workers: Mutex<Vec<(Arc<WorkerActivity>, Arc<InterruptHandle>)>>,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        let workers = self.0.workers.lock().map(|w| w.clone()).unwrap_or_default();
+        for (activity, interrupt) in workers {
+            // Checked under the lock the worker takes to start and finish a task, so the
+            // interrupt cannot land on the task after this one.
+            let Ok(running) = activity.running.lock() else { continue };
+            if running.iter().any(|t| Arc::ptr_eq(&t.0, &self.0)) {
+                activity.interrupted.store(true, Ordering::SeqCst);
+                interrupt.interrupt();
+            }
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// `cancel` because the deadline passed: an interrupted statement reports `Timeout`.
+    fn time_out(&self) {
+        self.0.timed_out.store(true, Ordering::SeqCst);
+        self.cancel();
+    }
+
+    /// Remember `worker`, which is about to be sent a task carrying this token.
+    fn watch(&self, worker: &WorkerHandle) {
+        if let Ok(mut workers) = self.0.workers.lock() {
+            if !workers.iter().any(|(a, _)| Arc::ptr_eq(a, &worker.activity)) {
+                workers.push((worker.activity.clone(), worker.interrupt.clone()));
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("CancelToken").field(&self.is_cancelled()).finish()
+    }
+}
+
+/// Marks one request in flight on a worker; dropping it records the time of last use.
+#[derive(Debug)]
+struct BusyGuard(Arc<WorkerActivity>);
+
+impl BusyGuard {
+    fn new(activity: &Arc<WorkerActivity>) -> Self {
+        activity.in_flight.fetch_add(1, Ordering::SeqCst);
+        Self(activity.clone())
+    }
+}
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        self.0.touch();
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Sets the flag when dropped.
+struct SetOnDrop(Arc<AtomicBool>);
+
+impl Drop for SetOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[derive(Clone)]
+pub struct WorkerHandle {
+    tx: std::sync::mpsc::Sender<Job>,
+    interrupt: Arc<InterruptHandle>,
+    request_timeout: Option<Duration>,
+    activity: Arc<WorkerActivity>,
+    thread: Arc<Mutex<Option<thread::JoinHandle<()>>>>,
+    /// Opened with SQLITE_OPEN_READ_ONLY; write calls fail with `ReadOnlyConnection`.
+    readonly: bool,
+    pub db_path: PathBuf,
+}
+
+impl std::fmt::Debug for WorkerHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkerHandle")
+            .field("db_path", &self.db_path)
+            .field("request_timeout", &self.request_timeout)
+            .field("readonly", &self.readonly)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WorkerHandle {
+    async fn spawn(db_path: PathBuf, config: ConnectionConfig) -> AppResult<Self> {
+        let (tx, rx) = std::sync::mpsc::channel::<Job>();
+        let (opened_tx, opened_rx) = oneshot::channel();
+        let activity = Arc::new(WorkerActivity::new());
+        let worker_activity = activity.clone();
+        let worker_path = db_path.clone();
+        let retry_budget = config.request_timeout;
+        let readonly = config.readonly;
+        let gave_up = Arc::new(AtomicBool::new(false));
+        let worker_gave_up = gave_up.clone();
+        // Opened on the worker thread, so a lock held by another process (opening reads the
+        // schema, so it can hit one just like a query) only holds up this caller, retries
+        // included; the result comes back so open failures still surface from ensure_worker.
+        let thread = thread::spawn(move || {
+            let retry = BusyRetry::new(retry_budget, &worker_gave_up);
+            match retry.run(|| open_conn(&worker_path, &config)) {
+                Ok(conn) => {
+                    let _ = opened_tx.send(Ok(conn.get_interrupt_handle()));
+                    db_worker_main(conn, rx, worker_activity, retry_budget);
+                }
+                Err(e) => {
+                    let _ = opened_tx.send(Err(e));
+                }
+            }
+        });
+        // Once the caller stops waiting (its request was cancelled), no further retry starts.
+        let _gave_up = SetOnDrop(gave_up);
+        let interrupt = opened_rx
+            .await
+            .map_err(|_| AppError::Internal("db worker exited while opening".into()))??;
+        Ok(Self {
+            interrupt: Arc::new(interrupt),
+            tx,
+            request_timeout: retry_budget,
+            activity,
+            thread: Arc::new(Mutex::new(Some(thread))),
+            readonly,
+            db_path,
+        })
+    }
+
+    /// False once the worker thread has exited; its handle can no longer serve requests.
+    fn is_alive(&self) -> bool {
+        self.thread
+            .lock()
+            .map(|t| t.as_ref().is_some_and(|t| !t.is_finished()))
+            .unwrap_or(false)
+    }
+
+    /// Shut the worker down without waiting for it: inside the runtime its thread is joined on
+    /// the blocking pool, so an eviction never stalls the request that triggered it behind the
+    /// evicted worker's queue.
+    fn retire(&self) {
+        let (tx, _rx) = oneshot::channel();
+        let _ = self.tx.send(Job::untracked(DbTask::Shutdown { respond_to: tx }));
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            self.join();
+            return;
+        };
+        let thread = self.thread.lock().ok().and_then(|mut t| t.take());
+        if let Some(thread) = thread {
+            runtime.spawn_blocking(move || thread.join());
+        }
+    }
+
+    /// Shut the worker down and join its thread (blocking until it has exited).
+    fn stop(&self) {
+        let (tx, _rx) = oneshot::channel();
+        let _ = self.tx.send(Job::untracked(DbTask::Shutdown { respond_to: tx }));
+        self.join();
+    }
+
+    /// Ask the worker to roll back any open transaction and exit; resolves once it has.
+    async fn shutdown(&self) {
+        let (tx, rx) = oneshot::channel();
+        if self.tx.send(Job::untracked(DbTask::Shutdown { respond_to: tx })).is_ok() {
+            let _ = rx.await;
+        }
+        // The acknowledgement is the thread's last act, so this does not wait long.
+        self.join();
+    }
+
+    fn join(&self) {
+        let thread = self.thread.lock().ok().and_then(|mut t| t.take());
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+    }
+
+    /// Handle whose requests use `timeout_ms` instead of the server default, capped at the
+    /// server's `--timeout-ms` so clients can shorten but never extend the deadline.
+    /// `None` or 0 keeps the default.
+    pub fn with_timeout_ms(mut self, timeout_ms: Option<u64>) -> Self {
+        if let Some(ms) = timeout_ms.filter(|&ms| ms > 0) {
+            let requested = Duration::from_millis(ms);
+            self.request_timeout = Some(self.request_timeout.map_or(requested, |max| max.min(requested)));
+        }
+        self
+    }
+
+    /// Fail fast with a clear error instead of SQLite's "attempt to write a readonly database".
+    fn ensure_writable(&self) -> AppResult<()> {
+        if self.readonly {
+            return Err(AppError::ReadOnlyConnection);
+        }
+        Ok(())
+    }
+
+    /// Queue `task` on the worker; the returned token abandons it (see `CancelToken`), and so
+    /// does the token of the request being served (`cancel::scoped`).
+    fn send(&self, task: DbTask) -> AppResult<CancelToken> {
+        let token = CancelToken::default();
+        let tokens: Vec<CancelToken> =
+            [Some(token.clone()), cancel::current_request()].into_iter().flatten().collect();
+        for t in &tokens {
+            t.watch(self);
         }
+        self.tx
+            .send(Job { task, tokens })
+            .map_err(|_| AppError::Internal("db worker unavailable".into()))?;
+        Ok(token)
+    }
+
+    async fn recv<T>(&self, rx: oneshot::Receiver<AppResult<T>>, token: CancelToken) -> AppResult<T> {
+        self.recv_within(rx, token, self.request_timeout).await
+    }
+
+    /// The task's result, or `Timeout` after `deadline`. A timed-out task is abandoned: if it is
+    /// still queued the worker skips it, so a write cannot commit after the caller gave up.
+    async fn recv_within<T>(
+        &self,
+        rx: oneshot::Receiver<AppResult<T>>,
+        token: CancelToken,
+        deadline: Option<Duration>,
+    ) -> AppResult<T> {
+        let _busy = BusyGuard::new(&self.activity);
+        let res = match deadline {
+            Some(deadline) => match tokio::time::timeout(deadline, rx).await {
+                Ok(res) => res,
+                Err(_) => {
+                    token.time_out();
+                    return Err(AppError::Timeout);
+                }
+            },
+            None => rx.await,
+        };
+        res.map_err(|_| match cancel::current_request() {
+            // Skipped by the worker.
+            Some(request) if request.is_cancelled() => AppError::Cancelled,
+            _ => AppError::Internal("db worker dropped response".into()),
+        })?
+    }
+
+    pub async fn query(
+        &self,
+        sql: String,
+        params: Option<QueryParams>,
+        limit: usize,
+        page: Page,
+        encode: EncodeOptions,
+    ) -> AppResult<QueryResult> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Query {
+            sql,
+            params,
+            limit,
+            page,
+            encode,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    /// Like `query`, but rows are pushed back one frame at a time as the worker reads them.
+    pub fn query_stream(
+        &self,
+        sql: String,
+        params: Option<QueryParams>,
+        limit: usize,
+        page: Page,
+        encode: EncodeOptions,
+    ) -> AppResult<QueryStream> {
+        self.stream_query(sql, params, limit, page, encode, false)
+    }
+
+    /// `query_stream` with `read_query`'s readonly check, done in the worker before any row is read.
+    pub fn read_query_stream(
+        &self,
+        sql: String,
+        params: Option<QueryParams>,
+        limit: usize,
+        page: Page,
+        encode: EncodeOptions,
+    ) -> AppResult<QueryStream> {
+        self.stream_query(sql, params, limit, page, encode, true)
+    }
+
+    fn stream_query(
+        &self,
+        sql: String,
+        params: Option<QueryParams>,
+        limit: usize,
+        page: Page,
+        encode: EncodeOptions,
+        read_only: bool,
+    ) -> AppResult<QueryStream> {
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER_FRAMES);
+        let token = self.send(DbTask::QueryStream {
+            sql,
+            params,
+            limit,
+            page,
+            encode,
+            read_only,
+            frames: tx,
+        })?;
+        Ok(FrameStream {
+            rx,
+            deadline: self.request_timeout.map(|d| tokio::time::Instant::now() + d),
+            token,
+            _busy: BusyGuard::new(&self.activity),
+        })
+    }
+
+    /// Stream a SQL dump of the schema (plus rows when `data`), optionally limited to `only`.
+    pub fn dump(&self, data: bool, only: Option<Vec<String>>) -> AppResult<DumpStream> {
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER_FRAMES);
+        let token = self.send(DbTask::Dump {
+            data,
+            only,
+            frames: tx,
+        })?;
+        Ok(FrameStream {
+            rx,
+            deadline: self.request_timeout.map(|d| tokio::time::Instant::now() + d),
+            token,
+            _busy: BusyGuard::new(&self.activity),
+        })
+    }
+
+    /// Query with a readonly check performed inside the DB worker (for MCP read_query).
+    pub async fn read_query(
+        &self,
+        sql: String,
+        params: Option<QueryParams>,
+        limit: usize,
+        page: Page,
+        encode: EncodeOptions,
+    ) -> AppResult<QueryResult> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::ReadQuery {
+            sql,
+            params,
+            limit,
+            page,
+            encode,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    /// Run a script of read-only statements, one result set (of at most `limit` rows) each;
+    /// fails with `NotReadonly` before running anything if one of them writes.
+    pub async fn read_query_multi(
+        &self,
+        sql: String,
+        limit: usize,
+        encode: EncodeOptions,
+    ) -> AppResult<Vec<StatementResult>> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::ReadQueryMulti {
+            sql,
+            limit,
+            encode,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    /// Run one write statement; with `dry_run` it is rolled back after reporting its changes.
+    pub async fn execute(
+        &self,
+        sql: String,
+        params: Option<QueryParams>,
+        encode: EncodeOptions,
+        dry_run: bool,
+    ) -> AppResult<ExecResult> {
+        self.ensure_writable()?;
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Execute {
+            sql,
+            params,
+            encode,
+            dry_run,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    /// Run multi-statement scripts atomically with per-statement results.
+    pub async fn execute_batch(&self, scripts: Vec<String>) -> AppResult<BatchResult> {
+        self.ensure_writable()?;
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::ExecuteBatch { scripts, respond_to: tx })?;
+        self.recv(rx, token).await
+    }
+
+    /// Update or delete the one row of `table` whose full primary key is `key`.
+    pub async fn edit_row(
+        &self,
+        table: String,
+        key: serde_json::Map<String, serde_json::Value>,
+        edit: RowEdit,
+        allow_no_match: bool,
+        encode: EncodeOptions,
+    ) -> AppResult<ExecResult> {
+        self.ensure_writable()?;
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::EditRow {
+            table,
+            key,
+            edit,
+            allow_no_match,
+            encode,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    /// Insert one row of column -> value `values` (`DEFAULT VALUES` when empty).
+    pub async fn insert_row(
+        &self,
+        table: String,
+        values: serde_json::Map<String, serde_json::Value>,
+        encode: EncodeOptions,
+    ) -> AppResult<ExecResult> {
+        self.ensure_writable()?;
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::InsertRow {
+            table,
+            values,
+            encode,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    /// One page of `table`, optionally sorted by a column, with its total row count.
+    pub async fn browse_table(
+        &self,
+        table: String,
+        order_by: Option<(String, OrderDir)>,
+        limit: usize,
+        offset: usize,
+        encode: EncodeOptions,
+    ) -> AppResult<BrowseResult> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::BrowseTable {
+            table,
+            order_by,
+            limit,
+            offset,
+            encode,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    /// Rows of an FTS5 table matching an FTS5 `query`, best `bm25()` score first.
+    pub async fn search(
+        &self,
+        table: String,
+        query: String,
+        limit: usize,
+        offset: usize,
+        encode: EncodeOptions,
+    ) -> AppResult<QueryResult> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Search {
+            table,
+            query,
+            limit,
+            offset,
+            encode,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    /// EXPLAIN QUERY PLAN for a read-only statement: raw rows, nested by parent id, and index use.
+    pub async fn explain(&self, sql: String, params: Option<QueryParams>) -> AppResult<ExplainResult> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Explain {
+            sql,
+            params,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    pub async fn read_blob(
+        &self,
+        table: String,
+        column: String,
+        row: BlobRow,
+        offset: usize,
+        length: usize,
+    ) -> AppResult<BlobChunk> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::ReadBlob {
+            table,
+            column,
+            row,
+            offset,
+            length,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    pub async fn begin(&self, mode: TransactionMode) -> AppResult<()> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Begin { mode, respond_to: tx })?;
+        self.recv(rx, token).await
+    }
+
+    pub async fn commit(&self) -> AppResult<()> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Commit { respond_to: tx })?;
+        self.recv(rx, token).await
+    }
+
+    pub async fn rollback(&self) -> AppResult<()> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Rollback { respond_to: tx })?;
+        self.recv(rx, token).await
+    }
+
+    /// Table names, then view names with `include_views`; with `include_attached`, attached
+    /// databases' tables (and views) follow as `alias.table`.
+    pub async fn tables(&self, include_attached: bool, include_views: bool) -> AppResult<Vec<String>> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Tables {
+            schema: None,
+            include_attached,
+            include_views,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    /// Tables (then views) of one schema: `main`, `temp` or an attached alias, whose tables are
+    /// named `alias.table`.
+    pub async fn tables_in(&self, schema: String, include_views: bool) -> AppResult<Vec<String>> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Tables {
+            schema: Some(schema),
+            include_attached: false,
+            include_views,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    pub async fn objects(&self) -> AppResult<Vec<SchemaObject>> {
+        self.objects_in("main".to_string()).await
+    }
+
+    /// Tables, views and triggers of `main`, `temp` or an attached database.
+    pub async fn objects_in(&self, schema: String) -> AppResult<Vec<SchemaObject>> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Objects { schema, respond_to: tx })?;
+        self.recv(rx, token).await
+    }
+
+    /// Stored `CREATE` statements of `table` and its indexes/triggers, or of the whole schema.
+    pub async fn ddl(&self, table: Option<String>) -> AppResult<DdlResult> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Ddl { table, respond_to: tx })?;
+        self.recv(rx, token).await
+    }
+
+    pub async fn columns(&self, table: String) -> AppResult<Vec<crate::core::types::ColumnMeta>> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Columns { table, respond_to: tx })?;
+        self.recv(rx, token).await
+    }
+
+    pub async fn indexes(&self, table: String) -> AppResult<Vec<IndexMeta>> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Indexes { table, respond_to: tx })?;
+        self.recv(rx, token).await
+    }
+
+    /// `count(*)` for one table (or, if `approximate`, the `ANALYZE` estimate, `None` when
+    /// there is none), interrupted with `AppError::Timeout` after `deadline` (or the request
+    /// timeout, if shorter) so one huge table cannot stall a report.
+    pub async fn row_count(&self, table: String, approximate: bool, deadline: Duration) -> AppResult<Option<u64>> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::RowCount { table, approximate, respond_to: tx })?;
+        let deadline = self.request_timeout.map_or(deadline, |t| t.min(deadline));
+        self.recv_within(rx, token, Some(deadline)).await
+    }
+
+    /// `row_count` for each table of a listing, `None` where the count failed or took longer
+    /// than `ROW_COUNT_TIMEOUT` (e.g. a virtual table whose module is missing), so one table
+    /// cannot fail the whole listing. Tables still uncounted once `ROW_COUNTS_TIMEOUT` has
+    /// passed in all are `None` too, so many slow tables cannot stall it either.
+    pub async fn row_counts(&self, tables: &[String], approximate: bool) -> Vec<Option<u64>> {
+        self.row_counts_within(tables, approximate, ROW_COUNTS_TIMEOUT).await
+    }
+
+    async fn row_counts_within(
+        &self,
+        tables: &[String],
+        approximate: bool,
+        total: Duration,
+    ) -> Vec<Option<u64>> {
+        let started = Instant::now();
+        let mut counts = Vec::with_capacity(tables.len());
+        for table in tables {
+            let left = total.saturating_sub(started.elapsed());
+            if left.is_zero() {
+                tracing::debug!(table = %table, "row count skipped: listing deadline passed");
+                counts.push(None);
+                continue;
+            }
+            let count = match self.row_count(table.clone(), approximate, left.min(ROW_COUNT_TIMEOUT)).await {
+                Ok(n) => n,
+                Err(e) => {
+                    tracing::debug!(table = %table, error = %e, "row count skipped");
+                    None
+                }
+            };
+            counts.push(count);
+        }
+        counts
+    }
+
+    /// `PRAGMA integrity_check` (or `quick_check`) of the main database, up to `max_errors` problems.
+    pub async fn integrity(&self, quick: bool, max_errors: usize) -> AppResult<IntegrityReport> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Integrity {
+            quick,
+            max_errors,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    /// Rows breaking a foreign key, of `table` or the whole main database, up to `max_rows` listed.
+    pub async fn foreign_key_check(&self, table: Option<String>, max_rows: usize) -> AppResult<ForeignKeyCheck> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::ForeignKeyCheck {
+            table,
+            max_rows,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    /// Page and header figures of the main database; see `analysis::storage_metrics`.
+    pub async fn storage_metrics(&self, vacuum_threshold: f64) -> AppResult<StorageMetrics> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::StorageMetrics {
+            vacuum_threshold,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+
+    /// Space per table and index of the main database; see `analysis::size_report`.
+    pub async fn size_report(&self) -> AppResult<SizeReport> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::SizeReport { respond_to: tx })?;
+        self.recv(rx, token).await
+    }
+
+    /// Index advice for the main database; see `analysis::index_advice`.
+    pub async fn index_advice(&self) -> AppResult<Vec<IndexFinding>> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::IndexAdvice { respond_to: tx })?;
+        self.recv(rx, token).await
+    }
+
+    /// Schema of the main database as `diff::diff` compares it.
+    pub async fn schema_snapshot(&self) -> AppResult<SchemaSnapshot> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::SchemaSnapshot { respond_to: tx })?;
+        self.recv(rx, token).await
+    }
 
-        let h = WorkerHandle::spawn(db_path.clone(), self.busy_timeout_ms)?;
-        guard.insert(db_path, h.clone());
-        Ok(h)
+    /// Triggers of the main database, or only those on `table`.
+    pub async fn triggers(&self, table: Option<String>) -> AppResult<Vec<TriggerMeta>> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Triggers { table, respond_to: tx })?;
+        self.recv(rx, token).await
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct WorkerHandle {
-    tx: std::sync::mpsc::Sender<DbTask>,
-    pub db_path: PathBuf,
-}
+    pub async fn foreign_keys(&self, table: String) -> AppResult<Vec<ForeignKeyMeta>> {
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::ForeignKeys { table, respond_to: tx })?;
+        self.recv(rx, token).await
+    }
 
-impl WorkerHandle {
-    fn spawn(db_path: PathBuf, busy_timeout_ms: u64) -> AppResult<Self> {
-        let (tx, rx) = std::sync::mpsc::channel::<DbTask>();
-        let path_for_thread = db_path.clone();
-        thread::spawn(move || db_worker_main(path_for_thread, busy_timeout_ms, rx));
-        Ok(Self { tx, db_path })
+    pub async fn optimize(&self, ops: Vec<MaintenanceOp>) -> AppResult<OptimizeResult> {
+        self.ensure_writable()?;
+        let (tx, rx) = oneshot::channel();
+        let token = self.send(DbTask::Optimize { ops, respond_to: tx })?;
+        self.recv(rx, token).await
     }
 
-    pub async fn query(
-        &self,
-        sql: String,
-        limit: usize,
-        offset: Option<usize>,
-    ) -> AppResult<QueryResult> {
+    /// Apply `settings` to this worker's connection, after those it was opened with.
+    pub async fn apply_pragmas(&self, settings: Vec<PragmaSetting>) -> AppResult<()> {
         let (tx, rx) = oneshot::channel();
-        self.tx
-            .send(DbTask::Query {
-                sql,
-                limit,
-                offset,
-                respond_to: tx,
-            })
-            .map_err(|_| AppError::Internal("db worker unavailable".into()))?;
-        rx.await.map_err(|_| AppError::Internal("db worker dropped response".into()))?
+        let token = self.send(DbTask::ApplyPragmas { settings, respond_to: tx })?;
+        self.recv(rx, token).await
     }
 
-    /// Query with a readonly check performed inside the DB worker (for MCP read_query).
-    pub async fn read_query(
-        &self,
-        sql: String,
-        limit: usize,
-        offset: Option<usize>,
-    ) -> AppResult<QueryResult> {
+    /// Attach `db` to this worker's connection; returns everything now attached.
+    pub async fn attach(&self, db: AttachedDatabase) -> AppResult<Vec<AttachedDatabase>> {
         let (tx, rx) = oneshot::channel();
-        self.tx
-            .send(DbTask::ReadQuery {
-                sql,
-                limit,
-                offset,
-                respond_to: tx,
-            })
-            .map_err(|_| AppError::Internal("db worker unavailable".into()))?;
-        rx.await
-            .map_err(|_| AppError::Internal("db worker dropped response".into()))?
+        let token = self.send(DbTask::Attach { db, respond_to: tx })?;
+        self.recv(rx, token).await
     }
 
-    pub async fn execute(&self, sql: String) -> AppResult<ExecResult> {
+    pub async fn detach(&self, alias: String) -> AppResult<Vec<AttachedDatabase>> {
         let (tx, rx) = oneshot::channel();
-        self.tx
-            .send(DbTask::Execute { sql, respond_to: tx })
-            .map_err(|_| AppError::Internal("db worker unavailable".into()))?;
-        rx.await.map_err(|_| AppError::Internal("db worker dropped response".into()))?
+        let token = self.send(DbTask::Detach { alias, respond_to: tx })?;
+        self.recv(rx, token).await
     }
 
-    pub async fn tables(&self) -> AppResult<Vec<String>> {
+    /// Open mode and effective pragma values of this worker's connection.
+    pub async fn connection_info(&self) -> AppResult<ConnectionInfo> {
         let (tx, rx) = oneshot::channel();
-        self.tx
-            .send(DbTask::Tables { respond_to: tx })
-            .map_err(|_| AppError::Internal("db worker unavailable".into()))?;
-        rx.await.map_err(|_| AppError::Internal("db worker dropped response".into()))?
+        let token = self.send(DbTask::ConnectionInfo { respond_to: tx })?;
+        self.recv(rx, token).await
     }
 
-    pub async fn columns(&self, table: String) -> AppResult<Vec<crate::core::types::ColumnMeta>> {
+    /// Online backup to `dest`, which the caller must have validated against the whitelist.
+    pub async fn backup(&self, dest: PathBuf, overwrite: bool) -> AppResult<BackupResult> {
         let (tx, rx) = oneshot::channel();
-        self.tx
-            .send(DbTask::Columns { table, respond_to: tx })
-            .map_err(|_| AppError::Internal("db worker unavailable".into()))?;
-        rx.await.map_err(|_| AppError::Internal("db worker dropped response".into()))?
+        let token = self.send(DbTask::Backup {
+            dest,
+            overwrite,
+            respond_to: tx,
+        })?;
+        self.recv(rx, token).await
+    }
+}
+
+/// Per-table cap on `row_counts`; a table that takes longer reports `None` instead of
+/// holding up the rest of the listing.
+pub const ROW_COUNT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Cap on all of one `row_counts` call together.
+pub const ROW_COUNTS_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Frames buffered between the worker and the consumer; bounds memory for large result sets.
+const STREAM_BUFFER_FRAMES: usize = 64;
+
+/// Receiving end of a streaming worker task (`query_stream`, `dump`).
+pub struct FrameStream<F> {
+    rx: mpsc::Receiver<AppResult<F>>,
+    deadline: Option<tokio::time::Instant>,
+    token: CancelToken,
+    _busy: BusyGuard,
+}
+
+pub type QueryStream = FrameStream<QueryFrame>;
+pub type DumpStream = FrameStream<DumpFrame>;
+
+impl<F> FrameStream<F> {
+    /// Next frame; `None` once the worker has finished (after `End` or an error).
+    pub async fn next(&mut self) -> Option<AppResult<F>> {
+        match self.deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, self.rx.recv()).await {
+                Ok(frame) => frame,
+                Err(_) => {
+                    self.token.time_out();
+                    self.rx.close();
+                    Some(Err(AppError::Timeout))
+                }
+            },
+            None => self.rx.recv().await,
+        }
+    }
+}
+
+/// A task as queued on a worker, with the tokens that abandon it.
+struct Job {
+    task: DbTask,
+    tokens: Vec<CancelToken>,
+}
+
+impl Job {
+    /// For tasks that are never abandoned (shutdown).
+    fn untracked(task: DbTask) -> Self {
+        Self { task, tokens: Vec::new() }
     }
 }
 
 enum DbTask {
     Query {
         sql: String,
+        params: Option<QueryParams>,
         limit: usize,
-        offset: Option<usize>,
+        page: Page,
+        encode: EncodeOptions,
         respond_to: oneshot::Sender<AppResult<QueryResult>>,
     },
+    QueryStream {
+        sql: String,
+        params: Option<QueryParams>,
+        limit: usize,
+        page: Page,
+        encode: EncodeOptions,
+        /// Fail with `NotReadonly` unless the statement is read-only.
+        read_only: bool,
+        frames: mpsc::Sender<AppResult<QueryFrame>>,
+    },
+    Dump {
+        data: bool,
+        only: Option<Vec<String>>,
+        frames: mpsc::Sender<AppResult<DumpFrame>>,
+    },
     ReadQuery {
         sql: String,
+        params: Option<QueryParams>,
         limit: usize,
-        offset: Option<usize>,
+        page: Page,
+        encode: EncodeOptions,
         respond_to: oneshot::Sender<AppResult<QueryResult>>,
     },
+    ReadQueryMulti {
+        sql: String,
+        limit: usize,
+        encode: EncodeOptions,
+        respond_to: oneshot::Sender<AppResult<Vec<StatementResult>>>,
+    },
     Execute {
         sql: String,
+        params: Option<QueryParams>,
+        encode: EncodeOptions,
+        dry_run: bool,
+        respond_to: oneshot::Sender<AppResult<ExecResult>>,
+    },
+    ExecuteBatch {
+        scripts: Vec<String>,
+        respond_to: oneshot::Sender<AppResult<BatchResult>>,
+    },
+    Explain {
+        sql: String,
+        params: Option<QueryParams>,
+        respond_to: oneshot::Sender<AppResult<ExplainResult>>,
+    },
+    ReadBlob {
+        table: String,
+        column: String,
+        row: BlobRow,
+        offset: usize,
+        length: usize,
+        respond_to: oneshot::Sender<AppResult<BlobChunk>>,
+    },
+    Begin {
+        mode: TransactionMode,
+        respond_to: oneshot::Sender<AppResult<()>>,
+    },
+    Commit {
+        respond_to: oneshot::Sender<AppResult<()>>,
+    },
+    Rollback {
+        respond_to: oneshot::Sender<AppResult<()>>,
+    },
+    InsertRow {
+        table: String,
+        values: serde_json::Map<String, serde_json::Value>,
+        encode: EncodeOptions,
+        respond_to: oneshot::Sender<AppResult<ExecResult>>,
+    },
+    EditRow {
+        table: String,
+        key: serde_json::Map<String, serde_json::Value>,
+        edit: RowEdit,
+        allow_no_match: bool,
+        encode: EncodeOptions,
         respond_to: oneshot::Sender<AppResult<ExecResult>>,
     },
+    BrowseTable {
+        table: String,
+        order_by: Option<(String, OrderDir)>,
+        limit: usize,
+        offset: usize,
+        encode: EncodeOptions,
+        respond_to: oneshot::Sender<AppResult<BrowseResult>>,
+    },
+    Search {
+        table: String,
+        query: String,
+        limit: usize,
+        offset: usize,
+        encode: EncodeOptions,
+        respond_to: oneshot::Sender<AppResult<QueryResult>>,
+    },
     Tables {
+        schema: Option<String>,
+        include_attached: bool,
+        include_views: bool,
         respond_to: oneshot::Sender<AppResult<Vec<String>>>,
     },
+    Objects {
+        schema: String,
+        respond_to: oneshot::Sender<AppResult<Vec<SchemaObject>>>,
+    },
+    Ddl {
+        table: Option<String>,
+        respond_to: oneshot::Sender<AppResult<DdlResult>>,
+    },
     Columns {
         table: String,
         respond_to: oneshot::Sender<AppResult<Vec<crate::core::types::ColumnMeta>>>,
     },
+    Indexes {
+        table: String,
+        respond_to: oneshot::Sender<AppResult<Vec<IndexMeta>>>,
+    },
+    Integrity {
+        quick: bool,
+        max_errors: usize,
+        respond_to: oneshot::Sender<AppResult<IntegrityReport>>,
+    },
+    ForeignKeyCheck {
+        table: Option<String>,
+        max_rows: usize,
+        respond_to: oneshot::Sender<AppResult<ForeignKeyCheck>>,
+    },
+    StorageMetrics {
+        vacuum_threshold: f64,
+        respond_to: oneshot::Sender<AppResult<StorageMetrics>>,
+    },
+    SizeReport {
+        respond_to: oneshot::Sender<AppResult<SizeReport>>,
+    },
+    IndexAdvice {
+        respond_to: oneshot::Sender<AppResult<Vec<IndexFinding>>>,
+    },
+    SchemaSnapshot {
+        respond_to: oneshot::Sender<AppResult<SchemaSnapshot>>,
+    },
+    Triggers {
+        table: Option<String>,
+        respond_to: oneshot::Sender<AppResult<Vec<TriggerMeta>>>,
+    },
+    ForeignKeys {
+        table: String,
+        respond_to: oneshot::Sender<AppResult<Vec<ForeignKeyMeta>>>,
+    },
+    RowCount {
+        table: String,
+        approximate: bool,
+        respond_to: oneshot::Sender<AppResult<Option<u64>>>,
+    },
+    Optimize {
+        ops: Vec<MaintenanceOp>,
+        respond_to: oneshot::Sender<AppResult<OptimizeResult>>,
+    },
+    Backup {
+        dest: PathBuf,
+        overwrite: bool,
+        respond_to: oneshot::Sender<AppResult<BackupResult>>,
+    },
+    ApplyPragmas {
+        settings: Vec<PragmaSetting>,
+        respond_to: oneshot::Sender<AppResult<()>>,
+    },
+    Attach {
+        db: AttachedDatabase,
+        respond_to: oneshot::Sender<AppResult<Vec<AttachedDatabase>>>,
+    },
+    Detach {
+        alias: String,
+        respond_to: oneshot::Sender<AppResult<Vec<AttachedDatabase>>>,
+    },
+    ConnectionInfo {
+        respond_to: oneshot::Sender<AppResult<ConnectionInfo>>,
+    },
+    Shutdown {
+        respond_to: oneshot::Sender<()>,
+    },
 }
 
-fn db_worker_main(db_path: PathBuf, busy_timeout_ms: u64, rx: std::sync::mpsc::Receiver<DbTask>) {
-    let conn = match open_conn(&db_path, busy_timeout_ms) {
-        Ok(c) => c,
-        Err(e) => {
-            tracing::error!(error=%e, path=%db_path.display(), "failed to open db in worker; dropping tasks");
-            // Drain tasks and respond error.
-            while let Ok(task) = rx.recv() {
-                respond_err(task, e.clone());
+fn db_worker_main(
+    conn: Connection,
+    rx: std::sync::mpsc::Receiver<Job>,
+    activity: Arc<WorkerActivity>,
+    retry_budget: Option<Duration>,
+) {
+    let retry = BusyRetry::new(retry_budget, &activity.interrupted);
+    let mut shutdown_ack = None;
+    while let Ok(Job { task, tokens }) = rx.recv() {
+        let task = match task {
+            DbTask::Shutdown { respond_to } => {
+                shutdown_ack = Some(respond_to);
+                break;
+            }
+            task => task,
+        };
+        activity.interrupted.store(false, Ordering::SeqCst);
+        // Marked running under the lock `CancelToken::cancel` checks: a token cancelled before
+        // this point skips the task, one cancelled after interrupts it.
+        match activity.running.lock() {
+            Ok(mut running) if !tokens.iter().any(CancelToken::is_cancelled) => *running = tokens.clone(),
+            _ => {
+                tracing::debug!("skipping abandoned db task");
+                continue;
             }
-            return;
         }
-    };
+        // A panic drops the task's responder (the caller sees "db worker dropped response")
+        // but keeps the connection serving later tasks.
+        let retry = retry.in_transaction(!conn.is_autocommit());
+        let tokens = TaskTokens(&tokens);
+        if panic::catch_unwind(AssertUnwindSafe(|| run_task(&conn, task, &retry, tokens))).is_err() {
+            tracing::error!("db worker task panicked");
+        }
+        if let Ok(mut running) = activity.running.lock() {
+            running.clear();
+        }
+        activity
+            .in_transaction
+            .store(!conn.is_autocommit(), Ordering::SeqCst);
+    }
 
-    while let Ok(task) = rx.recv() {
-        match task {
-            DbTask::Query {
-                sql,
-                limit,
-                offset,
-                respond_to,
-            } => {
-                let res = query::run_query(&conn, &sql, limit, offset);
-                let _ = respond_to.send(res);
-            }
-            DbTask::ReadQuery {
-                sql,
-                limit,
-                offset,
-                respond_to,
-            } => {
-                let res = match readonly::is_sql_readonly(&conn, &sql) {
-                    Ok(true) => query::run_query(&conn, &sql, limit, offset),
-                    Ok(false) => Err(AppError::NotReadonly),
-                    Err(e) => Err(e),
-                };
-                let _ = respond_to.send(res);
-            }
-            DbTask::Execute { sql, respond_to } => {
-                let res = query::run_execute(&conn, &sql);
-                let _ = respond_to.send(res);
-            }
-            DbTask::Tables { respond_to } => {
-                let res = schema::list_tables(&conn);
-                let _ = respond_to.send(res);
-            }
-            DbTask::Columns { table, respond_to } => {
-                let res = schema::list_columns(&conn, &table);
-                let _ = respond_to.send(res);
-            }
+    // Reached on explicit shutdown or once every handle is dropped.
+    transaction::rollback_if_open(&conn);
+    // Copy committed WAL frames into the database without waiting on readers; the last
+    // connection to close also removes the WAL file. A no-op outside WAL mode.
+    if let Err(e) = conn.query_row("PRAGMA main.wal_checkpoint(PASSIVE)", [], |_| Ok(())) {
+        tracing::debug!("wal checkpoint at shutdown failed: {e}");
+    }
+    if let Err((_, e)) = conn.close() {
+        tracing::warn!("closing database at shutdown failed: {e}");
+    }
+    if let Some(ack) = shutdown_ack {
+        let _ = ack.send(());
+    }
+}
+
+/// Runs one task. One-shot tasks go through `retry` so transient lock contention is retried;
+/// streams and dumps may already have sent frames and backups handle busy steps themselves.
+/// Tokens of the task being run, which tell why its statement was interrupted.
+#[derive(Clone, Copy)]
+struct TaskTokens<'a>(&'a [CancelToken]);
+
+impl TaskTokens<'_> {
+    /// `res`, with an interrupted statement (`Timeout`, see `From<rusqlite::Error>`) reported as
+    /// `Cancelled` when the request was cancelled rather than timed out.
+    fn result<T>(self, res: AppResult<T>) -> AppResult<T> {
+        res.map_err(|e| self.error(e))
+    }
+
+    fn error(self, e: AppError) -> AppError {
+        let cancelled = |t: &CancelToken| t.is_cancelled() && !t.0.timed_out.load(Ordering::SeqCst);
+        match e {
+            AppError::Timeout if self.0.iter().any(cancelled) => AppError::Cancelled,
+            e => e,
         }
     }
 }
 
-fn respond_err(task: DbTask, err: AppError) {
+fn run_task(conn: &Connection, task: DbTask, retry: &BusyRetry, tokens: TaskTokens) {
     match task {
-        DbTask::Query { respond_to, .. } => {
-            let _ = respond_to.send(Err(err));
+        DbTask::Query {
+            sql,
+            params,
+            limit,
+            page,
+            encode,
+            respond_to,
+        } => {
+            let res = retry.run(|| query::run_query(conn, &sql, params.as_ref(), limit, &page, encode));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::QueryStream {
+            sql,
+            params,
+            limit,
+            page,
+            encode,
+            read_only,
+            frames,
+        } => {
+            // blocking_send applies backpressure: the worker waits while the consumer drains.
+            let mut sink = |frame| frames.blocking_send(Ok(frame)).is_ok();
+            let res = match read_only.then(|| readonly::is_sql_readonly(conn, &sql)) {
+                Some(Ok(false)) => Err(AppError::NotReadonly),
+                Some(Err(e)) => Err(e),
+                _ => query::stream_query(conn, &sql, params.as_ref(), limit, &page, encode, &mut sink),
+            };
+            if let Err(e) = res {
+                let _ = frames.blocking_send(Err(tokens.error(e)));
+            }
+        }
+        DbTask::Dump { data, only, frames } => {
+            let mut sink = |frame| frames.blocking_send(Ok(frame)).is_ok();
+            if let Err(e) = dump::dump(conn, data, only.as_deref(), &mut sink) {
+                let _ = frames.blocking_send(Err(tokens.error(e)));
+            }
+        }
+        DbTask::ReadQuery {
+            sql,
+            params,
+            limit,
+            page,
+            encode,
+            respond_to,
+        } => {
+            let res = retry.run(|| match readonly::is_sql_readonly(conn, &sql) {
+                Ok(true) => query::run_query(conn, &sql, params.as_ref(), limit, &page, encode),
+                Ok(false) => Err(AppError::NotReadonly),
+                Err(e) => Err(e),
+            });
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::ReadQueryMulti {
+            sql,
+            limit,
+            encode,
+            respond_to,
+        } => {
+            let res = retry.run(|| query::run_query_multi(conn, &sql, limit, encode));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::Execute {
+            sql,
+            params,
+            encode,
+            dry_run,
+            respond_to,
+        } => {
+            let res = retry.run(|| {
+                if dry_run {
+                    query::run_execute_dry_run(conn, &sql, params.as_ref(), encode)
+                } else {
+                    query::run_execute(conn, &sql, params.as_ref(), encode)
+                }
+            });
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::InsertRow {
+            table,
+            values,
+            encode,
+            respond_to,
+        } => {
+            let res = retry.run(|| rows::insert_row(conn, &table, &values, encode));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::EditRow {
+            table,
+            key,
+            edit,
+            allow_no_match,
+            encode,
+            respond_to,
+        } => {
+            let res = retry.run(|| rows::edit_row(conn, &table, &key, &edit, allow_no_match, encode));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::BrowseTable {
+            table,
+            order_by,
+            limit,
+            offset,
+            encode,
+            respond_to,
+        } => {
+            let order_by = order_by.as_ref().map(|(column, dir)| (column.as_str(), *dir));
+            let res = retry.run(|| rows::browse(conn, &table, order_by, limit, offset, encode));
+            let _ = respond_to.send(tokens.result(res));
         }
-        DbTask::ReadQuery { respond_to, .. } => {
-            let _ = respond_to.send(Err(err));
+        DbTask::Search {
+            table,
+            query,
+            limit,
+            offset,
+            encode,
+            respond_to,
+        } => {
+            let res = retry.run(|| fts::search(conn, &table, &query, limit, offset, encode));
+            let _ = respond_to.send(tokens.result(res));
         }
-        DbTask::Execute { respond_to, .. } => {
-            let _ = respond_to.send(Err(err));
+        DbTask::ExecuteBatch { scripts, respond_to } => {
+            let res = retry.run(|| query::run_batch(conn, &scripts));
+            let _ = respond_to.send(tokens.result(res));
         }
-        DbTask::Tables { respond_to } => {
-            let _ = respond_to.send(Err(err));
+        DbTask::Explain {
+            sql,
+            params,
+            respond_to,
+        } => {
+            let res = retry.run(|| explain::explain_query_plan(conn, &sql, params.as_ref()));
+            let _ = respond_to.send(tokens.result(res));
         }
-        DbTask::Columns { respond_to, .. } => {
-            let _ = respond_to.send(Err(err));
+        DbTask::ReadBlob {
+            table,
+            column,
+            row,
+            offset,
+            length,
+            respond_to,
+        } => {
+            let res = retry.run(|| blob::read_blob(conn, &table, &column, &row, offset, length));
+            let _ = respond_to.send(tokens.result(res));
         }
+        DbTask::Begin { mode, respond_to } => {
+            let _ = respond_to.send(tokens.result(retry.run(|| transaction::begin(conn, mode))));
+        }
+        DbTask::Commit { respond_to } => {
+            let _ = respond_to.send(tokens.result(retry.run(|| transaction::commit(conn))));
+        }
+        DbTask::Rollback { respond_to } => {
+            let _ = respond_to.send(tokens.result(transaction::rollback(conn)));
+        }
+        DbTask::Tables {
+            schema: db,
+            include_attached,
+            include_views,
+            respond_to,
+        } => {
+            let res = retry.run(|| match &db {
+                Some(db) => schema::list_tables_in(conn, db, include_views),
+                None => schema::list_tables(conn, include_attached, include_views),
+            });
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::Objects { schema: db, respond_to } => {
+            let res = retry.run(|| schema::list_objects(conn, &db));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::Ddl { table, respond_to } => {
+            let res = retry.run(|| schema::ddl(conn, table.as_deref()));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::Columns { table, respond_to } => {
+            let res = retry.run(|| schema::list_columns(conn, &table));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::Indexes { table, respond_to } => {
+            let res = retry.run(|| schema::list_indexes(conn, &table));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::Integrity {
+            quick,
+            max_errors,
+            respond_to,
+        } => {
+            let res = retry.run(|| analysis::integrity(conn, quick, max_errors));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::ForeignKeyCheck {
+            table,
+            max_rows,
+            respond_to,
+        } => {
+            let res = retry.run(|| analysis::foreign_key_check(conn, table.as_deref(), max_rows));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::StorageMetrics {
+            vacuum_threshold,
+            respond_to,
+        } => {
+            let res = retry.run(|| analysis::storage_metrics(conn, vacuum_threshold));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::SizeReport { respond_to } => {
+            let res = retry.run(|| analysis::size_report(conn));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::IndexAdvice { respond_to } => {
+            let res = retry.run(|| analysis::index_advice(conn));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::SchemaSnapshot { respond_to } => {
+            let res = retry.run(|| diff::snapshot(conn));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::Triggers { table, respond_to } => {
+            let res = retry.run(|| schema::list_triggers(conn, table.as_deref()));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::ForeignKeys { table, respond_to } => {
+            let res = retry.run(|| schema::list_foreign_keys(conn, &table));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::RowCount { table, approximate, respond_to } => {
+            let res = retry.run(|| {
+                if approximate {
+                    schema::estimate_rows(conn, &table)
+                } else {
+                    schema::count_rows(conn, &table).map(Some)
+                }
+            });
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::Optimize { ops, respond_to } => {
+            let res = retry.run(|| maintenance::optimize(conn, &ops));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::Backup {
+            dest,
+            overwrite,
+            respond_to,
+        } => {
+            let res = backup::backup_to(conn, &dest, overwrite, retry.stop_flag());
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::ApplyPragmas { settings, respond_to } => {
+            let _ = respond_to.send(tokens.result(pragmas::apply(conn, &settings)));
+        }
+        DbTask::Attach { db, respond_to } => {
+            let res = retry.run(|| attach::attach(conn, &db)).and_then(|()| attach::list(conn));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::Detach { alias, respond_to } => {
+            let res = retry.run(|| attach::detach(conn, &alias)).and_then(|()| attach::list(conn));
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::ConnectionInfo { respond_to } => {
+            let res = pragmas::current(conn).and_then(|pragmas| {
+                Ok(ConnectionInfo {
+                    path: conn.path().unwrap_or_default().to_string(),
+                    readonly: conn.is_readonly(rusqlite::DatabaseName::Main).unwrap_or(false),
+                    in_transaction: !conn.is_autocommit(),
+                    pragmas,
+                    attached: attach::list(conn)?,
+                })
+            });
+            let _ = respond_to.send(tokens.result(res));
+        }
+        DbTask::Shutdown { .. } => unreachable!("handled by db_worker_main"),
     }
 }
 
-fn open_conn(path: &Path, busy_timeout_ms: u64) -> AppResult<Connection> {
-    let flags = OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE;
+fn open_conn(path: &Path, config: &ConnectionConfig) -> AppResult<Connection> {
+    // SQLITE_OPEN_CREATE only on request: a mistyped path should fail, not silently become
+    // an empty database that every read then "succeeds" against.
+    let mut flags = if config.readonly {
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+    } else if config.create {
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+    } else {
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+    };
+    // Validated `file:` URIs (e.g. `?immutable=1` for read-only media).
+    if paths::is_uri(path) {
+        flags |= OpenFlags::SQLITE_OPEN_URI;
+    }
     let conn = Connection::open_with_flags(path, flags)
         .map_err(|source| AppError::DbOpenFailed {
             path: path.to_path_buf(),
             source,
         })?;
-    let _ = conn.busy_timeout(std::time::Duration::from_millis(busy_timeout_ms));
+    let _ = conn.busy_timeout(std::time::Duration::from_millis(config.busy_timeout_ms));
+    conn.set_prepared_statement_cache_capacity(config.statement_cache_size);
+    attach::guard(&conn);
+
+    if let Some(mode) = config.journal_mode {
+        // SQLite answers with the mode actually in effect; it silently keeps the old one when the
+        // switch is impossible (e.g. WAL on some network filesystems, or a read-only database).
+        let actual: String =
+            conn.query_row(&format!("PRAGMA journal_mode = {}", mode.as_pragma()), [], |r| r.get(0))?;
+        if !actual.eq_ignore_ascii_case(mode.as_pragma()) {
+            return Err(AppError::JournalModeRejected {
+                path: path.to_path_buf(),
+                requested: mode.as_pragma().to_string(),
+                actual,
+            });
+        }
+    }
+    if let Some(sync) = config.synchronous {
+        conn.execute_batch(&format!("PRAGMA synchronous = {}", sync.as_pragma()))?;
+    }
+    let foreign_keys = if config.foreign_keys { "ON" } else { "OFF" };
+    conn.execute_batch(&format!("PRAGMA foreign_keys = {foreign_keys}"))?;
+    pragmas::apply(&conn, &config.pragmas)?;
+    for db in &config.attached {
+        // A database that can no longer be attached (e.g. deleted) should not keep the main
+        // one from opening.
+        if let Err(e) = attach::attach(&conn, db) {
+            tracing::warn!(alias = %db.alias, path = %db.path.display(), error = %e, "re-attach failed");
+        }
+    }
+    let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |r| r.get(0))?;
+    let synchronous: i64 = conn.query_row("PRAGMA synchronous", [], |r| r.get(0))?;
+    let foreign_keys: bool = conn.query_row("PRAGMA foreign_keys", [], |r| r.get(0))?;
+    tracing::info!(
+        path = %path.display(),
+        journal_mode = %journal_mode,
+        synchronous,
+        foreign_keys,
+        "opened database"
+    );
     Ok(conn)
 }
 
 fn canonicalize_lossy(path: &Path) -> AppResult<PathBuf> {
     // canonicalize requires file exists; SQLite DB might be created on open.
-    // Use absolute path when possible.
-    if path.is_absolute() {
+    // Use absolute path when possible. URIs come from `validate_db_path` with an absolute path.
+    if path.is_absolute() || paths::is_uri(path) {
         Ok(path.to_path_buf())
     } else {
         let cwd = std::env::current_dir()?;
@@ -238,3 +1921,609 @@ fn canonicalize_lossy(path: &Path) -> AppResult<PathBuf> {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn foreign_keys_are_enforced_unless_disabled() {
+        for foreign_keys in [true, false] {
+            let config = ConnectionConfig {
+                foreign_keys,
+                create: true,
+                ..ConnectionConfig::default()
+            };
+            let conn = open_conn(Path::new(":memory:"), &config).unwrap();
+            conn.execute_batch("CREATE TABLE p (id INTEGER PRIMARY KEY); CREATE TABLE c (p REFERENCES p (id));")
+                .unwrap();
+            assert_eq!(conn.execute("INSERT INTO c VALUES (1)", []).is_err(), foreign_keys);
+        }
+    }
+
+    #[test]
+    fn client_sql_cannot_attach_or_detach() {
+        let config = ConnectionConfig {
+            create: true,
+            ..ConnectionConfig::default()
+        };
+        let conn = open_conn(Path::new(":memory:"), &config).unwrap();
+        let encode = EncodeOptions::default();
+        // ATTACH would read any file, whatever --allowed-dir says.
+        let err = query::run_query_multi(&conn, "SELECT 1; ATTACH ':memory:' AS x", 10, encode).unwrap_err();
+        assert!(matches!(err, AppError::NotReadonly), "{err}");
+        assert!(query::run_execute(&conn, "ATTACH ':memory:' AS x", None, encode).is_err());
+        assert!(conn.execute_batch("ATTACH ':memory:' AS x").is_err());
+
+        let db = AttachedDatabase::new("x", PathBuf::from(":memory:")).unwrap();
+        attach::attach(&conn, &db).unwrap();
+        assert!(conn.execute_batch("DETACH x").is_err());
+        attach::detach(&conn, "x").unwrap();
+        assert!(attach::list(&conn).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn write_lock_held_by_another_worker_fails_as_busy() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-busy-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        // Two managers stand in for two processes. A new file uses the rollback journal (no
+        // WAL), so an exclusive writer keeps even readers out. Without a request deadline,
+        // which would report `Timeout` instead, only the retry count bounds the wait.
+        let config = ConnectionConfig {
+            busy_timeout_ms: 20,
+            request_timeout: None,
+            ..ConnectionConfig::default()
+        };
+        let (a, b) = (ConnectionManager::new(config.clone()), ConnectionManager::new(config));
+        let create = OpenMode {
+            create: true,
+            ..OpenMode::default()
+        };
+        let encode = EncodeOptions::default();
+
+        let writer = a.ensure_worker_with(&path, create).await.unwrap();
+        writer.execute("CREATE TABLE t (x)".into(), None, encode, false).await.unwrap();
+        // Opened first: opening reads pragmas, which would itself wait for the lock.
+        let other = b.ensure_worker(&path).await.unwrap();
+        writer.begin(TransactionMode::Exclusive).await.unwrap();
+        writer.execute("INSERT INTO t VALUES (1)".into(), None, encode, false).await.unwrap();
+
+        let started = std::time::Instant::now();
+        let res = other.query("SELECT * FROM t".into(), None, 10, Page::Start, encode).await;
+        assert!(matches!(res, Err(AppError::DbBusy(_))), "{res:?}");
+        assert_eq!(res.unwrap_err().code(), "DB_BUSY");
+        // Retried with backoff after the first busy timeout (20ms) rather than failing on it.
+        assert!(started.elapsed() >= Duration::from_millis(100), "{:?}", started.elapsed());
+
+        writer.commit().await.unwrap();
+        let qr = other.query("SELECT * FROM t".into(), None, 10, Page::Start, encode).await.unwrap();
+        assert_eq!(qr.rows.len(), 1);
+
+        a.shutdown().await;
+        b.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn dropping_the_manager_closes_workers_and_their_wal() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-drop-{}.db", std::process::id()));
+        let wal = path.with_extension("db-wal");
+        let _ = std::fs::remove_file(&path);
+        let cm = ConnectionManager::new(ConnectionConfig {
+            journal_mode: Some(JournalMode::Wal),
+            ..ConnectionConfig::default()
+        });
+        let create = OpenMode {
+            create: true,
+            ..OpenMode::default()
+        };
+
+        let worker = cm.ensure_worker_with(&path, create).await.unwrap();
+        let encode = EncodeOptions::default();
+        worker.execute("CREATE TABLE t (x)".into(), None, encode, false).await.unwrap();
+        worker.begin(TransactionMode::Immediate).await.unwrap();
+        worker.execute("INSERT INTO t VALUES (1)".into(), None, encode, false).await.unwrap();
+        assert!(wal.exists());
+
+        // A clone going away leaves the workers running.
+        drop(cm.clone());
+        assert!(worker.is_alive());
+        drop(cm);
+        assert!(!worker.is_alive());
+        assert!(!wal.exists(), "WAL file left behind");
+        let conn = Connection::open(&path).unwrap();
+        let n: i64 = conn.query_row("SELECT count(*) FROM t", [], |r| r.get(0)).unwrap();
+        assert_eq!(n, 0, "the open transaction is rolled back");
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn a_timed_out_task_is_skipped_without_interrupting_the_one_running() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-deadline-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let cm = ConnectionManager::new(ConnectionConfig::default());
+        let create = OpenMode {
+            create: true,
+            ..OpenMode::default()
+        };
+        let worker = cm.ensure_worker_with(&path, create).await.unwrap();
+        let encode = EncodeOptions::default();
+        worker.execute("CREATE TABLE t (x)".into(), None, encode, false).await.unwrap();
+
+        let slow = tokio::spawn({
+            let worker = worker.clone();
+            let sql = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 3000000) \
+                       SELECT count(*) AS n FROM n";
+            async move { worker.query(sql.into(), None, 1, Page::Start, encode).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        // Queued behind the slow query, so its deadline passes before it starts.
+        let write = worker.clone().with_timeout_ms(Some(50));
+        let res = write.execute("INSERT INTO t VALUES (1)".into(), None, encode, false).await;
+        assert!(matches!(res, Err(AppError::Timeout)), "{res:?}");
+
+        let qr = slow.await.unwrap().unwrap();
+        assert_eq!(qr.rows[0]["n"], 3_000_000);
+        let qr = worker.query("SELECT count(*) AS n FROM t".into(), None, 1, Page::Start, encode).await.unwrap();
+        assert_eq!(qr.rows[0]["n"], 0, "the abandoned write never ran");
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn a_cancelled_request_is_skipped_without_interrupting_the_one_running() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-cancel-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let cm = ConnectionManager::new(ConnectionConfig::default());
+        let create = OpenMode {
+            create: true,
+            ..OpenMode::default()
+        };
+        let worker = cm.ensure_worker_with(&path, create).await.unwrap();
+        let encode = EncodeOptions::default();
+        worker.execute("CREATE TABLE t (x)".into(), None, encode, false).await.unwrap();
+
+        let cancels = cancel::CancelRegistry::default();
+        let slow = tokio::spawn(cancel::scoped(cancels.start("slow"), {
+            let worker = worker.clone();
+            let sql = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 3000000) \
+                       SELECT count(*) AS n FROM n";
+            async move { worker.query(sql.into(), None, 1, Page::Start, encode).await }
+        }));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let write = tokio::spawn(cancel::scoped(cancels.start("write"), {
+            let worker = worker.clone();
+            async move { worker.execute("INSERT INTO t VALUES (1)".into(), None, encode, false).await }
+        }));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        // Still queued behind the slow query.
+        assert!(cancels.cancel("write"));
+
+        let qr = slow.await.unwrap().unwrap();
+        assert_eq!(qr.rows[0]["n"], 3_000_000);
+        let res = write.await.unwrap();
+        assert!(matches!(res, Err(AppError::Cancelled)), "{res:?}");
+        let qr = worker.query("SELECT count(*) AS n FROM t".into(), None, 1, Page::Start, encode).await.unwrap();
+        assert_eq!(qr.rows[0]["n"], 0, "the cancelled write never ran");
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn peeking_at_a_schema_leaves_no_worker_open() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-peek-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let cm = ConnectionManager::new(ConnectionConfig::default());
+        let create = OpenMode {
+            create: true,
+            ..OpenMode::default()
+        };
+        let worker = cm.ensure_worker_with(&path, create).await.unwrap();
+        worker
+            .execute("CREATE TABLE t (x)".into(), None, EncodeOptions::default(), false)
+            .await
+            .unwrap();
+        // Served by the live worker.
+        assert_eq!(cm.peek_objects(&path).await.unwrap()[0].name, "t");
+
+        assert!(cm.close(&path).await.unwrap());
+        drop(worker);
+        let objects = cm.peek_objects(&path).await.unwrap();
+        assert_eq!(objects.iter().map(|o| o.name.as_str()).collect::<Vec<_>>(), ["t"]);
+        assert!(cm.inner.lock().unwrap().is_empty());
+        assert!(cm.peek_objects(&path.with_extension("missing")).await.is_err());
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn dead_worker_is_respawned() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-respawn-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let cm = ConnectionManager::new(ConnectionConfig::default());
+        let create = OpenMode {
+            create: true,
+            ..OpenMode::default()
+        };
+
+        let worker = cm.ensure_worker_with(&path, create).await.unwrap();
+        worker
+            .execute("CREATE TABLE t (x)".into(), None, EncodeOptions::default(), false)
+            .await
+            .unwrap();
+        // Kill the thread but leave the stale handle registered, as a crashed worker would.
+        worker.stop();
+        let encode = EncodeOptions::default();
+        assert!(worker.query("SELECT 1".into(), None, 10, Page::Start, encode).await.is_err());
+
+        let worker = cm.ensure_worker(&path).await.unwrap();
+        let qr = worker
+            .query("SELECT count(*) AS n FROM t".into(), None, 10, Page::Start, encode)
+            .await
+            .unwrap();
+        assert_eq!(qr.rows[0]["n"], 0);
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn idle_workers_are_closed_on_the_next_open() {
+        let dir = std::env::temp_dir();
+        let paths: Vec<PathBuf> = (0..2)
+            .map(|i| dir.join(format!("sqlite-helper-idle-{}-{i}.db", std::process::id())))
+            .collect();
+        let cm = ConnectionManager::new(ConnectionConfig {
+            idle_ttl: Some(Duration::from_millis(50)),
+            ..ConnectionConfig::default()
+        });
+        let create = OpenMode {
+            create: true,
+            ..OpenMode::default()
+        };
+
+        let first = cm.ensure_worker_with(&paths[0], create).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cm.ensure_worker_with(&paths[1], create).await.unwrap();
+        let open: Vec<PathBuf> = cm.inner.lock().unwrap().keys().map(|(path, _)| path.clone()).collect();
+        assert_eq!(open, [paths[1].clone()]);
+        // The evicted worker exits on its own; the next request reopens the database.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let encode = EncodeOptions::default();
+        assert!(first.query("SELECT 1".into(), None, 1, Page::Start, encode).await.is_err());
+        let reopened = cm.ensure_worker_with(&paths[0], create).await.unwrap();
+        reopened.query("SELECT 1".into(), None, 1, Page::Start, encode).await.unwrap();
+
+        cm.shutdown().await;
+        for path in &paths {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_worker_is_closed_at_the_cap() {
+        let dir = std::env::temp_dir();
+        let paths: Vec<PathBuf> = (0..3)
+            .map(|i| dir.join(format!("sqlite-helper-lru-{}-{i}.db", std::process::id())))
+            .collect();
+        let cm = ConnectionManager::new(ConnectionConfig {
+            max_workers: Some(2),
+            ..ConnectionConfig::default()
+        });
+
+        let create = OpenMode {
+            create: true,
+            ..OpenMode::default()
+        };
+
+        cm.ensure_worker_with(&paths[0], create).await.unwrap();
+        cm.ensure_worker_with(&paths[1], create).await.unwrap();
+        cm.ensure_worker_with(&paths[0], create).await.unwrap();
+        cm.ensure_worker_with(&paths[2], create).await.unwrap();
+
+        let open: Vec<PathBuf> = cm.inner.lock().unwrap().keys().map(|(path, _)| path.clone()).collect();
+        assert_eq!(open.len(), 2);
+        assert!(open.contains(&paths[0]) && open.contains(&paths[2]));
+        // The evicted database stays known (it is still listed as a resource).
+        assert_eq!(cm.opened_paths().len(), 3);
+        // `reader` reuses the open read-write worker rather than opening a read-only one.
+        cm.reader(&paths[2]).await.unwrap();
+        assert_eq!(cm.inner.lock().unwrap().len(), 2);
+
+        // A worker inside a transaction is never the one closed, even if that overshoots the cap.
+        let in_tx = cm.ensure_worker_with(&paths[0], create).await.unwrap();
+        in_tx.begin(TransactionMode::Deferred).await.unwrap();
+        cm.ensure_worker_with(&paths[2], create).await.unwrap();
+        cm.ensure_worker_with(&paths[1], create).await.unwrap();
+        let open: Vec<PathBuf> = cm.inner.lock().unwrap().keys().map(|(path, _)| path.clone()).collect();
+        assert!(open.contains(&paths[0]) && open.contains(&paths[1]) && !open.contains(&paths[2]));
+        in_tx.rollback().await.unwrap();
+
+        cm.shutdown().await;
+        for path in &paths {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[tokio::test]
+    async fn row_counts_report_failed_and_late_tables_as_none() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-row-counts-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let cm = ConnectionManager::new(ConnectionConfig::default());
+        let create = OpenMode {
+            create: true,
+            ..OpenMode::default()
+        };
+        let worker = cm.ensure_worker_with(&path, create).await.unwrap();
+        let sql = "CREATE TABLE a (x); INSERT INTO a VALUES (1), (2); CREATE TABLE b (x)";
+        worker.execute_batch(vec![sql.into()]).await.unwrap();
+        let tables = ["a".to_string(), "missing".to_string(), "b".to_string()];
+
+        assert_eq!(worker.row_counts(&tables, false).await, [Some(2), None, Some(0)]);
+        // Nothing is counted once the listing's deadline has passed.
+        assert_eq!(worker.row_counts_within(&tables, false, Duration::ZERO).await, [None, None, None]);
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn a_read_only_connection_refuses_writes_that_a_read_write_one_accepts() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-read-only-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        rusqlite::Connection::open(&path).unwrap().execute_batch("CREATE TABLE t (x)").unwrap();
+        let cm = ConnectionManager::new(ConnectionConfig::default());
+        let read_only = OpenMode {
+            readonly: true,
+            ..OpenMode::default()
+        };
+        let insert = || "INSERT INTO t VALUES (1)".to_string();
+
+        let reader = cm.ensure_worker_with(&path, read_only).await.unwrap();
+        let err = reader.execute(insert(), None, EncodeOptions::default(), false).await.unwrap_err();
+        assert_eq!(err.code(), "READ_ONLY");
+        let writer = cm.ensure_worker(&path).await.unwrap();
+        writer.execute(insert(), None, EncodeOptions::default(), false).await.unwrap();
+        assert_eq!(AppError::ReadonlyMode.code(), "READONLY_MODE");
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn writes_bind_quotes_and_blobs_without_escaping() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-write-params-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let setup = rusqlite::Connection::open(&path).unwrap();
+        setup.execute_batch("CREATE TABLE t (name TEXT, data BLOB)").unwrap();
+        let cm = ConnectionManager::new(ConnectionConfig::default());
+        let worker = cm.ensure_worker(&path).await.unwrap();
+        let encode = EncodeOptions::default();
+
+        let values = serde_json::json!(["O'Brien", { "$type": "blob", "base64": "AP8=" }]);
+        let params = QueryParams::from_input(Some(serde_json::from_value(values).unwrap()), None);
+        let sql = "INSERT INTO t (name, data) VALUES (?, ?)".to_string();
+        let res = worker.execute(sql, params, encode, false).await.unwrap();
+        assert_eq!((res.changes, res.last_insert_rowid), (1, Some(1)));
+
+        let (name, data): (String, Vec<u8>) =
+            setup.query_row("SELECT name, data FROM t", [], |r| Ok((r.get(0)?, r.get(1)?))).unwrap();
+        assert_eq!((name.as_str(), data.as_slice()), ("O'Brien", &[0x00, 0xff][..]));
+
+        drop(setup);
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn transactions_span_requests_and_roll_back_on_close() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-transaction-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let check = rusqlite::Connection::open(&path).unwrap();
+        check.execute_batch("CREATE TABLE t (x)").unwrap();
+        let count = || check.query_row("SELECT count(*) FROM t", [], |r| r.get::<_, i64>(0)).unwrap();
+        let cm = ConnectionManager::new(ConnectionConfig::default());
+        let worker = cm.ensure_worker(&path).await.unwrap();
+        let encode = EncodeOptions::default();
+        let insert = || worker.execute("INSERT INTO t VALUES (1)".into(), None, encode, false);
+
+        worker.begin(TransactionMode::Deferred).await.unwrap();
+        insert().await.unwrap();
+        assert_eq!(worker.begin(TransactionMode::Immediate).await.unwrap_err().code(), "TRANSACTION_ACTIVE");
+        worker.rollback().await.unwrap();
+        assert_eq!(worker.commit().await.unwrap_err().code(), "NO_TRANSACTION");
+
+        worker.begin(TransactionMode::Immediate).await.unwrap();
+        insert().await.unwrap();
+        insert().await.unwrap();
+        assert_eq!(count(), 0);
+        worker.commit().await.unwrap();
+        assert_eq!(count(), 2);
+
+        // Closing a database mid-transaction rolls it back.
+        worker.begin(TransactionMode::Exclusive).await.unwrap();
+        insert().await.unwrap();
+        assert!(cm.close(&path).await.unwrap());
+        assert_eq!(count(), 2);
+
+        drop(check);
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn journal_mode_and_synchronous_are_applied_or_refused() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-journal-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let config = ConnectionConfig {
+            journal_mode: Some(JournalMode::Wal),
+            synchronous: Some(Synchronous::Normal),
+            create: true,
+            ..ConnectionConfig::default()
+        };
+        let conn = open_conn(&path, &config).unwrap();
+        let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |r| r.get(0)).unwrap();
+        let synchronous: i64 = conn.query_row("PRAGMA synchronous", [], |r| r.get(0)).unwrap();
+        assert_eq!((journal_mode.as_str(), synchronous), ("wal", 1));
+        drop(conn);
+
+        // An in-memory database cannot switch to WAL; SQLite keeps `memory` and the open fails.
+        let err = open_conn(Path::new(":memory:"), &config).unwrap_err();
+        let AppError::JournalModeRejected { requested, actual, .. } = &err else {
+            panic!("{err}");
+        };
+        assert_eq!((requested.as_str(), actual.as_str()), ("wal", "memory"));
+        assert_eq!(err.code(), "JOURNAL_MODE_REJECTED");
+
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+
+    #[tokio::test]
+    async fn closed_databases_release_their_files_and_reopen_on_demand() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-reopen-{}.db", std::process::id()));
+        let wal = PathBuf::from(format!("{}-wal", path.display()));
+        let _ = std::fs::remove_file(&path);
+        rusqlite::Connection::open(&path).unwrap().execute_batch("CREATE TABLE t (x)").unwrap();
+        let cm = ConnectionManager::new(ConnectionConfig {
+            journal_mode: Some(JournalMode::Wal),
+            ..ConnectionConfig::default()
+        });
+        let encode = EncodeOptions::default();
+        let worker = cm.ensure_worker(&path).await.unwrap();
+        let insert = "INSERT INTO t VALUES (1)".to_string();
+        worker.execute(insert, None, encode, false).await.unwrap();
+        assert!(wal.exists());
+
+        assert!(cm.close(&path).await.unwrap());
+        assert!(!wal.exists(), "the last connection checkpoints and removes the WAL");
+        assert!(!cm.close(&path).await.unwrap());
+        let count = || "SELECT count(*) AS n FROM t".to_string();
+        assert!(worker.query(count(), None, 10, Page::Start, encode).await.is_err());
+
+        // The next request reopens the database with its data intact.
+        let worker = cm.ensure_worker(&path).await.unwrap();
+        let result = worker.query(count(), None, 10, Page::Start, encode).await.unwrap();
+        assert_eq!(result.rows[0]["n"], 1);
+        assert_eq!(cm.close_all().await, 1);
+        assert_eq!(cm.close_all().await, 0);
+        assert!(!wal.exists());
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn a_locked_database_does_not_hold_up_opening_another() {
+        let path = |name: &str| {
+            std::env::temp_dir().join(format!("sqlite-helper-open-{name}-{}.db", std::process::id()))
+        };
+        let (locked, free) = (path("locked"), path("free"));
+        for p in [&locked, &free] {
+            let _ = std::fs::remove_file(p);
+            rusqlite::Connection::open(p).unwrap().execute_batch("CREATE TABLE t (x)").unwrap();
+        }
+        let holder = rusqlite::Connection::open(&locked).unwrap();
+        holder.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        let cm = ConnectionManager::new(ConnectionConfig {
+            busy_timeout_ms: 100,
+            request_timeout: Some(Duration::from_secs(5)),
+            ..ConnectionConfig::default()
+        });
+
+        // The test runtime has a single thread, like the servers'.
+        let opening = tokio::spawn({
+            let (cm, locked) = (cm.clone(), locked.clone());
+            async move { cm.ensure_worker(&locked).await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let started = Instant::now();
+        let worker = cm.ensure_worker(&free).await.unwrap();
+        let sql = "SELECT count(*) AS n FROM t".to_string();
+        worker.query(sql, None, 10, Page::Start, EncodeOptions::default()).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1), "{:?}", started.elapsed());
+        assert!(!opening.is_finished());
+
+        // Once the lock is released the waiting open goes through.
+        holder.execute_batch("COMMIT").unwrap();
+        opening.await.unwrap().unwrap();
+
+        drop(holder);
+        cm.shutdown().await;
+        for p in [&locked, &free] {
+            let _ = std::fs::remove_file(p);
+        }
+    }
+
+    #[tokio::test]
+    async fn read_only_workers_open_despite_a_journal_mode() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-ro-journal-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let sql = "CREATE TABLE t (x); INSERT INTO t VALUES (1)";
+        rusqlite::Connection::open(&path).unwrap().execute_batch(sql).unwrap();
+        let config = ConnectionConfig {
+            journal_mode: Some(JournalMode::Wal),
+            ..ConnectionConfig::default()
+        };
+        let query = || "SELECT x FROM t".to_string();
+        let encode = EncodeOptions::default();
+
+        let cm = ConnectionManager::new(config.clone());
+        let read_only = OpenMode {
+            readonly: true,
+            create: false,
+        };
+        let worker = cm.ensure_worker_with(&path, read_only).await.unwrap();
+        assert_eq!(worker.query(query(), None, 10, Page::Start, encode).await.unwrap().rows.len(), 1);
+        let worker = cm.reader(&path).await.unwrap();
+        assert_eq!(worker.query(query(), None, 10, Page::Start, encode).await.unwrap().rows.len(), 1);
+        cm.shutdown().await;
+
+        // Likewise under --readonly, which leaves the database in its own journal mode.
+        let cm = ConnectionManager::new(ConnectionConfig { readonly: true, ..config });
+        let worker = cm.ensure_worker(&path).await.unwrap();
+        assert_eq!(worker.query(query(), None, 10, Page::Start, encode).await.unwrap().rows.len(), 1);
+        cm.shutdown().await;
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |r| r.get(0)).unwrap();
+        assert_eq!(journal_mode, "delete");
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn an_interrupted_statement_reports_why_it_was_interrupted() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-interrupt-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let cm = ConnectionManager::new(ConnectionConfig::default());
+        let create = OpenMode {
+            create: true,
+            ..OpenMode::default()
+        };
+        let worker = cm.ensure_worker_with(&path, create).await.unwrap();
+        let encode = EncodeOptions::default();
+        let slow = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n) SELECT count(*) FROM n";
+
+        let cancels = cancel::CancelRegistry::default();
+        let running = tokio::spawn(cancel::scoped(cancels.start("slow"), {
+            let worker = worker.clone();
+            async move { worker.query(slow.into(), None, 1, Page::Start, encode).await }
+        }));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(cancels.cancel("slow"));
+        let res = running.await.unwrap();
+        assert!(matches!(res, Err(AppError::Cancelled)), "{res:?}");
+
+        let timed = worker.clone().with_timeout_ms(Some(50));
+        let res = timed.query(slow.into(), None, 1, Page::Start, encode).await;
+        assert!(matches!(res, Err(AppError::Timeout)), "{res:?}");
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+}
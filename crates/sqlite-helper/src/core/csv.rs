@@ -0,0 +1,150 @@
+use serde_json::Value;
+
+use crate::core::types::QueryResult;
+
+/// Render a query result as RFC 4180 CSV with CRLF line endings, after a header row from
+/// `columns` if `header`.
+///
+/// NULL is an empty field and the empty string a quoted one (`""`), so the two stay apart.
+/// BLOBs are `base64:<data>`, or a `<blob N bytes>` placeholder when the data was cut short or
+/// left out (`blob_mode` `truncate`/`metadata`).
+///
+/// Works on the already-encoded JSON rows, so `int_mode`/`float_mode`/`blob_mode` still apply.
+pub fn to_csv(qr: &QueryResult, header: bool) -> String {
+    let names: Vec<&str> = qr.columns.iter().map(|c| c.name.as_str()).collect();
+    let rows: Vec<Vec<Option<String>>> = qr.rows.iter().map(|row| row.values().map(cell_text).collect()).collect();
+    records(header.then_some(&names[..]), &rows)
+}
+
+/// CSV of plain records, `None` fields being NULL, with the quoting rules of `to_csv`.
+pub fn records(header: Option<&[&str]>, rows: &[Vec<Option<String>>]) -> String {
+    let mut out = String::new();
+    if let Some(header) = header {
+        write_record(&mut out, header.iter().map(|h| Some(*h)));
+    }
+    for row in rows {
+        write_record(&mut out, row.iter().map(Option::as_deref));
+    }
+    out
+}
+
+/// One line; `None` fields (NULL) are written as nothing at all.
+fn write_record<'a>(out: &mut String, fields: impl Iterator<Item = Option<&'a str>>) {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if let Some(field) = field {
+            write_field(out, field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+/// Quote only when needed (and always when empty, see `to_csv`); embedded quotes are doubled.
+fn write_field(out: &mut String, field: &str) {
+    if field.is_empty() || field.contains([',', '"', '\r', '\n']) {
+        out.push('"');
+        out.push_str(&field.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(field);
+    }
+}
+
+fn cell_text(v: &Value) -> Option<String> {
+    Some(match v {
+        Value::Null => return None,
+        Value::String(s) => s.clone(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        // Tagged values from `encode_value`: complete blobs as prefixed base64, others as a
+        // placeholder; int64/float sentinels their textual value.
+        Value::Object(obj) => match obj.get("$type").and_then(|t| t.as_str()) {
+            Some("blob") => match obj.get("base64").and_then(|b| b.as_str()) {
+                Some(b64) if obj.get("truncated") != Some(&Value::Bool(true)) => format!("base64:{b64}"),
+                _ => {
+                    let size = obj.get("size").and_then(|s| s.as_u64()).unwrap_or_default();
+                    format!("<blob {size} bytes>")
+                }
+            },
+            Some(_) => match obj.get("value") {
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+                None => v.to_string(),
+            },
+            None => v.to_string(),
+        },
+        Value::Array(_) => v.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::ColumnMeta;
+
+    fn result(columns: &[&str], rows: Vec<Value>) -> QueryResult {
+        QueryResult {
+            columns: columns
+                .iter()
+                .map(|name| ColumnMeta {
+                    name: name.to_string(),
+                    ..ColumnMeta::default()
+                })
+                .collect(),
+            rows: rows
+                .into_iter()
+                .map(|r| match r {
+                    Value::Object(obj) => obj,
+                    _ => unreachable!(),
+                })
+                .collect(),
+            truncated: false,
+            next_offset: None,
+            next_cursor: None,
+            duration_ms: 0.0,
+            rows_scanned: 0,
+            elapsed_ms: None,
+        }
+    }
+
+    #[test]
+    fn quotes_fields_with_separators_quotes_and_newlines() {
+        let qr = result(
+            &["plain", "comma", "quote", "newline", "a,b"],
+            vec![serde_json::json!({
+                "plain": "abc",
+                "comma": "x,y",
+                "quote": "say \"hi\"",
+                "newline": "line1\nline2",
+                "a,b": "",
+            })],
+        );
+        assert_eq!(
+            to_csv(&qr, true),
+            "plain,comma,quote,newline,\"a,b\"\r\nabc,\"x,y\",\"say \"\"hi\"\"\",\"line1\nline2\",\"\"\r\n"
+        );
+        assert!(to_csv(&qr, false).starts_with("abc,"));
+    }
+
+    #[test]
+    fn renders_null_numbers_and_tagged_values() {
+        let qr = result(
+            &["n", "i", "f", "big", "blob", "cut", "nan"],
+            vec![serde_json::json!({
+                "n": null,
+                "i": 7,
+                "f": 1.5,
+                "big": { "$type": "int64", "value": "9007199254740993" },
+                "blob": { "$type": "blob", "base64": "AQI=", "size": 2 },
+                "cut": { "$type": "blob", "base64": "AQI=", "size": 3, "truncated": true },
+                "nan": { "$type": "float", "value": "NaN" },
+            })],
+        );
+        assert_eq!(
+            to_csv(&qr, true),
+            "n,i,f,big,blob,cut,nan\r\n,7,1.5,9007199254740993,base64:AQI=,<blob 3 bytes>,NaN\r\n"
+        );
+    }
+}
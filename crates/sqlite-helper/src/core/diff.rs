@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+
+use rusqlite::Connection;
+
+use crate::{
+    core::{
+        schema,
+        types::{ColumnDiff, ColumnMeta, ObjectRef, SchemaDiff, TableDiff},
+    },
+    error::AppResult,
+};
+
+/// The parts of a database's schema that `diff` compares, read on its worker. Keyed by
+/// lower-cased name, since SQLite names are case-insensitive; the values keep them as written.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaSnapshot {
+    /// Name, `CREATE TABLE` statement and columns of each table.
+    tables: BTreeMap<String, (String, Option<String>, Vec<ColumnMeta>)>,
+    /// Indexes, views and triggers with their statements.
+    objects: BTreeMap<ObjectRef, (ObjectRef, Option<String>)>,
+}
+
+/// Tables (with `table_xinfo` columns), indexes, views and triggers of the main database.
+/// SQLite's internal tables and autoindexes are left out: the latter follow from the
+/// `CREATE TABLE` statements, which are compared.
+pub fn snapshot(conn: &Connection) -> AppResult<SchemaSnapshot> {
+    let mut snapshot = SchemaSnapshot::default();
+    for obj in schema::ddl(conn, None)?.objects {
+        if obj.name.starts_with("sqlite_") {
+            continue;
+        }
+        if obj.kind == "table" {
+            let columns = schema::list_columns(conn, &obj.name)?;
+            snapshot.tables.insert(obj.name.to_lowercase(), (obj.name, obj.sql, columns));
+        } else {
+            let key = ObjectRef {
+                kind: obj.kind.clone(),
+                name: obj.name.to_lowercase(),
+            };
+            let object = ObjectRef {
+                kind: obj.kind,
+                name: obj.name,
+            };
+            snapshot.objects.insert(key, (object, obj.sql));
+        }
+    }
+    Ok(snapshot)
+}
+
+/// What changed from `a` to `b`. Statements are compared after `schema::normalize_sql`, so
+/// reformatting alone is not a difference; tables, objects and columns are matched by name,
+/// ignoring case, and reported as `a` names them (`b` for those only in `b`).
+pub fn diff(a: &SchemaSnapshot, b: &SchemaSnapshot) -> SchemaDiff {
+    let mut out = SchemaDiff::default();
+    for (key, (name, sql_a, cols_a)) in &a.tables {
+        let Some((_, sql_b, cols_b)) = b.tables.get(key) else {
+            out.tables_only_in_a.push(name.clone());
+            continue;
+        };
+        let mut table = TableDiff {
+            name: name.clone(),
+            ..TableDiff::default()
+        };
+        for col_a in cols_a {
+            match cols_b.iter().find(|c| c.name.eq_ignore_ascii_case(&col_a.name)) {
+                None => table.columns_only_in_a.push(col_a.name.clone()),
+                Some(col_b) => {
+                    let fields = changed_fields(col_a, col_b);
+                    if !fields.is_empty() {
+                        table.changed_columns.push(ColumnDiff {
+                            name: col_a.name.clone(),
+                            fields,
+                            a: col_a.clone(),
+                            b: col_b.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        table.columns_only_in_b = cols_b
+            .iter()
+            .filter(|c| !cols_a.iter().any(|a| a.name.eq_ignore_ascii_case(&c.name)))
+            .map(|c| c.name.clone())
+            .collect();
+        if !same_sql(sql_a, sql_b) {
+            table.sql_a = sql_a.clone();
+            table.sql_b = sql_b.clone();
+        }
+        let unchanged = table.columns_only_in_a.is_empty()
+            && table.columns_only_in_b.is_empty()
+            && table.changed_columns.is_empty()
+            && table.sql_a.is_none()
+            && table.sql_b.is_none();
+        if !unchanged {
+            out.changed_tables.push(table);
+        }
+    }
+    out.tables_only_in_b = b
+        .tables
+        .iter()
+        .filter(|(key, _)| !a.tables.contains_key(*key))
+        .map(|(_, (name, _, _))| name.clone())
+        .collect();
+
+    for (key, (object, sql_a)) in &a.objects {
+        match b.objects.get(key) {
+            None => out.objects_only_in_a.push(object.clone()),
+            Some((_, sql_b)) if !same_sql(sql_a, sql_b) => out.changed_objects.push(object.clone()),
+            Some(_) => {}
+        }
+    }
+    out.objects_only_in_b = b
+        .objects
+        .iter()
+        .filter(|(key, _)| !a.objects.contains_key(*key))
+        .map(|(_, (object, _))| object.clone())
+        .collect();
+
+    out.identical = out.tables_only_in_a.is_empty()
+        && out.tables_only_in_b.is_empty()
+        && out.changed_tables.is_empty()
+        && out.objects_only_in_a.is_empty()
+        && out.objects_only_in_b.is_empty()
+        && out.changed_objects.is_empty();
+    out
+}
+
+fn same_sql(a: &Option<String>, b: &Option<String>) -> bool {
+    a.as_deref().map(schema::normalize_sql) == b.as_deref().map(schema::normalize_sql)
+}
+
+/// Serialized `ColumnMeta` fields (other than the name) whose values differ; declared types
+/// are compared ignoring case, as SQLite does.
+fn changed_fields(a: &ColumnMeta, b: &ColumnMeta) -> Vec<String> {
+    let fields = |c: &ColumnMeta| {
+        let c = ColumnMeta {
+            decl_type: c.decl_type.as_ref().map(|t| t.to_ascii_uppercase()),
+            ..c.clone()
+        };
+        match serde_json::to_value(c) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        }
+    };
+    let (a, b) = (fields(a), fields(b));
+    let mut keys: Vec<&String> = a.keys().chain(b.keys()).filter(|k| *k != "name").collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter().filter(|k| a.get(*k) != b.get(*k)).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_tables_columns_and_objects_that_differ() {
+        let a = Connection::open_in_memory().unwrap();
+        a.execute_batch(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age INT);
+             CREATE TABLE old_logs (msg);
+             CREATE TABLE same (x);
+             CREATE TABLE Orders (id);
+             CREATE INDEX users_name ON users (name);
+             CREATE VIEW adults AS SELECT * FROM users WHERE age >= 18;",
+        )
+        .unwrap();
+        let b = Connection::open_in_memory().unwrap();
+        b.execute_batch(
+            "create table users (id integer primary key, name text not null, email text);
+             create   table same(x)  ;
+             CREATE TABLE logs (msg);
+             CREATE TABLE orders (id);
+             CREATE INDEX USERS_NAME ON users (name, email);
+             CREATE TRIGGER users_ins AFTER INSERT ON users BEGIN SELECT 1; END;",
+        )
+        .unwrap();
+
+        let d = diff(&snapshot(&a).unwrap(), &snapshot(&b).unwrap());
+        assert!(!d.identical);
+        assert_eq!(d.tables_only_in_a, ["old_logs"]);
+        assert_eq!(d.tables_only_in_b, ["logs"]);
+        // `same` differs only in case and whitespace, `Orders` only in the case of its name.
+        assert_eq!(d.changed_tables.len(), 1);
+        let users = &d.changed_tables[0];
+        assert_eq!(users.columns_only_in_a, ["age"]);
+        assert_eq!(users.columns_only_in_b, ["email"]);
+        // Declared types differ only in case; `name` became NOT NULL.
+        assert_eq!(users.changed_columns.len(), 1);
+        assert_eq!(users.changed_columns[0].name, "name");
+        assert_eq!(users.changed_columns[0].fields, ["notnull"]);
+        assert!(users.sql_a.is_some() && users.sql_b.is_some());
+
+        let names = |v: &[ObjectRef]| v.iter().map(|o| format!("{} {}", o.kind, o.name)).collect::<Vec<_>>();
+        assert_eq!(names(&d.objects_only_in_a), ["view adults"]);
+        assert_eq!(names(&d.objects_only_in_b), ["trigger users_ins"]);
+        assert_eq!(names(&d.changed_objects), ["index users_name"]);
+
+        assert!(diff(&snapshot(&a).unwrap(), &snapshot(&a).unwrap()).identical);
+    }
+}
@@ -0,0 +1,310 @@
+use std::fmt::Write;
+
+use rusqlite::{types::ValueRef, Connection};
+
+use crate::core::schema::{check_name, quote_ident};
+use crate::core::types::DumpFrame;
+use crate::error::AppResult;
+
+/// Flush accumulated SQL text to the sink once it reaches this size.
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Write the schema (and, with `data`, every row as an `INSERT`) as a SQL script that
+/// recreates the database, in the style of the sqlite3 shell's `.dump`.
+///
+/// Output goes to `sink` in `Chunk` frames of roughly `CHUNK_BYTES`, so large tables are
+/// never buffered whole; `sink` returning `false` (consumer gone) stops the dump. `only`
+/// restricts the dump to the named tables plus their indexes and triggers.
+///
+/// Everything is read in one transaction, so a writer committing meanwhile cannot leave tables
+/// from different points in time in the script.
+pub fn dump(
+    conn: &Connection,
+    data: bool,
+    only: Option<&[String]>,
+    sink: &mut dyn FnMut(DumpFrame) -> bool,
+) -> AppResult<()> {
+    for name in only.unwrap_or_default() {
+        check_name(name, "table")?;
+    }
+    conn.execute_batch("SAVEPOINT dump")?;
+    let res = write_dump(conn, data, only, sink);
+    // Only reads happened, so releasing is all there is to undo.
+    conn.execute_batch("RELEASE dump")?;
+    res
+}
+
+fn write_dump(
+    conn: &Connection,
+    data: bool,
+    only: Option<&[String]>,
+    sink: &mut dyn FnMut(DumpFrame) -> bool,
+) -> AppResult<()> {
+    let wanted = |name: &str| only.is_none_or(|names| names.iter().any(|n| n == name));
+
+    let mut out = Writer {
+        buf: String::new(),
+        sink,
+        open: true,
+    };
+    out.push("PRAGMA foreign_keys=OFF;\nBEGIN TRANSACTION;\n");
+
+    // Tables (with their rows) first, then the objects that depend on them. Shadow tables are
+    // left out: `CREATE VIRTUAL TABLE` recreates them, and a virtual table's rows are inserted
+    // through the table itself so its module rebuilds them.
+    let mut tables = 0;
+    let mut rows = 0;
+    let filter = "type = 'table' AND name NOT LIKE 'sqlite_%' AND name NOT IN \
+                  (SELECT name FROM pragma_table_list WHERE schema = 'main' AND type = 'shadow')";
+    for (name, _, sql) in schema_entries(conn, filter)? {
+        if !wanted(&name) {
+            continue;
+        }
+        tables += 1;
+        out.push(&format!("{sql};\n"));
+        if data {
+            rows += dump_rows(conn, &name, &mut out)?;
+        }
+        if !out.open {
+            return Ok(());
+        }
+    }
+    if data && only.is_none() && has_table(conn, "sqlite_sequence")? {
+        // AUTOINCREMENT counters; not counted as table rows.
+        out.push("DELETE FROM sqlite_sequence;\n");
+        dump_rows(conn, "sqlite_sequence", &mut out)?;
+    }
+    for (_, tbl_name, sql) in schema_entries(conn, "type IN ('index', 'trigger', 'view')")? {
+        if wanted(&tbl_name) {
+            out.push(&format!("{sql};\n"));
+        }
+    }
+    out.push("COMMIT;\n");
+    out.flush();
+    if out.open {
+        (out.sink)(DumpFrame::End { tables, rows });
+    }
+    Ok(())
+}
+
+struct Writer<'a> {
+    buf: String,
+    sink: &'a mut dyn FnMut(DumpFrame) -> bool,
+    /// False once the sink has rejected a frame.
+    open: bool,
+}
+
+impl Writer<'_> {
+    fn push(&mut self, s: &str) {
+        self.buf.push_str(s);
+        if self.buf.len() >= CHUNK_BYTES {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.open && !self.buf.is_empty() {
+            self.open = (self.sink)(DumpFrame::Chunk(std::mem::take(&mut self.buf)));
+        }
+    }
+}
+
+/// `(name, tbl_name, sql)` of schema objects matching `filter`, in creation order. Objects
+/// without SQL (auto-indexes) are skipped since their table's constraints recreate them.
+fn schema_entries(conn: &Connection, filter: &str) -> AppResult<Vec<(String, String, String)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name, tbl_name, sql FROM sqlite_master WHERE {filter} AND sql IS NOT NULL ORDER BY rowid"
+    ))?;
+    let rows = stmt
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+fn has_table(conn: &Connection, name: &str) -> AppResult<bool> {
+    let n: i64 = conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [name],
+        |r| r.get(0),
+    )?;
+    Ok(n > 0)
+}
+
+/// One `INSERT` per row, with the table and column names quoted.
+fn dump_rows(conn: &Connection, table: &str, out: &mut Writer<'_>) -> AppResult<u64> {
+    // Generated columns cannot be inserted into; list the stored ones explicitly when present.
+    let mut columns = Vec::new();
+    let mut has_generated = false;
+    {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_xinfo({})", quote_ident(table)))?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let name: String = row.get("name")?;
+            match row.get::<_, i64>("hidden")? {
+                0 => columns.push(quote_ident(&name)),
+                2 | 3 => has_generated = true,
+                // Hidden columns of virtual tables (e.g. FTS5's `rank`), which take no value.
+                _ => {}
+            }
+        }
+    }
+    if columns.is_empty() {
+        return Ok(0);
+    }
+    let column_list = columns.join(",");
+    let target = if has_generated {
+        format!("{}({column_list})", quote_ident(table))
+    } else {
+        quote_ident(table)
+    };
+
+    let mut stmt = conn.prepare(&format!("SELECT {column_list} FROM {}", quote_ident(table)))?;
+    let mut rows = stmt.query([])?;
+    let mut n = 0;
+    let mut line = String::new();
+    while let Some(row) = rows.next()? {
+        line.clear();
+        let _ = write!(line, "INSERT INTO {target} VALUES(");
+        for i in 0..columns.len() {
+            if i > 0 {
+                line.push(',');
+            }
+            write_literal(&mut line, row.get_ref(i)?);
+        }
+        line.push_str(");\n");
+        out.push(&line);
+        n += 1;
+        if !out.open {
+            break;
+        }
+    }
+    Ok(n)
+}
+
+/// SQL literal for one value: NULL, numbers as-is, text with `'` doubled, BLOBs as `X'..'`.
+fn write_literal(out: &mut String, v: ValueRef<'_>) {
+    match v {
+        ValueRef::Null => out.push_str("NULL"),
+        ValueRef::Integer(i) => {
+            let _ = write!(out, "{i}");
+        }
+        // `{:?}` keeps a decimal point so the value reads back as REAL; SQLite stores
+        // infinities but has no literal for them.
+        ValueRef::Real(f) if f.is_infinite() => {
+            out.push_str(if f > 0.0 { "1e999" } else { "-1e999" });
+        }
+        ValueRef::Real(f) => {
+            let _ = write!(out, "{f:?}");
+        }
+        ValueRef::Text(t) => {
+            out.push('\'');
+            out.push_str(&String::from_utf8_lossy(t).replace('\'', "''"));
+            out.push('\'');
+        }
+        ValueRef::Blob(b) => {
+            out.push_str("X'");
+            for byte in b {
+                let _ = write!(out, "{byte:02X}");
+            }
+            out.push('\'');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_escapes_literals_and_restores() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (s TEXT, b BLOB, r REAL, n);
+             INSERT INTO t VALUES ('it''s', X'00FF', 2.0, NULL);",
+        )
+        .unwrap();
+
+        let mut sql = String::new();
+        let mut sink = |frame| {
+            if let DumpFrame::Chunk(chunk) = frame {
+                sql.push_str(&chunk);
+            }
+            true
+        };
+        dump(&conn, true, None, &mut sink).unwrap();
+        assert!(sql.contains(r#"INSERT INTO "t" VALUES('it''s',X'00FF',2.0,NULL);"#), "{sql}");
+
+        let restored = Connection::open_in_memory().unwrap();
+        restored.execute_batch(&sql).unwrap();
+        let (s, b, r): (String, Vec<u8>, f64) = restored
+            .query_row("SELECT s, b, r FROM t", [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap();
+        assert_eq!((s.as_str(), b.as_slice(), r), ("it's", &[0x00, 0xFF][..], 2.0));
+    }
+
+    #[test]
+    fn fts5_tables_restore_without_their_shadow_tables() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE notes USING fts5(title, body);
+             INSERT INTO notes VALUES ('groceries', 'buy apples'), ('work', 'ship the release');",
+        )
+        .unwrap();
+
+        let mut sql = String::new();
+        let mut end = None;
+        let mut sink = |frame| {
+            match frame {
+                DumpFrame::Chunk(chunk) => sql.push_str(&chunk),
+                DumpFrame::End { tables, rows } => end = Some((tables, rows)),
+            }
+            true
+        };
+        dump(&conn, true, None, &mut sink).unwrap();
+        assert!(!sql.contains("notes_data") && !sql.contains("notes_content"), "{sql}");
+        assert_eq!(end, Some((1, 2)));
+
+        let restored = Connection::open_in_memory().unwrap();
+        restored.execute_batch(&sql).unwrap();
+        let title: String = restored
+            .query_row("SELECT title FROM notes WHERE notes MATCH 'release'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(title, "work");
+    }
+
+    #[test]
+    fn dumps_read_one_snapshot() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-dump-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE a (s TEXT);
+             CREATE TABLE b (x);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000)
+             INSERT INTO a SELECT hex(randomblob(100)) FROM n;",
+        )
+        .unwrap();
+        let writer = Connection::open(&path).unwrap();
+
+        // A write committed after the first chunk (while `a` is being dumped) is not in `b`'s rows.
+        let mut sql = String::new();
+        let mut sink = |frame| {
+            if let DumpFrame::Chunk(chunk) = frame {
+                if sql.is_empty() {
+                    writer.execute_batch("INSERT INTO b VALUES (42)").unwrap();
+                }
+                sql.push_str(&chunk);
+            }
+            true
+        };
+        dump(&conn, true, None, &mut sink).unwrap();
+        assert!(!sql.contains("VALUES(42)"), "{sql}");
+        assert!(conn.is_autocommit());
+
+        drop((conn, writer));
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use rusqlite::Connection;
+
+use crate::core::params::{bind_params, QueryParams};
+use crate::core::readonly;
+use crate::core::statements::ensure_single_statement;
+use crate::core::types::{ExplainResult, PlanNode, PlanRow, PlanTable};
+use crate::error::{AppError, AppResult};
+
+/// Run `EXPLAIN QUERY PLAN` for a read-only statement: the raw rows, the rows nested by
+/// `parent`, and per table whether the plan reaches it through an index.
+pub fn explain_query_plan(
+    conn: &Connection,
+    sql: &str,
+    params: Option<&QueryParams>,
+) -> AppResult<ExplainResult> {
+    ensure_single_statement(conn, sql)?;
+    if !readonly::is_sql_readonly(conn, sql)? {
+        return Err(AppError::NotReadonly);
+    }
+
+    let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {sql}"))?;
+    // Unbound parameters plan as NULL, so binding is only enforced when params are given.
+    if params.is_some() {
+        bind_params(&mut stmt, params)?;
+    }
+
+    let mut flat = Vec::new();
+    let mut rows = stmt.raw_query();
+    while let Some(row) = rows.next()? {
+        flat.push(PlanRow {
+            id: row.get("id")?,
+            parent: row.get("parent")?,
+            notused: row.get("notused")?,
+            detail: row.get("detail")?,
+        });
+    }
+
+    Ok(ExplainResult {
+        plan: build_tree(&flat, 0),
+        tables: plan_tables(&flat),
+        rows: flat,
+    })
+}
+
+fn build_tree(flat: &[PlanRow], parent: i64) -> Vec<PlanNode> {
+    flat.iter()
+        .filter(|r| r.parent == parent)
+        .map(|r| PlanNode {
+            id: r.id,
+            parent: r.parent,
+            detail: r.detail.clone(),
+            children: build_tree(flat, r.id),
+        })
+        .collect()
+}
+
+/// The tables the plan visits (`SCAN x` / `SEARCH x ...` steps), in plan order, as the plan
+/// names them: the alias when the query gives one. Subqueries and CTEs the plan materializes
+/// or runs as co-routines are not tables and are left out.
+///
+/// A table uses an index when every visit looks rows up through one: a `SEARCH` (by index or
+/// rowid), or a virtual table scan its module narrowed with constraints. A `SCAN ... USING
+/// [COVERING] INDEX` still reads every entry (e.g. for `ORDER BY`), and an `AUTOMATIC` index is
+/// built per query by scanning, so neither counts.
+fn plan_tables(flat: &[PlanRow]) -> Vec<PlanTable> {
+    let derived: Vec<&str> = flat
+        .iter()
+        .filter_map(|r| r.detail.strip_prefix("MATERIALIZE ").or_else(|| r.detail.strip_prefix("CO-ROUTINE ")))
+        .collect();
+    let mut tables: Vec<PlanTable> = Vec::new();
+    for row in flat {
+        let (search, access) = match (row.detail.strip_prefix("SEARCH "), row.detail.strip_prefix("SCAN ")) {
+            (Some(access), _) => (true, access),
+            (None, Some(access)) => (false, access),
+            (None, None) => continue,
+        };
+        let (name, how) = [" USING ", " VIRTUAL TABLE "]
+            .iter()
+            .find_map(|sep| access.split_once(sep))
+            .unwrap_or((access, ""));
+        if derived.contains(&name) {
+            continue;
+        }
+        let indexed = if search {
+            !how.starts_with("AUTOMATIC ")
+        } else {
+            // `INDEX <idxNum>:<idxStr>`; both are zero/empty when the module got no constraints.
+            how.strip_prefix("INDEX ")
+                .and_then(|index| index.split_once(':'))
+                .is_some_and(|(num, s)| num != "0" || !s.is_empty())
+        };
+        match tables.iter_mut().find(|t| t.table == name) {
+            Some(t) => t.uses_index &= indexed,
+            None => tables.push(PlanTable {
+                table: name.to_string(),
+                uses_index: indexed,
+            }),
+        }
+    }
+    tables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_index_use_per_table() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"CREATE TABLE t (a INTEGER PRIMARY KEY, b);
+               CREATE INDEX t_b ON t (b);
+               CREATE TABLE "my t" (x, y);
+               CREATE VIRTUAL TABLE notes USING fts5(body);"#,
+        )
+        .unwrap();
+        let summary = |sql: &str| {
+            let res = explain_query_plan(&conn, sql, None).unwrap();
+            res.tables.into_iter().map(|t| (t.table, t.uses_index)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(summary("SELECT * FROM t WHERE b = ?"), [("t".to_string(), true)]);
+        // Walks the whole index in order: still a full scan.
+        assert_eq!(summary("SELECT * FROM t ORDER BY b"), [("t".to_string(), false)]);
+        assert_eq!(
+            summary(r#"SELECT * FROM "my t" JOIN t ON t.a = "my t".x"#),
+            [("my t".to_string(), false), ("t".to_string(), true)]
+        );
+        assert_eq!(summary("SELECT * FROM notes WHERE notes MATCH 'x'"), [("notes".to_string(), true)]);
+        assert_eq!(summary("SELECT * FROM notes"), [("notes".to_string(), false)]);
+        let cte = summary("WITH c AS MATERIALIZED (SELECT * FROM t) SELECT * FROM c WHERE a = 1");
+        assert_eq!(cte, [("t".to_string(), false)]);
+        let res = explain_query_plan(&conn, "WITH c AS MATERIALIZED (SELECT * FROM t) SELECT * FROM c", None).unwrap();
+        assert_eq!((res.rows.len(), res.plan.len(), res.plan[0].children.len()), (3, 2, 1));
+
+        assert!(matches!(
+            explain_query_plan(&conn, "DELETE FROM t", None),
+            Err(AppError::NotReadonly)
+        ));
+    }
+}
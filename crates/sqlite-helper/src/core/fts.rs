@@ -0,0 +1,85 @@
+//! Full-text search over FTS5 virtual tables.
+
+use rusqlite::Connection;
+
+use crate::{
+    core::{
+        paging::Page,
+        params::QueryParams,
+        query,
+        schema::{self, quote_ident, TableRef},
+        types::{EncodeOptions, QueryResult},
+    },
+    error::{AppError, AppResult},
+};
+
+/// Names of the FTS5 tables of `schema` (`CREATE VIRTUAL TABLE ... USING fts5`), as stored.
+pub fn fts5_tables(conn: &Connection, schema: &str) -> AppResult<Vec<String>> {
+    Ok(schema::list_objects(conn, schema)?
+        .into_iter()
+        .filter(|o| o.kind == "table" && o.module.as_deref() == Some("fts5"))
+        .map(|o| o.name)
+        .collect())
+}
+
+/// Rows of the FTS5 table `table` matching `query` (FTS5 query syntax, bound as a parameter),
+/// best first: `rowid`, the table's columns and `score`, the `bm25()` rank (lower is better).
+/// Anything but an FTS5 table is refused, naming the ones the database has.
+pub fn search(
+    conn: &Connection,
+    table: &str,
+    query: &str,
+    limit: usize,
+    offset: usize,
+    encode: EncodeOptions,
+) -> AppResult<QueryResult> {
+    let target = TableRef::resolve(conn, table)?;
+    let tables = fts5_tables(conn, target.schema.unwrap_or("main"))?;
+    let Some(name) = tables.iter().find(|t| t.eq_ignore_ascii_case(target.name)) else {
+        let known = match tables.is_empty() {
+            true => "none".to_string(),
+            false => tables.join(", "),
+        };
+        return Err(AppError::InvalidRequest(format!("{table} is not an FTS5 table (FTS5 tables: {known})")));
+    };
+    // The table's own name is its hidden column, which MATCH and bm25() take.
+    let column = quote_ident(name);
+    let sql = format!(
+        "SELECT rowid, *, bm25({column}) AS score FROM {} WHERE {column} MATCH ?1 ORDER BY score",
+        target.quoted()
+    );
+    let params = QueryParams {
+        positional: vec![query.into()],
+        ..QueryParams::default()
+    };
+    query::run_query(conn, &sql, Some(&params), limit, &Page::Offset(offset), encode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn searches_fts5_tables_by_rank() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE docs USING fts5(title, body);
+             INSERT INTO docs VALUES ('rust', 'sqlite from rust'), ('sqlite', 'sqlite sqlite sqlite'),
+                                     ('other', 'nothing here');
+             CREATE TABLE plain (body TEXT);",
+        )
+        .unwrap();
+        assert_eq!(fts5_tables(&conn, "main").unwrap(), ["docs"]);
+        let enc = EncodeOptions::default();
+
+        let qr = search(&conn, "Docs", "sqlite", 10, 0, enc).unwrap();
+        let titles: Vec<&str> = qr.rows.iter().map(|r| r["title"].as_str().unwrap()).collect();
+        assert_eq!(titles, ["sqlite", "rust"]);
+        assert!(qr.rows.iter().all(|r| r["score"].is_f64() && r["rowid"].is_i64()));
+        // The query is data, not SQL.
+        assert!(search(&conn, "docs", "x') OR 1=1 --", 10, 0, enc).map_or(true, |qr| qr.rows.is_empty()));
+
+        let err = search(&conn, "plain", "sqlite", 10, 0, enc).unwrap_err();
+        assert!(err.to_string().contains("not an FTS5 table (FTS5 tables: docs)"), "{err}");
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::Instant;
+
+use rusqlite::Connection;
+
+use crate::core::query::run_execute;
+use crate::core::types::{EncodeOptions, MaintenanceOp, MaintenanceStep, OptimizeResult};
+use crate::error::{AppError, AppResult};
+
+/// Run `ops` in order on the worker connection and report per-step timing plus the
+/// main database file size before and after.
+///
+/// `VACUUM` rebuilds the whole file (needing up to twice its size on disk while it runs)
+/// and cannot run inside a transaction, so an open one is rejected up front with
+/// `TransactionActive` instead of partway through the list.
+pub fn optimize(conn: &Connection, ops: &[MaintenanceOp]) -> AppResult<OptimizeResult> {
+    if ops.contains(&MaintenanceOp::Vacuum) && !conn.is_autocommit() {
+        return Err(AppError::TransactionActive);
+    }
+
+    let size_before = file_size(conn);
+    let started = Instant::now();
+    let mut steps = Vec::with_capacity(ops.len());
+    for &op in ops {
+        let res = run_execute(conn, op.as_sql(), None, EncodeOptions::default())?;
+        steps.push(MaintenanceStep {
+            operation: op,
+            duration_ms: res.duration_ms,
+        });
+    }
+    let duration_ms = started.elapsed().as_micros() as f64 / 1000.0;
+    let size_after = file_size(conn);
+
+    Ok(OptimizeResult {
+        steps,
+        size_before,
+        size_after,
+        freed_bytes: size_before
+            .zip(size_after)
+            .map(|(before, after)| before as i64 - after as i64),
+        duration_ms,
+    })
+}
+
+/// Size of the main database file; `None` for in-memory databases. Pages still in a WAL
+/// file are not counted until they are checkpointed.
+fn file_size(conn: &Connection) -> Option<u64> {
+    let path = conn.path().filter(|p| !p.is_empty())?;
+    std::fs::metadata(path).map(|m| m.len()).ok()
+}
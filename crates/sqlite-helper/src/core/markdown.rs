@@ -0,0 +1,165 @@
+use serde_json::Value;
+
+use crate::core::types::QueryResult;
+
+/// Cells longer than this many characters are cut off with `…` in markdown tables, unless the
+/// caller picks another width.
+pub const MAX_CELL_CHARS: usize = 80;
+
+/// Render a query result as a GitHub-flavored markdown table (header, separator, one line per
+/// row), followed by notes when cells were shortened or more rows are available.
+///
+/// NULL is shown as `NULL` and BLOBs as a `<blob N bytes>` placeholder; `|` is escaped and line
+/// breaks become `<br>` so every row stays on one line.
+pub fn to_markdown(qr: &QueryResult, max_cell_chars: usize) -> String {
+    if qr.columns.is_empty() {
+        return "_No columns._\n".to_string();
+    }
+    let header: Vec<&str> = qr.columns.iter().map(|c| c.name.as_str()).collect();
+    let rows: Vec<Vec<String>> = qr.rows.iter().map(|row| row.values().map(cell_text).collect()).collect();
+    let (mut out, cut) = table(&header, &rows, max_cell_chars);
+
+    let mut notes = Vec::new();
+    if qr.rows.is_empty() {
+        notes.push("No rows.".to_string());
+    }
+    if cut {
+        notes.push(truncation_note(max_cell_chars));
+    }
+    if qr.truncated {
+        let more = match (qr.next_offset, &qr.next_cursor) {
+            (_, Some(cursor)) => format!(" (next_cursor: {cursor})"),
+            (Some(offset), None) => format!(" (next_offset: {offset})"),
+            (None, None) => String::new(),
+        };
+        notes.push(format!("Showing the first {} rows; more are available{more}.", qr.rows.len()));
+    }
+    if !notes.is_empty() {
+        out.push('\n');
+        out.push_str(&format!("_{}_\n", notes.join(" ")));
+    }
+    out
+}
+
+/// A markdown table of `header` and `rows`, every cell escaped and cut to `max_cell_chars`
+/// (at least one); also whether any cell was cut.
+pub fn table(header: &[&str], rows: &[Vec<String>], max_cell_chars: usize) -> (String, bool) {
+    let max = max_cell_chars.max(1);
+    let mut cut = false;
+    let mut out = String::new();
+    write_row(&mut out, header.iter().map(|h| cell(h, max, &mut cut)));
+    write_row(&mut out, header.iter().map(|_| "---".to_string()));
+    for row in rows {
+        write_row(&mut out, row.iter().map(|v| cell(v, max, &mut cut)));
+    }
+    (out, cut)
+}
+
+/// The note to put under a `table` that cut cells.
+pub fn truncation_note(max_cell_chars: usize) -> String {
+    format!("Cells longer than {} characters are truncated (…).", max_cell_chars.max(1))
+}
+
+fn write_row(out: &mut String, cells: impl Iterator<Item = String>) {
+    out.push('|');
+    for c in cells {
+        out.push(' ');
+        out.push_str(&c);
+        out.push_str(" |");
+    }
+    out.push('\n');
+}
+
+/// Escape `text` for a table cell, shortening it to `max` characters (and setting `cut`).
+fn cell(text: &str, max: usize, cut: &mut bool) -> String {
+    let mut chars = text.chars();
+    let mut shown: String = chars.by_ref().take(max).collect();
+    if chars.next().is_some() {
+        shown.push('…');
+        *cut = true;
+    }
+    shown
+        .replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace(['\n', '\r'], "<br>")
+}
+
+fn cell_text(v: &Value) -> String {
+    match v {
+        Value::Null => "NULL".to_string(),
+        Value::String(s) => s.clone(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        // Tagged values from `encode_value`: a placeholder for blobs, the textual value of
+        // int64/float sentinels.
+        Value::Object(obj) => match obj.get("$type").and_then(|t| t.as_str()) {
+            Some("blob") => {
+                let size = obj.get("size").and_then(|s| s.as_u64()).unwrap_or_default();
+                format!("<blob {size} bytes>")
+            }
+            Some(_) => match obj.get("value") {
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+                None => v.to_string(),
+            },
+            None => v.to_string(),
+        },
+        Value::Array(_) => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::ColumnMeta;
+
+    fn result(columns: &[&str], rows: Vec<Value>, truncated: bool) -> QueryResult {
+        QueryResult {
+            columns: columns
+                .iter()
+                .map(|name| ColumnMeta {
+                    name: name.to_string(),
+                    ..ColumnMeta::default()
+                })
+                .collect(),
+            rows: rows.into_iter().map(|r| r.as_object().unwrap().clone()).collect(),
+            truncated,
+            next_offset: truncated.then_some(1),
+            next_cursor: None,
+            duration_ms: 0.0,
+            rows_scanned: 0,
+            elapsed_ms: None,
+        }
+    }
+
+    #[test]
+    fn renders_a_table_with_placeholders_and_escapes() {
+        let qr = result(
+            &["id", "note", "data"],
+            vec![serde_json::json!({
+                "id": { "$type": "int64", "value": "9007199254740993" },
+                "note": "a|b\nc",
+                "data": { "$type": "blob", "size": 2048, "truncated": true },
+            })],
+            false,
+        );
+        assert_eq!(
+            to_markdown(&qr, MAX_CELL_CHARS),
+            "| id | note | data |\n| --- | --- | --- |\n| 9007199254740993 | a\\|b<br>c | <blob 2048 bytes> |\n"
+        );
+    }
+
+    #[test]
+    fn notes_truncated_cells_and_rows() {
+        let long = "x".repeat(MAX_CELL_CHARS + 5);
+        let qr = result(&["v", "n"], vec![serde_json::json!({ "v": long, "n": null })], true);
+        let md = to_markdown(&qr, MAX_CELL_CHARS);
+        assert!(md.contains(&format!("| {}… | NULL |", "x".repeat(MAX_CELL_CHARS))));
+        assert!(md.ends_with("more are available (next_offset: 1)._\n"));
+        assert!(md.contains("truncated (…)"));
+
+        assert!(to_markdown(&result(&["v"], vec![], false), MAX_CELL_CHARS).ends_with("_No rows._\n"));
+        assert!(to_markdown(&qr, 3).contains("| xxx… | NUL… |"));
+    }
+}
@@ -1,7 +1,28 @@
+pub mod analysis;
+pub mod attach;
+pub mod backup;
+pub mod blob;
+pub mod busy;
+pub mod cancel;
 pub mod connection;
+pub mod csv;
+pub mod diff;
+pub mod dump;
+pub mod explain;
+pub mod fts;
+pub mod maintenance;
+pub mod markdown;
+pub mod ndjson;
+pub mod paging;
+pub mod params;
+pub mod paths;
+pub mod pragmas;
 pub mod query;
 pub mod schema;
+pub mod statements;
+pub mod transaction;
 pub mod types;
 pub mod readonly;
+pub mod rows;
 pub mod limits;
 
@@ -0,0 +1,99 @@
+use crate::{
+    core::{connection::QueryStream, types::QueryFrame},
+    error::{AppError, AppResult},
+};
+
+/// A query rendered as NDJSON, with the paging state of the stream's `end` frame.
+#[derive(Debug)]
+pub struct Ndjson {
+    pub text: String,
+    pub truncated: bool,
+    pub next_offset: Option<usize>,
+    pub next_cursor: Option<String>,
+}
+
+/// Render a query as newline-delimited JSON: a leading `{"columns": [...]}` line, then one
+/// object per row, each line terminated by `\n`.
+///
+/// Row lines are appended as the worker streams the frames, so the rows are never also held as
+/// a `QueryResult`; the columns line goes in front at the end, once `sqlite_type` is known. Rows
+/// are encoded as in `QueryResult.rows`, so `int_mode`/`float_mode`/`blob_mode` still apply, and
+/// clients such as `jq` can process them line by line.
+pub async fn collect(mut stream: QueryStream) -> AppResult<Ndjson> {
+    let mut columns = Vec::new();
+    let mut rows = String::new();
+    while let Some(frame) = stream.next().await {
+        match frame? {
+            QueryFrame::Columns(c) => columns = c,
+            QueryFrame::Row(row) => {
+                // Serializing a JSON map cannot fail.
+                rows.push_str(&serde_json::to_string(&row).unwrap_or_default());
+                rows.push('\n');
+            }
+            QueryFrame::End {
+                truncated,
+                next_offset,
+                next_cursor,
+                column_types,
+                ..
+            } => {
+                for (column, sqlite_type) in columns.iter_mut().zip(column_types) {
+                    column.sqlite_type = Some(sqlite_type);
+                }
+                let mut text = serde_json::json!({ "columns": columns }).to_string();
+                text.push('\n');
+                text.push_str(&rows);
+                return Ok(Ndjson {
+                    text,
+                    truncated,
+                    next_offset,
+                    next_cursor,
+                });
+            }
+        }
+    }
+    Err(AppError::Internal("query stream ended without an end frame".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        connection::{ConnectionConfig, ConnectionManager},
+        paging::Page,
+        types::EncodeOptions,
+    };
+
+    #[tokio::test]
+    async fn one_line_per_row_after_the_columns() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-ndjson-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let sql = "CREATE TABLE t (id INTEGER, note TEXT);
+                   INSERT INTO t VALUES (1, 'two\nlines'), (9007199254740993, NULL), (3, 'x');";
+        rusqlite::Connection::open(&path).unwrap().execute_batch(sql).unwrap();
+        let cm = ConnectionManager::new(ConnectionConfig::default());
+        let worker = cm.ensure_worker(&path).await.unwrap();
+        let query = || "SELECT id, note FROM t ORDER BY rowid".to_string();
+        let encode = EncodeOptions::default();
+
+        let out = collect(worker.query_stream(query(), None, 2, Page::Start, encode).unwrap()).await.unwrap();
+        let lines: Vec<serde_json::Value> =
+            out.text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["columns"][1]["name"], "note");
+        assert_eq!(lines[0]["columns"][1]["sqlite_type"], "text");
+        assert_eq!(lines[1], serde_json::json!({ "id": 1, "note": "two\nlines" }));
+        assert_eq!(lines[2], serde_json::json!({ "id": 9007199254740993_i64, "note": null }));
+        assert!(out.text.ends_with("}\n"));
+        assert_eq!((out.truncated, out.next_offset), (true, Some(2)));
+
+        let delete = "DELETE FROM t".to_string();
+        let stream = worker.read_query_stream(delete, None, 10, Page::Start, encode).unwrap();
+        assert_eq!(collect(stream).await.unwrap_err().code(), "NOT_READONLY");
+        // Refused before anything ran.
+        assert_eq!(worker.query(query(), None, 10, Page::Start, encode).await.unwrap().rows.len(), 3);
+
+        cm.shutdown().await;
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,129 @@
+//! Where a page of query results starts: a row offset, or an opaque keyset cursor.
+//!
+//! Keyset paging wraps the statement as `SELECT * FROM (<sql>) WHERE "key" > <last> ORDER BY
+//! "key"`, so each page is an index seek instead of re-stepping every earlier row. Constraints:
+//! - the statement must be a `SELECT` (or `VALUES`/`WITH ...`) usable as a subquery;
+//! - the key must be a result column holding unique, non-NULL integers — typically the
+//!   table's `INTEGER PRIMARY KEY` or `rowid`;
+//! - rows come back ordered by that key, ascending, whatever the statement's own `ORDER BY`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::query::base64;
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Default)]
+pub enum Page {
+    /// From the first row.
+    #[default]
+    Start,
+    /// Skip this many rows by stepping past them.
+    Offset(usize),
+    /// Ordered by an integer key column, resuming after `Keyset::after`.
+    Keyset(Keyset),
+}
+
+impl Page {
+    /// Combine the `offset`, `cursor_key` and `cursor` request fields; a `cursor` carries its
+    /// own key, so `cursor_key` only starts a new cursor.
+    pub fn from_request(
+        sql: &str,
+        offset: Option<usize>,
+        cursor_key: Option<String>,
+        cursor: Option<&str>,
+    ) -> AppResult<Self> {
+        if offset.is_some() && (cursor_key.is_some() || cursor.is_some()) {
+            return Err(AppError::InvalidRequest(
+                "offset cannot be combined with cursor paging".into(),
+            ));
+        }
+        if let Some(token) = cursor {
+            let keyset = Keyset::decode(sql, token)?;
+            if cursor_key.is_some_and(|k| k != keyset.key) {
+                return Err(AppError::InvalidRequest(
+                    "cursor_key does not match the cursor".into(),
+                ));
+            }
+            return Ok(Page::Keyset(keyset));
+        }
+        if let Some(key) = cursor_key {
+            return Ok(Page::Keyset(Keyset::first(sql, key)?));
+        }
+        Ok(offset.map_or(Page::Start, Page::Offset))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keyset {
+    /// Result column name the pages are ordered by.
+    pub key: String,
+    /// Last key value of the previous page; `None` on the first page.
+    pub after: Option<i64>,
+    sql_hash: u64,
+}
+
+/// Serialized form of a cursor, base64-encoded so clients treat it as opaque.
+#[derive(Serialize, Deserialize)]
+struct Token {
+    k: String,
+    a: i64,
+    q: String,
+}
+
+impl Keyset {
+    fn first(sql: &str, key: String) -> AppResult<Self> {
+        if key.is_empty() {
+            return Err(AppError::InvalidRequest("cursor_key must not be empty".into()));
+        }
+        Ok(Keyset {
+            key,
+            after: None,
+            sql_hash: fnv1a(sql.trim()),
+        })
+    }
+
+    fn decode(sql: &str, token: &str) -> AppResult<Self> {
+        let invalid = || AppError::InvalidRequest("invalid cursor".into());
+        let bytes = base64::decode(token).map_err(|_| invalid())?;
+        let t: Token = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+        // A cursor only makes sense for the statement that produced it.
+        if t.q != format!("{:016x}", fnv1a(sql.trim())) {
+            return Err(AppError::InvalidRequest(
+                "cursor was issued for a different query".into(),
+            ));
+        }
+        let mut keyset = Keyset::first(sql, t.k)?;
+        keyset.after = Some(t.a);
+        Ok(keyset)
+    }
+
+    /// Token resuming after `last`, the key of the final row on this page.
+    pub fn next_token(&self, last: i64) -> String {
+        let t = Token {
+            k: self.key.clone(),
+            a: last,
+            q: format!("{:016x}", self.sql_hash),
+        };
+        base64::encode(&serde_json::to_vec(&t).unwrap_or_default())
+    }
+
+    /// Wrap `stmt` (one statement, trailing `;` allowed) to order by the key and skip past
+    /// `after`. The key value is an integer, so it is inlined rather than bound, leaving the
+    /// statement's own parameter numbering untouched.
+    pub fn wrap(&self, stmt: &str) -> String {
+        let inner = stmt.trim().trim_end_matches(';');
+        let key = format!("\"{}\"", self.key.replace('"', "\"\""));
+        // The newline keeps a trailing `-- comment` in `inner` from swallowing the `)`.
+        match self.after {
+            Some(after) => format!("SELECT * FROM ({inner}\n) WHERE {key} > {after} ORDER BY {key}"),
+            None => format!("SELECT * FROM ({inner}\n) ORDER BY {key}"),
+        }
+    }
+}
+
+/// FNV-1a; stable across builds, unlike `DefaultHasher`, so cursors survive a sidecar restart.
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
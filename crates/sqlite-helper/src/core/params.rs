@@ -0,0 +1,167 @@
+use rusqlite::{types::Value as SqlValue, Statement};
+use serde::Deserialize;
+
+use crate::core::query::base64;
+use crate::error::{AppError, AppResult};
+
+/// Wire form of the `params` field.
+///
+/// Either a JSON array (positional `?` binds) or a JSON object
+/// (named `:name` / `@name` / `$name` binds).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ParamsInput {
+    Positional(Vec<serde_json::Value>),
+    Named(serde_json::Map<String, serde_json::Value>),
+}
+
+/// Bound parameters supplied alongside a SQL string.
+#[derive(Debug, Clone, Default)]
+pub struct QueryParams {
+    pub positional: Vec<serde_json::Value>,
+    pub named: serde_json::Map<String, serde_json::Value>,
+}
+
+impl QueryParams {
+    /// Combine the `params` and `named_params` request fields; `None` when neither is set.
+    pub fn from_input(
+        params: Option<ParamsInput>,
+        named_params: Option<serde_json::Map<String, serde_json::Value>>,
+    ) -> Option<Self> {
+        if params.is_none() && named_params.is_none() {
+            return None;
+        }
+        let mut out = QueryParams::default();
+        match params {
+            Some(ParamsInput::Positional(v)) => out.positional = v,
+            Some(ParamsInput::Named(m)) => out.named = m,
+            None => {}
+        }
+        if let Some(m) = named_params {
+            out.named.extend(m);
+        }
+        Some(out)
+    }
+}
+
+/// Bind `params` onto a freshly prepared statement.
+///
+/// Anonymous slots (`?`, `?NNN`) take positional values in order; named slots
+/// are looked up by name with or without their sigil. Must be followed by
+/// `raw_query`/`raw_execute`; `query([])` would re-bind and fail.
+pub fn bind_params(stmt: &mut Statement<'_>, params: Option<&QueryParams>) -> AppResult<()> {
+    let empty = QueryParams::default();
+    let params = params.unwrap_or(&empty);
+
+    let count = stmt.parameter_count();
+    let mut anonymous = Vec::new();
+    let mut named = Vec::new();
+    for idx in 1..=count {
+        match stmt.parameter_name(idx) {
+            Some(name) if !name.starts_with('?') => named.push((idx, name.to_string())),
+            _ => anonymous.push(idx),
+        }
+    }
+
+    if anonymous.len() != params.positional.len() {
+        return Err(AppError::InvalidRequest(format!(
+            "parameter count mismatch: statement expects {} positional parameter(s), got {}",
+            anonymous.len(),
+            params.positional.len()
+        )));
+    }
+    if named.len() != params.named.len() {
+        return Err(AppError::InvalidRequest(format!(
+            "parameter count mismatch: statement expects {} named parameter(s), got {}",
+            named.len(),
+            params.named.len()
+        )));
+    }
+
+    for (idx, v) in anonymous.into_iter().zip(&params.positional) {
+        stmt.raw_bind_parameter(idx, json_to_sql(v)?)?;
+    }
+    for (idx, name) in named {
+        let Some(v) = lookup_named(&params.named, &name) else {
+            return Err(AppError::InvalidRequest(format!(
+                "missing value for named parameter: {name}"
+            )));
+        };
+        stmt.raw_bind_parameter(idx, json_to_sql(v)?)?;
+    }
+    Ok(())
+}
+
+fn lookup_named<'a>(
+    named: &'a serde_json::Map<String, serde_json::Value>,
+    name: &str,
+) -> Option<&'a serde_json::Value> {
+    // `name` comes from SQLite and always carries its sigil (`:id`, `@id`, `$id`).
+    named.get(name).or_else(|| named.get(&name[1..]))
+}
+
+/// Map a JSON value to the SQLite value it binds as (inverse of the result type mapping).
+pub fn json_to_sql(v: &serde_json::Value) -> AppResult<SqlValue> {
+    use serde_json::Value;
+
+    Ok(match v {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(i64::from(*b)),
+        Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                SqlValue::Integer(i)
+            } else if let Some(f) = n.as_f64() {
+                SqlValue::Real(f)
+            } else {
+                return Err(AppError::InvalidRequest(format!("unsupported number parameter: {n}")));
+            }
+        }
+        Value::String(s) => SqlValue::Text(s.clone()),
+        Value::Object(obj) if obj.get("$type").and_then(|t| t.as_str()) == Some("blob") => {
+            let Some(b64) = obj.get("base64").and_then(|b| b.as_str()) else {
+                return Err(AppError::InvalidRequest(
+                    "blob parameter requires a base64 string".into(),
+                ));
+            };
+            SqlValue::Blob(base64::decode(b64)?)
+        }
+        other => {
+            return Err(AppError::InvalidRequest(format!(
+                "unsupported parameter value: {other}"
+            )))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::*;
+    use crate::core::{paging::Page, query::run_query, types::EncodeOptions};
+
+    #[test]
+    fn positional_and_named_values_bind_with_their_sqlite_types() {
+        let conn = Connection::open_in_memory().unwrap();
+        let query = |sql: &str, params: serde_json::Value| {
+            let params = QueryParams::from_input(Some(serde_json::from_value(params).unwrap()), None);
+            run_query(&conn, sql, params.as_ref(), 10, &Page::Start, EncodeOptions::default())
+        };
+
+        let values = serde_json::json!(["it's", 42, 1.5, null, { "$type": "blob", "base64": "AAE=" }]);
+        let sql = "SELECT typeof(?) AS a, typeof(?) AS b, typeof(?) AS c, typeof(?) AS d, ? AS e";
+        let row = serde_json::Value::Object(query(sql, values).unwrap().rows.remove(0));
+        assert_eq!(row["a"], "text");
+        assert_eq!((&row["b"], &row["c"], &row["d"]), (&"integer".into(), &"real".into(), &"null".into()));
+        assert_eq!((&row["e"]["$type"], &row["e"]["base64"]), (&"blob".into(), &"AAE=".into()));
+
+        let named = serde_json::json!({ "name": "it's", "@id": 1 });
+        let qr = query("SELECT :name AS n, @id + 1 AS i", named).unwrap();
+        assert_eq!((&qr.rows[0]["n"], &qr.rows[0]["i"]), (&"it's".into(), &2.into()));
+
+        let err = query("SELECT ?, ?", serde_json::json!([1])).unwrap_err();
+        assert!(matches!(&err, AppError::InvalidRequest(m) if m.contains("expects 2")), "{err}");
+        let err = query("SELECT :a", serde_json::json!({ "b": 1 })).unwrap_err();
+        assert!(matches!(&err, AppError::InvalidRequest(m) if m.contains(":a")), "{err}");
+    }
+}
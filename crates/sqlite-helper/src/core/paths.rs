@@ -0,0 +1,491 @@
+use std::{
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    core::types::DatabaseFile,
+    error::{AppError, AppResult},
+};
+
+/// Resolve `db_path` to a lexically normalized absolute path and enforce the `--allowed-dir`
+/// whitelist. An empty whitelist allows any path.
+///
+/// With a whitelist, symlinks are resolved before the check (and in the returned path, so the
+/// file that is checked is the file that gets opened): the file itself when it exists, else its
+/// parent directory. `allowed_dirs` are expected to be resolved already; see
+/// [`resolve_allowed_dirs`].
+///
+/// With `allow_uri` (`--allow-uri`), SQLite `file:` URI filenames are accepted too (see
+/// [`validate_db_uri`]); the result is then a URI as well, to be opened with `SQLITE_OPEN_URI`
+/// (see [`is_uri`]). Without it they are refused rather than taken as a relative path.
+pub fn validate_db_path(db_path: &Path, allowed_dirs: &[PathBuf], allow_uri: bool) -> AppResult<PathBuf> {
+    if let Some(uri) = db_path.to_str().and_then(|s| s.strip_prefix("file:")) {
+        if !allow_uri {
+            return Err(AppError::InvalidRequest("file: URIs are only accepted with --allow-uri".into()));
+        }
+        return validate_db_uri(uri, allowed_dirs);
+    }
+    validate_fs_path(db_path, allowed_dirs)
+}
+
+fn validate_fs_path(db_path: &Path, allowed_dirs: &[PathBuf]) -> AppResult<PathBuf> {
+    let abs = if db_path.is_absolute() {
+        db_path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(db_path)
+    };
+
+    if allowed_dirs.is_empty() {
+        return Ok(normalize_lexical(&abs));
+    }
+
+    let abs_norm = normalize_lexical(&abs);
+    let Some(resolved) = resolve_symlinks(&abs_norm) else {
+        return Err(AppError::PathNotAllowed(abs_norm));
+    };
+    for d in allowed_dirs {
+        if is_within(&resolved, &normalize_lexical(d)) {
+            return Ok(resolved);
+        }
+    }
+    Err(AppError::PathNotAllowed(resolved))
+}
+
+/// URI query parameters accepted and their allowed values. Anything else (notably `vfs`, and
+/// `mode=rwc`/`mode=memory`, which would bypass `create` or the path check) is rejected
+/// rather than passed through to SQLite.
+const URI_PARAMS: [(&str, &[&str]); 4] = [
+    ("mode", &["ro", "rw"]),
+    ("immutable", &["0", "1"]),
+    ("cache", &["shared", "private"]),
+    ("nolock", &["0", "1"]),
+];
+
+/// Whether a validated database path is a `file:` URI filename.
+pub fn is_uri(db_path: &Path) -> bool {
+    db_path.to_str().is_some_and(|s| s.starts_with("file:"))
+}
+
+/// Validate the part of a `file:` URI after the scheme: the embedded path goes through the
+/// same checks as a plain path, and the URI is rebuilt around the resolved path with only the
+/// known parameters, so e.g. `file:///data/./a.db?immutable=1` becomes
+/// `file:/data/a.db?immutable=1` (a different worker than plain `/data/a.db`).
+fn validate_db_uri(uri: &str, allowed_dirs: &[PathBuf]) -> AppResult<PathBuf> {
+    let invalid = |msg: &str| AppError::InvalidRequest(format!("invalid file: URI: {msg}"));
+    let uri = uri.split('#').next().unwrap_or_default();
+    let (path, query) = uri.split_once('?').unwrap_or((uri, ""));
+    // `file:///p` and `file://localhost/p` both mean `file:/p`.
+    let path = match path.strip_prefix("//") {
+        Some(rest) => {
+            let slash = rest.find('/').unwrap_or(rest.len());
+            let authority = &rest[..slash];
+            if !authority.is_empty() && !authority.eq_ignore_ascii_case("localhost") {
+                return Err(invalid("authority must be empty or localhost"));
+            }
+            &rest[slash..]
+        }
+        None => path,
+    };
+    let path = percent_decode(path).ok_or_else(|| invalid("bad percent-encoding in path"))?;
+    // `/C:/x` is how URIs spell a Windows drive path.
+    #[cfg(windows)]
+    let path = match path.as_bytes() {
+        [b'/', d, b':', ..] if d.is_ascii_alphabetic() => path[1..].to_string(),
+        _ => path,
+    };
+    if path.is_empty() {
+        return Err(invalid("missing path"));
+    }
+
+    let mut params = Vec::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let known = URI_PARAMS.iter().find(|(k, _)| *k == key);
+        match known {
+            Some((_, values)) if values.contains(&value) => params.push(format!("{key}={value}")),
+            Some(_) => return Err(invalid(&format!("unsupported value for {key}: {value}"))),
+            None => return Err(invalid(&format!("unsupported parameter: {key}"))),
+        }
+    }
+
+    let resolved = validate_fs_path(Path::new(&path), allowed_dirs)?;
+    let mut out = String::from("file:");
+    let resolved = resolved.to_string_lossy();
+    if !resolved.starts_with('/') {
+        out.push('/');
+    }
+    for c in resolved.chars() {
+        match c {
+            // Only these would be misread when SQLite parses the URI back.
+            '%' | '?' | '#' => out.push_str(&format!("%{:02X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    if !params.is_empty() {
+        out.push('?');
+        out.push_str(&params.join("&"));
+    }
+    Ok(PathBuf::from(out))
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Make `--allowed-dir` entries absolute and resolve their symlinks, once at startup, so that
+/// `./data` works and matches the resolved paths `validate_db_path` compares against.
+/// Directories that do not exist (yet) are only normalized lexically.
+pub fn resolve_allowed_dirs(dirs: &[PathBuf]) -> AppResult<Vec<PathBuf>> {
+    let cwd = std::env::current_dir()?;
+    Ok(dirs
+        .iter()
+        .map(|d| {
+            let abs = normalize_lexical(&cwd.join(d));
+            canonicalize(&abs).unwrap_or(abs)
+        })
+        .collect())
+}
+
+/// Follow symlinks in an absolute, normalized path. A database that does not exist yet is
+/// resolved through its parent directory, falling back to the path as given when neither
+/// exists. `None` for a dangling symlink, whose target cannot be checked but would be created.
+fn resolve_symlinks(p: &Path) -> Option<PathBuf> {
+    if let Ok(real) = canonicalize(p) {
+        return Some(real);
+    }
+    if std::fs::symlink_metadata(p).is_ok() {
+        return None;
+    }
+    if let (Some(parent), Some(name)) = (p.parent(), p.file_name()) {
+        if let Ok(dir) = canonicalize(parent) {
+            return Some(dir.join(name));
+        }
+    }
+    Some(p.to_path_buf())
+}
+
+/// File name globs `find_databases` matches by default (comma-separated, case-insensitive).
+pub const DEFAULT_DB_PATTERNS: &str = "*.db,*.sqlite,*.sqlite3,*.db3";
+
+/// Directory levels below each allowed dir that `find_databases` descends into by default.
+pub const MAX_SCAN_DEPTH: usize = 4;
+
+/// First 16 bytes of every SQLite 3 database file.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Database files in and below `dirs` whose names match one of `patterns` (comma-separated
+/// globs with `*` and `?`) and that start with the SQLite header, sorted by path: the first
+/// `cap` of them. Directories are descended `max_depth` levels. Symlinks are skipped
+/// (directories are not followed), so a link cannot lead the scan out of the whitelist or
+/// into a cycle; unreadable directories and files are skipped.
+pub fn find_databases(dirs: &[PathBuf], patterns: &str, max_depth: usize, cap: usize) -> Vec<DatabaseFile> {
+    let patterns: Vec<&str> = patterns.split(',').map(str::trim).filter(|p| !p.is_empty()).collect();
+    let mut candidates = Vec::new();
+    let mut pending: Vec<(PathBuf, usize)> = dirs.iter().map(|d| (d.clone(), 0)).collect();
+    while let Some((dir, depth)) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else { continue };
+            let path = entry.path();
+            if file_type.is_dir() {
+                if depth < max_depth {
+                    pending.push((path, depth + 1));
+                }
+                continue;
+            }
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if file_type.is_file() && patterns.iter().any(|p| glob_match(p, &name)) {
+                candidates.push(path);
+            }
+        }
+    }
+    // Sorted before `cap` applies, so a large tree yields the first files by path (which
+    // `resources/list` pages through) rather than whichever the walk reached first. Only the
+    // headers of files that can make the cut are read.
+    candidates.sort();
+    candidates.dedup();
+    candidates.into_iter().filter_map(sniff_database).take(cap).collect()
+}
+
+/// `path` with its size and modification time, if it begins with the SQLite header (so a
+/// `notes.db` that is not a database, or an empty file, is left out).
+fn sniff_database(path: PathBuf) -> Option<DatabaseFile> {
+    let mut header = [0u8; 16];
+    let mut file = std::fs::File::open(&path).ok()?;
+    file.read_exact(&mut header).ok()?;
+    if &header != SQLITE_HEADER {
+        return None;
+    }
+    let meta = file.metadata().ok()?;
+    let modified_ms = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64);
+    Some(DatabaseFile {
+        path,
+        size_bytes: meta.len(),
+        modified_ms,
+    })
+}
+
+/// Shell-style match of a file name: `*` is any run of characters, `?` any one; ASCII
+/// letters compare case-insensitively (`*.db` matches `X.DB`).
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (p, n): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut pi, mut ni) = (0, 0);
+    // Position after the last `*` and the name position it was tried at, to backtrack to.
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi].eq_ignore_ascii_case(&n[ni])) {
+            pi += 1;
+            ni += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi + 1, ni));
+            pi += 1;
+        } else if let Some((sp, sn)) = star {
+            pi = sp;
+            ni = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// `std::fs::canonicalize`, minus the `\\?\` verbatim prefix it adds on Windows, so results
+/// look like the paths clients send (and SQLite error messages stay readable).
+fn canonicalize(p: &Path) -> std::io::Result<PathBuf> {
+    let real = std::fs::canonicalize(p)?;
+    #[cfg(windows)]
+    let real = PathBuf::from(windows::strip_verbatim(&real.to_string_lossy()));
+    Ok(real)
+}
+
+/// Whether `path` lies inside `dir`, compared the way the filesystem does: component-wise, and
+/// on Windows also case-insensitively and regardless of `/` vs `\` or verbatim prefixes.
+fn is_within(path: &Path, dir: &Path) -> bool {
+    #[cfg(windows)]
+    return windows::is_within(&path.to_string_lossy(), &dir.to_string_lossy());
+    #[cfg(not(windows))]
+    path.starts_with(dir)
+}
+
+fn normalize_lexical(p: &Path) -> PathBuf {
+    // Normalize lexically (remove `.` and resolve `..`) without touching filesystem,
+    // so it works even if DB file doesn't exist yet.
+    let mut out = PathBuf::new();
+
+    for comp in p.components() {
+        match comp {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                // Pop only if we have something to pop and the last isn't a prefix/root.
+                let popped = out.pop();
+                if !popped {
+                    // Can't go above root/prefix; keep as-is (still prevents escaping during starts_with checks).
+                }
+            }
+            Component::Prefix(prefix) => out.push(prefix.as_os_str()),
+            Component::RootDir => out.push(comp.as_os_str()),
+            Component::Normal(c) => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Windows path comparison on plain strings, so it is testable on every platform.
+#[cfg_attr(not(windows), allow(dead_code))]
+mod windows {
+    /// `\\?\C:\x` -> `C:\x` and `\\?\UNC\srv\share\x` -> `\\srv\share\x`; other paths unchanged.
+    pub(super) fn strip_verbatim(p: &str) -> String {
+        if let Some(rest) = p.strip_prefix(r"\\?\UNC\") {
+            format!(r"\\{rest}")
+        } else if let Some(rest) = p.strip_prefix(r"\\?\") {
+            rest.to_string()
+        } else {
+            p.to_string()
+        }
+    }
+
+    /// Case-folded components, with the prefix (`c:` or `\\server\share`) as the first one.
+    fn components(p: &str) -> Vec<String> {
+        let p = strip_verbatim(&p.replace('/', "\\"));
+        let (prefix, rest) = if let Some(unc) = p.strip_prefix(r"\\") {
+            let mut parts = unc.splitn(3, '\\');
+            let server = parts.next().unwrap_or("");
+            let share = parts.next().unwrap_or("");
+            (format!(r"\\{server}\{share}"), parts.next().unwrap_or(""))
+        } else if p.len() >= 2 && p.as_bytes()[0].is_ascii_alphabetic() && p.as_bytes()[1] == b':' {
+            (p[..2].to_string(), &p[2..])
+        } else {
+            (String::new(), p.as_str())
+        };
+        // NTFS compares names case-insensitively; Unicode lowercasing is a close approximation.
+        let mut out = vec![prefix.to_lowercase()];
+        for c in rest.split('\\') {
+            match c {
+                "" | "." => {}
+                ".." => {
+                    if out.len() > 1 {
+                        out.pop();
+                    }
+                }
+                c => out.push(c.to_lowercase()),
+            }
+        }
+        out
+    }
+
+    pub(super) fn is_within(path: &str, dir: &str) -> bool {
+        let (path, dir) = (components(path), components(dir));
+        path.len() >= dir.len() && path[..dir.len()] == dir[..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whitelist_rejects_escapes_with_the_normalized_path() {
+        let allowed = vec![PathBuf::from("/srv/data")];
+        assert_eq!(
+            validate_db_path(Path::new("/srv/data/./app.db"), &allowed, false).unwrap(),
+            PathBuf::from("/srv/data/app.db")
+        );
+        match validate_db_path(Path::new("/srv/data/../secret.db"), &allowed, false) {
+            Err(AppError::PathNotAllowed(p)) => assert_eq!(p, PathBuf::from("/srv/secret.db")),
+            other => panic!("expected PathNotAllowed, got {other:?}"),
+        }
+        // An empty whitelist keeps allowing everything.
+        assert!(validate_db_path(Path::new("/srv/secret.db"), &[], false).is_ok());
+    }
+
+    #[test]
+    fn file_uris_are_validated_and_normalized() {
+        let allowed = vec![PathBuf::from("/srv/data")];
+        let check = |uri: &str| validate_db_path(Path::new(uri), &allowed, true);
+        assert_eq!(
+            check("file:///srv/data/./a%20b.db?immutable=1&mode=ro").unwrap(),
+            PathBuf::from("file:/srv/data/a b.db?immutable=1&mode=ro")
+        );
+        assert_eq!(
+            check("file://localhost/srv/data/what%3F.db").unwrap(),
+            PathBuf::from("file:/srv/data/what%3F.db")
+        );
+        assert!(matches!(check("file:/srv/data/../x.db"), Err(AppError::PathNotAllowed(_))));
+        let bad = ["file:/srv/data/a.db?vfs=unix-none", "file:/srv/data/a.db?mode=rwc", "file://h/srv/a.db"];
+        for bad in bad {
+            assert!(matches!(check(bad), Err(AppError::InvalidRequest(_))), "{bad}");
+        }
+        // Only with --allow-uri.
+        let res = validate_db_path(Path::new("file:/srv/data/a.db"), &allowed, false);
+        assert!(matches!(res, Err(AppError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn windows_paths_match_regardless_of_case_separators_and_prefixes() {
+        use super::windows::is_within;
+
+        let dir = r"C:\Users\me\data";
+        assert!(is_within(r"c:\users\ME\data\app.db", dir));
+        assert!(is_within("c:/Users/me/data/sub/app.db", dir));
+        assert!(is_within(r"\\?\C:\Users\me\data\app.db", dir));
+        assert!(is_within(r"C:\Users\me\data\app.db", r"\\?\c:\users\me\data\"));
+        assert!(!is_within(r"C:\Users\me\database.db", dir));
+        assert!(!is_within(r"D:\Users\me\data\app.db", dir));
+        assert!(!is_within(r"C:\Users\me\data\..\secret.db", dir));
+
+        let share = r"\\Server\Share\dbs";
+        assert!(is_within(r"\\server\share\DBS\app.db", share));
+        assert!(is_within(r"\\?\UNC\server\share\dbs\app.db", share));
+        assert!(is_within("//server/share/dbs/app.db", share));
+        assert!(!is_within(r"\\server\other\dbs\app.db", share));
+        assert!(!is_within(r"\\server\share\app.db", share));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_cannot_escape_the_whitelist() {
+        use std::os::unix::fs::symlink;
+
+        let root = std::env::temp_dir().join(format!("sqlite-helper-paths-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let (data, outside) = (root.join("data"), root.join("outside"));
+        std::fs::create_dir_all(&data).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("secret.db"), b"").unwrap();
+        std::fs::write(data.join("app.db"), b"").unwrap();
+        symlink(outside.join("secret.db"), data.join("file-link.db")).unwrap();
+        symlink(&outside, data.join("dir-link")).unwrap();
+        symlink(outside.join("missing.db"), data.join("dangling.db")).unwrap();
+        let allowed = resolve_allowed_dirs(std::slice::from_ref(&data)).unwrap();
+
+        assert!(validate_db_path(&data.join("app.db"), &allowed, false).is_ok());
+        // New files are resolved through their parent directory.
+        assert!(validate_db_path(&data.join("new.db"), &allowed, false).is_ok());
+        for escape in ["file-link.db", "dir-link/secret.db", "dir-link/new.db", "dangling.db"] {
+            let res = validate_db_path(&data.join(escape), &allowed, false);
+            assert!(matches!(res, Err(AppError::PathNotAllowed(_))), "{escape}: {res:?}");
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_databases_matches_patterns_and_header_and_skips_symlinks() {
+        let root = std::env::temp_dir().join(format!("sqlite-helper-find-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sub/deeper")).unwrap();
+        for f in ["a.db", "sub/b.SQLITE", "sub/deeper/c.db", "z.db", "notes.txt", "fake.db", "empty.db"] {
+            std::fs::write(root.join(f), b"").unwrap();
+        }
+        for f in ["a.db", "sub/b.SQLITE", "sub/deeper/c.db", "z.db", "notes.txt"] {
+            rusqlite::Connection::open(root.join(f)).unwrap().execute_batch("CREATE TABLE t (x)").unwrap();
+        }
+        std::fs::write(root.join("fake.db"), b"not a database, just a long enough text file").unwrap();
+        std::os::unix::fs::symlink(root.join("a.db"), root.join("link.db")).unwrap();
+
+        let dirs = std::slice::from_ref(&root);
+        let paths = |found: Vec<DatabaseFile>| found.into_iter().map(|d| d.path).collect::<Vec<_>>();
+        let found = find_databases(dirs, DEFAULT_DB_PATTERNS, MAX_SCAN_DEPTH, 100);
+        assert!(found[0].size_bytes > 0 && found[0].modified_ms.is_some());
+        let all = ["a.db", "sub/b.SQLITE", "sub/deeper/c.db", "z.db"].map(|f| root.join(f));
+        assert_eq!(paths(found), all);
+        let shallow = find_databases(dirs, DEFAULT_DB_PATTERNS, 1, 100);
+        assert_eq!(paths(shallow), [root.join("a.db"), root.join("sub/b.SQLITE"), root.join("z.db")]);
+        let patterned = find_databases(dirs, "*.txt, ?.db", 0, 100);
+        assert_eq!(paths(patterned), [root.join("a.db"), root.join("notes.txt"), root.join("z.db")]);
+        // The cap keeps the first databases by path, wherever the walk found them.
+        assert_eq!(paths(find_databases(dirs, DEFAULT_DB_PATTERNS, MAX_SCAN_DEPTH, 2)), all[..2]);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn glob_matches_stars_and_question_marks() {
+        assert!(glob_match("*.db", "app.DB"));
+        assert!(glob_match("*.db", ".db"));
+        assert!(!glob_match("*.db", "app.db-wal"));
+        assert!(glob_match("data-??.sqlite*", "data-01.sqlite3"));
+        assert!(glob_match("*a*b*", "xxaxxbxx"));
+        assert!(!glob_match("*a*b", "xxbxxa"));
+        assert!(glob_match("*", ""));
+    }
+}
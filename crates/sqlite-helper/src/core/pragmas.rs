@@ -0,0 +1,159 @@
+use std::str::FromStr;
+
+use rusqlite::{types::ValueRef, Connection, OptionalExtension};
+
+use crate::error::{AppError, AppResult};
+
+/// Values a settable PRAGMA accepts.
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Bool,
+    Int,
+    /// Integers that must not be negative.
+    Count,
+    OneOf(&'static [&'static str]),
+}
+
+/// PRAGMAs that may be set on open (`--pragma`, `connect` payloads) and are reported by
+/// `connection_info`. Per-connection settings only: nothing that rewrites the file
+/// (`page_size`, `auto_vacuum`) or loosens safety beyond what SQLite's own defaults allow
+/// (no `synchronous = off`, `ignore_check_constraints` or `trusted_schema`).
+const ALLOWED: [(&str, Kind); 13] = [
+    ("foreign_keys", Kind::Bool),
+    ("defer_foreign_keys", Kind::Bool),
+    ("recursive_triggers", Kind::Bool),
+    ("automatic_index", Kind::Bool),
+    ("cell_size_check", Kind::Bool),
+    ("query_only", Kind::Bool),
+    ("busy_timeout", Kind::Count),
+    ("cache_size", Kind::Int),
+    ("mmap_size", Kind::Count),
+    ("wal_autocheckpoint", Kind::Count),
+    ("temp_store", Kind::OneOf(&["default", "file", "memory", "0", "1", "2"])),
+    ("secure_delete", Kind::OneOf(&["on", "off", "fast", "1", "0"])),
+    ("synchronous", Kind::OneOf(&["normal", "full", "extra", "1", "2", "3"])),
+];
+
+/// Reported by `connection_info` but only set through its own option: `--journal-mode`
+/// checks that SQLite actually switched.
+const REPORTED: [&str; 1] = ["journal_mode"];
+
+/// One validated `PRAGMA name = value`; `value` is normalized to lower case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PragmaSetting {
+    pub name: String,
+    pub value: String,
+}
+
+impl PragmaSetting {
+    pub fn new(name: &str, value: &str) -> AppResult<Self> {
+        let name = name.trim().to_ascii_lowercase();
+        let value = value.trim().to_ascii_lowercase();
+        if name == "journal_mode" {
+            return Err(AppError::InvalidRequest("journal_mode is set with --journal-mode".into()));
+        }
+        let Some((_, kind)) = ALLOWED.iter().find(|(n, _)| *n == name) else {
+            let names: Vec<&str> = ALLOWED.iter().map(|(n, _)| *n).collect();
+            return Err(AppError::InvalidRequest(format!(
+                "unsupported pragma: {name} (allowed: {})",
+                names.join(", ")
+            )));
+        };
+        let ok = match kind {
+            Kind::Bool => ["on", "off", "true", "false", "yes", "no", "1", "0"].contains(&value.as_str()),
+            Kind::Int => value.parse::<i64>().is_ok(),
+            Kind::Count => value.parse::<u64>().is_ok(),
+            Kind::OneOf(values) => values.contains(&value.as_str()),
+        };
+        if !ok {
+            return Err(AppError::InvalidRequest(format!(
+                "invalid value for pragma {name}: {value}"
+            )));
+        }
+        Ok(Self { name, value })
+    }
+
+    /// From a JSON payload value: booleans become ON/OFF, numbers and strings are taken as is.
+    pub fn from_json(name: &str, value: &serde_json::Value) -> AppResult<Self> {
+        match value {
+            serde_json::Value::Bool(b) => Self::new(name, if *b { "on" } else { "off" }),
+            serde_json::Value::Number(n) => Self::new(name, &n.to_string()),
+            serde_json::Value::String(s) => Self::new(name, s),
+            _ => Err(AppError::InvalidRequest(format!(
+                "invalid value for pragma {name}: expected a boolean, number or string"
+            ))),
+        }
+    }
+}
+
+/// `name=value`, as given to `--pragma`.
+impl FromStr for PragmaSetting {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> AppResult<Self> {
+        let (name, value) = s
+            .split_once('=')
+            .ok_or_else(|| AppError::InvalidRequest(format!("expected NAME=VALUE, got {s}")))?;
+        Self::new(name, value)
+    }
+}
+
+/// Apply `settings` in order, so a later setting for the same pragma wins.
+pub fn apply(conn: &Connection, settings: &[PragmaSetting]) -> AppResult<()> {
+    for p in settings {
+        // Both parts were checked against `ALLOWED`, so formatting them in is safe. Stepping
+        // through the result also covers pragmas that answer with a row (`journal_mode`).
+        let mut stmt = conn.prepare(&format!("PRAGMA {} = {}", p.name, p.value))?;
+        let mut rows = stmt.raw_query();
+        while rows.next()?.is_some() {}
+    }
+    Ok(())
+}
+
+/// Current value of every allow-listed pragma (and `journal_mode`), by name; `null` where the
+/// connection has none (e.g. `mmap_size` of an in-memory database).
+pub fn current(conn: &Connection) -> AppResult<serde_json::Map<String, serde_json::Value>> {
+    let mut out = serde_json::Map::new();
+    for name in ALLOWED.iter().map(|(n, _)| *n).chain(REPORTED) {
+        let value = conn.query_row(&format!("PRAGMA {name}"), [], |r| {
+            Ok(match r.get_ref(0)? {
+                ValueRef::Integer(i) => serde_json::Value::from(i),
+                ValueRef::Text(t) => serde_json::Value::from(String::from_utf8_lossy(t).into_owned()),
+                _ => serde_json::Value::Null,
+            })
+        });
+        out.insert(name.to_string(), value.optional()?.unwrap_or_default());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_validated_applied_and_reported() {
+        assert!("foreign_keys=ON".parse::<PragmaSetting>().is_ok());
+        let unsafe_or_duplicate =
+            ["synchronous=off", "trusted_schema=on", "ignore_check_constraints=1", "journal_mode=wal"];
+        for bad in ["page_size=4096", "foreign_keys=maybe", "busy_timeout=-1", "journal_mode"]
+            .into_iter()
+            .chain(unsafe_or_duplicate)
+        {
+            assert!(bad.parse::<PragmaSetting>().is_err(), "{bad}");
+        }
+
+        let conn = Connection::open_in_memory().unwrap();
+        let settings = [
+            PragmaSetting::from_json("foreign_keys", &serde_json::json!(true)).unwrap(),
+            "cache_size=-4000".parse().unwrap(),
+            "temp_store=MEMORY".parse().unwrap(),
+        ];
+        apply(&conn, &settings).unwrap();
+        let current = current(&conn).unwrap();
+        assert_eq!(current["foreign_keys"], 1);
+        assert_eq!(current["cache_size"], -4000);
+        assert_eq!(current["temp_store"], 2);
+        assert_eq!(current["journal_mode"], "memory");
+    }
+}
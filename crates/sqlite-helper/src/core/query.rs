@@ -1,134 +1,1059 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
 
-use rusqlite::{types::ValueRef, Connection, Row};
+use rusqlite::{types::ValueRef, Connection, Row, Statement, StatementStatus};
 
-use crate::core::types::{ColumnMeta, ExecResult, QueryResult};
-use crate::error::AppResult;
+use crate::core::paging::Page;
+use crate::core::params::{bind_params, QueryParams};
+use crate::core::readonly::{is_sql_readonly, starts_with_attach, starts_with_transaction_control};
+use crate::core::statements::{ensure_single_statement, next_statement, prepare_cached, total_changes};
+use crate::core::types::{
+    BatchResult, BatchStatementResult, BlobMode, ColumnMeta, DbRow, EncodeOptions, ExecResult, FloatMode,
+    IntMode, QueryFrame, QueryResult, StatementResult,
+};
+use crate::error::{AppError, AppResult};
 
 pub fn run_query(
     conn: &Connection,
     sql: &str,
+    params: Option<&QueryParams>,
     limit: usize,
-    offset: Option<usize>,
+    page: &Page,
+    encode: EncodeOptions,
 ) -> AppResult<QueryResult> {
-    // v0: implement offset by wrapping query if provided. This avoids relying on client SQL edits,
-    // but still keeps things simple. For complex queries, user should provide LIMIT/OFFSET in SQL.
-    let effective_sql = if let Some(off) = offset {
-        format!("SELECT * FROM ({sql}) LIMIT {limit} OFFSET {off}")
-    } else {
-        sql.to_string()
+    let mut result = QueryResult {
+        columns: Vec::new(),
+        rows: Vec::new(),
+        truncated: false,
+        next_offset: None,
+        next_cursor: None,
+        duration_ms: 0.0,
+        rows_scanned: 0,
+        elapsed_ms: None,
     };
+    stream_query(conn, sql, params, limit, page, encode, &mut |frame| {
+        match frame {
+            QueryFrame::Columns(columns) => result.columns = columns,
+            QueryFrame::Row(row) => result.rows.push(row),
+            QueryFrame::End {
+                truncated,
+                next_offset,
+                next_cursor,
+                duration_ms,
+                rows_scanned,
+                column_types,
+            } => {
+                for (column, sqlite_type) in result.columns.iter_mut().zip(column_types) {
+                    column.sqlite_type = Some(sqlite_type);
+                }
+                result.truncated = truncated;
+                result.next_offset = next_offset;
+                result.next_cursor = next_cursor;
+                result.duration_ms = duration_ms;
+                result.rows_scanned = rows_scanned;
+            }
+        }
+        true
+    })?;
+    Ok(result)
+}
 
-    let mut stmt = conn.prepare(&effective_sql)?;
-    let col_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+/// Run a query, handing each frame to `sink` as soon as it is produced instead of buffering rows.
+///
+/// Frames arrive as `Columns`, then zero or more `Row`, then `End`. If `sink` returns `false`
+/// (the consumer went away) the query stops early without emitting `End`.
+pub fn stream_query(
+    conn: &Connection,
+    sql: &str,
+    params: Option<&QueryParams>,
+    limit: usize,
+    page: &Page,
+    encode: EncodeOptions,
+    sink: &mut dyn FnMut(QueryFrame) -> bool,
+) -> AppResult<()> {
+    // The SQL is run as given (no `SELECT * FROM (...)` wrapping, which breaks PRAGMA, CTEs and
+    // trailing `;`); `offset` is applied by stepping past the first rows of the cursor. Only
+    // keyset paging, which needs the `WHERE`/`ORDER BY`, wraps the statement.
+    ensure_single_statement(conn, sql)?;
+    let keyset = match page {
+        Page::Keyset(keyset) => Some(keyset),
+        _ => None,
+    };
+    let wrapped = match keyset {
+        Some(keyset) => next_statement(conn, sql)?.map(|(stmt_sql, _)| keyset.wrap(stmt_sql)),
+        None => None,
+    };
+    // Only time spent in SQLite is counted; row encoding and the sink are excluded.
+    let started = Instant::now();
+    let mut stmt = match &wrapped {
+        Some(wrapped) => prepare_cached(conn, wrapped).map_err(AppError::without_error_offset)?,
+        None => prepare_cached(conn, sql)?,
+    };
+    // Status counters of a cached statement accumulate across runs.
+    stmt.reset_status(StatementStatus::FullscanStep);
+    let mut sqlite_time = started.elapsed();
+    // Row objects are keyed by column name, so duplicate names (`SELECT a.id, b.id`) get a
+    // suffix (`id`, `id_1`) instead of overwriting each other. `columns` carries the same keys.
+    let col_names = unique_column_names(stmt.column_names());
 
-    let mut columns = Vec::with_capacity(col_names.len());
-    for (i, name) in col_names.iter().enumerate() {
-        let decl_type = stmt.column_decltype(i).map(|s| s.to_string());
-        columns.push(ColumnMeta {
+    let columns: Vec<ColumnMeta> = stmt
+        .columns()
+        .iter()
+        .zip(&col_names)
+        .map(|(c, name)| ColumnMeta {
             name: name.clone(),
-            decl_type,
-            sqlite_type: decl_type.clone(),
-        });
+            decl_type: c.decl_type().map(|s| s.to_string()),
+            // `sqlite_type` is only known once rows have been seen; reported in `End`.
+            ..ColumnMeta::default()
+        })
+        .collect();
+    let mut column_types: Vec<Option<&'static str>> = vec![None; col_names.len()];
+    // Index of the key column and the key of the last emitted row, in keyset mode.
+    let key_index = match keyset {
+        Some(keyset) => Some(stmt.column_index(&keyset.key)?),
+        None => None,
+    };
+    let mut last_key = keyset.and_then(|k| k.after);
+
+    bind_params(&mut stmt, params)?;
+    if !sink(QueryFrame::Columns(columns)) {
+        return Ok(());
     }
 
-    let mut rows = Vec::new();
+    let mut emitted = 0;
     let mut truncated = false;
     let mut next_offset = None;
+    let mut next_cursor = None;
+    let offset = match page {
+        Page::Offset(n) => *n,
+        _ => 0,
+    };
 
-    let mut r = stmt.query([])?;
-    while let Some(row) = r.next()? {
-        if rows.len() >= limit {
+    let mut r = stmt.raw_query();
+    let step_started = Instant::now();
+    for _ in 0..offset {
+        if r.next()?.is_none() {
+            break;
+        }
+    }
+    sqlite_time += step_started.elapsed();
+    loop {
+        let step_started = Instant::now();
+        let row = r.next()?;
+        sqlite_time += step_started.elapsed();
+        let Some(row) = row else { break };
+        if emitted >= limit {
             truncated = true;
-            next_offset = Some(offset.unwrap_or(0) + rows.len());
+            match keyset {
+                Some(keyset) => next_cursor = last_key.map(|last| keyset.next_token(last)),
+                None => next_offset = Some(offset + emitted),
+            }
             break;
         }
+        if let (Some(keyset), Some(i)) = (keyset, key_index) {
+            last_key = Some(keyset_value(row, i, &keyset.key, last_key)?);
+        }
 
-        rows.push(row_to_json_object(row, &col_names)?);
+        for (i, seen) in column_types.iter_mut().enumerate() {
+            if seen.is_none() {
+                *seen = storage_class(row.get_ref(i)?);
+            }
+        }
+        if !sink(QueryFrame::Row(row_to_json_object(row, &col_names, encode)?)) {
+            return Ok(());
+        }
+        emitted += 1;
     }
+    drop(r);
 
-    Ok(QueryResult {
-        columns,
-        rows,
+    sink(QueryFrame::End {
         truncated,
         next_offset,
-    })
+        next_cursor,
+        duration_ms: as_millis_f64(sqlite_time),
+        rows_scanned: fullscan_steps(&stmt),
+        column_types: column_types
+            .into_iter()
+            .map(|t| t.unwrap_or("null").to_string())
+            .collect(),
+    });
+    Ok(())
 }
 
-pub fn run_execute(conn: &Connection, sql: &str) -> AppResult<ExecResult> {
-    let changes = conn.execute(sql, [])?;
+/// Run each statement of a script of read-only queries, one result set per statement.
+///
+/// Every statement is checked before the first one runs, so a script containing a write fails
+/// with `NotReadonly` and no side effects. `limit` applies to each result set separately.
+pub fn run_query_multi(
+    conn: &Connection,
+    sql: &str,
+    limit: usize,
+    encode: EncodeOptions,
+) -> AppResult<Vec<StatementResult>> {
+    // Byte offset of each statement in `sql`, so error offsets point into the whole script.
+    let offset_of = |stmt: &str| stmt.as_ptr() as usize - sql.as_ptr() as usize;
+    let mut statements = Vec::new();
+    let mut rest = sql;
+    loop {
+        if starts_with_attach(rest) {
+            return Err(AppError::NotReadonly);
+        }
+        let Some((stmt_sql, tail)) =
+            next_statement(conn, rest).map_err(|e| e.shift_error_offset(sql.len() - rest.len()))?
+        else {
+            break;
+        };
+        if !is_sql_readonly(conn, stmt_sql).map_err(|e| e.shift_error_offset(offset_of(stmt_sql)))? {
+            return Err(AppError::NotReadonly);
+        }
+        statements.push(stmt_sql);
+        rest = tail;
+    }
+    if statements.is_empty() {
+        return Err(AppError::InvalidRequest("empty SQL".into()));
+    }
+    statements
+        .into_iter()
+        .enumerate()
+        .map(|(index, stmt_sql)| {
+            let result = run_query(conn, stmt_sql, None, limit, &Page::Start, encode)
+                .map_err(|e| e.shift_error_offset(offset_of(stmt_sql)))?;
+            Ok(StatementResult {
+                index,
+                sql: stmt_sql.to_string(),
+                result,
+            })
+        })
+        .collect()
+}
+
+/// Run one write statement. Rows produced by a `RETURNING` clause are collected into `rows`.
+pub fn run_execute(
+    conn: &Connection,
+    sql: &str,
+    params: Option<&QueryParams>,
+    encode: EncodeOptions,
+) -> AppResult<ExecResult> {
+    ensure_single_statement(conn, sql)?;
+    let started = Instant::now();
+    let mut stmt = prepare_cached(conn, sql)?;
+    stmt.reset_status(StatementStatus::FullscanStep);
+    bind_params(&mut stmt, params)?;
+    let rowid_before = conn.last_insert_rowid();
+    let total_before = total_changes(conn);
+
+    // `raw_execute` fails with "Execute returned results" on `RETURNING`, so step those instead.
+    // Encoding the few returned rows is cheap enough to leave inside the timed section.
+    let (changes, rows) = if stmt.column_count() > 0 {
+        let col_names = unique_column_names(stmt.column_names());
+        let mut rows = Vec::new();
+        let mut r = stmt.raw_query();
+        while let Some(row) = r.next()? {
+            rows.push(row_to_json_object(row, &col_names, encode)?);
+        }
+        // Change counts are only final once every returned row has been stepped.
+        (conn.changes(), Some(rows))
+    } else {
+        (stmt.raw_execute()? as u64, None)
+    };
+    // As in `run_batch_statement`: `changes()` keeps the count of the last INSERT, UPDATE or
+    // DELETE, so after a SELECT, DDL or PRAGMA it is stale unless this statement wrote rows.
+    let changes = if total_changes(conn) > total_before { changes } else { 0 };
+    let duration_ms = as_millis_f64(started.elapsed());
+    // `last_insert_rowid()` is connection-wide and keeps the value of the last INSERT, so only
+    // report it when this statement moved it. (Re-inserting the very same rowid, or inserting
+    // into a WITHOUT ROWID table, therefore reports none.)
     let last_id = conn.last_insert_rowid();
+    let inserted = changes > 0 && last_id != rowid_before;
     Ok(ExecResult {
-        changes: changes as u64,
-        last_insert_rowid: Some(last_id),
+        changes,
+        last_insert_rowid: inserted.then_some(last_id),
+        total_changes: total_changes(conn),
+        duration_ms,
+        rows_scanned: fullscan_steps(&stmt),
+        rows,
+        dry_run: false,
     })
 }
 
-fn row_to_json_object(row: &Row<'_>, col_names: &[String]) -> AppResult<HashMap<String, serde_json::Value>> {
-    let mut out = HashMap::with_capacity(col_names.len());
-    for (i, name) in col_names.iter().enumerate() {
-        let v = match row.get_ref(i)? {
-            ValueRef::Null => serde_json::Value::Null,
-            ValueRef::Integer(x) => serde_json::Value::from(x),
-            ValueRef::Real(x) => serde_json::Value::from(x),
-            ValueRef::Text(t) => serde_json::Value::from(String::from_utf8_lossy(t).to_string()),
-            ValueRef::Blob(b) => serde_json::json!({
-                "$type": "blob",
-                "base64": base64::encode(b),
-                "size": b.len()
-            }),
-        };
-        out.insert(name.clone(), v);
+/// Run one write statement inside a savepoint and roll it back, reporting what it would have
+/// changed. Works inside an explicit transaction too, leaving it as it was.
+///
+/// Statements SQLite does not undo (`VACUUM` fails inside a savepoint; `PRAGMA journal_mode`
+/// and similar settings take effect anyway) are not made safe by this.
+pub fn run_execute_dry_run(
+    conn: &Connection,
+    sql: &str,
+    params: Option<&QueryParams>,
+    encode: EncodeOptions,
+) -> AppResult<ExecResult> {
+    conn.execute_batch("SAVEPOINT sqliting_dry_run")?;
+    let res = run_execute(conn, sql, params, encode);
+    // Roll back even on success; a failure to do so outranks the statement's own result.
+    conn.execute_batch("ROLLBACK TO sqliting_dry_run; RELEASE sqliting_dry_run")?;
+    let mut er = res?;
+    er.dry_run = true;
+    // The rolled-back rowid was never persisted.
+    er.last_insert_rowid = None;
+    Ok(er)
+}
+
+/// The key column's value for this row, which must be an integer above the previous key; a
+/// repeated or NULL key would make the next page skip or repeat rows.
+fn keyset_value(row: &Row<'_>, i: usize, key: &str, prev: Option<i64>) -> AppResult<i64> {
+    match row.get_ref(i)? {
+        ValueRef::Integer(v) if prev.is_none_or(|p| v > p) => Ok(v),
+        ValueRef::Integer(_) => Err(AppError::InvalidRequest(format!(
+            "cursor key column {key} has duplicate values; use a unique key such as the primary key"
+        ))),
+        other => Err(AppError::InvalidRequest(format!(
+            "cursor key column {key} must hold integers, got {}",
+            storage_class(other).unwrap_or("null")
+        ))),
     }
-    Ok(out)
 }
 
-// NOTE: base64 is used for BLOB encoding. Keep it minimal.
-mod base64 {
-    pub fn encode(bytes: &[u8]) -> String {
-        base64_simd::STANDARD.encode_to_string(bytes)
-    }
-}
-
-mod base64_simd {
-    pub use base64_simd_impl::*;
-    mod base64_simd_impl {
-        // Minimal embedded base64 encoder to avoid adding dependency right now.
-        // This is intentionally simple and not optimized; can be replaced by `base64` crate later.
-        const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
-
-        pub struct Standard;
-        pub const STANDARD: Standard = Standard;
-
-        impl Standard {
-            pub fn encode_to_string(&self, input: &[u8]) -> String {
-                let mut out = String::with_capacity(((input.len() + 2) / 3) * 4);
-                let mut i = 0;
-                while i + 3 <= input.len() {
-                    let n = ((input[i] as u32) << 16) | ((input[i + 1] as u32) << 8) | (input[i + 2] as u32);
-                    out.push(TABLE[((n >> 18) & 63) as usize] as char);
-                    out.push(TABLE[((n >> 12) & 63) as usize] as char);
-                    out.push(TABLE[((n >> 6) & 63) as usize] as char);
-                    out.push(TABLE[(n & 63) as usize] as char);
-                    i += 3;
+/// Storage class of a value as `typeof()` reports it; `None` for NULL so a later row can decide.
+fn storage_class(v: ValueRef<'_>) -> Option<&'static str> {
+    match v {
+        ValueRef::Null => None,
+        ValueRef::Integer(_) => Some("integer"),
+        ValueRef::Real(_) => Some("real"),
+        ValueRef::Text(_) => Some("text"),
+        ValueRef::Blob(_) => Some("blob"),
+    }
+}
+
+/// Milliseconds with microsecond resolution (avoids float noise like `0.031149000000000003`).
+fn as_millis_f64(d: Duration) -> f64 {
+    d.as_micros() as f64 / 1000.0
+}
+
+fn fullscan_steps(stmt: &Statement<'_>) -> u64 {
+    stmt.get_status(StatementStatus::FullscanStep).max(0) as u64
+}
+
+/// Run one or more multi-statement scripts atomically, one statement at a time.
+///
+/// The scripts run inside a single savepoint (so they also nest inside an open transaction);
+/// the first failing statement rolls everything back and is reported with its error.
+/// Transaction control (`BEGIN`, `COMMIT`, `ROLLBACK`, `SAVEPOINT`, ...) fails like an error,
+/// since it would end or escape that savepoint.
+pub fn run_batch(conn: &Connection, scripts: &[String]) -> AppResult<BatchResult> {
+    let started = Instant::now();
+    let rowid_before = conn.last_insert_rowid();
+    let owns_transaction = conn.is_autocommit();
+    conn.execute_batch("SAVEPOINT sqliting_batch")?;
+    let mut result = BatchResult {
+        statements: Vec::new(),
+        committed: false,
+        failed_index: None,
+        total: ExecResult {
+            changes: 0,
+            last_insert_rowid: None,
+            total_changes: 0,
+            duration_ms: 0.0,
+            rows_scanned: 0,
+            rows: None,
+            dry_run: false,
+        },
+    };
+
+    'scripts: for script in scripts {
+        let mut rest = script.as_str();
+        loop {
+            let (stmt_sql, tail) = match next_statement(conn, rest) {
+                Ok(Some(next)) => next,
+                Ok(None) => break,
+                Err(e) => {
+                    // The remaining text does not compile; report it as the failing statement.
+                    result.statements.push(BatchStatementResult {
+                        sql: rest.trim().to_string(),
+                        changes: 0,
+                        error: Some(e.to_string()),
+                    });
+                    break 'scripts;
+                }
+            };
+            let ran = if starts_with_transaction_control(stmt_sql) {
+                Err(AppError::InvalidRequest(
+                    "transaction control is not allowed in a batch, which runs in its own savepoint; \
+                     use begin/commit/rollback around it instead"
+                        .into(),
+                ))
+            } else {
+                run_batch_statement(conn, stmt_sql)
+            };
+            match ran {
+                Ok((changes, rows_scanned)) => {
+                    result.total.changes += changes;
+                    result.total.rows_scanned += rows_scanned;
+                    result.statements.push(BatchStatementResult {
+                        sql: stmt_sql.to_string(),
+                        changes,
+                        error: None,
+                    });
                 }
-                let rem = input.len() - i;
-                if rem == 1 {
-                    let n = (input[i] as u32) << 16;
-                    out.push(TABLE[((n >> 18) & 63) as usize] as char);
-                    out.push(TABLE[((n >> 12) & 63) as usize] as char);
-                    out.push('=');
-                    out.push('=');
-                } else if rem == 2 {
-                    let n = ((input[i] as u32) << 16) | ((input[i + 1] as u32) << 8);
-                    out.push(TABLE[((n >> 18) & 63) as usize] as char);
-                    out.push(TABLE[((n >> 12) & 63) as usize] as char);
-                    out.push(TABLE[((n >> 6) & 63) as usize] as char);
-                    out.push('=');
+                Err(e) => {
+                    result.statements.push(BatchStatementResult {
+                        sql: stmt_sql.to_string(),
+                        changes: 0,
+                        error: Some(e.to_string()),
+                    });
+                    break 'scripts;
                 }
-                out
             }
+            rest = tail;
+        }
+    }
+
+    if result.statements.last().is_some_and(|s| s.error.is_some()) {
+        result.failed_index = Some(result.statements.len() - 1);
+        conn.execute_batch("ROLLBACK TO sqliting_batch; RELEASE sqliting_batch")?;
+        result.total.changes = 0;
+    } else {
+        if let Err(e) = conn.execute_batch("RELEASE sqliting_batch") {
+            // Outside a transaction RELEASE commits, which can be refused (SQLITE_BUSY). Undo
+            // the batch so the connection is back in autocommit and a retry starts clean.
+            let undo = match owns_transaction {
+                true => "ROLLBACK",
+                false => "ROLLBACK TO sqliting_batch; RELEASE sqliting_batch",
+            };
+            let _ = conn.execute_batch(undo);
+            return Err(e.into());
+        }
+        result.committed = true;
+        // Same rule as `run_execute`: only report a rowid the batch itself produced.
+        let last_id = conn.last_insert_rowid();
+        result.total.last_insert_rowid = (last_id != rowid_before).then_some(last_id);
+    }
+    result.total.total_changes = total_changes(conn);
+    result.total.duration_ms = as_millis_f64(started.elapsed());
+    Ok(result)
+}
+
+/// Returns the statement's `(changes, rows_scanned)`.
+fn run_batch_statement(conn: &Connection, sql: &str) -> AppResult<(u64, u64)> {
+    let mut stmt = conn.prepare(sql)?;
+    let before = total_changes(conn);
+    // Step through any rows (e.g. `PRAGMA journal_mode = WAL`) instead of failing like `execute`.
+    let mut rows = stmt.raw_query();
+    while rows.next()?.is_some() {}
+    drop(rows);
+    // `changes()` is stale after DDL/PRAGMA, while the total also counts trigger writes;
+    // only trust `changes()` when this statement actually modified rows.
+    let changes = if total_changes(conn) > before { conn.changes() } else { 0 };
+    Ok((changes, fullscan_steps(&stmt)))
+}
+
+fn unique_column_names(names: Vec<&str>) -> Vec<String> {
+    let taken: HashSet<&str> = names.iter().copied().collect();
+    let mut used: HashSet<String> = HashSet::with_capacity(names.len());
+    let mut out = Vec::with_capacity(names.len());
+    for name in names {
+        let mut candidate = name.to_string();
+        let mut n = 1;
+        while used.contains(&candidate) || (candidate != name && taken.contains(candidate.as_str())) {
+            candidate = format!("{name}_{n}");
+            n += 1;
+        }
+        used.insert(candidate.clone());
+        out.push(candidate);
+    }
+    out
+}
+
+fn row_to_json_object(row: &Row<'_>, col_names: &[String], encode: EncodeOptions) -> AppResult<DbRow> {
+    // serde_json is built with `preserve_order`, so keys keep SELECT-list order.
+    let mut out = DbRow::with_capacity(col_names.len());
+    for (i, name) in col_names.iter().enumerate() {
+        out.insert(name.clone(), value_to_json(row.get_ref(i)?, encode));
+    }
+    Ok(out)
+}
+
+fn value_to_json(v: ValueRef<'_>, encode: EncodeOptions) -> serde_json::Value {
+    match v {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(x) => int_to_json(x, encode.int_mode),
+        ValueRef::Real(x) => real_to_json(x, encode.float_mode),
+        ValueRef::Text(t) => serde_json::Value::from(String::from_utf8_lossy(t).to_string()),
+        ValueRef::Blob(b) => blob_to_json(b, encode),
+    }
+}
+
+fn blob_to_json(b: &[u8], encode: EncodeOptions) -> serde_json::Value {
+    if encode.blob_mode == BlobMode::Full || b.len() <= encode.max_blob_bytes {
+        return serde_json::json!({
+            "$type": "blob",
+            "base64": base64::encode(b),
+            "size": b.len()
+        });
+    }
+    match encode.blob_mode {
+        BlobMode::Metadata => serde_json::json!({
+            "$type": "blob",
+            "size": b.len(),
+            "truncated": true
+        }),
+        _ => serde_json::json!({
+            "$type": "blob",
+            "base64": base64::encode(&b[..encode.max_blob_bytes]),
+            "size": b.len(),
+            "truncated": true
+        }),
+    }
+}
+
+/// Largest integer magnitude a JavaScript number represents exactly (2^53 - 1).
+const JS_MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+fn int_to_json(x: i64, mode: IntMode) -> serde_json::Value {
+    if mode == IntMode::Number || (-JS_MAX_SAFE_INTEGER..=JS_MAX_SAFE_INTEGER).contains(&x) {
+        return serde_json::Value::from(x);
+    }
+    match mode {
+        IntMode::String => serde_json::Value::from(x.to_string()),
+        _ => serde_json::json!({ "$type": "int64", "value": x.to_string() }),
+    }
+}
+
+fn real_to_json(x: f64, mode: FloatMode) -> serde_json::Value {
+    if x.is_finite() {
+        return serde_json::Value::from(x);
+    }
+    match mode {
+        FloatMode::Null => serde_json::Value::Null,
+        FloatMode::Sentinel => {
+            let value = if x.is_nan() {
+                "NaN"
+            } else if x > 0.0 {
+                "Infinity"
+            } else {
+                "-Infinity"
+            };
+            serde_json::json!({ "$type": "float", "value": value })
         }
     }
 }
 
+// NOTE: base64 is used for BLOB encoding (results) and decoding (bound parameters).
+pub(crate) mod base64 {
+    use ::base64::{engine::general_purpose::STANDARD, Engine};
+
+    use super::AppError;
+
+    pub fn encode(bytes: &[u8]) -> String {
+        STANDARD.encode(bytes)
+    }
+
+    pub fn decode(input: &str) -> Result<Vec<u8>, AppError> {
+        STANDARD
+            .decode(input)
+            .map_err(|e| AppError::InvalidRequest(format!("invalid base64 in blob value: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::params::ParamsInput;
+
+    #[test]
+    fn cached_statements_rebind_and_report_their_own_scan_counts() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (a, b); INSERT INTO t VALUES (1, 'x'), (2, 'y'), (3, 'z');")
+            .unwrap();
+        let sql = "SELECT b FROM t WHERE a = ?";
+        for (a, b) in [(1, "x"), (3, "z")] {
+            let params = QueryParams::from_input(Some(ParamsInput::Positional(vec![a.into()])), None);
+            let qr = run_query(&conn, sql, params.as_ref(), 10, &Page::Start, EncodeOptions::default()).unwrap();
+            assert_eq!(qr.rows[0]["b"], b);
+            // Counted per run, not accumulated on the cached statement.
+            assert_eq!(qr.rows_scanned, 2);
+        }
+    }
+
+    #[test]
+    fn multi_statement_reads_return_one_result_per_statement() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1), (2), (3);").unwrap();
+        let encode = EncodeOptions::default();
+
+        let script = "SELECT x FROM t WHERE x > 1; -- a ; comment\nSELECT ';' AS s;\n\nSELECT count(*) AS n FROM t";
+        let results = run_query_multi(&conn, script, 1, encode).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!((results[0].index, results[0].result.truncated), (0, true));
+        assert_eq!(results[1].sql, "-- a ; comment\nSELECT ';' AS s;");
+        assert_eq!(results[1].result.rows[0]["s"], ";");
+        assert_eq!(results[2].result.rows[0]["n"], 3);
+
+        // A write anywhere fails the whole script before anything runs.
+        let err = run_query_multi(&conn, "SELECT 1; DELETE FROM t; SELECT 2", 10, encode).unwrap_err();
+        assert!(matches!(err, AppError::NotReadonly));
+        assert_eq!(conn.query_row("SELECT count(*) FROM t", [], |r| r.get::<_, i64>(0)).unwrap(), 3);
+
+        // Errors in later statements point into the whole script.
+        let err = run_query_multi(&conn, "SELECT 1; SELECT nope FROM t", 10, encode).unwrap_err();
+        assert_eq!(err.details().and_then(|d| d.get("error_offset").cloned()), Some(17.into()));
+        assert!(run_query_multi(&conn, " ; -- nothing", 10, encode).is_err());
+    }
+
+    #[test]
+    fn blob_round_trips_through_execute_and_query() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE b (data BLOB)").unwrap();
+
+        // RFC 4648 test vector: "foobar" -> "Zm9vYmFy".
+        let blob = serde_json::json!({ "$type": "blob", "base64": "Zm9vYmFy" });
+        let params = QueryParams::from_input(Some(ParamsInput::Positional(vec![blob])), None);
+        run_execute(
+            &conn,
+            "INSERT INTO b (data) VALUES (?)",
+            params.as_ref(),
+            EncodeOptions::default(),
+        )
+        .unwrap();
+
+        let stored: Vec<u8> = conn.query_row("SELECT data FROM b", [], |r| r.get(0)).unwrap();
+        assert_eq!(stored, b"foobar");
+
+        let encode = EncodeOptions::default();
+        let qr = run_query(&conn, "SELECT data FROM b", None, 10, &Page::Start, encode).unwrap();
+        assert_eq!(qr.rows[0]["data"]["base64"], "Zm9vYmFy");
+        assert_eq!(qr.rows[0]["data"]["size"], 6);
+    }
+
+    #[test]
+    fn last_insert_rowid_is_only_reported_for_inserts() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (x)").unwrap();
+        let enc = EncodeOptions::default();
+
+        let ins = run_execute(&conn, "INSERT INTO t (x) VALUES (1), (2)", None, enc).unwrap();
+        assert_eq!(ins.last_insert_rowid, Some(2));
+        assert_eq!(ins.total_changes, 2);
+
+        // Same connection: the rowid left over from the INSERT must not leak into these.
+        let upd = run_execute(&conn, "UPDATE t SET x = x + 1", None, enc).unwrap();
+        assert_eq!(upd.changes, 2);
+        assert_eq!(upd.last_insert_rowid, None);
+        assert_eq!(upd.total_changes, 4);
+
+        let del = run_execute(&conn, "DELETE FROM t WHERE rowid = 1", None, enc).unwrap();
+        assert_eq!(del.last_insert_rowid, None);
+        let noop = run_execute(&conn, "INSERT INTO t SELECT x FROM t WHERE 0", None, enc).unwrap();
+        assert_eq!(noop.last_insert_rowid, None);
+
+        let json = serde_json::to_value(&upd).unwrap();
+        assert!(json.get("last_insert_rowid").is_none());
+
+        let again = run_execute(&conn, "INSERT INTO t (x) VALUES (9)", None, enc).unwrap();
+        assert_eq!(again.last_insert_rowid, Some(3));
+    }
+
+    #[test]
+    fn non_finite_reals_are_distinguishable_from_null() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE f (v REAL);
+             INSERT INTO f VALUES (1e999), (-1e999), (NULL), (1.5);",
+        )
+        .unwrap();
+
+        let sql = "SELECT v FROM f ORDER BY rowid";
+        let qr = run_query(&conn, sql, None, 10, &Page::Start, EncodeOptions::default()).unwrap();
+        let cells: Vec<_> = qr.rows.iter().map(|r| r["v"].clone()).collect();
+        assert_eq!(cells[0], serde_json::json!({ "$type": "float", "value": "Infinity" }));
+        assert_eq!(cells[1], serde_json::json!({ "$type": "float", "value": "-Infinity" }));
+        assert_eq!(cells[2], serde_json::Value::Null);
+        assert_eq!(cells[3], serde_json::json!(1.5));
+
+        // SQLite stores NaN as NULL, so cover it at the value-mapping level.
+        assert_eq!(
+            real_to_json(f64::NAN, FloatMode::Sentinel),
+            serde_json::json!({ "$type": "float", "value": "NaN" })
+        );
+
+        let lossy = EncodeOptions {
+            float_mode: FloatMode::Null,
+            ..EncodeOptions::default()
+        };
+        let qr = run_query(&conn, sql, None, 10, &Page::Start, lossy).unwrap();
+        assert!(qr.rows[..3].iter().all(|r| r["v"].is_null()));
+        assert_eq!(real_to_json(f64::NAN, FloatMode::Null), serde_json::Value::Null);
+    }
+
+    #[test]
+    fn large_integers_survive_lossless_modes() {
+        let conn = Connection::open_in_memory().unwrap();
+        let sql = "SELECT -9223372036854775808 AS min, 9223372036854775807 AS max, 9007199254740991 AS safe";
+
+        let qr = run_query(&conn, sql, None, 10, &Page::Start, EncodeOptions::default()).unwrap();
+        assert_eq!(qr.rows[0]["max"], serde_json::json!(i64::MAX));
+
+        let string = EncodeOptions {
+            int_mode: IntMode::String,
+            ..EncodeOptions::default()
+        };
+        let qr = run_query(&conn, sql, None, 10, &Page::Start, string).unwrap();
+        assert_eq!(qr.rows[0]["min"], "-9223372036854775808");
+        assert_eq!(qr.rows[0]["max"], "9223372036854775807");
+        assert_eq!(qr.rows[0]["safe"], serde_json::json!(9007199254740991_i64));
+
+        let lossless = EncodeOptions {
+            int_mode: IntMode::Lossless,
+            ..EncodeOptions::default()
+        };
+        let qr = run_query(&conn, sql, None, 10, &Page::Start, lossless).unwrap();
+        assert_eq!(
+            qr.rows[0]["min"],
+            serde_json::json!({ "$type": "int64", "value": i64::MIN.to_string() })
+        );
+        assert_eq!(
+            qr.rows[0]["max"],
+            serde_json::json!({ "$type": "int64", "value": i64::MAX.to_string() })
+        );
+        assert_eq!(qr.rows[0]["safe"], serde_json::json!(9007199254740991_i64));
+    }
+
+    #[test]
+    fn blobs_over_the_limit_are_truncated() {
+        let conn = Connection::open_in_memory().unwrap();
+        let sql = "SELECT x'' AS empty, x'0102' AS exact, x'010203' AS over";
+        let limited = EncodeOptions {
+            max_blob_bytes: 2,
+            ..EncodeOptions::default()
+        };
+
+        let qr = run_query(&conn, sql, None, 10, &Page::Start, limited).unwrap();
+        let row = &qr.rows[0];
+        assert_eq!(row["empty"], serde_json::json!({ "$type": "blob", "base64": "", "size": 0 }));
+        assert_eq!(row["exact"], serde_json::json!({ "$type": "blob", "base64": "AQI=", "size": 2 }));
+        assert_eq!(
+            row["over"],
+            serde_json::json!({ "$type": "blob", "base64": "AQI=", "size": 3, "truncated": true })
+        );
+
+        let metadata = EncodeOptions {
+            blob_mode: BlobMode::Metadata,
+            ..limited
+        };
+        let qr = run_query(&conn, sql, None, 10, &Page::Start, metadata).unwrap();
+        assert_eq!(qr.rows[0]["exact"]["base64"], "AQI=");
+        assert_eq!(
+            qr.rows[0]["over"],
+            serde_json::json!({ "$type": "blob", "size": 3, "truncated": true })
+        );
+
+        let full = EncodeOptions {
+            blob_mode: BlobMode::Full,
+            ..limited
+        };
+        let qr = run_query(&conn, sql, None, 10, &Page::Start, full).unwrap();
+        assert_eq!(qr.rows[0]["over"], serde_json::json!({ "$type": "blob", "base64": "AQID", "size": 3 }));
+    }
+
+    #[test]
+    fn sqlite_type_reports_the_runtime_storage_class() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (n NUMERIC, v);
+             INSERT INTO t VALUES (NULL, 'x'), (1.5, 2);",
+        )
+        .unwrap();
+
+        let sql = "SELECT n, v, n * 2 AS expr, NULL AS empty FROM t";
+        let qr = run_query(&conn, sql, None, 10, &Page::Start, EncodeOptions::default()).unwrap();
+        let types: Vec<_> = qr.columns.iter().map(|c| c.sqlite_type.as_deref()).collect();
+        assert_eq!(types, [Some("real"), Some("text"), Some("real"), Some("null")]);
+        assert_eq!(qr.columns[0].decl_type.as_deref(), Some("NUMERIC"));
+        assert_eq!(qr.columns[2].decl_type, None);
+    }
+
+    #[test]
+    fn batch_scripts_share_one_transaction() {
+        let conn = Connection::open_in_memory().unwrap();
+        let scripts = vec![
+            "CREATE TABLE t (x); INSERT INTO t VALUES (1), (2);".to_string(),
+            "UPDATE t SET x = x + 1".to_string(),
+        ];
+        let br = run_batch(&conn, &scripts).unwrap();
+        assert!(br.committed);
+        let changes: Vec<_> = br.statements.iter().map(|s| s.changes).collect();
+        assert_eq!(changes, [0, 2, 2]);
+        assert_eq!(br.total.changes, 4);
+        assert_eq!(br.total.last_insert_rowid, Some(2));
+
+        // A failure in a later script also undoes the earlier ones.
+        let scripts = vec!["DELETE FROM t".to_string(), "INSERT INTO nope VALUES (1)".to_string()];
+        let br = run_batch(&conn, &scripts).unwrap();
+        assert!(!br.committed);
+        assert_eq!(br.failed_index, Some(1));
+        assert_eq!(br.total.changes, 0);
+        let n: i64 = conn.query_row("SELECT count(*) FROM t", [], |r| r.get(0)).unwrap();
+        assert_eq!(n, 2);
+
+        // A COMMIT would end the batch's savepoint and keep the writes before a later failure.
+        let scripts = vec!["DELETE FROM t; COMMIT;".to_string(), "INSERT INTO nope VALUES (1)".to_string()];
+        let br = run_batch(&conn, &scripts).unwrap();
+        assert_eq!((br.committed, br.failed_index), (false, Some(1)));
+        assert!(br.statements[1].error.as_deref().unwrap().contains("transaction control"));
+        let n: i64 = conn.query_row("SELECT count(*) FROM t", [], |r| r.get(0)).unwrap();
+        assert_eq!(n, 2);
+        assert!(conn.is_autocommit());
+    }
+
+    #[test]
+    fn batch_whose_commit_is_refused_leaves_nothing_behind() {
+        let path = std::env::temp_dir().join(format!("sqlite-helper-batch-busy-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let writer = Connection::open(&path).unwrap();
+        writer.busy_timeout(Duration::ZERO).unwrap();
+        writer.execute_batch("CREATE TABLE t (x)").unwrap();
+        // A reader mid-transaction keeps its shared lock, so the writer cannot commit.
+        let reader = Connection::open(&path).unwrap();
+        reader.execute_batch("BEGIN; SELECT * FROM t;").unwrap();
+
+        let err = run_batch(&writer, &["INSERT INTO t VALUES (1)".to_string()]).unwrap_err();
+        assert!(matches!(err, AppError::DbBusy(_)), "{err}");
+        assert!(writer.is_autocommit());
+
+        reader.execute_batch("COMMIT").unwrap();
+        assert!(run_batch(&writer, &["INSERT INTO t VALUES (1)".to_string()]).unwrap().committed);
+        let n: i64 = reader.query_row("SELECT count(*) FROM t", [], |r| r.get(0)).unwrap();
+        assert_eq!(n, 1);
+        drop((writer, reader));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn keyset_cursor_pages_by_integer_key() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, v);
+             INSERT INTO t VALUES (5, 'a'), (1, 'b'), (3, 'c'), (9, 'd'), (7, 'e');",
+        )
+        .unwrap();
+        let encode = EncodeOptions::default();
+        let sql = "SELECT id, v FROM t WHERE id > ? -- comment\n;";
+        let params = QueryParams::from_input(Some(ParamsInput::Positional(vec![1.into()])), None);
+
+        let mut ids = Vec::new();
+        let mut page = Page::from_request(sql, None, Some("id".into()), None).unwrap();
+        loop {
+            let qr = run_query(&conn, sql, params.as_ref(), 2, &page, encode).unwrap();
+            ids.extend(qr.rows.iter().map(|r| r["id"].as_i64().unwrap()));
+            assert_eq!(qr.next_offset, None);
+            let Some(cursor) = qr.next_cursor else { break };
+            page = Page::from_request(sql, None, None, Some(&cursor)).unwrap();
+        }
+        assert_eq!(ids, [3, 5, 7, 9]);
+
+        // Cursors are tied to their query, and keys must be unique integers.
+        let Page::Keyset(keyset) = page else { panic!("expected keyset paging") };
+        let token = keyset.next_token(3);
+        assert!(Page::from_request("SELECT * FROM t", None, None, Some(&token)).is_err());
+        let page = Page::from_request("SELECT v FROM t", None, Some("v".into()), None).unwrap();
+        let err = run_query(&conn, "SELECT v FROM t", None, 10, &page, encode).unwrap_err();
+        assert!(matches!(err, AppError::InvalidRequest(m) if m.contains("integers")));
+    }
+
+    #[test]
+    fn dry_run_reports_changes_without_persisting() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1), (2);").unwrap();
+        let encode = EncodeOptions::default();
+        let count = |conn: &Connection| {
+            conn.query_row("SELECT count(*) FROM t", [], |r| r.get::<_, i64>(0))
+                .unwrap()
+        };
+
+        let er = run_execute_dry_run(&conn, "DELETE FROM t", None, encode).unwrap();
+        assert_eq!(er.changes, 2);
+        assert!(er.dry_run);
+        assert_eq!(count(&conn), 2);
+
+        // Inside an explicit transaction, earlier work survives and the transaction stays open.
+        conn.execute_batch("BEGIN; INSERT INTO t VALUES (3);").unwrap();
+        run_execute_dry_run(&conn, "DROP TABLE t", None, encode).unwrap();
+        assert!(!conn.is_autocommit());
+        assert_eq!(count(&conn), 3);
+        conn.execute_batch("ROLLBACK").unwrap();
+
+        let err = run_execute_dry_run(&conn, "INSERT INTO missing VALUES (1)", None, encode).unwrap_err();
+        assert!(matches!(err, AppError::SqlError(_)));
+        assert!(conn.is_autocommit());
+    }
+
+    #[test]
+    fn offset_works_for_pragma_and_trailing_semicolon() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE p (a, b, c, d)").unwrap();
+        let encode = EncodeOptions::default();
+
+        let qr = run_query(&conn, "PRAGMA table_info(p)", None, 2, &Page::Offset(1), encode).unwrap();
+        let names: Vec<_> = qr.rows.iter().map(|r| r["name"].clone()).collect();
+        assert_eq!(names, ["b", "c"]);
+        assert!(qr.truncated);
+        assert_eq!(qr.next_offset, Some(3));
+
+        let qr = run_query(&conn, "PRAGMA table_info(p)", None, 2, &Page::Offset(3), encode).unwrap();
+        assert_eq!(qr.rows.len(), 1);
+        assert!(!qr.truncated);
+        assert_eq!(qr.next_offset, None);
+
+        let sql = "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n LIMIT 5)
+                   SELECT x FROM n;";
+        let qr = run_query(&conn, sql, None, 10, &Page::Offset(2), encode).unwrap();
+        let xs: Vec<_> = qr.rows.iter().map(|r| r["x"].clone()).collect();
+        assert_eq!(xs, [3, 4, 5]);
+
+        let qr = run_query(&conn, "SELECT 1 AS one;", None, 10, &Page::Offset(5), encode).unwrap();
+        assert!(qr.rows.is_empty());
+        assert_eq!(qr.columns[0].name, "one");
+
+        // EXPLAIN cannot be used as a subquery either.
+        let sql = "EXPLAIN SELECT a FROM p ORDER BY b";
+        let all = run_query(&conn, sql, None, 1000, &Page::Start, encode).unwrap();
+        let qr = run_query(&conn, sql, None, 1000, &Page::Offset(2), encode).unwrap();
+        assert_eq!(qr.rows.len(), all.rows.len() - 2);
+        assert_eq!(qr.rows[0]["addr"], 2);
+    }
+
+    #[test]
+    fn prepare_errors_report_the_offset_into_the_request_sql() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (x)").unwrap();
+        let encode = EncodeOptions::default();
+        let offset = |e: AppError| e.details().and_then(|d| d.get("error_offset").cloned());
+
+        let err = run_query(&conn, "SELECT x FRM t", None, 10, &Page::Start, encode).unwrap_err();
+        assert_eq!(err.code(), "SYNTAX_ERROR");
+        assert_eq!(offset(err), Some(13.into()));
+        // Offsets count from the start of the request, past empty statements and comments.
+        let err = run_query(&conn, ";; /* c */ SELECT x FRM t", None, 10, &Page::Start, encode).unwrap_err();
+        assert_eq!(offset(err), Some(24.into()));
+        let err = run_execute(&conn, "UPDATE t SET x = WHERE 1", None, encode).unwrap_err();
+        assert_eq!(offset(err), Some(17.into()));
+        let err = crate::core::readonly::is_sql_readonly(&conn, "SELECT nope FROM t").unwrap_err();
+        assert_eq!((err.code(), offset(err)), ("SQL_ERROR", Some(7.into())));
+
+        // No position to report: the field is left out.
+        let err = run_query(&conn, "SELECT (", None, 10, &Page::Start, encode).unwrap_err();
+        assert_eq!(offset(err), None);
+    }
+
+    #[test]
+    fn trailing_statements_are_rejected_not_dropped() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (x, y); INSERT INTO t VALUES (0, 0);").unwrap();
+
+        let err = run_execute(
+            &conn,
+            "UPDATE t SET x = 1; UPDATE t SET y = 2;",
+            None,
+            EncodeOptions::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(&err, AppError::InvalidRequest(m) if m.contains("UPDATE t SET y = 2")));
+        let x: i64 = conn.query_row("SELECT x FROM t", [], |r| r.get(0)).unwrap();
+        assert_eq!(x, 0, "nothing may run when trailing SQL is rejected");
+
+        let encode = EncodeOptions::default();
+        let err = run_query(&conn, "SELECT 1; SELECT 2", None, 10, &Page::Start, encode).unwrap_err();
+        assert!(matches!(err, AppError::InvalidRequest(_)));
+
+        // Semicolons inside literals and trailing comments are not statement boundaries.
+        run_execute(&conn, "UPDATE t SET y = ';' -- done;\n", None, EncodeOptions::default()).unwrap();
+        run_query(&conn, "SELECT ';' AS s; /* trailing */", None, 10, &Page::Start, encode).unwrap();
+    }
+
+    #[test]
+    fn invalid_base64_is_rejected() {
+        assert!(matches!(base64::decode("not base64!"), Err(AppError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn duplicate_column_names_are_kept_in_select_order() {
+        let conn = Connection::open_in_memory().unwrap();
+        let sql = "SELECT 2 AS b, 1 AS id, 3 AS id, 4 AS id_1, 5 AS id";
+        let qr = run_query(&conn, sql, None, 10, &Page::Start, EncodeOptions::default()).unwrap();
+        let names: Vec<&str> = qr.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["b", "id", "id_2", "id_1", "id_3"]);
+        // Row keys follow the columns, not alphabetical order, and no value is lost.
+        let keys: Vec<&str> = qr.rows[0].keys().map(String::as_str).collect();
+        assert_eq!(keys, names);
+        let values: Vec<i64> = qr.rows[0].values().map(|v| v.as_i64().unwrap()).collect();
+        assert_eq!(values, [2, 1, 3, 4, 5]);
+    }
+
+    #[test]
+    fn streamed_frames_are_columns_rows_then_end() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1), (2), (3);").unwrap();
+        let stream = |page: &Page, stop_after: usize| {
+            let mut frames = Vec::new();
+            let mut sink = |frame| {
+                frames.push(frame);
+                frames.len() < stop_after
+            };
+            let encode = EncodeOptions::default();
+            stream_query(&conn, "SELECT x FROM t", None, 2, page, encode, &mut sink).unwrap();
+            frames
+        };
+
+        let frames = stream(&Page::Start, usize::MAX);
+        assert!(matches!(&frames[0], QueryFrame::Columns(c) if c[0].name == "x"));
+        assert!(matches!(&frames[1], QueryFrame::Row(r) if r["x"] == 1));
+        assert!(matches!(&frames[2], QueryFrame::Row(r) if r["x"] == 2));
+        let QueryFrame::End { truncated, next_offset, column_types, .. } = &frames[3] else {
+            panic!("{:?}", frames[3]);
+        };
+        assert_eq!((*truncated, *next_offset), (true, Some(2)));
+        assert_eq!(column_types, &["integer"]);
+        let frames = stream(&Page::Offset(2), usize::MAX);
+        assert!(matches!(&frames[1], QueryFrame::Row(r) if r["x"] == 3));
+        assert!(matches!(&frames[2], QueryFrame::End { truncated: false, next_offset: None, .. }));
+
+        // A consumer that goes away stops the statement: no further frames, no end.
+        assert_eq!(stream(&Page::Start, 2).len(), 2);
+    }
+
+    #[test]
+    fn returning_rows_come_back_with_the_change_counts() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT, n INTEGER DEFAULT 7)")
+            .unwrap();
+        let encode = EncodeOptions::default();
+        let execute = |sql: &str| run_execute(&conn, sql, None, encode).unwrap();
+
+        let res = execute("INSERT INTO t (name) VALUES ('a'), ('b') RETURNING id, n");
+        assert_eq!((res.changes, res.last_insert_rowid), (2, Some(2)));
+        let rows = serde_json::to_value(res.rows.unwrap()).unwrap();
+        assert_eq!(rows, serde_json::json!([{ "id": 1, "n": 7 }, { "id": 2, "n": 7 }]));
+
+        let res = execute("UPDATE t SET n = n + 1 WHERE id = 2 RETURNING n");
+        assert_eq!((res.changes, res.last_insert_rowid), (1, None));
+        assert_eq!(serde_json::to_value(res.rows.unwrap()).unwrap(), serde_json::json!([{ "n": 8 }]));
+        let res = execute("DELETE FROM t WHERE id > 5 RETURNING id");
+        assert_eq!((res.changes, res.rows.unwrap().len()), (0, 0));
+        assert!(execute("DELETE FROM t").rows.is_none());
+    }
+
+    #[test]
+    fn statements_that_write_nothing_report_no_changes() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (x)").unwrap();
+        let encode = EncodeOptions::default();
+        let execute = |sql: &str| run_execute(&conn, sql, None, encode).unwrap();
+
+        assert_eq!(execute("INSERT INTO t VALUES (1), (2)").changes, 2);
+        let res = execute("SELECT 1 AS one");
+        assert_eq!(res.changes, 0);
+        assert_eq!(serde_json::to_value(res.rows.unwrap()).unwrap(), serde_json::json!([{ "one": 1 }]));
+        assert_eq!(execute("CREATE TABLE u (y)").changes, 0);
+        assert_eq!(execute("PRAGMA user_version = 3").changes, 0);
+        assert_eq!(execute("UPDATE t SET x = 0").changes, 2);
+    }
+}
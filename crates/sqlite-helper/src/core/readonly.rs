@@ -1,12 +1,66 @@
-use rusqlite::{ffi, Connection};
+use rusqlite::Connection;
 
+use crate::core::statements::prepare_cached;
 use crate::error::AppResult;
 
+/// Whether `sql` (one statement) only reads. `ATTACH` and `DETACH` count as writes: SQLite
+/// calls them read-only, but they change which files the connection can read.
 pub fn is_sql_readonly(conn: &Connection, sql: &str) -> AppResult<bool> {
-    let mut stmt = conn.prepare(sql)?;
-    // SAFETY: rusqlite Statement exposes a valid sqlite3_stmt pointer.
-    let ptr = stmt.as_ptr();
-    let ro = unsafe { ffi::sqlite3_stmt_readonly(ptr) };
-    Ok(ro != 0)
+    if starts_with_attach(sql) {
+        return Ok(false);
+    }
+    // Cached, so the query that follows reuses this statement instead of preparing it again.
+    let stmt = prepare_cached(conn, sql)?;
+    // Statement::readonly wraps sqlite3_stmt_readonly.
+    Ok(stmt.readonly())
 }
 
+/// Whether the (first) statement of `sql` is `ATTACH` or `DETACH`. Worker connections refuse
+/// to even prepare those (`attach::guard`), so this is checked on the text.
+pub fn starts_with_attach(sql: &str) -> bool {
+    let keyword = first_keyword(sql).unwrap_or_default();
+    keyword.eq_ignore_ascii_case("attach") || keyword.eq_ignore_ascii_case("detach")
+}
+
+/// Whether the (first) statement of `sql` begins, ends or marks a transaction: `BEGIN`,
+/// `COMMIT`/`END`, `ROLLBACK`, `SAVEPOINT` or `RELEASE`.
+pub fn starts_with_transaction_control(sql: &str) -> bool {
+    let keyword = first_keyword(sql).unwrap_or_default();
+    ["begin", "commit", "end", "rollback", "savepoint", "release"]
+        .iter()
+        .any(|k| keyword.eq_ignore_ascii_case(k))
+}
+
+/// The first word of `sql` after whitespace and comments.
+fn first_keyword(mut sql: &str) -> Option<&str> {
+    loop {
+        sql = sql.trim_start();
+        if let Some(rest) = sql.strip_prefix("--") {
+            sql = rest.split_once('\n').map_or("", |(_, tail)| tail);
+        } else if let Some(rest) = sql.strip_prefix("/*") {
+            sql = rest.split_once("*/").map_or("", |(_, tail)| tail);
+        } else {
+            break;
+        }
+    }
+    let end = sql.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(sql.len());
+    (end > 0).then(|| &sql[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attach_and_detach_are_not_readonly() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(is_sql_readonly(&conn, "SELECT 1").unwrap());
+        for sql in [
+            "ATTACH DATABASE '/elsewhere.db' AS x",
+            "  -- note\n /* c */ attach ':memory:' AS x",
+            "DETACH x",
+        ] {
+            assert!(!is_sql_readonly(&conn, sql).unwrap(), "{sql}");
+        }
+    }
+}
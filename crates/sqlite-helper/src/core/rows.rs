@@ -0,0 +1,288 @@
+use rusqlite::Connection;
+use serde_json::{Map, Value};
+
+use crate::{
+    core::{
+        paging::Page,
+        params::QueryParams,
+        query,
+        schema::{self, quote_ident, TableRef},
+        types::{BrowseResult, ColumnMeta, EncodeOptions, ExecResult, OrderDir},
+    },
+    error::{AppError, AppResult},
+};
+
+/// A change to the single row of a table a primary key value addresses.
+#[derive(Debug, Clone)]
+pub enum RowEdit {
+    /// Set these columns to these values.
+    Update(Map<String, Value>),
+    Delete,
+}
+
+/// Apply `edit` to the row of `table` whose primary key is `key` (every primary key column by
+/// name, nothing else), so a client can change one row without writing SQL.
+///
+/// Column names are checked against the table's own columns and quoted; values are bound
+/// parameters in the `params` JSON forms. Tables without a primary key are refused, since
+/// nothing then guarantees a single row. Unless `allow_no_match`, a key that matches no row is
+/// an error.
+pub fn edit_row(
+    conn: &Connection,
+    table: &str,
+    key: &Map<String, Value>,
+    edit: &RowEdit,
+    allow_no_match: bool,
+    encode: EncodeOptions,
+) -> AppResult<ExecResult> {
+    let target = TableRef::resolve(conn, table)?;
+    let columns = schema::list_columns(conn, table)?;
+    if columns.is_empty() {
+        return Err(AppError::InvalidRequest(format!("no such table: {table}")));
+    }
+
+    let mut values = Vec::new();
+    let mut sql = match edit {
+        RowEdit::Update(set) => {
+            if set.is_empty() {
+                return Err(AppError::InvalidRequest("set must name at least one column".into()));
+            }
+            let mut assignments = Vec::with_capacity(set.len());
+            for (name, value) in set {
+                let column = column(&columns, name, table)?;
+                values.push(value.clone());
+                assignments.push(format!("{} = ?{}", quote_ident(column), values.len()));
+            }
+            format!("UPDATE {} SET {}", target.quoted(), assignments.join(", "))
+        }
+        RowEdit::Delete => format!("DELETE FROM {}", target.quoted()),
+    };
+    sql.push_str(&format!(" WHERE {}", key_filter(&columns, key, table, &mut values)?));
+
+    let params = QueryParams {
+        positional: values,
+        ..QueryParams::default()
+    };
+    let er = query::run_execute(conn, &sql, Some(&params), encode)?;
+    // A full primary key matches at most one row, so the only other outcome is none.
+    if er.changes == 0 && !allow_no_match {
+        return Err(AppError::InvalidRequest(format!("no row of {table} has that primary key")));
+    }
+    Ok(er)
+}
+
+/// Insert one row of `values` (column -> value, in the `params` JSON forms) into `table`;
+/// without any, `INSERT ... DEFAULT VALUES`. Column names are checked against the table's own
+/// columns and quoted, values bound. `last_insert_rowid` reports the new row's rowid (none for
+/// `WITHOUT ROWID` tables).
+pub fn insert_row(
+    conn: &Connection,
+    table: &str,
+    values: &Map<String, Value>,
+    encode: EncodeOptions,
+) -> AppResult<ExecResult> {
+    let target = TableRef::resolve(conn, table)?;
+    let columns = schema::list_columns(conn, table)?;
+    if columns.is_empty() {
+        return Err(AppError::InvalidRequest(format!("no such table: {table}")));
+    }
+    if values.is_empty() {
+        let sql = format!("INSERT INTO {} DEFAULT VALUES", target.quoted());
+        return query::run_execute(conn, &sql, None, encode);
+    }
+    let names = values
+        .keys()
+        .map(|name| column(&columns, name, table).map(quote_ident))
+        .collect::<AppResult<Vec<_>>>()?;
+    let slots: Vec<String> = (1..=values.len()).map(|i| format!("?{i}")).collect();
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        target.quoted(),
+        names.join(", "),
+        slots.join(", ")
+    );
+    let params = QueryParams {
+        positional: values.values().cloned().collect(),
+        ..QueryParams::default()
+    };
+    query::run_execute(conn, &sql, Some(&params), encode)
+}
+
+/// Rows `offset..offset + limit` of `table` (a view too), optionally sorted by the column
+/// `order_by`, together with the table's total row count, for a data browser.
+///
+/// The column is checked against the table's own columns and quoted. Both reads run inside one
+/// savepoint, so the count and the page come from the same snapshot of the database.
+pub fn browse(
+    conn: &Connection,
+    table: &str,
+    order_by: Option<(&str, OrderDir)>,
+    limit: usize,
+    offset: usize,
+    encode: EncodeOptions,
+) -> AppResult<BrowseResult> {
+    let target = TableRef::resolve(conn, table)?;
+    let columns = schema::list_columns(conn, table)?;
+    if columns.is_empty() {
+        return Err(AppError::InvalidRequest(format!("no such table: {table}")));
+    }
+    let mut sql = format!("SELECT * FROM {}", target.quoted());
+    if let Some((name, dir)) = order_by {
+        let column = column(&columns, name, table)?;
+        sql.push_str(&format!(" ORDER BY {} {}", quote_ident(column), dir.as_sql()));
+    }
+
+    conn.execute_batch("SAVEPOINT browse_table")?;
+    let res = schema::count_rows(conn, table).and_then(|total| {
+        let page = query::run_query(conn, &sql, None, limit, &Page::Offset(offset), encode)?;
+        Ok(BrowseResult { total, page })
+    });
+    // Only reads happened, so releasing is all there is to undo.
+    conn.execute_batch("RELEASE browse_table")?;
+    res
+}
+
+/// `"pk1" = ?n AND ...` over every primary key column, in key order, pushing the values.
+fn key_filter(
+    columns: &[ColumnMeta],
+    key: &Map<String, Value>,
+    table: &str,
+    values: &mut Vec<Value>,
+) -> AppResult<String> {
+    let mut pk: Vec<&ColumnMeta> = columns.iter().filter(|c| c.pk.unwrap_or(0) > 0).collect();
+    pk.sort_by_key(|c| c.pk);
+    if pk.is_empty() {
+        return Err(AppError::InvalidRequest(format!(
+            "{table} has no primary key, so a row cannot be addressed safely"
+        )));
+    }
+    let pk_names = || pk.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ");
+    if let Some(extra) = key.keys().find(|k| !pk.iter().any(|c| c.name.eq_ignore_ascii_case(k))) {
+        return Err(AppError::InvalidRequest(format!(
+            "key column {extra} is not part of the primary key of {table} ({})",
+            pk_names()
+        )));
+    }
+    let mut clauses = Vec::with_capacity(pk.len());
+    for c in &pk {
+        let Some((_, value)) = key.iter().find(|(k, _)| c.name.eq_ignore_ascii_case(k)) else {
+            return Err(AppError::InvalidRequest(format!(
+                "key must give every primary key column of {table} ({}); missing {}",
+                pk_names(),
+                c.name
+            )));
+        };
+        values.push(value.clone());
+        clauses.push(format!("{} = ?{}", quote_ident(&c.name), values.len()));
+    }
+    Ok(clauses.join(" AND "))
+}
+
+/// The table column named `name` (ignoring case, as SQLite does), spelled as declared.
+fn column<'a>(columns: &'a [ColumnMeta], name: &str, table: &str) -> AppResult<&'a str> {
+    columns
+        .iter()
+        .find(|c| c.name.eq_ignore_ascii_case(name))
+        .map(|c| c.name.as_str())
+        .ok_or_else(|| AppError::InvalidRequest(format!("no such column: {table}.{name}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn map(v: Value) -> Map<String, Value> {
+        v.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn edits_exactly_the_row_the_primary_key_names() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"CREATE TABLE t (a INT, "b c" TEXT, note TEXT, PRIMARY KEY (a, "b c"));
+               INSERT INTO t VALUES (1, 'x', 'one'), (1, 'y', 'two');
+               CREATE TABLE loose (v);"#,
+        )
+        .unwrap();
+        let enc = EncodeOptions::default();
+        let key = map(json!({ "A": 1, "b c": "y" }));
+
+        let set = RowEdit::Update(map(json!({ "note": "it's \"2\"" })));
+        assert_eq!(edit_row(&conn, "t", &key, &set, false, enc).unwrap().changes, 1);
+        let notes: Vec<String> = conn
+            .prepare("SELECT note FROM t ORDER BY \"b c\"")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(notes, ["one", "it's \"2\""]);
+
+        // A partial key, a column outside the key, an unknown column, or no primary key at all.
+        for (key, set) in [
+            (json!({ "a": 1 }), json!({ "note": "" })),
+            (json!({ "a": 1, "b c": "x", "note": "one" }), json!({ "note": "" })),
+            (json!({ "a": 1, "b c": "x" }), json!({ "note; DROP TABLE t": "" })),
+            (json!({ "a": 1, "b c": "x" }), json!({})),
+        ] {
+            let edit = RowEdit::Update(map(set));
+            assert!(edit_row(&conn, "t", &map(key), &edit, false, enc).is_err());
+        }
+        let err = edit_row(&conn, "loose", &map(json!({ "v": 1 })), &RowEdit::Delete, false, enc).unwrap_err();
+        assert!(err.to_string().contains("no primary key"), "{err}");
+
+        let gone = map(json!({ "a": 2, "b c": "x" }));
+        assert!(edit_row(&conn, "t", &gone, &RowEdit::Delete, false, enc).is_err());
+        assert_eq!(edit_row(&conn, "t", &gone, &RowEdit::Delete, true, enc).unwrap().changes, 0);
+        assert_eq!(edit_row(&conn, "t", &key, &RowEdit::Delete, false, enc).unwrap().changes, 1);
+        let left: i64 = conn.query_row("SELECT count(*) FROM t", [], |r| r.get(0)).unwrap();
+        assert_eq!(left, 1);
+    }
+
+    #[test]
+    fn inserts_bound_values_or_defaults() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (id INTEGER PRIMARY KEY, \"my note\" TEXT DEFAULT 'none', data BLOB)")
+            .unwrap();
+        let enc = EncodeOptions::default();
+
+        let values = map(json!({ "My Note": "a'b", "data": { "$type": "blob", "base64": "AQI=" } }));
+        let er = insert_row(&conn, "t", &values, enc).unwrap();
+        assert_eq!((er.changes, er.last_insert_rowid), (1, Some(1)));
+        let er = insert_row(&conn, "t", &Map::new(), enc).unwrap();
+        assert_eq!(er.last_insert_rowid, Some(2));
+        let rows: Vec<(String, Option<Vec<u8>>)> = conn
+            .prepare("SELECT \"my note\", data FROM t ORDER BY id")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(rows, [("a'b".to_string(), Some(vec![1, 2])), ("none".to_string(), None)]);
+
+        assert!(insert_row(&conn, "t", &map(json!({ "nope": 1 })), enc).is_err());
+        assert!(insert_row(&conn, "missing", &Map::new(), enc).is_err());
+    }
+
+    #[test]
+    fn browses_a_sorted_page_with_the_total() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, \"my name\" TEXT);
+             INSERT INTO t (\"my name\") VALUES ('c'), ('a'), ('d'), ('b');",
+        )
+        .unwrap();
+        let enc = EncodeOptions::default();
+
+        let br = browse(&conn, "t", Some(("My Name", OrderDir::Desc)), 2, 1, enc).unwrap();
+        assert_eq!(br.total, 4);
+        let names: Vec<&Value> = br.page.rows.iter().map(|r| &r["my name"]).collect();
+        assert_eq!(names, [&json!("c"), &json!("b")]);
+        assert_eq!(br.page.next_offset, Some(3));
+        assert!(conn.is_autocommit());
+
+        assert!(browse(&conn, "t", Some(("id; DROP TABLE t", OrderDir::Asc)), 2, 0, enc).is_err());
+        assert!(browse(&conn, "missing", None, 2, 0, enc).is_err());
+    }
+}
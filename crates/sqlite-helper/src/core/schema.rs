@@ -1,43 +1,495 @@
-use rusqlite::{Connection, Row};
+use rusqlite::{Connection, OptionalExtension, Row};
 
-use crate::core::types::ColumnMeta;
+use crate::core::attach;
+use crate::core::types::{ColumnMeta, DdlResult, ForeignKeyMeta, IndexMeta, SchemaObject, TriggerMeta};
 use crate::error::{AppError, AppResult};
 
-pub fn list_tables(conn: &Connection) -> AppResult<Vec<String>> {
-    let mut stmt = conn.prepare(
-        "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
-    )?;
+/// Tables of the main database (then its views, with `include_views`), followed with
+/// `include_attached` by those of each attached database as `alias.table`.
+pub fn list_tables(conn: &Connection, include_attached: bool, include_views: bool) -> AppResult<Vec<String>> {
+    let mut tables = tables_in(conn, "main", include_views)?;
+    if include_attached {
+        for db in attach::list(conn)? {
+            let names = tables_in(conn, &db.alias, include_views)?;
+            tables.extend(names.into_iter().map(|t| format!("{}.{t}", db.alias)));
+        }
+    }
+    Ok(tables)
+}
+
+/// Tables (then views, with `include_views`) of one database of the connection: `main`, `temp`
+/// or an attached alias. Outside `main` they are named `schema.table`, as `list_tables` names
+/// those of attached databases, so they can be passed on to the per-table calls.
+pub fn list_tables_in(conn: &Connection, schema: &str, include_views: bool) -> AppResult<Vec<String>> {
+    check_schema(conn, schema)?;
+    let tables = tables_in(conn, schema, include_views)?;
+    if schema.eq_ignore_ascii_case("main") {
+        return Ok(tables);
+    }
+    Ok(tables.into_iter().map(|t| format!("{schema}.{t}")).collect())
+}
+
+fn tables_in(conn: &Connection, schema: &str, include_views: bool) -> AppResult<Vec<String>> {
+    let types = if include_views { "'table', 'view'" } else { "'table'" };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name FROM {}.sqlite_master WHERE type IN ({types}) AND name NOT LIKE 'sqlite_%' \
+         ORDER BY type = 'view', name",
+        quote_ident(schema)
+    ))?;
     let rows = stmt
         .query_map([], |r| r.get::<_, String>(0))?
         .collect::<Result<Vec<_>, _>>()?;
     Ok(rows)
 }
 
-pub fn list_columns(conn: &Connection, table: &str) -> AppResult<Vec<ColumnMeta>> {
-    // Use PRAGMA table_info; table name is not parameterizable in SQLite, so we must
-    // validate it as an identifier to prevent injection.
-    if !is_safe_identifier(table) {
-        return Err(AppError::InvalidRequest(format!(
-            "invalid table identifier: {table}"
-        )));
+/// A table as clients name it: `table`, or `schema.table` when the part before the first `.`
+/// names a database of the connection (`main`, `temp` or an attached alias). Any other dot is
+/// part of the name, so tables created by other tools (`"order.details"`) can be addressed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TableRef<'a> {
+    pub schema: Option<&'a str>,
+    pub name: &'a str,
+}
+
+impl<'a> TableRef<'a> {
+    /// Split `table` given the connection's attached `aliases`.
+    pub fn parse(table: &'a str, aliases: &[impl AsRef<str>]) -> AppResult<Self> {
+        check_name(table, "table")?;
+        if let Some((schema, name)) = table.split_once('.') {
+            let known = ["main", "temp"].iter().any(|s| s.eq_ignore_ascii_case(schema))
+                || aliases.iter().any(|a| a.as_ref().eq_ignore_ascii_case(schema));
+            if known && !name.is_empty() {
+                return Ok(Self { schema: Some(schema), name });
+            }
+        }
+        Ok(Self { schema: None, name: table })
+    }
+
+    /// `parse` against the databases currently attached to `conn`.
+    pub fn resolve(conn: &Connection, table: &'a str) -> AppResult<Self> {
+        let aliases = match table.contains('.') {
+            true => attach::list(conn)?.into_iter().map(|db| db.alias).collect(),
+            false => Vec::new(),
+        };
+        Self::parse(table, &aliases)
+    }
+
+    /// `"schema".` (or nothing) to put before `sqlite_master` or a pragma name.
+    pub fn schema_prefix(&self) -> String {
+        self.schema.map(|s| format!("{}.", quote_ident(s))).unwrap_or_default()
+    }
+
+    /// The reference as SQL: `"schema"."name"` or `"name"`.
+    pub fn quoted(&self) -> String {
+        format!("{}{}", self.schema_prefix(), quote_ident(self.name))
+    }
+}
+
+/// `PRAGMA [schema.]pragma(table)` for a table reference, plus the resolved reference for
+/// follow-up queries on the same database.
+fn table_pragma<'a>(conn: &Connection, pragma: &str, table: &'a str) -> AppResult<(String, TableRef<'a>)> {
+    let table = TableRef::resolve(conn, table)?;
+    // PRAGMA arguments cannot be bound, so the name is quoted.
+    Ok((format!("PRAGMA {}{pragma}({})", table.schema_prefix(), quote_ident(table.name)), table))
+}
+
+/// Tables, views and triggers of `schema` (indexes have their own call), ordered by type then
+/// name; names are as stored, without the schema. Virtual tables and their shadow tables are
+/// tagged, from `PRAGMA table_list`.
+pub fn list_objects(conn: &Connection, schema: &str) -> AppResult<Vec<SchemaObject>> {
+    check_schema(conn, schema)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT m.name, m.type, m.tbl_name, m.sql, l.type FROM {}.sqlite_master m \
+         LEFT JOIN pragma_table_list l ON l.schema = ?1 COLLATE NOCASE AND l.name = m.name \
+         WHERE m.type IN ('table', 'view', 'trigger') AND m.name NOT LIKE 'sqlite_%' \
+         ORDER BY CASE m.type WHEN 'table' THEN 0 WHEN 'view' THEN 1 ELSE 2 END, m.name",
+        quote_ident(schema)
+    ))?;
+    let rows = stmt
+        .query_map([schema], |r| {
+            let sql: Option<String> = r.get(3)?;
+            let table_type: Option<String> = r.get(4)?;
+            Ok(SchemaObject {
+                name: r.get(0)?,
+                kind: r.get(1)?,
+                tbl_name: r.get(2)?,
+                module: match table_type.as_deref() {
+                    Some("virtual") => sql.as_deref().and_then(virtual_table_module),
+                    _ => None,
+                },
+                shadow: table_type.as_deref() == Some("shadow"),
+                sql,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// The stored DDL of `table` (which may be `alias.table` or a view) with its indexes and
+/// triggers, or of the whole main database; in creation order (`sqlite_master` rowid), as
+/// `.schema` lists it, so each statement only depends on earlier ones.
+pub fn ddl(conn: &Connection, table: Option<&str>) -> AppResult<DdlResult> {
+    let table = table.map(|t| TableRef::resolve(conn, t)).transpose()?;
+    let prefix = table.as_ref().map(TableRef::schema_prefix).unwrap_or_default();
+    let filter = if table.is_some() { "AND tbl_name = ?1 COLLATE NOCASE" } else { "" };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name, type, tbl_name, sql FROM {prefix}sqlite_master \
+         WHERE type IN ('table', 'index', 'view', 'trigger') {filter} ORDER BY rowid"
+    ))?;
+    let row = |r: &Row<'_>| {
+        Ok(SchemaObject {
+            name: r.get(0)?,
+            kind: r.get(1)?,
+            tbl_name: r.get(2)?,
+            sql: r.get(3)?,
+            module: None,
+            shadow: false,
+        })
+    };
+    let objects = match &table {
+        Some(t) => stmt.query_map([t.name], row)?.collect::<Result<Vec<_>, _>>()?,
+        None => stmt.query_map([], row)?.collect::<Result<Vec<_>, _>>()?,
+    };
+    if let (Some(t), true) = (&table, objects.is_empty()) {
+        return Err(AppError::InvalidRequest(format!("no such table or view: {}", t.name)));
+    }
+    let mut sql = String::new();
+    for text in objects.iter().filter_map(|o| o.sql.as_deref()) {
+        sql.push_str(text);
+        sql.push_str(";\n");
+    }
+    Ok(DdlResult { objects, sql })
+}
+
+/// Triggers of the main database, or only those on `table` (which may be `alias.table` or a
+/// view), ordered by table and name.
+pub fn list_triggers(conn: &Connection, table: Option<&str>) -> AppResult<Vec<TriggerMeta>> {
+    let table = table.map(|t| TableRef::resolve(conn, t)).transpose()?;
+    let prefix = table.as_ref().map(TableRef::schema_prefix).unwrap_or_default();
+    let filter = if table.is_some() { "AND tbl_name = ?1 COLLATE NOCASE" } else { "" };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name, tbl_name, sql FROM {prefix}sqlite_master \
+         WHERE type = 'trigger' {filter} ORDER BY tbl_name COLLATE NOCASE, name"
+    ))?;
+    let row = |r: &Row<'_>| {
+        let sql: Option<String> = r.get(2)?;
+        let (timing, event, columns) = sql.as_deref().and_then(trigger_firing).unwrap_or_default();
+        Ok(TriggerMeta {
+            name: r.get(0)?,
+            table: r.get(1)?,
+            timing,
+            event,
+            columns,
+            sql,
+        })
+    };
+    let triggers = match &table {
+        Some(t) => stmt.query_map([t.name], row)?.collect::<Result<Vec<_>, _>>()?,
+        None => stmt.query_map([], row)?.collect::<Result<Vec<_>, _>>()?,
+    };
+    Ok(triggers)
+}
+
+/// Timing, event and `UPDATE OF` columns from
+/// `CREATE [TEMP] TRIGGER [IF NOT EXISTS] [schema.]name [BEFORE|AFTER|INSTEAD OF] event ...`.
+fn trigger_firing(ddl: &str) -> Option<(String, String, Vec<String>)> {
+    let mut tokens = sql_tokens(ddl).map(|(_, t)| t).peekable();
+    tokens.find(|t| t.eq_ignore_ascii_case("TRIGGER"))?;
+    if tokens.next_if(|t| t.eq_ignore_ascii_case("IF")).is_some() {
+        tokens.nth(1)?; // NOT EXISTS
+    }
+    tokens.next()?; // name, or schema
+    if tokens.next_if_eq(&".").is_some() {
+        tokens.next()?;
+    }
+    let first = tokens.next()?.to_ascii_uppercase();
+    let (timing, event) = match first.as_str() {
+        "BEFORE" | "AFTER" => (first.clone(), tokens.next()?.to_ascii_uppercase()),
+        "INSTEAD" => {
+            tokens.next()?; // OF
+            ("INSTEAD OF".to_string(), tokens.next()?.to_ascii_uppercase())
+        }
+        _ => ("BEFORE".to_string(), first),
+    };
+    let mut columns = Vec::new();
+    if event == "UPDATE" && tokens.next_if(|t| t.eq_ignore_ascii_case("OF")).is_some() {
+        for t in tokens.take_while(|t| !t.eq_ignore_ascii_case("ON")) {
+            if t != "," {
+                columns.push(unquote_ident(t));
+            }
+        }
     }
+    Some((timing, event, columns))
+}
+
+/// `fts5` from `CREATE VIRTUAL TABLE docs USING fts5(...)`.
+fn virtual_table_module(ddl: &str) -> Option<String> {
+    let mut tokens = sql_tokens(ddl).map(|(_, t)| t);
+    tokens.find(|t| t.eq_ignore_ascii_case("USING"))?;
+    tokens.next().map(|m| unquote_ident(m).to_ascii_lowercase())
+}
 
-    let sql = format!("PRAGMA table_info({table})");
+/// Columns as `PRAGMA table_xinfo` reports them: including the hidden columns of virtual
+/// tables and generated columns, which `table_info` leaves out.
+pub fn list_columns(conn: &Connection, table: &str) -> AppResult<Vec<ColumnMeta>> {
+    let (sql, table) = table_pragma(conn, "table_xinfo", table)?;
     let mut stmt = conn.prepare(&sql)?;
-    let cols = stmt
+    let mut cols = stmt
         .query_map([], |row: &Row<'_>| {
-            let name: String = row.get("name")?;
-            let decl_type: Option<String> = row.get("type")?;
+            // 1 = hidden column of a virtual table, 2 = VIRTUAL generated, 3 = STORED generated.
+            let hidden: i64 = row.get("hidden")?;
             Ok(ColumnMeta {
-                name,
-                decl_type,
-                sqlite_type: None,
+                name: row.get("name")?,
+                decl_type: row.get("type")?,
+                pk: Some(row.get("pk")?),
+                notnull: Some(row.get::<_, i64>("notnull")? != 0),
+                default_value: row.get("dflt_value")?,
+                autoincrement: Some(false),
+                hidden: Some(hidden == 1),
+                generated: match hidden {
+                    2 => Some("virtual".to_string()),
+                    3 => Some("stored".to_string()),
+                    _ => None,
+                },
+                ..ColumnMeta::default()
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
+
+    // Only a sole `INTEGER PRIMARY KEY` column can be AUTOINCREMENT. `sqlite_sequence` has no
+    // row for the table until its first insert, so the keyword is looked up in the DDL, as
+    // are generation expressions.
+    let mut pk_cols = cols.iter().filter(|c| c.pk.unwrap_or(0) > 0);
+    let autoinc_candidate = match (pk_cols.next(), pk_cols.next()) {
+        (Some(pk), None) => pk.decl_type.as_deref().is_some_and(|t| t.eq_ignore_ascii_case("INTEGER")),
+        _ => false,
+    };
+    if !autoinc_candidate && cols.iter().all(|c| c.generated.is_none()) {
+        return Ok(cols);
+    }
+    let Some(ddl) = table_ddl(conn, &table)? else {
+        return Ok(cols);
+    };
+    if autoinc_candidate && sql_tokens(&ddl).any(|(_, t)| t.eq_ignore_ascii_case("AUTOINCREMENT")) {
+        if let Some(pk) = cols.iter_mut().find(|c| c.pk.unwrap_or(0) > 0) {
+            pk.autoincrement = Some(true);
+        }
+    }
+    for (name, expr) in generation_exprs(&ddl) {
+        let col = cols.iter_mut().find(|c| c.generated.is_some() && c.name.eq_ignore_ascii_case(&name));
+        if let Some(col) = col {
+            col.generation_expr = Some(expr.to_string());
+        }
+    }
     Ok(cols)
 }
 
+/// The `CREATE TABLE` statement of `table`.
+fn table_ddl(conn: &Connection, table: &TableRef<'_>) -> AppResult<Option<String>> {
+    let sql = format!("SELECT sql FROM {}sqlite_master WHERE type = 'table' AND name = ?1", table.schema_prefix());
+    let sql: Option<Option<String>> = conn
+        .query_row(
+            &sql,
+            [table.name],
+            |r| r.get(0),
+        )
+        .optional()?;
+    Ok(sql.flatten())
+}
+
+/// `(column, expression)` for each `AS (expr)` column definition of a `CREATE TABLE`.
+fn generation_exprs(ddl: &str) -> Vec<(String, &str)> {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    // The column definition being read, whether its last token was `AS`, and where its
+    // generation expression starts.
+    let mut column: Option<String> = None;
+    let mut after_as = false;
+    let mut expr_start = None;
+    for (pos, tok) in sql_tokens(ddl) {
+        match tok {
+            "(" => {
+                depth += 1;
+                if depth == 2 && after_as {
+                    expr_start = Some(pos + 1);
+                }
+            }
+            ")" => {
+                if depth == 2 {
+                    if let (Some(name), Some(start)) = (&column, expr_start.take()) {
+                        out.push((name.clone(), ddl[start..pos].trim()));
+                    }
+                }
+                depth -= 1;
+            }
+            "," if depth == 1 => column = None,
+            _ if depth == 1 && column.is_none() => column = Some(unquote_ident(tok)),
+            _ => {}
+        }
+        after_as = depth == 1 && tok.eq_ignore_ascii_case("AS");
+    }
+    out
+}
+
+/// `"a""b"`, `` `a` `` and `[a]` to the bare name; other tokens are returned as they are.
+fn unquote_ident(tok: &str) -> String {
+    let inner = tok.get(1..tok.len().saturating_sub(1)).unwrap_or("");
+    match tok.as_bytes().first() {
+        Some(b'"') => inner.replace("\"\"", "\""),
+        Some(b'`') => inner.replace("``", "`"),
+        Some(b'[') => inner.to_string(),
+        _ => tok.to_string(),
+    }
+}
+
+/// `sql` with comments dropped, whitespace reduced to single spaces between tokens and
+/// unquoted words uppercased, so statements that differ only in formatting compare equal.
+pub(crate) fn normalize_sql(sql: &str) -> String {
+    let tokens: Vec<String> = sql_tokens(sql)
+        .map(|(_, t)| match t.as_bytes()[0] {
+            b'\'' | b'"' | b'`' | b'[' => t.to_string(),
+            _ => t.to_ascii_uppercase(),
+        })
+        .collect();
+    tokens.join(" ")
+}
+
+/// Tokens of `sql` with their byte offsets: words, string literals and quoted identifiers
+/// (quotes included), and single punctuation characters. Whitespace and comments are skipped.
+fn sql_tokens(sql: &str) -> impl Iterator<Item = (usize, &str)> {
+    let bytes = sql.as_bytes();
+    let is_word = |c: u8| c.is_ascii_alphanumeric() || c == b'_' || c >= 0x80;
+    let mut i = 0;
+    std::iter::from_fn(move || {
+        while i < bytes.len() {
+            let start = i;
+            match bytes[i] {
+                b'\'' | b'"' | b'`' | b'[' => {
+                    let close = if bytes[i] == b'[' { b']' } else { bytes[i] };
+                    i += 1;
+                    // A doubled quote inside a quoted token is an escaped quote, not its end.
+                    while i < bytes.len() {
+                        i += 1;
+                        if bytes[i - 1] == close && bytes.get(i) != Some(&close) {
+                            break;
+                        }
+                        if bytes[i - 1] == close {
+                            i += 1;
+                        }
+                    }
+                    return Some((start, &sql[start..i]));
+                }
+                b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                    i = sql[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                }
+                b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                    i = sql[i + 2..].find("*/").map_or(bytes.len(), |n| i + n + 4);
+                }
+                c if c.is_ascii_whitespace() => i += 1,
+                c if is_word(c) => {
+                    while i < bytes.len() && is_word(bytes[i]) {
+                        i += 1;
+                    }
+                    return Some((start, &sql[start..i]));
+                }
+                _ => {
+                    i += 1;
+                    return Some((start, &sql[start..i]));
+                }
+            }
+        }
+        None
+    })
+}
+
+pub fn list_indexes(conn: &Connection, table: &str) -> AppResult<Vec<IndexMeta>> {
+    let (sql, table) = table_pragma(conn, "index_list", table)?;
+    let mut stmt = conn.prepare(&sql)?;
+    let mut indexes = stmt
+        .query_map([], |row: &Row<'_>| {
+            Ok(IndexMeta {
+                name: row.get("name")?,
+                unique: row.get::<_, i64>("unique")? != 0,
+                origin: row.get("origin")?,
+                partial: row.get::<_, i64>("partial")? != 0,
+                columns: Vec::new(),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for idx in &mut indexes {
+        // Index names come from sqlite_master, not the caller, but may still need quoting.
+        let sql = format!("PRAGMA {}index_info({})", table.schema_prefix(), quote_ident(&idx.name));
+        let mut stmt = conn.prepare(&sql)?;
+        // Rows are ordered by `seqno`; expression columns have a NULL name.
+        idx.columns = stmt
+            .query_map([], |row: &Row<'_>| row.get::<_, Option<String>>("name"))?
+            .collect::<Result<Vec<_>, _>>()?;
+    }
+    Ok(indexes)
+}
+
+pub fn list_foreign_keys(conn: &Connection, table: &str) -> AppResult<Vec<ForeignKeyMeta>> {
+    let (sql, _) = table_pragma(conn, "foreign_key_list", table)?;
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([])?;
+    // Composite keys span several rows sharing an `id`, ordered by `seq`.
+    let mut fks: Vec<ForeignKeyMeta> = Vec::new();
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get("id")?;
+        let from: String = row.get("from")?;
+        let to: Option<String> = row.get("to")?;
+        match fks.iter_mut().find(|fk| fk.id == id) {
+            Some(fk) => {
+                fk.from.push(from);
+                fk.to.push(to);
+            }
+            None => fks.push(ForeignKeyMeta {
+                id,
+                table: row.get("table")?,
+                from: vec![from],
+                to: vec![to],
+                on_update: row.get("on_update")?,
+                on_delete: row.get("on_delete")?,
+            }),
+        }
+    }
+    Ok(fks)
+}
+
+/// Exact `count(*)` for one table; scans the whole table, so callers should bound it with a deadline.
+pub fn count_rows(conn: &Connection, table: &str) -> AppResult<u64> {
+    let table = TableRef::resolve(conn, table)?;
+    let n: i64 = conn.query_row(&format!("SELECT count(*) FROM {}", table.quoted()), [], |r| r.get(0))?;
+    Ok(n as u64)
+}
+
+/// Row count recorded by the last `ANALYZE` (the first number of the table's `sqlite_stat1`
+/// entry), or `None` if the table has not been analyzed. No table pages are read; `dbstat`
+/// is not consulted as it visits every page of the table, which is what `count(*)` costs.
+pub fn estimate_rows(conn: &Connection, table: &str) -> AppResult<Option<u64>> {
+    let table = TableRef::resolve(conn, table)?;
+    let prefix = table.schema_prefix();
+    let analyzed: Option<i64> = conn
+        .query_row(
+            &format!("SELECT 1 FROM {prefix}sqlite_master WHERE type = 'table' AND name = 'sqlite_stat1'"),
+            [],
+            |r| r.get(0),
+        )
+        .optional()?;
+    if analyzed.is_none() {
+        return Ok(None);
+    }
+    let stat: Option<String> = conn
+        .query_row(
+            &format!("SELECT stat FROM {prefix}sqlite_stat1 WHERE tbl = ?1 LIMIT 1"),
+            [table.name],
+            |r| r.get(0),
+        )
+        .optional()?;
+    Ok(stat.and_then(|s| s.split_whitespace().next()?.parse().ok()))
+}
+
 pub(crate) fn is_safe_identifier(s: &str) -> bool {
     // Minimal safe subset: [A-Za-z_][A-Za-z0-9_]*
     let mut chars = s.chars();
@@ -48,22 +500,274 @@ pub(crate) fn is_safe_identifier(s: &str) -> bool {
     chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
-pub(crate) fn is_safe_table_ref(s: &str) -> bool {
-    // Allow either `table` or `schema.table`, both segments must be safe identifiers.
-    let mut parts = s.split('.');
-    let Some(first) = parts.next() else { return false };
-    if !is_safe_identifier(first) {
-        return false;
+/// `schema` must be `main`, `temp` or the alias of an attached database; aliases are plain
+/// identifiers (see `attach`), so anything else is rejected before it reaches SQL.
+pub(crate) fn check_schema(conn: &Connection, schema: &str) -> AppResult<()> {
+    if !is_safe_identifier(schema) {
+        return Err(AppError::InvalidRequest(format!("invalid schema name: {schema:?}")));
     }
-    match parts.next() {
-        None => true,
-        Some(second) => {
-            // Only allow one dot for now; keep it simple.
-            if parts.next().is_some() {
-                return false;
-            }
-            is_safe_identifier(second)
-        }
+    let known = ["main", "temp"].iter().any(|s| s.eq_ignore_ascii_case(schema))
+        || attach::list(conn)?.iter().any(|db| db.alias.eq_ignore_ascii_case(schema));
+    if !known {
+        return Err(AppError::InvalidRequest(format!(
+            "no such schema: {schema} (main, temp or an attached database alias)"
+        )));
+    }
+    Ok(())
+}
+
+/// Names are quoted wherever they are interpolated, so any name is accepted except an empty
+/// one or one with control characters (never legitimate, and unreadable in errors and logs).
+pub(crate) fn check_name(name: &str, what: &str) -> AppResult<()> {
+    if name.is_empty() || name.chars().any(char::is_control) {
+        return Err(AppError::InvalidRequest(format!("invalid {what} identifier: {name:?}")));
     }
+    Ok(())
 }
 
+/// `name` as a double-quoted SQL identifier, embedded quotes doubled.
+pub(crate) fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexes_and_foreign_keys_describe_composite_keys() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE parent (a, b, PRIMARY KEY (a, b));
+             CREATE TABLE child (id INTEGER PRIMARY KEY, pa, pb, note,
+                 FOREIGN KEY (pa, pb) REFERENCES parent (a, b) ON DELETE CASCADE);
+             CREATE INDEX child_note ON child (note, lower(note));
+             ATTACH ':memory:' AS aux;
+             CREATE TABLE aux.t (x UNIQUE);",
+        )
+        .unwrap();
+
+        let idx = list_indexes(&conn, "child").unwrap();
+        assert_eq!(idx.len(), 1);
+        assert_eq!((idx[0].name.as_str(), idx[0].unique), ("child_note", false));
+        assert_eq!(idx[0].columns, [Some("note".to_string()), None]);
+        assert_eq!(list_indexes(&conn, "parent").unwrap()[0].origin, "pk");
+
+        let fks = list_foreign_keys(&conn, "child").unwrap();
+        assert_eq!(fks.len(), 1);
+        assert_eq!(fks[0].table, "parent");
+        assert_eq!(fks[0].from, ["pa", "pb"]);
+        assert_eq!(fks[0].to, [Some("a".to_string()), Some("b".to_string())]);
+        assert_eq!(fks[0].on_delete, "CASCADE");
+
+        // Index columns of an attached table are read from that database.
+        let idx = list_indexes(&conn, "aux.t").unwrap();
+        assert_eq!((idx[0].unique, idx[0].columns.clone()), (true, vec![Some("x".to_string())]));
+        // Names are quoted, so this is just a table that does not exist.
+        assert!(list_indexes(&conn, "child; DROP TABLE child").unwrap().is_empty());
+        assert!(list_tables(&conn, false, false).unwrap().contains(&"child".to_string()));
+    }
+
+    #[test]
+    fn views_and_virtual_tables_are_told_apart() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (x);
+             CREATE VIEW v AS SELECT x FROM t;
+             CREATE VIRTUAL TABLE docs USING FTS5(body);",
+        )
+        .unwrap();
+
+        let tables = list_tables(&conn, false, false).unwrap();
+        assert!(tables.contains(&"docs".to_string()) && !tables.contains(&"v".to_string()));
+        assert_eq!(list_tables(&conn, false, true).unwrap().last().unwrap(), "v");
+
+        let objects = list_objects(&conn, "main").unwrap();
+        let find = |name: &str| objects.iter().find(|o| o.name == name).unwrap();
+        assert_eq!((find("docs").module.as_deref(), find("docs").shadow), (Some("fts5"), false));
+        assert_eq!((find("docs_data").module.as_deref(), find("docs_data").shadow), (None, true));
+        assert_eq!((find("t").module.as_deref(), find("t").shadow), (None, false));
+        assert_eq!(find("v").kind, "view");
+    }
+
+    #[test]
+    fn names_that_need_quoting_are_quoted() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"CREATE TABLE "order details" ("unit price" REAL, "group" TEXT);
+               CREATE TABLE größe (wert);
+               CREATE TABLE "a.b" ("say ""hi""");
+               CREATE INDEX "odd ""index""" ON "order details" ("group");
+               ATTACH ':memory:' AS aux;
+               CREATE TABLE aux."my table" (x);"#,
+        )
+        .unwrap();
+
+        let cols = list_columns(&conn, "order details").unwrap();
+        assert_eq!((cols[0].name.as_str(), cols[1].name.as_str()), ("unit price", "group"));
+        assert_eq!(list_indexes(&conn, "order details").unwrap()[0].columns, [Some("group".to_string())]);
+        assert_eq!(list_columns(&conn, "größe").unwrap()[0].name, "wert");
+        assert_eq!(count_rows(&conn, "größe").unwrap(), 0);
+        // `a` is not a database, so the dot is part of the table name.
+        assert_eq!(list_columns(&conn, "a.b").unwrap()[0].name, "say \"hi\"");
+        assert_eq!(list_columns(&conn, "aux.my table").unwrap()[0].name, "x");
+        assert_eq!(list_tables(&conn, true, false).unwrap().last().unwrap(), "aux.my table");
+        assert_eq!(list_tables_in(&conn, "AUX", false).unwrap(), ["AUX.my table"]);
+        assert_eq!(list_objects(&conn, "aux").unwrap()[0].name, "my table");
+        assert!(list_objects(&conn, "temp").unwrap().is_empty());
+        for bad in ["other", "aux\"; DROP", ""] {
+            assert!(matches!(list_objects(&conn, bad), Err(AppError::InvalidRequest(_))), "{bad}");
+        }
+
+        assert!(list_columns(&conn, "bad\nname").is_err());
+        assert!(list_columns(&conn, "").is_err());
+    }
+
+    #[test]
+    fn ddl_lists_a_table_with_its_indexes_and_triggers_in_creation_order() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, email TEXT UNIQUE);
+             CREATE TABLE log (msg);
+             CREATE TRIGGER t_ins AFTER INSERT ON t BEGIN INSERT INTO log VALUES (new.email); END;
+             CREATE INDEX t_email ON T (lower(email));
+             CREATE VIEW v AS SELECT email FROM t;
+             ATTACH ':memory:' AS aux;
+             CREATE TABLE aux.t (x);",
+        )
+        .unwrap();
+
+        let table = ddl(&conn, Some("t")).unwrap();
+        let names: Vec<_> = table.objects.iter().map(|o| (o.kind.as_str(), o.name.as_str())).collect();
+        assert_eq!(
+            names,
+            [("table", "t"), ("index", "sqlite_autoindex_t_1"), ("trigger", "t_ins"), ("index", "t_email")]
+        );
+        // The autoindex has no statement of its own; it is only listed.
+        assert!(table.objects[1].sql.is_none());
+        assert_eq!(table.sql.matches(";\n").count(), 3);
+        assert!(table.sql.starts_with("CREATE TABLE t (id INTEGER PRIMARY KEY, email TEXT UNIQUE);\n"));
+
+        assert_eq!(ddl(&conn, None).unwrap().objects.len(), 6);
+        assert_eq!(ddl(&conn, Some("aux.t")).unwrap().sql, "CREATE TABLE t (x);\n");
+        assert_eq!(ddl(&conn, Some("v")).unwrap().objects[0].kind, "view");
+        assert!(ddl(&conn, Some("missing")).is_err());
+    }
+
+    #[test]
+    fn triggers_report_timing_and_event() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"CREATE TABLE t (a, b, "c d");
+               CREATE TABLE audit (msg);
+               CREATE VIEW v AS SELECT a, b FROM t;
+               CREATE TRIGGER t_ins INSERT ON t BEGIN INSERT INTO audit VALUES ('ins'); END;
+               CREATE TRIGGER IF NOT EXISTS main.t_upd AFTER UPDATE OF b, "c d" ON t
+                   BEGIN INSERT INTO audit VALUES ('upd'); END;
+               CREATE TRIGGER "v insert" INSTEAD OF INSERT ON v
+                   BEGIN INSERT INTO t (a, b) VALUES (new.a, new.b); END;
+               CREATE TRIGGER v_del instead of delete on V BEGIN DELETE FROM t WHERE a = old.a; END;"#,
+        )
+        .unwrap();
+
+        let all = list_triggers(&conn, None).unwrap();
+        let firing: Vec<_> = all
+            .iter()
+            .map(|t| (t.name.as_str(), t.table.as_str(), t.timing.as_str(), t.event.as_str()))
+            .collect();
+        // `table` is spelled as in the trigger's DDL (`on V`).
+        assert_eq!(
+            firing,
+            [
+                ("t_ins", "t", "BEFORE", "INSERT"),
+                ("t_upd", "t", "AFTER", "UPDATE"),
+                ("v insert", "v", "INSTEAD OF", "INSERT"),
+                ("v_del", "V", "INSTEAD OF", "DELETE"),
+            ]
+        );
+        assert_eq!(all[1].columns, ["b", "c d"]);
+        assert!(all[0].columns.is_empty());
+        assert!(all[2].sql.as_deref().unwrap().starts_with("CREATE TRIGGER \"v insert\""));
+
+        // The INSTEAD OF triggers make the view writable.
+        conn.execute("INSERT INTO v VALUES (1, 2)", []).unwrap();
+        let on_view = list_triggers(&conn, Some("v")).unwrap();
+        assert_eq!(on_view.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), ["v insert", "v_del"]);
+        assert!(list_triggers(&conn, Some("audit")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn estimated_row_counts_come_from_analyze() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, v);
+             CREATE INDEX t_v ON t (v);
+             INSERT INTO t (v) WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 40)
+                 SELECT i % 3 FROM n;
+             ATTACH ':memory:' AS aux;
+             CREATE TABLE aux.u (x);
+             INSERT INTO aux.u VALUES (1), (2);",
+        )
+        .unwrap();
+
+        // Nothing analyzed yet: no estimate, no matter how many rows there are.
+        assert_eq!(estimate_rows(&conn, "t").unwrap(), None);
+        conn.execute_batch("ANALYZE main; INSERT INTO t (v) VALUES (9);").unwrap();
+        assert_eq!(estimate_rows(&conn, "t").unwrap(), Some(40));
+        assert_eq!(count_rows(&conn, "t").unwrap(), 41);
+        assert_eq!(estimate_rows(&conn, "aux.u").unwrap(), None);
+        conn.execute_batch("ANALYZE aux").unwrap();
+        assert_eq!(estimate_rows(&conn, "aux.u").unwrap(), Some(2));
+    }
+
+    #[test]
+    fn columns_report_keys_defaults_and_autoincrement() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL DEFAULT 'x', n);
+             CREATE TABLE plain (id INTEGER PRIMARY KEY, \"autoincrement\" INT DEFAULT 0 /* autoincrement */);
+             CREATE TABLE pair (a, b, PRIMARY KEY (b, a));",
+        )
+        .unwrap();
+
+        let cols = list_columns(&conn, "t").unwrap();
+        assert_eq!((cols[0].pk, cols[0].autoincrement, cols[0].notnull), (Some(1), Some(true), Some(false)));
+        assert_eq!((cols[1].pk, cols[1].notnull), (Some(0), Some(true)));
+        assert_eq!(cols[1].default_value.as_deref(), Some("'x'"));
+        assert_eq!((cols[2].default_value.as_deref(), cols[2].autoincrement), (None, Some(false)));
+
+        // A column (or comment) naming the keyword does not make the key AUTOINCREMENT.
+        let cols = list_columns(&conn, "plain").unwrap();
+        assert_eq!((cols[0].pk, cols[0].autoincrement), (Some(1), Some(false)));
+        assert_eq!(cols[1].default_value.as_deref(), Some("0"));
+
+        let cols = list_columns(&conn, "pair").unwrap();
+        assert_eq!((cols[0].pk, cols[1].pk), (Some(2), Some(1)));
+    }
+
+    #[test]
+    fn columns_include_hidden_and_generated_ones() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE docs USING fts5(title, body);
+             CREATE TABLE prices (
+                 net REAL,
+                 \"gross, total\" REAL GENERATED ALWAYS AS (round(net * 1.2, 2)) STORED,
+                 label TEXT AS ('€' || CAST(net AS TEXT)) -- virtual by default
+             );",
+        )
+        .unwrap();
+
+        let cols = list_columns(&conn, "docs").unwrap();
+        let hidden: Vec<_> = cols.iter().filter(|c| c.hidden == Some(true)).map(|c| &c.name).collect();
+        assert_eq!(hidden, ["docs", "rank"]);
+        assert_eq!(cols[0].hidden, Some(false));
+
+        let cols = list_columns(&conn, "prices").unwrap();
+        assert_eq!((cols[0].generated.as_deref(), cols[0].generation_expr.as_deref()), (None, None));
+        assert_eq!(cols[1].generated.as_deref(), Some("stored"));
+        assert_eq!(cols[1].generation_expr.as_deref(), Some("round(net * 1.2, 2)"));
+        assert_eq!(cols[2].generated.as_deref(), Some("virtual"));
+        assert_eq!(cols[2].generation_expr.as_deref(), Some("'€' || CAST(net AS TEXT)"));
+    }
+}
@@ -0,0 +1,98 @@
+use std::os::raw::{c_char, c_int};
+
+use rusqlite::{ffi, CachedStatement, Connection};
+
+use crate::error::{AppError, AppResult};
+
+/// Split off the first statement of `sql` using SQLite's own parser (the prepare tail
+/// pointer), so `;` inside string literals, comments or trigger bodies is handled correctly.
+///
+/// Returns the trimmed statement and the remaining text, or `None` when `sql` holds only
+/// whitespace/comments. Only the head is compiled, so callers running a script can execute
+/// each statement before the next one (which may depend on it) is prepared.
+pub fn next_statement<'a>(
+    conn: &Connection,
+    sql: &'a str,
+) -> AppResult<Option<(&'a str, &'a str)>> {
+    let mut rest = sql;
+    while !rest.trim().is_empty() {
+        let (consumed, is_statement) =
+            prepare_head(conn, rest).map_err(|e| e.shift_error_offset(sql.len() - rest.len()))?;
+        if is_statement {
+            return Ok(Some((rest[..consumed].trim(), &rest[consumed..])));
+        }
+        if consumed == 0 {
+            break;
+        }
+        rest = &rest[consumed..];
+    }
+    Ok(None)
+}
+
+/// Error unless `sql` holds exactly one statement; trailing SQL would otherwise be silently
+/// ignored by `prepare`.
+pub fn ensure_single_statement(conn: &Connection, sql: &str) -> AppResult<()> {
+    // Statements are separated by `;`, so without one (or a comment that could hide one)
+    // there is at most one; errors in it are left to the prepare that follows. This keeps
+    // repeated queries from being parsed again just for this check.
+    if !sql.trim().is_empty() && !sql.contains(';') && !sql.contains("--") && !sql.contains("/*") {
+        return Ok(());
+    }
+    let Some((_, rest)) = next_statement(conn, sql)? else {
+        return Err(AppError::InvalidRequest("empty SQL".into()));
+    };
+    // A tail that fails to compile (e.g. it depends on the first statement) still counts.
+    match next_statement(conn, rest) {
+        Ok(None) => Ok(()),
+        Ok(Some(_)) | Err(_) => Err(AppError::InvalidRequest(format!(
+            "multiple statements are not supported here (use execute_batch); trailing SQL would be ignored: {}",
+            rest.trim()
+        ))),
+    }
+}
+
+/// Prepare `sql` through the connection's statement cache (see `--statement-cache-size`).
+///
+/// The cache prepares the trimmed SQL, so error offsets are shifted back onto `sql`.
+pub fn prepare_cached<'c>(conn: &'c Connection, sql: &str) -> AppResult<CachedStatement<'c>> {
+    let leading = sql.len() - sql.trim_start().len();
+    conn.prepare_cached(sql).map_err(|e| AppError::from(e).shift_error_offset(leading))
+}
+
+/// Rows changed by all INSERT/UPDATE/DELETE statements since the connection was opened.
+///
+/// Unlike `Connection::changes`, this does not keep reporting the last DML count after
+/// DDL or PRAGMA statements, so per-statement deltas are accurate.
+pub fn total_changes(conn: &Connection) -> u64 {
+    // SAFETY: the handle is valid for the lifetime of `conn`; the call only reads a counter.
+    let n = unsafe { ffi::sqlite3_total_changes64(conn.handle()) };
+    n.max(0) as u64
+}
+
+/// Compile the first statement of `sql` and report how many bytes it consumed and whether
+/// it was an actual statement (vs. only whitespace/comments).
+fn prepare_head(conn: &Connection, sql: &str) -> AppResult<(usize, bool)> {
+    let len = c_int::try_from(sql.len()).map_err(|_| AppError::InvalidRequest("SQL text too long".into()))?;
+    let mut stmt: *mut ffi::sqlite3_stmt = std::ptr::null_mut();
+    let mut tail: *const c_char = std::ptr::null();
+    // SAFETY: `sql` outlives the call and `len` bounds the read (no NUL terminator needed);
+    // the connection handle is valid for the lifetime of `conn` and is used on this thread only.
+    let rc = unsafe {
+        ffi::sqlite3_prepare_v2(conn.handle(), sql.as_ptr().cast(), len, &mut stmt, &mut tail)
+    };
+    if rc != ffi::SQLITE_OK {
+        // Let rusqlite build the error (message and offset) from the same input.
+        conn.prepare(sql)?;
+        return Err(AppError::Internal(format!("sqlite3_prepare_v2 failed with code {rc}")));
+    }
+
+    let consumed = if tail.is_null() {
+        sql.len()
+    } else {
+        tail as usize - sql.as_ptr() as usize
+    };
+    let is_statement = !stmt.is_null();
+    // SAFETY: finalizing a null pointer is a harmless no-op.
+    unsafe { ffi::sqlite3_finalize(stmt) };
+    Ok((consumed, is_statement))
+}
@@ -0,0 +1,49 @@
+use rusqlite::Connection;
+
+use crate::core::types::TransactionMode;
+use crate::error::{AppError, AppResult};
+
+/// Start an explicit transaction on the worker connection.
+///
+/// The worker owns a single connection per db path, so the transaction stays
+/// open across requests until `commit`/`rollback`.
+pub fn begin(conn: &Connection, mode: TransactionMode) -> AppResult<()> {
+    if !conn.is_autocommit() {
+        return Err(AppError::TransactionActive);
+    }
+    let sql = match mode {
+        TransactionMode::Deferred => "BEGIN DEFERRED",
+        TransactionMode::Immediate => "BEGIN IMMEDIATE",
+        TransactionMode::Exclusive => "BEGIN EXCLUSIVE",
+    };
+    conn.execute_batch(sql)?;
+    Ok(())
+}
+
+pub fn commit(conn: &Connection) -> AppResult<()> {
+    if conn.is_autocommit() {
+        return Err(AppError::NoTransaction("nothing to commit".into()));
+    }
+    conn.execute_batch("COMMIT")?;
+    Ok(())
+}
+
+pub fn rollback(conn: &Connection) -> AppResult<()> {
+    if conn.is_autocommit() {
+        return Err(AppError::NoTransaction("nothing to roll back".into()));
+    }
+    conn.execute_batch("ROLLBACK")?;
+    Ok(())
+}
+
+/// Roll back a transaction left open when the worker shuts down, so a client that
+/// disconnects mid-edit never leaves the database locked or half-written.
+pub fn rollback_if_open(conn: &Connection) {
+    if conn.is_autocommit() {
+        return;
+    }
+    tracing::warn!("rolling back transaction left open at shutdown");
+    if let Err(e) = conn.execute_batch("ROLLBACK") {
+        tracing::warn!("rollback at shutdown failed: {e}");
+    }
+}
@@ -1,15 +1,301 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ColumnMeta {
     pub name: String,
+    /// Declared type from the table definition; `None` for expressions.
     #[serde(default)]
     pub decl_type: Option<String>,
+    /// Storage class of the first non-NULL value returned (`integer`, `real`, `text`, `blob`),
+    /// or `null` if every value was NULL. Set on query results only.
     #[serde(default)]
     pub sqlite_type: Option<String>,
+    /// 1-based position in the primary key, 0 if not part of it. Set on schema columns only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pk: Option<u32>,
+    /// Declared `NOT NULL`. Set on schema columns only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notnull: Option<bool>,
+    /// SQL text of the `DEFAULT` expression (`'x'`, `0`, `CURRENT_TIMESTAMP`); absent if none.
+    /// Also read as `dflt_value`, the `PRAGMA table_info` name.
+    #[serde(default, alias = "dflt_value", skip_serializing_if = "Option::is_none")]
+    pub default_value: Option<String>,
+    /// The table's `INTEGER PRIMARY KEY AUTOINCREMENT` column. Set on schema columns only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autoincrement: Option<bool>,
+    /// Hidden column of a virtual table (e.g. an FTS5 table's own-name and `rank` columns),
+    /// not returned by `SELECT *`. Set on schema columns only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hidden: Option<bool>,
+    /// `virtual` or `stored` for a generated column (`table_xinfo`'s `hidden` 2 or 3, reported
+    /// here rather than as `hidden`); absent otherwise. INSERTs must leave these columns out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated: Option<String>,
+    /// Expression of a generated column, as written in the table's DDL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation_expr: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexMeta {
+    pub name: String,
+    pub unique: bool,
+    /// `c` = CREATE INDEX, `u` = UNIQUE constraint, `pk` = PRIMARY KEY.
+    pub origin: String,
+    pub partial: bool,
+    /// Indexed columns in key order; `None` for expression columns.
+    pub columns: Vec<Option<String>>,
+}
+
+/// A named schema object from `sqlite_master`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaObject {
+    pub name: String,
+    /// `table`, `view` or `trigger` (or `index`, in `DdlResult`).
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Table the object belongs to (a trigger's target; the object itself otherwise).
+    pub tbl_name: String,
+    /// Original `CREATE` statement; `None` for some internal objects.
+    pub sql: Option<String>,
+    /// Module of a virtual table (`fts5`, `rtree`, ...); previews of these may behave
+    /// differently (hidden columns, MATCH-only access). Absent for ordinary tables.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    /// Internal table backing a virtual table (e.g. `docs_data` of an fts5 table `docs`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shadow: bool,
+}
+
+/// A database file found under the allowed directories.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseFile {
+    pub path: std::path::PathBuf,
+    pub size_bytes: u64,
+    /// Last modification, in milliseconds since the Unix epoch (if the platform reports it).
+    pub modified_ms: Option<u64>,
+}
+
+/// A trigger, with when it fires parsed from its DDL.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerMeta {
+    pub name: String,
+    /// Table or view the trigger is attached to.
+    pub table: String,
+    /// `BEFORE` (also SQLite's default when none is given), `AFTER` or `INSTEAD OF`.
+    pub timing: String,
+    /// `INSERT`, `UPDATE` or `DELETE`.
+    pub event: String,
+    /// Columns of an `UPDATE OF a, b` trigger; empty when any UPDATE fires it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub columns: Vec<String>,
+    pub sql: Option<String>,
+}
+
+/// Stored `CREATE` statements, as the sqlite3 shell's `.schema` prints them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DdlResult {
+    /// Tables, indexes, views and triggers in creation order; internal autoindexes are
+    /// included with `sql: null`.
+    pub objects: Vec<SchemaObject>,
+    /// The non-null statements, each terminated by `;\n`.
+    pub sql: String,
+}
+
+/// Storage figures of the main database from cheap pragmas (`core::analysis::storage_metrics`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageMetrics {
+    pub page_size: u64,
+    pub page_count: u64,
+    /// Unused pages kept in the file until a `VACUUM` (or `incremental_vacuum`) returns them.
+    pub freelist_count: u64,
+    /// `none`, `full` or `incremental`.
+    pub auto_vacuum: String,
+    pub journal_mode: String,
+    pub user_version: i64,
+    pub application_id: i64,
+    /// Text encoding, e.g. `UTF-8`.
+    pub encoding: String,
+    /// `page_size * page_count`.
+    pub total_bytes: u64,
+    /// `page_size * freelist_count`.
+    pub free_bytes: u64,
+    /// `freelist_count / page_count` (0 for an empty file).
+    pub fragmentation_ratio: f64,
+    /// Set when the free pages exceed the caller's threshold: the statement that reclaims them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recommendation: Option<String>,
+}
+
+/// Space taken by each table and index of the main database, from the `dbstat` virtual table
+/// (`core::analysis::size_report`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeReport {
+    /// Largest first.
+    pub objects: Vec<ObjectSize>,
+    /// Sums over `objects`; free pages are not counted (see `StorageMetrics::free_bytes`).
+    pub pages: u64,
+    pub bytes: u64,
+    pub unused_bytes: u64,
+}
+
+/// The pages of one table or index b-tree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectSize {
+    pub name: String,
+    /// `table` or `index`; `sqlite_schema` itself counts as a table.
+    pub kind: String,
+    /// The table an index belongs to; the name again for a table.
+    pub table: String,
+    pub pages: u64,
+    /// `pages * page_size`, overflow pages included.
+    pub bytes: u64,
+    /// Bytes of those pages holding no data.
+    pub unused_bytes: u64,
+    /// Bytes of stored records (keys and values).
+    pub payload_bytes: u64,
+}
+
+/// Rows breaking a foreign key, from `PRAGMA foreign_key_check` (`core::analysis`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForeignKeyCheck {
+    /// The violating rows, up to the row cap; `[]` for a clean database.
+    pub violations: Vec<ForeignKeyViolation>,
+    /// Violations found, including any past the cap.
+    pub total: u64,
+    /// Violations per table.
+    pub by_table: std::collections::BTreeMap<String, u64>,
+    /// `violations` stops short of `total`.
+    pub truncated: bool,
+}
+
+/// A row whose foreign key value has no matching row in the parent table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForeignKeyViolation {
+    pub table: String,
+    /// `None` for `WITHOUT ROWID` tables.
+    pub rowid: Option<i64>,
+    /// The parent table the row should reference.
+    pub references: String,
+    /// `id` of the broken constraint among the table's `foreign_keys`.
+    pub constraint_index: i64,
+    /// Its referencing columns.
+    pub from: Vec<String>,
+    /// Its referenced columns; `None` for the parent's primary key.
+    pub to: Vec<Option<String>>,
+}
+
+/// Outcome of `PRAGMA integrity_check` or `quick_check` (`core::analysis::integrity`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    /// `integrity_check` or `quick_check`.
+    pub check: String,
+    pub ok: bool,
+    /// The problems SQLite reported; empty when `ok`.
+    pub errors: Vec<String>,
+    /// More problems exist than `errors` holds.
+    pub truncated: bool,
+}
+
+/// One piece of index advice from `core::analysis`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexFinding {
+    pub severity: Severity,
+    pub kind: FindingKind,
+    pub table: String,
+    /// The index the finding is about, for `redundant_index`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+    /// The columns concerned, in index order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub columns: Vec<String>,
+    pub message: String,
+    /// A statement that acts on the finding, for the user to review and run (`write_query`).
+    pub suggested_sql: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warn,
 }
 
-pub type DbRow = std::collections::HashMap<String, serde_json::Value>;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    /// Foreign key columns no index starts with: every change to the parent table scans this one.
+    UnindexedForeignKey,
+    /// An index whose columns lead another index, which serves every lookup it does.
+    RedundantIndex,
+    /// No `sqlite_stat1` rows, so the planner guesses at the table's size and selectivity.
+    NotAnalyzed,
+}
+
+/// Schema differences from database `a` to database `b` (see `core::diff`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchemaDiff {
+    /// No differences at all.
+    pub identical: bool,
+    pub tables_only_in_a: Vec<String>,
+    pub tables_only_in_b: Vec<String>,
+    /// Tables in both whose columns or `CREATE TABLE` statements differ.
+    pub changed_tables: Vec<TableDiff>,
+    /// Indexes, views and triggers in only one database, or whose statements differ.
+    pub objects_only_in_a: Vec<ObjectRef>,
+    pub objects_only_in_b: Vec<ObjectRef>,
+    pub changed_objects: Vec<ObjectRef>,
+}
+
+/// A table present in both databases that differs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TableDiff {
+    pub name: String,
+    pub columns_only_in_a: Vec<String>,
+    pub columns_only_in_b: Vec<String>,
+    pub changed_columns: Vec<ColumnDiff>,
+    /// Both `CREATE TABLE` statements, when they differ beyond whitespace and keyword case
+    /// (e.g. a constraint was added, which `table_info` does not show).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sql_a: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sql_b: Option<String>,
+}
+
+/// A column present in both versions of a table whose definition differs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnDiff {
+    pub name: String,
+    /// `ColumnMeta` fields that differ, e.g. `decl_type`, `notnull`.
+    pub fields: Vec<String>,
+    pub a: ColumnMeta,
+    pub b: ColumnMeta,
+}
+
+/// An index, view or trigger, by type and name.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ObjectRef {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+}
+
+/// One foreign key constraint; composite keys list their columns pairwise in `from`/`to`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForeignKeyMeta {
+    pub id: i64,
+    /// Referenced (parent) table.
+    pub table: String,
+    /// Referencing columns in this table.
+    pub from: Vec<String>,
+    /// Referenced columns; `None` when the key implicitly targets the parent's primary key.
+    pub to: Vec<Option<String>>,
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+/// One result row keyed by column name, in `columns` order.
+pub type DbRow = serde_json::Map<String, serde_json::Value>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryResult {
@@ -19,13 +305,351 @@ pub struct QueryResult {
     pub truncated: bool,
     #[serde(default)]
     pub next_offset: Option<usize>,
+    /// Opaque token for the next page in keyset (`cursor_key`) mode; replaces `next_offset`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// Time spent inside SQLite (prepare + step), excluding JSON encoding.
+    #[serde(default)]
+    pub duration_ms: f64,
+    /// Rows visited by full table scans (`SQLITE_STMTSTATUS_FULLSCAN_STEP`); 0 when indexes were used.
+    #[serde(default)]
+    pub rows_scanned: u64,
+    /// Wall time on the server from handing the query to the worker until its result is back:
+    /// waiting for the worker, SQLite and encoding row values, but not writing the response. The
+    /// gap to `duration_ms` is queueing plus value encoding. Only with `timing: true`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<f64>,
+}
+
+/// One result set of a multi-statement read (`multi: true`), in script order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementResult {
+    /// 0-based position of the statement in the script.
+    pub index: usize,
+    pub sql: String,
+    #[serde(flatten)]
+    pub result: QueryResult,
+}
+
+/// A page of a table (`browse_table`) with the table's total row count, read together.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrowseResult {
+    /// `count(*)` of the whole table.
+    pub total: u64,
+    #[serde(flatten)]
+    pub page: QueryResult,
+}
+
+/// Sort direction of `browse_table`'s `order_by`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum OrderDir {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl OrderDir {
+    pub fn as_sql(self) -> &'static str {
+        match self {
+            OrderDir::Asc => "ASC",
+            OrderDir::Desc => "DESC",
+        }
+    }
+}
+
+/// Incremental pieces of a `QueryResult`, in emission order: `Columns`, `Row`*, `End`.
+#[derive(Debug, Clone)]
+pub enum QueryFrame {
+    Columns(Vec<ColumnMeta>),
+    Row(DbRow),
+    End {
+        truncated: bool,
+        next_offset: Option<usize>,
+        next_cursor: Option<String>,
+        duration_ms: f64,
+        rows_scanned: u64,
+        /// Per-column storage class, as `ColumnMeta::sqlite_type`.
+        column_types: Vec<String>,
+    },
+}
+
+/// Pieces of a SQL dump, in emission order: `Chunk`*, `End`.
+#[derive(Debug, Clone)]
+pub enum DumpFrame {
+    /// Complete statements, each terminated by `;\n`.
+    Chunk(String),
+    End { tables: usize, rows: u64 },
+}
+
+/// How non-finite REAL values (NaN, +/-Infinity) are encoded; JSON has no literal for them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FloatMode {
+    /// `{"$type":"float","value":"NaN" | "Infinity" | "-Infinity"}`
+    #[default]
+    Sentinel,
+    /// Lossy: emit `null`, indistinguishable from SQL NULL.
+    Null,
+}
+
+/// How INTEGER values outside the JavaScript safe range (±(2^53 - 1)) are encoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum IntMode {
+    /// Plain JSON numbers; large values lose precision in `JSON.parse`.
+    #[default]
+    Number,
+    /// Out-of-range values as decimal strings.
+    String,
+    /// Out-of-range values as `{"$type":"int64","value":"..."}`.
+    Lossless,
+}
+
+/// How BLOB values larger than `EncodeOptions::max_blob_bytes` are returned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlobMode {
+    /// Base64 of the first `max_blob_bytes` bytes, flagged `"truncated": true`.
+    #[default]
+    Truncate,
+    /// Only `$type`/`size`/`truncated`, no data.
+    Metadata,
+    /// Always the whole blob, ignoring the size limit.
+    Full,
+}
+
+/// Serialization of a buffered query result.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultFormat {
+    /// `QueryResult` as a JSON object.
+    #[default]
+    Json,
+    /// RFC 4180 text with a header row; see `core::csv`.
+    Csv,
+    /// JSON lines: a `columns` line, then one object per row; see `core::ndjson`.
+    Ndjson,
+    /// GitHub-flavored markdown table, cells shortened for reading; see `core::markdown`.
+    Markdown,
+}
+
+/// Default `--max-blob-bytes`.
+pub const DEFAULT_MAX_BLOB_BYTES: usize = 64 * 1024;
+
+/// Locking behaviour of an explicit `BEGIN`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionMode {
+    /// Locks are taken lazily on first read/write.
+    #[default]
+    Deferred,
+    /// Take the write lock immediately.
+    Immediate,
+    /// Take the write lock and, outside WAL mode, block readers.
+    Exclusive,
+}
+
+/// `PRAGMA journal_mode` applied to every connection (`--journal-mode`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum JournalMode {
+    Delete,
+    /// Readers no longer block on (or block) a writer; best with several workers.
+    Wal,
+    Memory,
+    Off,
+}
+
+impl JournalMode {
+    /// Value as SQLite reports it back from `PRAGMA journal_mode`.
+    pub fn as_pragma(self) -> &'static str {
+        match self {
+            JournalMode::Delete => "delete",
+            JournalMode::Wal => "wal",
+            JournalMode::Memory => "memory",
+            JournalMode::Off => "off",
+        }
+    }
+}
+
+/// `PRAGMA synchronous` applied to every connection (`--synchronous`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+impl Synchronous {
+    pub fn as_pragma(self) -> &'static str {
+        match self {
+            Synchronous::Off => "OFF",
+            Synchronous::Normal => "NORMAL",
+            Synchronous::Full => "FULL",
+            Synchronous::Extra => "EXTRA",
+        }
+    }
+}
+
+/// Options controlling the SQLite -> JSON value mapping in query results.
+#[derive(Debug, Clone, Copy)]
+pub struct EncodeOptions {
+    pub float_mode: FloatMode,
+    pub int_mode: IntMode,
+    pub blob_mode: BlobMode,
+    pub max_blob_bytes: usize,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            float_mode: FloatMode::default(),
+            int_mode: IntMode::default(),
+            blob_mode: BlobMode::default(),
+            max_blob_bytes: DEFAULT_MAX_BLOB_BYTES,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecResult {
     pub changes: u64,
-    #[serde(default)]
+    /// Rowid of the inserted row; absent unless the statement inserted into a rowid table.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub last_insert_rowid: Option<i64>,
+    /// Cumulative rows changed on this connection (all statements, including triggers).
+    #[serde(default)]
+    pub total_changes: u64,
+    #[serde(default)]
+    pub duration_ms: f64,
+    #[serde(default)]
+    pub rows_scanned: u64,
+    /// Rows produced by a `RETURNING` clause; absent for statements without one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<Vec<DbRow>>,
+    /// Set when the statement was rolled back after running (`dry_run` requests).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+/// `sql` of an `execute_batch` request: one script of `;`-separated statements, or a list
+/// of them (each element may itself hold several statements).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum BatchSql {
+    Script(String),
+    Scripts(Vec<String>),
+}
+
+impl BatchSql {
+    pub fn into_scripts(self) -> Vec<String> {
+        match self {
+            BatchSql::Script(s) => vec![s],
+            BatchSql::Scripts(v) => v,
+        }
+    }
+}
+
+/// Outcome of one statement in an `execute_batch` script.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchStatementResult {
+    pub sql: String,
+    pub changes: u64,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Per-statement results of a batch; statements after the first failure are not run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub statements: Vec<BatchStatementResult>,
+    /// True when every statement succeeded and the batch was committed.
+    pub committed: bool,
+    /// Index into `statements` of the statement that failed, if any.
+    #[serde(default)]
+    pub failed_index: Option<usize>,
+    /// Totals over the whole batch; `changes` is zero when it was rolled back.
+    #[serde(flatten)]
+    pub total: ExecResult,
+}
+
+/// `connection_info`: how a worker's connection is set up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+    /// File name SQLite opened (empty for in-memory databases).
+    pub path: String,
+    pub readonly: bool,
+    pub in_transaction: bool,
+    /// Current value of each settable pragma and `journal_mode` (see `core::pragmas`).
+    pub pragmas: serde_json::Map<String, serde_json::Value>,
+    pub attached: Vec<AttachedDatabase>,
+}
+
+/// A database attached to a worker connection; its tables are queried as `alias.table`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttachedDatabase {
+    pub alias: String,
+    pub path: std::path::PathBuf,
+}
+
+/// Outcome of a `backup_db` run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupResult {
+    pub dest: std::path::PathBuf,
+    /// Pages in the source database when the backup finished.
+    pub pages_total: u64,
+    /// Pages left to copy; 0 for a complete backup.
+    pub pages_remaining: u64,
+    /// Backup steps taken, including retries while the source was busy.
+    pub steps: u64,
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+    #[serde(default)]
+    pub duration_ms: f64,
+}
+
+/// One step of the `optimize` maintenance command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceOp {
+    /// `PRAGMA optimize`: cheap, refreshes statistics only where the planner would benefit.
+    Optimize,
+    /// `ANALYZE`: rebuilds planner statistics for every table and index.
+    Analyze,
+    /// `VACUUM`: rewrites the whole file to reclaim free pages.
+    Vacuum,
+}
+
+impl MaintenanceOp {
+    pub fn as_sql(self) -> &'static str {
+        match self {
+            MaintenanceOp::Optimize => "PRAGMA optimize",
+            MaintenanceOp::Analyze => "ANALYZE",
+            MaintenanceOp::Vacuum => "VACUUM",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceStep {
+    pub operation: MaintenanceOp,
+    pub duration_ms: f64,
+}
+
+/// Outcome of `optimize`; sizes are `None` for in-memory databases.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptimizeResult {
+    pub steps: Vec<MaintenanceStep>,
+    #[serde(default)]
+    pub size_before: Option<u64>,
+    #[serde(default)]
+    pub size_after: Option<u64>,
+    /// `size_before - size_after`; negative if the file grew.
+    #[serde(default)]
+    pub freed_bytes: Option<i64>,
+    #[serde(default)]
+    pub duration_ms: f64,
 }
 
 #[derive(Debug, Clone)]
@@ -33,3 +657,52 @@ pub struct Limits {
     pub max_rows: usize,
 }
 
+/// `explain_query`: the plan of one read-only statement, raw and nested, with a summary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExplainResult {
+    /// The `EXPLAIN QUERY PLAN` rows as SQLite returns them.
+    pub rows: Vec<PlanRow>,
+    /// The same rows nested by `parent`.
+    pub plan: Vec<PlanNode>,
+    /// Each table the plan visits, in plan order.
+    pub tables: Vec<PlanTable>,
+}
+
+/// One raw `EXPLAIN QUERY PLAN` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanRow {
+    pub id: i64,
+    pub parent: i64,
+    pub notused: i64,
+    pub detail: String,
+}
+
+/// A table in a query plan, named as the plan names it (the query's alias if it has one).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanTable {
+    pub table: String,
+    /// Every visit looks rows up (by index, rowid or virtual table constraint) instead of scanning.
+    pub uses_index: bool,
+}
+
+/// One `EXPLAIN QUERY PLAN` step; nested subqueries hang off `children`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanNode {
+    pub id: i64,
+    pub parent: i64,
+    pub detail: String,
+    #[serde(default)]
+    pub children: Vec<PlanNode>,
+}
+
+/// A slice of one BLOB cell returned by `read_blob`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobChunk {
+    /// Total size of the BLOB in bytes.
+    pub size: usize,
+    pub offset: usize,
+    pub length: usize,
+    pub base64: String,
+    /// True when this chunk reaches the end of the BLOB.
+    pub eof: bool,
+}
@@ -1,7 +1,55 @@
-use std::path::PathBuf;
+use std::{fmt, path::PathBuf};
 
+use serde::Serialize;
 use thiserror::Error;
 
+/// A SQL failure with SQLite's numeric result codes preserved, so clients can branch on
+/// e.g. SQLITE_CONSTRAINT vs SQLITE_BUSY without matching on the message.
+#[derive(Debug, Clone, Serialize)]
+pub struct SqlErrorInfo {
+    pub message: String,
+    /// Primary result code (e.g. 19 = SQLITE_CONSTRAINT); `None` for non-SQLite failures.
+    pub sqlite_code: Option<i32>,
+    /// Extended result code (e.g. 2067 = SQLITE_CONSTRAINT_UNIQUE).
+    pub extended_code: Option<i32>,
+    /// Byte offset into the request's SQL of the token a prepare failure points at
+    /// (`sqlite3_error_offset`); `None` when SQLite reports no position.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_offset: Option<usize>,
+}
+
+impl SqlErrorInfo {
+    /// `details` fields shared by every SQL error class.
+    fn details(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut details = serde_json::Map::new();
+        details.insert("sqlite_code".into(), self.sqlite_code.into());
+        details.insert("extended_code".into(), self.extended_code.into());
+        if let Some(offset) = self.error_offset {
+            details.insert("error_offset".into(), offset.into());
+        }
+        details
+    }
+}
+
+impl fmt::Display for SqlErrorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// What an SQLITE_CONSTRAINT failure violated, parsed from SQLite's message
+/// ("UNIQUE constraint failed: t.a, t.b", "CHECK constraint failed: positive_price").
+#[derive(Debug, Clone)]
+pub struct ConstraintInfo {
+    pub info: SqlErrorInfo,
+    /// Kind from the extended code: `unique`, `not_null`, `check`, `foreign_key`, ...
+    pub constraint: &'static str,
+    pub table: Option<String>,
+    pub columns: Vec<String>,
+    /// Name of a named CHECK constraint.
+    pub name: Option<String>,
+}
+
 #[derive(Debug, Error)]
 pub enum AppError {
     #[error("invalid request: {0}")]
@@ -17,15 +65,58 @@ pub enum AppError {
         source: rusqlite::Error,
     },
 
+    #[error("journal_mode={requested} was not applied to {path}: sqlite kept {actual}")]
+    JournalModeRejected {
+        path: PathBuf,
+        requested: String,
+        actual: String,
+    },
+
     #[error("sql error: {0}")]
-    SqlError(String),
+    SqlError(SqlErrorInfo),
+
+    #[error("constraint violation: {}", .0.info)]
+    ConstraintViolation(Box<ConstraintInfo>),
+
+    /// SQLITE_BUSY/SQLITE_LOCKED that outlasted the busy timeout and the worker's retries.
+    #[error("database busy: {0}")]
+    DbBusy(SqlErrorInfo),
+
+    /// SQLITE_CORRUPT or SQLITE_NOTADB: the file is damaged or not a database.
+    #[error("database corrupt: {0}")]
+    DbCorrupt(SqlErrorInfo),
+
+    /// A statement SQLite could not parse; `error_offset` points at the offending token.
+    #[error("syntax error: {0}")]
+    SyntaxError(SqlErrorInfo),
 
     #[error("query is not read-only")]
     NotReadonly,
 
+    /// A write refused because the whole server runs with `--readonly`; no request can write.
+    #[error("writes are disabled: server is running with --readonly")]
+    ReadonlyMode,
+
+    /// A write refused on one connection opened read-only (`readonly` on `connect`, a query or
+    /// an MCP tool); the same database can still be written through a read-write one.
+    #[error("writes are disabled: connection was opened read-only")]
+    ReadOnlyConnection,
+
+    #[error("a transaction is already active")]
+    TransactionActive,
+
+    #[error("no active transaction: {0}")]
+    NoTransaction(String),
+
     #[error("timeout")]
     Timeout,
 
+    #[error("cancelled")]
+    Cancelled,
+
+    #[error("not supported by this SQLite build: {0}")]
+    Unsupported(String),
+
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -38,25 +129,218 @@ pub enum AppError {
 
 impl From<rusqlite::Error> for AppError {
     fn from(e: rusqlite::Error) -> Self {
-        AppError::SqlError(e.to_string())
+        match e {
+            // A statement is only interrupted through a `CancelToken`; the worker reports it as
+            // `Cancelled` instead when the request was cancelled rather than timed out.
+            rusqlite::Error::SqliteFailure(err, _)
+                if err.code == rusqlite::ErrorCode::OperationInterrupted =>
+            {
+                AppError::Timeout
+            }
+            rusqlite::Error::SqliteFailure(err, _) => AppError::classify(
+                err,
+                SqlErrorInfo {
+                    message: e.to_string(),
+                    sqlite_code: Some(err.extended_code & 0xff),
+                    extended_code: Some(err.extended_code),
+                    error_offset: None,
+                },
+            ),
+            // Prepare failures that point at a token; the message leaves out the (echoed) SQL.
+            rusqlite::Error::SqlInputError { error, msg, offset, .. } => {
+                let info = SqlErrorInfo {
+                    message: msg,
+                    sqlite_code: Some(error.extended_code & 0xff),
+                    extended_code: Some(error.extended_code),
+                    // -1 when SQLite has no position for the error.
+                    error_offset: usize::try_from(offset).ok(),
+                };
+                if is_syntax_message(&info.message) {
+                    AppError::SyntaxError(info)
+                } else {
+                    AppError::classify(error, info)
+                }
+            }
+            e => AppError::SqlError(SqlErrorInfo {
+                message: e.to_string(),
+                sqlite_code: None,
+                extended_code: None,
+                error_offset: None,
+            }),
+        }
+    }
+}
+
+/// Messages SQLite's parser produces, as opposed to e.g. "no such table" from name resolution.
+fn is_syntax_message(msg: &str) -> bool {
+    msg.contains("syntax error") || msg.starts_with("incomplete input") || msg.starts_with("unrecognized token")
+}
+
+/// Kind of constraint from an SQLITE_CONSTRAINT_* extended code.
+fn constraint_kind(extended_code: i32) -> &'static str {
+    match extended_code {
+        275 => "check",
+        531 => "commit_hook",
+        787 => "foreign_key",
+        1043 => "function",
+        1299 => "not_null",
+        1555 => "primary_key",
+        1811 => "trigger",
+        2067 => "unique",
+        2323 => "vtab",
+        2579 => "rowid",
+        2835 => "pinned",
+        3091 => "datatype",
+        _ => "other",
     }
 }
 
 impl AppError {
+    fn classify(err: rusqlite::ffi::Error, info: SqlErrorInfo) -> Self {
+        use rusqlite::ErrorCode;
+        match err.code {
+            ErrorCode::ConstraintViolation => {
+                let detail = info.message.split_once("constraint failed: ").map(|(_, d)| d);
+                let (mut table, mut columns, mut name) = (None, Vec::new(), None);
+                for item in detail.into_iter().flat_map(|d| d.split(", ")) {
+                    match item.rsplit_once('.') {
+                        Some((t, c)) => {
+                            table = Some(t.to_string());
+                            columns.push(c.to_string());
+                        }
+                        None => name = Some(item.to_string()),
+                    }
+                }
+                AppError::ConstraintViolation(Box::new(ConstraintInfo {
+                    constraint: constraint_kind(err.extended_code),
+                    info,
+                    table,
+                    columns,
+                    name,
+                }))
+            }
+            ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked => AppError::DbBusy(info),
+            ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase => AppError::DbCorrupt(info),
+            _ => AppError::SqlError(info),
+        }
+    }
+
+    fn sql_info_mut(&mut self) -> Option<&mut SqlErrorInfo> {
+        match self {
+            AppError::SqlError(info)
+            | AppError::DbBusy(info)
+            | AppError::DbCorrupt(info)
+            | AppError::SyntaxError(info) => Some(info),
+            AppError::ConstraintViolation(c) => Some(&mut c.info),
+            _ => None,
+        }
+    }
+
+    /// For errors from preparing a suffix of the request's SQL: make `error_offset` relative
+    /// to the whole text again.
+    pub fn shift_error_offset(mut self, by: usize) -> Self {
+        if let Some(offset) = self.sql_info_mut().and_then(|info| info.error_offset.as_mut()) {
+            *offset += by;
+        }
+        self
+    }
+
+    /// For errors from SQL the server generated around the request's (e.g. keyset paging),
+    /// where an offset would not point into the request's text.
+    pub fn without_error_offset(mut self) -> Self {
+        if let Some(info) = self.sql_info_mut() {
+            info.error_offset = None;
+        }
+        self
+    }
+
     pub fn code(&self) -> &'static str {
         match self {
             AppError::InvalidRequest(_) => "INVALID_REQUEST",
             AppError::PathNotAllowed(_) => "PATH_NOT_ALLOWED",
             AppError::DbOpenFailed { .. } => "DB_OPEN_FAILED",
+            AppError::JournalModeRejected { .. } => "JOURNAL_MODE_REJECTED",
             AppError::SqlError(_) => "SQL_ERROR",
+            AppError::ConstraintViolation(_) => "CONSTRAINT_VIOLATION",
+            AppError::DbBusy(_) => "DB_BUSY",
+            AppError::DbCorrupt(_) => "DB_CORRUPT",
+            AppError::SyntaxError(_) => "SYNTAX_ERROR",
             AppError::NotReadonly => "NOT_READONLY",
+            AppError::ReadonlyMode => "READONLY_MODE",
+            AppError::ReadOnlyConnection => "READ_ONLY",
+            AppError::TransactionActive => "TRANSACTION_ACTIVE",
+            AppError::NoTransaction(_) => "NO_TRANSACTION",
             AppError::Timeout => "TIMEOUT",
+            AppError::Cancelled => "CANCELLED",
+            AppError::Unsupported(_) => "UNSUPPORTED",
             AppError::Io(_) => "IO_ERROR",
             AppError::Json(_) => "JSON_ERROR",
             AppError::Internal(_) => "INTERNAL",
         }
     }
+
+    /// Machine-readable extras for the error response (`details` on the bridge).
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            AppError::SqlError(info) | AppError::DbBusy(info) | AppError::DbCorrupt(info)
+                if info.sqlite_code.is_some() =>
+            {
+                Some(info.details().into())
+            }
+            AppError::SyntaxError(info) => Some(info.details().into()),
+            AppError::ConstraintViolation(c) => {
+                let mut details = c.info.details();
+                details.insert("constraint".into(), c.constraint.into());
+                details.insert("table".into(), c.table.clone().into());
+                details.insert("columns".into(), c.columns.clone().into());
+                details.insert("name".into(), c.name.clone().into());
+                Some(details.into())
+            }
+            AppError::PathNotAllowed(path) => Some(serde_json::json!({ "path": path })),
+            _ => None,
+        }
+    }
 }
 
 pub type AppResult<T> = Result<T, AppError>;
 
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::*;
+
+    #[test]
+    fn sqlite_errors_are_classified() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (a INTEGER, b TEXT NOT NULL, CONSTRAINT positive CHECK (a > 0), UNIQUE (a, b));
+             INSERT INTO t VALUES (1, 'x');",
+        )
+        .unwrap();
+        let err = |sql: &str| AppError::from(conn.execute_batch(sql).unwrap_err());
+
+        let e = err("INSERT INTO t VALUES (1, 'x')");
+        assert_eq!(e.code(), "CONSTRAINT_VIOLATION");
+        assert_eq!(
+            e.details().unwrap(),
+            serde_json::json!({
+                "sqlite_code": 19,
+                "extended_code": 2067,
+                "constraint": "unique",
+                "table": "t",
+                "columns": ["a", "b"],
+                "name": null,
+            })
+        );
+        let e = err("INSERT INTO t VALUES (-1, 'y')");
+        assert_eq!(e.details().unwrap()["name"], "positive");
+        assert_eq!(err("INSERT INTO t (a) VALUES (2)").details().unwrap()["constraint"], "not_null");
+
+        let e = AppError::from(conn.prepare("SELECT * FRM t").unwrap_err());
+        assert_eq!(e.code(), "SYNTAX_ERROR");
+        assert_eq!(e.details().unwrap()["error_offset"], 9);
+        // Name resolution failures stay generic.
+        assert_eq!(AppError::from(conn.prepare("SELECT * FROM nope").unwrap_err()).code(), "SQL_ERROR");
+    }
+}
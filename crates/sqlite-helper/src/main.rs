@@ -1,3 +1,6 @@
+// The MCP tools/list schema is one large `serde_json::json!` literal.
+#![recursion_limit = "256"]
+
 mod adapters;
 mod cli;
 mod core;
@@ -9,8 +12,9 @@ use clap::Parser;
 use crate::{cli::Args, error::AppResult};
 
 fn main() -> AppResult<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
     logging::init(&args.log_level);
+    args.allowed_dir = core::paths::resolve_allowed_dirs(&args.allowed_dir)?;
 
     if args.mcp {
         let rt = tokio::runtime::Builder::new_current_thread()